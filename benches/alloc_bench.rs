@@ -0,0 +1,115 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Counts heap allocations per HashWires proof, with a fresh and with a reused `HwProver`, and
+//! per verification, with `Commitment::verify` and `Commitment::verify_in_place`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use num_bigint::BigUint;
+use num_traits::Num;
+
+use blake3::Hasher as Blake3;
+use hashwires::hashwires::{scratch_size, Secret};
+use hashwires::params::HwParams;
+use hashwires::prover::HwProver;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn count_allocations<F: FnMut()>(mut f: F) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn main() {
+    let cases = [
+        (
+            "base4",
+            4,
+            64,
+            "18446744073709551614",
+            "9223372036854775807",
+        ),
+        (
+            "base16",
+            16,
+            64,
+            "18446744073709551614",
+            "9223372036854775807",
+        ),
+        (
+            "base256",
+            256,
+            64,
+            "18446744073709551614",
+            "9223372036854775807",
+        ),
+    ];
+
+    for (name, base, max_number_bits, value, threshold) in cases.iter() {
+        let value = BigUint::from_str_radix(value, 10).unwrap();
+        let threshold = BigUint::from_str_radix(threshold, 10).unwrap();
+        let secret = Secret::<Blake3>::gen(&[0u8; 32], &value);
+
+        let fresh = count_allocations(|| {
+            secret.prove(*base, *max_number_bits, &threshold).unwrap();
+        });
+
+        let mut prover = HwProver::new();
+        prover
+            .prove(&secret, *base, *max_number_bits, &threshold)
+            .unwrap();
+        let reused = count_allocations(|| {
+            prover
+                .prove(&secret, *base, *max_number_bits, &threshold)
+                .unwrap();
+        });
+
+        println!(
+            "hw_proof_gen_{}_max: {} allocations with a fresh prover, {} with a reused one",
+            name, fresh, reused
+        );
+
+        let commitment = secret.commit(*base, *max_number_bits).unwrap();
+        let proof = secret.prove(*base, *max_number_bits, &threshold).unwrap();
+        let mut scratch = vec![0u8; scratch_size(&HwParams::new(*base, *max_number_bits)).unwrap()];
+        let allocating = count_allocations(|| {
+            commitment.verify(&proof, &threshold).unwrap();
+        });
+        let in_place = count_allocations(|| {
+            commitment
+                .verify_in_place(&proof, &threshold, &mut scratch)
+                .unwrap();
+        });
+
+        println!(
+            "hw_verify_{}_max: {} allocations with verify, {} with verify_in_place",
+            name, allocating, in_place
+        );
+    }
+}
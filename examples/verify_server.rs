@@ -0,0 +1,65 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! A minimal HTTP verification endpoint on top of `std::net`, serving `POST /verify` with the
+//! body of a serialized request. Frameworks such as axum or hyper call
+//! `RequestVerifier::verify_request` from their handler the same way.
+//!
+//! Run with `cargo run --example verify_server --features server`.
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use hashwires::server::{RequestVerifier, DEFAULT_MAX_REQUEST_SIZE};
+
+fn handle(verifier: &RequestVerifier, stream: TcpStream) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    let mut content_length = 0usize;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(usize::MAX);
+            }
+        }
+    }
+
+    let (status, body) = if !request_line.starts_with("POST /verify ") {
+        (404, Vec::new())
+    } else if content_length > DEFAULT_MAX_REQUEST_SIZE {
+        (413, Vec::new())
+    } else {
+        let mut request = vec![0u8; content_length];
+        reader.read_exact(&mut request)?;
+        let response = verifier.verify_request(&request);
+        (response.http_status(), response.serialize())
+    };
+
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} \r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\n\r\n",
+        status,
+        body.len()
+    )?;
+    stream.write_all(&body)
+}
+
+fn main() -> std::io::Result<()> {
+    let verifier = RequestVerifier::new(b"example-relying-party");
+    let listener = TcpListener::bind("127.0.0.1:8080")?;
+    println!("listening on http://127.0.0.1:8080/verify");
+    for stream in listener.incoming() {
+        if let Err(e) = handle(&verifier, stream?) {
+            eprintln!("request failed: {}", e);
+        }
+    }
+    Ok(())
+}
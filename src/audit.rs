@@ -0,0 +1,295 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! An append-only, hash-chained log of the commitments issued by an issuer
+//!
+//! Each entry records an issued commitment, a hash of its parameters and the issuance
+//! timestamp; user secrets are never stored. The log head after `n` entries is
+//!
+//! ```text
+//! head_0 = 0^32
+//! head_i = H(CHAIN_SALT || head_{i-1} || H(ENTRY_SALT || i - 1 (8) || entry_{i-1}))
+//! ```
+//!
+//! so publishing the latest head commits the issuer to the whole history, and an
+//! [`AuditProof`] shows a given entry is part of it, at the index hashed with the entry.
+use std::marker::PhantomData;
+
+use digest::Digest;
+
+use crate::errors::HwError;
+use crate::hashwires::Commitment;
+use crate::params::HwParams;
+use crate::serialization::{i2osp_bounded, os2ip, serialize_bounded, take_slice, tokenize_slice};
+use crate::traits::Hash;
+
+const ENTRY_SALT: &[u8; 32] = b"61234567890123456789012345678901";
+const CHAIN_SALT: &[u8; 32] = b"71234567890123456789012345678901";
+
+/// An issued commitment, as recorded in an [`AuditLog`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditEntry {
+    commitment: Vec<u8>,
+    params_hash: [u8; 32],
+    timestamp: u64,
+}
+
+impl AuditEntry {
+    /// The serialized commitment.
+    pub fn commitment(&self) -> &[u8] {
+        &self.commitment
+    }
+
+    /// The hash of the parameters the commitment was generated with, see [`HwParams::digest`].
+    pub fn params_hash(&self) -> &[u8; 32] {
+        &self.params_hash
+    }
+
+    /// The issuance timestamp, as provided by the issuer.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Serialize the entry.
+    pub fn serialize(&self) -> Vec<u8> {
+        [
+            &serialize_bounded(&self.commitment, 2)[..],
+            &self.params_hash,
+            &self.timestamp.to_be_bytes(),
+        ]
+        .concat()
+    }
+
+    /// Deserialize an entry.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (commitment, remainder) = tokenize_slice(input, 2)?;
+        let (params_hash, remainder) = take_slice(remainder, 32)?;
+        if remainder.len() != 8 {
+            return Err(HwError::SerializationError);
+        }
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(params_hash);
+        let mut timestamp = [0u8; 8];
+        timestamp.copy_from_slice(remainder);
+        Ok(Self {
+            commitment: commitment.to_vec(),
+            params_hash: hash,
+            timestamp: u64::from_be_bytes(timestamp),
+        })
+    }
+
+    // The digest of the entry at `index` of the log.
+    fn digest<D: Hash>(&self, index: usize) -> [u8; 32] {
+        let mut hasher = D::new();
+        hasher.update(ENTRY_SALT);
+        hasher.update((index as u64).to_be_bytes());
+        hasher.update(serialize_bounded(&self.commitment, 2));
+        hasher.update(self.params_hash);
+        hasher.update(self.timestamp.to_be_bytes());
+        let mut output = [0u8; 32];
+        output.copy_from_slice(&hasher.finalize()[..32]);
+        output
+    }
+}
+
+fn chain<D: Hash>(head: &[u8; 32], entry_digest: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = D::new();
+    hasher.update(CHAIN_SALT);
+    hasher.update(head);
+    hasher.update(entry_digest);
+    let mut output = [0u8; 32];
+    output.copy_from_slice(&hasher.finalize()[..32]);
+    output
+}
+
+/// An append-only log of issued commitments.
+pub struct AuditLog<D: Hash> {
+    entries: Vec<AuditEntry>,
+    // heads[i] is the head after i entries
+    heads: Vec<[u8; 32]>,
+    _d: PhantomData<D>,
+}
+
+impl<D: Hash> Default for AuditLog<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D: Hash> AuditLog<D> {
+    /// Create an empty log.
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            heads: vec![[0u8; 32]],
+            _d: PhantomData,
+        }
+    }
+
+    /// Record an issued commitment, returning the index of its entry.
+    pub fn append(
+        &mut self,
+        commitment: &Commitment<D>,
+        params: &HwParams,
+        timestamp: u64,
+    ) -> usize {
+        let entry = AuditEntry {
+            commitment: commitment.serialize(),
+            params_hash: params.digest::<D>(),
+            timestamp,
+        };
+        let head = chain::<D>(self.head(), &entry.digest::<D>(self.entries.len()));
+        self.entries.push(entry);
+        self.heads.push(head);
+        self.entries.len() - 1
+    }
+
+    /// The number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the log is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entry at `index`.
+    pub fn entry(&self, index: usize) -> Option<&AuditEntry> {
+        self.entries.get(index)
+    }
+
+    /// The current head of the log, to be published by the issuer.
+    pub fn head(&self) -> &[u8; 32] {
+        &self.heads[self.entries.len()]
+    }
+
+    /// Prove that the entry at `index` is part of the log with the current head.
+    pub fn prove_inclusion(&self, index: usize) -> Result<AuditProof, HwError> {
+        if index >= self.entries.len() {
+            return Err(HwError::InclusionProofError);
+        }
+        Ok(AuditProof {
+            index,
+            previous_head: self.heads[index],
+            subsequent_entries: self.entries[index + 1..]
+                .iter()
+                .enumerate()
+                .map(|(i, e)| e.digest::<D>(index + 1 + i))
+                .collect(),
+        })
+    }
+}
+
+/// A proof that an [`AuditEntry`] is part of a log with a given head.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditProof {
+    index: usize,
+    previous_head: [u8; 32],
+    subsequent_entries: Vec<[u8; 32]>,
+}
+
+impl AuditProof {
+    /// The index of the entry in the log.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Verify that `entry` is part of the log with head `head`, at the index of the proof.
+    pub fn verify<D: Hash>(&self, entry: &AuditEntry, head: &[u8; 32]) -> Result<(), HwError> {
+        let mut current = chain::<D>(&self.previous_head, &entry.digest::<D>(self.index));
+        for digest in self.subsequent_entries.iter() {
+            current = chain::<D>(&current, digest);
+        }
+        if current != *head {
+            return Err(HwError::ProofVerificationError);
+        }
+        Ok(())
+    }
+
+    /// Serialize the proof.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = i2osp_bounded(self.index, 8);
+        result.extend_from_slice(&self.previous_head);
+        for digest in self.subsequent_entries.iter() {
+            result.extend_from_slice(digest);
+        }
+        result
+    }
+
+    /// Deserialize a proof.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (index, remainder) = take_slice(input, 8)?;
+        let (previous_head, remainder) = take_slice(remainder, 32)?;
+        if remainder.len() % 32 != 0 {
+            return Err(HwError::SerializationError);
+        }
+        let mut head = [0u8; 32];
+        head.copy_from_slice(previous_head);
+        Ok(Self {
+            index: os2ip(index)?,
+            previous_head: head,
+            subsequent_entries: remainder
+                .chunks(32)
+                .map(|c| {
+                    let mut digest = [0u8; 32];
+                    digest.copy_from_slice(c);
+                    digest
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::Secret;
+    use blake3::Hasher as Blake3;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn test_audit_log() -> Result<(), HwError> {
+        let params = HwParams::new(16, 32);
+        let mut log = AuditLog::<Blake3>::new();
+        let empty_head = *log.head();
+        for (i, value) in [402u32, 3413, 9999].iter().enumerate() {
+            let secret = Secret::<Blake3>::gen(&[i as u8; 32], &BigUint::from(*value));
+            let commitment = secret.commit(params.base, params.max_number_bits)?;
+            assert_eq!(
+                log.append(&commitment, &params, 1_600_000_000 + i as u64),
+                i
+            );
+        }
+        assert_eq!(log.len(), 3);
+        assert_ne!(*log.head(), empty_head);
+
+        let head = *log.head();
+        for i in 0..log.len() {
+            let proof = AuditProof::deserialize(&log.prove_inclusion(i)?.serialize())?;
+            let entry = AuditEntry::deserialize(&log.entry(i).unwrap().serialize())?;
+            assert_eq!(proof.index(), i);
+            proof.verify::<Blake3>(&entry, &head)?;
+        }
+        assert!(log.prove_inclusion(3).is_err());
+
+        // A tampered entry does not verify.
+        let proof = log.prove_inclusion(1)?;
+        let mut entry = log.entry(1).unwrap().clone();
+        entry.timestamp += 1;
+        assert!(proof.verify::<Blake3>(&entry, &head).is_err());
+        assert!(proof
+            .verify::<Blake3>(log.entry(0).unwrap(), &head)
+            .is_err());
+
+        // A proof presented for another index does not verify.
+        let mut moved = log.prove_inclusion(1)?;
+        moved.index = 2;
+        assert!(moved
+            .verify::<Blake3>(log.entry(1).unwrap(), &head)
+            .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,313 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Export of the inclusion proofs of HashWires proofs as RFC 6962-style audit paths
+//!
+//! The inclusion proof of a [`Proof`] is an `smtree` Merkle proof of a single leaf, the salted
+//! PLR root, in a sparse Merkle tree of height `tree_height`. [`AuditPath`] holds the same proof
+//! in the shape of an RFC 6962 inclusion proof, for transparency-log tooling: the position of
+//! the leaf, the number of leaf slots of the tree (`2^tree_height`), and the sibling hashes from
+//! the sibling of the leaf up to the child of the root. The hashes are the node hashes of the
+//! HashWires tree, not the RFC 6962 leaf and node hashes.
+//!
+//! [`AuditPath::to_json`] writes a single JSON object, with hex-encoded hashes:
+//!
+//! ```text
+//! {"leaf_index":5,"tree_size":16,"tree_height":4,"audit_path":["<hex>","<hex>","<hex>","<hex>"]}
+//! ```
+//!
+//! [`AuditPath::from_json`] reads it back, and [`AuditPath::smt_inclusion_parts`] turns it into
+//! the parts of [`ProofBuilder::smt_inclusion_parts`].
+//!
+//! [`ProofBuilder::smt_inclusion_parts`]: crate::hashwires::ProofBuilder::smt_inclusion_parts
+use smtree::index::TreeIndex;
+
+use crate::errors::HwError;
+use crate::hashwires::Proof;
+use crate::traits::Hash;
+
+// The leaf index is exported as a u64.
+const MAX_TREE_HEIGHT: usize = 63;
+
+/// The inclusion proof of a HashWires proof as an RFC 6962-style audit path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditPath {
+    leaf_index: u64,
+    tree_height: usize,
+    audit_path: Vec<Vec<u8>>,
+}
+
+impl AuditPath {
+    /// The audit path of the inclusion proof of `proof`, generated with the digest `D`.
+    pub fn from_proof<D: Hash>(proof: &Proof) -> Result<Self, HwError> {
+        let indexes = proof.smt_leaf_indexes::<D>()?;
+        let siblings = proof.smt_sibling_hashes::<D>()?;
+        let index = match indexes.as_slice() {
+            [index] => index,
+            _ => return Err(HwError::SerializationError),
+        };
+        let tree_height = index.get_height();
+        if tree_height > MAX_TREE_HEIGHT || siblings.len() != tree_height {
+            return Err(HwError::SerializationError);
+        }
+        let leaf_index = (0..tree_height).fold(0u64, |acc, i| acc << 1 | index.get_bit(i) as u64);
+        Ok(AuditPath {
+            leaf_index,
+            tree_height,
+            audit_path: siblings.into_iter().rev().collect(),
+        })
+    }
+
+    /// The position of the proven leaf.
+    pub fn leaf_index(&self) -> u64 {
+        self.leaf_index
+    }
+
+    /// The height of the sparse Merkle tree.
+    pub fn tree_height(&self) -> usize {
+        self.tree_height
+    }
+
+    /// The number of leaf slots of the sparse Merkle tree.
+    pub fn tree_size(&self) -> u64 {
+        1 << self.tree_height
+    }
+
+    /// The sibling hashes, from the sibling of the leaf to the child of the root.
+    pub fn audit_path(&self) -> &[Vec<u8>] {
+        &self.audit_path
+    }
+
+    /// The leaf indexes and sibling hashes of the inclusion proof, in the order of
+    /// [`Proof::smt_leaf_indexes`] and [`Proof::smt_sibling_hashes`].
+    pub fn smt_inclusion_parts(&self) -> (Vec<TreeIndex>, Vec<Vec<u8>>) {
+        (
+            vec![TreeIndex::from_u64(self.tree_height, self.leaf_index)],
+            self.audit_path.iter().rev().cloned().collect(),
+        )
+    }
+
+    /// Write the audit path as a JSON object.
+    pub fn to_json(&self) -> String {
+        let hashes: Vec<String> = self
+            .audit_path
+            .iter()
+            .map(|hash| format!("\"{}\"", hex::encode(hash)))
+            .collect();
+        format!(
+            "{{\"leaf_index\":{},\"tree_size\":{},\"tree_height\":{},\"audit_path\":[{}]}}",
+            self.leaf_index,
+            self.tree_size(),
+            self.tree_height,
+            hashes.join(",")
+        )
+    }
+
+    /// Read an audit path written by [`AuditPath::to_json`]. Whitespace between tokens and any
+    /// order of the keys are accepted; any other key or an inconsistent tree size is rejected.
+    pub fn from_json(json: &str) -> Result<Self, HwError> {
+        let mut parser = JsonParser {
+            input: json.as_bytes(),
+            pos: 0,
+        };
+        let (mut leaf_index, mut tree_size, mut tree_height, mut audit_path) =
+            (None, None, None, None);
+        parser.expect(b'{')?;
+        loop {
+            let key = parser.string()?;
+            parser.expect(b':')?;
+            let slot_taken = match key.as_str() {
+                "leaf_index" => leaf_index.replace(parser.number()?).is_some(),
+                "tree_size" => tree_size.replace(parser.number()?).is_some(),
+                "tree_height" => tree_height.replace(parser.number()?).is_some(),
+                "audit_path" => audit_path.replace(parser.hashes()?).is_some(),
+                _ => return Err(HwError::SerializationError),
+            };
+            if slot_taken {
+                return Err(HwError::SerializationError);
+            }
+            if !parser.next_item(b'}')? {
+                break;
+            }
+        }
+        parser.end()?;
+
+        let (leaf_index, tree_size, tree_height, audit_path) =
+            match (leaf_index, tree_size, tree_height, audit_path) {
+                (Some(i), Some(s), Some(h), Some(p)) => (i, s, h as usize, p),
+                _ => return Err(HwError::SerializationError),
+            };
+        if tree_height > MAX_TREE_HEIGHT
+            || tree_size != 1 << tree_height
+            || leaf_index >= tree_size
+            || audit_path.len() != tree_height
+        {
+            return Err(HwError::SerializationError);
+        }
+        Ok(AuditPath {
+            leaf_index,
+            tree_height,
+            audit_path,
+        })
+    }
+}
+
+// A reader of the subset of JSON written by `AuditPath::to_json`.
+struct JsonParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl JsonParser<'_> {
+    fn peek(&mut self) -> Option<u8> {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.input.get(self.pos) {
+            self.pos += 1;
+        }
+        self.input.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), HwError> {
+        if self.peek() != Some(byte) {
+            return Err(HwError::SerializationError);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    // After an item, consume either a comma (returning true) or the closing byte.
+    fn next_item(&mut self, close: u8) -> Result<bool, HwError> {
+        match self.peek() {
+            Some(b',') => {
+                self.pos += 1;
+                Ok(true)
+            }
+            Some(byte) if byte == close => {
+                self.pos += 1;
+                Ok(false)
+            }
+            _ => Err(HwError::SerializationError),
+        }
+    }
+
+    fn end(&mut self) -> Result<(), HwError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(HwError::SerializationError),
+        }
+    }
+
+    // A string without escapes.
+    fn string(&mut self) -> Result<String, HwError> {
+        self.expect(b'"')?;
+        let start = self.pos;
+        let len = self.input[start..]
+            .iter()
+            .position(|byte| *byte == b'"')
+            .ok_or(HwError::SerializationError)?;
+        self.pos += len + 1;
+        let string = &self.input[start..start + len];
+        if string.contains(&b'\\') {
+            return Err(HwError::SerializationError);
+        }
+        String::from_utf8(string.to_vec()).map_err(|_| HwError::SerializationError)
+    }
+
+    // A non-negative integer fitting in a u64, without leading zeros.
+    fn number(&mut self) -> Result<u64, HwError> {
+        self.peek();
+        let start = self.pos;
+        let len = self.input[start..]
+            .iter()
+            .take_while(|byte| byte.is_ascii_digit())
+            .count();
+        self.pos += len;
+        let digits = &self.input[start..start + len];
+        if digits.is_empty() || (digits.len() > 1 && digits[0] == b'0') {
+            return Err(HwError::SerializationError);
+        }
+        std::str::from_utf8(digits)
+            .ok()
+            .and_then(|digits| digits.parse().ok())
+            .ok_or(HwError::SerializationError)
+    }
+
+    // An array of hex-encoded hashes.
+    fn hashes(&mut self) -> Result<Vec<Vec<u8>>, HwError> {
+        self.expect(b'[')?;
+        let mut hashes = Vec::new();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(hashes);
+        }
+        loop {
+            let hash = hex::decode(self.string()?).map_err(|_| HwError::SerializationError)?;
+            hashes.push(hash);
+            if !self.next_item(b']')? {
+                return Ok(hashes);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::{ProofBuilder, Secret};
+    use blake3::Hasher as Blake3;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn test_audit_path_roundtrip() -> Result<(), HwError> {
+        let value = BigUint::from(3413u32);
+        let threshold = BigUint::from(1000u32);
+        let secret = Secret::<Blake3>::gen(&[1u8; 32], &value);
+        let commitment = secret.commit(16, 32)?;
+        let proof = secret.prove(16, 32, &threshold)?;
+
+        let audit_path = AuditPath::from_proof::<Blake3>(&proof)?;
+        assert_eq!(audit_path.tree_height(), 3);
+        assert_eq!(audit_path.tree_size(), 8);
+        assert!(audit_path.leaf_index() < 8);
+        assert_eq!(
+            audit_path.audit_path()[0],
+            *proof.smt_sibling_hashes::<Blake3>()?.last().unwrap()
+        );
+
+        let json = audit_path.to_json();
+        assert!(json.starts_with(&format!(
+            "{{\"leaf_index\":{},\"tree_size\":8,\"tree_height\":3,\"audit_path\":[\"",
+            audit_path.leaf_index()
+        )));
+        assert_eq!(AuditPath::from_json(&json)?, audit_path);
+        let spaced = json.replace(',', " ,\n ").replace(':', " : ");
+        assert_eq!(AuditPath::from_json(&spaced)?, audit_path);
+
+        // The imported audit path rebuilds the same, valid, proof.
+        let (leaf_indexes, sibling_hashes) = AuditPath::from_json(&json)?.smt_inclusion_parts();
+        let mut builder = ProofBuilder::new(proof.digest_id())
+            .plr_padding(proof.plr_padding())
+            .mdp_salt(proof.mdp_salt())
+            .smt_inclusion_parts::<Blake3>(&leaf_indexes, &sibling_hashes);
+        for chain_node in proof.chain_nodes() {
+            builder = builder.chain_node(chain_node);
+        }
+        let rebuilt = builder.build()?;
+        assert_eq!(rebuilt.serialize(), proof.serialize());
+        commitment.verify(&rebuilt, &threshold)?;
+
+        for malformed in [
+            json.replace("\"tree_size\":8", "\"tree_size\":16"),
+            json.replace("\"tree_height\":3", "\"tree_height\":03"),
+            json.replace("leaf_index", "index"),
+            format!("{} ", json.trim_end_matches('}')),
+            format!("{}{{}}", json),
+        ]
+        .iter()
+        {
+            assert!(AuditPath::from_json(malformed).is_err());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,321 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Compact commitments to boolean and bitfield attributes
+//!
+//! In base 2, HashWires commits to the largest MDP and proofs carry one chain node per digit
+//! along with an inclusion proof in the MDP tree, although every chain has at most two
+//! nodes. For attributes which are sets of flags (e.g. the verifications a holder passed),
+//! the natural statements are that some bits are set and others unset, e.g. that a license is
+//! held and not revoked. This module proves them selectively, with one 32-byte opening per
+//! bit and a single combined accumulator instead of the MDP tree.
+//!
+//! Bit `i` of an `n`-bit field, `i` counting from the least significant bit, is wired to a
+//! node derived from the secret `s_i` of the bit with a salt depending on the bit, and the
+//! commitment accumulates all of the nodes:
+//!
+//! ```text
+//! s_i = H(BITFIELD_SEED_SALT || i || seed), as in the derivation of the hash chain seeds
+//! node_i = H(BIT_SET_SALT || i (2) || s_i) if bit i is set, H(BIT_UNSET_SALT || i (2) || s_i)
+//!     otherwise
+//! root = H(BITFIELD_SALT || n (2) || node_0 || ... || node_{n-1})
+//! ```
+//!
+//! A proof opens the bits claimed set or unset with their secrets, from which the verifier
+//! recomputes their nodes under `BIT_SET_SALT` or `BIT_UNSET_SALT`, and reveals the nodes of
+//! the other bits, which hide whether these bits are set. As for HashWires, the commitment is
+//! generated by the issuer: a holder cannot open a bit with the other salt. A serialized commitment is laid out as
+//! `n (2) || root (32)`, and a serialized proof as `n (2) || n openings or nodes (32 each)`,
+//! least significant bit first.
+use std::marker::PhantomData;
+
+use digest::Digest;
+use generic_array::typenum::U32;
+use num_bigint::BigUint;
+use num_traits::Zero;
+
+use crate::errors::HwError;
+use crate::hashes::generate_subseeds;
+use crate::serialization::{i2osp_bounded, os2ip, take_slice};
+use crate::traits::Hash;
+
+const BITFIELD_SEED_SALT: &[u8; 32] = b"v1234567890123456789012345678901";
+const BIT_SET_SALT: &[u8; 32] = b"w1234567890123456789012345678901";
+const BIT_UNSET_SALT: &[u8; 32] = b"x1234567890123456789012345678901";
+const BITFIELD_SALT: &[u8; 32] = b"y1234567890123456789012345678901";
+
+/// The maximum number of bits of a bitfield.
+pub const MAX_BITFIELD_BITS: usize = 256;
+
+fn salted_digest<D: Hash>(salt: &[u8; 32], inputs: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = D::new();
+    hasher.update(salt);
+    for input in inputs {
+        hasher.update(input);
+    }
+    let mut output = [0u8; 32];
+    output.copy_from_slice(&hasher.finalize()[..32]);
+    output
+}
+
+fn node<D: Hash>(index: usize, secret: &[u8], set: bool) -> [u8; 32] {
+    let salt = match set {
+        true => BIT_SET_SALT,
+        false => BIT_UNSET_SALT,
+    };
+    salted_digest::<D>(salt, &[&i2osp_bounded(index, 2), secret])
+}
+
+fn accumulate<D: Hash>(nodes: &[[u8; 32]]) -> [u8; 32] {
+    let mut hasher = D::new();
+    hasher.update(BITFIELD_SALT);
+    hasher.update(i2osp_bounded(nodes.len(), 2));
+    for node in nodes.iter() {
+        hasher.update(node);
+    }
+    let mut output = [0u8; 32];
+    output.copy_from_slice(&hasher.finalize()[..32]);
+    output
+}
+
+fn check_bits(value: &BigUint, bits: usize) -> Result<(), HwError> {
+    if bits == 0 || bits > MAX_BITFIELD_BITS || value.bits() > bits as u64 {
+        return Err(HwError::ParameterError);
+    }
+    Ok(())
+}
+
+fn check_masks(set: &BigUint, unset: &BigUint, bits: usize) -> Result<(), HwError> {
+    check_bits(set, bits)?;
+    check_bits(unset, bits)?;
+    if !(set & unset).is_zero() {
+        return Err(HwError::ParameterError);
+    }
+    Ok(())
+}
+
+/// The secret of a bitfield commitment.
+pub struct BitfieldSecret<D: Hash> {
+    value: BigUint,
+    bits: usize,
+    secrets: Vec<[u8; 32]>,
+    _d: PhantomData<D>,
+}
+
+impl<D: Hash> BitfieldSecret<D> {
+    /// Generate the secret of the `bits`-bit field `value` from `seed`, failing if `value` has
+    /// more bits or if `bits` is not between 1 and [`MAX_BITFIELD_BITS`].
+    pub fn gen(seed: &[u8], value: &BigUint, bits: usize) -> Result<Self, HwError> {
+        check_bits(value, bits)?;
+        let secrets = generate_subseeds::<D, U32>(BITFIELD_SEED_SALT, seed, bits)
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        Ok(Self {
+            value: value.clone(),
+            bits,
+            secrets,
+            _d: PhantomData,
+        })
+    }
+
+    fn nodes(&self) -> Vec<[u8; 32]> {
+        self.secrets
+            .iter()
+            .enumerate()
+            .map(|(i, secret)| node::<D>(i, secret, self.value.bit(i as u64)))
+            .collect()
+    }
+
+    /// Commit to the bitfield.
+    pub fn commit(&self) -> BitfieldCommitment {
+        BitfieldCommitment {
+            bits: self.bits,
+            root: accumulate::<D>(&self.nodes()),
+        }
+    }
+
+    /// Prove that every bit of `mask` is set in the bitfield, failing with
+    /// [`HwError::MdpError`] otherwise.
+    pub fn prove(&self, mask: &BigUint) -> Result<BitfieldProof, HwError> {
+        self.prove_bits(mask, &BigUint::zero())
+    }
+
+    /// Prove that every bit of `set` is set and every bit of `unset` is unset in the bitfield,
+    /// revealing nothing on the other bits. Fails with [`HwError::ParameterError`] if the
+    /// masks overlap, and with [`HwError::MdpError`] if the bitfield does not match them.
+    pub fn prove_bits(&self, set: &BigUint, unset: &BigUint) -> Result<BitfieldProof, HwError> {
+        check_masks(set, unset, self.bits)?;
+        if &self.value & set != *set || !(&self.value & unset).is_zero() {
+            return Err(HwError::MdpError);
+        }
+        let opened = set | unset;
+        let openings = self
+            .nodes()
+            .into_iter()
+            .zip(self.secrets.iter())
+            .enumerate()
+            .map(|(i, (node, secret))| match opened.bit(i as u64) {
+                true => *secret,
+                false => node,
+            })
+            .collect();
+        Ok(BitfieldProof { openings })
+    }
+}
+
+/// A commitment to a bitfield.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BitfieldCommitment {
+    bits: usize,
+    root: [u8; 32],
+}
+
+impl BitfieldCommitment {
+    /// The number of bits of the bitfield.
+    pub fn bits(&self) -> usize {
+        self.bits
+    }
+
+    /// Verify a proof that every bit of `mask` is set in the committed bitfield, under the
+    /// digest `D` of the commitment.
+    pub fn verify<D: Hash>(&self, proof: &BitfieldProof, mask: &BigUint) -> Result<(), HwError> {
+        self.verify_bits::<D>(proof, mask, &BigUint::zero())
+    }
+
+    /// Verify a proof that every bit of `set` is set and every bit of `unset` is unset in the
+    /// committed bitfield, under the digest `D` of the commitment.
+    pub fn verify_bits<D: Hash>(
+        &self,
+        proof: &BitfieldProof,
+        set: &BigUint,
+        unset: &BigUint,
+    ) -> Result<(), HwError> {
+        check_masks(set, unset, self.bits)?;
+        if proof.openings.len() != self.bits {
+            return Err(HwError::ProofVerificationError);
+        }
+        let nodes: Vec<[u8; 32]> = proof
+            .openings
+            .iter()
+            .enumerate()
+            .map(|(i, opening)| {
+                if set.bit(i as u64) {
+                    node::<D>(i, opening, true)
+                } else if unset.bit(i as u64) {
+                    node::<D>(i, opening, false)
+                } else {
+                    *opening
+                }
+            })
+            .collect();
+        if accumulate::<D>(&nodes) != self.root {
+            return Err(HwError::ProofVerificationError);
+        }
+        Ok(())
+    }
+
+    /// Serialize the commitment.
+    pub fn serialize(&self) -> Vec<u8> {
+        [&i2osp_bounded(self.bits, 2)[..], &self.root].concat()
+    }
+
+    /// Deserialize a commitment.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (bits, root) = take_slice(input, 2)?;
+        let bits = os2ip(bits)?;
+        if bits == 0 || bits > MAX_BITFIELD_BITS || root.len() != 32 {
+            return Err(HwError::SerializationError);
+        }
+        let mut commitment = Self {
+            bits,
+            root: [0u8; 32],
+        };
+        commitment.root.copy_from_slice(root);
+        Ok(commitment)
+    }
+}
+
+/// A proof that every bit of a mask is set in a committed bitfield.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BitfieldProof {
+    // the secrets of the bits of the mask and the nodes of the other bits
+    openings: Vec<[u8; 32]>,
+}
+
+impl BitfieldProof {
+    /// Serialize the proof.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = i2osp_bounded(self.openings.len(), 2);
+        for opening in self.openings.iter() {
+            result.extend_from_slice(opening);
+        }
+        result
+    }
+
+    /// Deserialize a proof.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (bits, openings) = take_slice(input, 2)?;
+        let bits = os2ip(bits)?;
+        if bits > MAX_BITFIELD_BITS || openings.len() != bits * 32 {
+            return Err(HwError::SerializationError);
+        }
+        let openings = openings
+            .chunks_exact(32)
+            .map(|chunk| {
+                let mut opening = [0u8; 32];
+                opening.copy_from_slice(chunk);
+                opening
+            })
+            .collect();
+        Ok(Self { openings })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::Secret;
+    use blake3::Hasher as Blake3;
+
+    #[test]
+    fn test_bitfield() -> Result<(), HwError> {
+        let value = BigUint::from(0b1011_0010u32);
+        let secret = BitfieldSecret::<Blake3>::gen(&[3u8; 32], &value, 8)?;
+        let commitment = BitfieldCommitment::deserialize(&secret.commit().serialize())?;
+        let mask = BigUint::from(0b1000_0010u32);
+        let proof = BitfieldProof::deserialize(&secret.prove(&mask)?.serialize())?;
+        commitment.verify::<Blake3>(&proof, &mask)?;
+        assert!(commitment
+            .verify::<Blake3>(&proof, &BigUint::from(0b1000_0000u32))
+            .is_err());
+
+        // unset bits cannot be proven, nor opened with the secret of the bit
+        let unset = BigUint::from(0b0000_0001u32);
+        assert!(secret.prove(&unset).is_err());
+        let mut forged = secret.prove(&BigUint::from(0u32))?;
+        forged.openings[0] = secret.secrets[0];
+        assert!(commitment.verify::<Blake3>(&forged, &unset).is_err());
+        assert!(BitfieldSecret::<Blake3>::gen(&[3u8; 32], &value, 7).is_err());
+
+        // selective proofs of set and unset bits, which cannot be swapped
+        let set = BigUint::from(0b0010_0000u32);
+        let proof = secret.prove_bits(&set, &unset)?;
+        commitment.verify_bits::<Blake3>(&proof, &set, &unset)?;
+        assert!(commitment
+            .verify_bits::<Blake3>(&proof, &unset, &set)
+            .is_err());
+        assert!(secret.prove_bits(&unset, &set).is_err());
+        assert!(matches!(
+            secret.prove_bits(&set, &set),
+            Err(HwError::ParameterError)
+        ));
+
+        // far smaller than a base-2 HashWires proof over the same value
+        let hashwires = Secret::<Blake3>::gen(&[3u8; 32], &value).prove(2, 8, &mask)?;
+        assert!(proof.serialize().len() * 3 < hashwires.serialize().len() * 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! A container format for transporting several HashWires proofs at once
+//!
+//! A serialized bundle is laid out as follows (all integers are big-endian):
+//!
+//! ```text
+//! version (1) || #commitments (2) || #proofs (2)
+//! for each commitment: base (4) || I2OSP(len, 2) || commitment
+//! for each proof (index): commitment index (2) || I2OSP(len, 2) || threshold || proof length (4)
+//! proofs, concatenated in index order
+//! ```
+use num_bigint::BigUint;
+
+use crate::errors::HwError;
+use crate::hashwires::{Commitment, Proof};
+use crate::params::take_base;
+use crate::serialization::{
+    i2osp_bounded, os2ip, serialize_bounded, take_slice, take_version, tokenize_slice,
+};
+use crate::traits::Hash;
+
+const BUNDLE_VERSION: u8 = 1;
+const MAX_BUNDLE_ENTRIES: usize = u16::MAX as usize;
+
+/// A proof in a [`ProofBundle`], along with the threshold it proves and the commitment it
+/// refers to.
+pub struct BundleEntry {
+    commitment_index: usize,
+    threshold: BigUint,
+    proof: Proof,
+}
+
+impl BundleEntry {
+    /// The index of the commitment (in the bundle) this proof is verified against.
+    pub fn commitment_index(&self) -> usize {
+        self.commitment_index
+    }
+
+    /// The threshold proven by this proof.
+    pub fn threshold(&self) -> &BigUint {
+        &self.threshold
+    }
+
+    /// The HashWires proof.
+    pub fn proof(&self) -> &Proof {
+        &self.proof
+    }
+}
+
+/// A set of HashWires proofs, possibly over different commitments. Commitments are stored
+/// once and referenced by index from each proof.
+#[derive(Default)]
+pub struct ProofBundle {
+    commitments: Vec<(u32, Vec<u8>)>,
+    entries: Vec<BundleEntry>,
+}
+
+impl ProofBundle {
+    /// Create an empty bundle.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a commitment to the bundle, returning its index. Adding the same commitment twice
+    /// returns the index of the existing entry.
+    pub fn add_commitment<D: Hash>(
+        &mut self,
+        commitment: &Commitment<D>,
+    ) -> Result<usize, HwError> {
+        let entry = (commitment.base(), commitment.serialize());
+        if let Some(index) = self.commitments.iter().position(|c| *c == entry) {
+            return Ok(index);
+        }
+        if self.commitments.len() == MAX_BUNDLE_ENTRIES {
+            return Err(HwError::SerializationError);
+        }
+        self.commitments.push(entry);
+        Ok(self.commitments.len() - 1)
+    }
+
+    /// Add a proof of `threshold` over the commitment at `commitment_index`. Fails with
+    /// [`HwError::SerializationError`] if `threshold` does not fit its 2-byte length prefix.
+    pub fn add_proof(
+        &mut self,
+        commitment_index: usize,
+        threshold: &BigUint,
+        proof: Proof,
+    ) -> Result<(), HwError> {
+        if commitment_index >= self.commitments.len()
+            || self.entries.len() == MAX_BUNDLE_ENTRIES
+            || threshold.to_bytes_be().len() > u16::MAX as usize
+        {
+            return Err(HwError::SerializationError);
+        }
+        self.entries.push(BundleEntry {
+            commitment_index,
+            threshold: threshold.clone(),
+            proof,
+        });
+        Ok(())
+    }
+
+    /// The number of proofs in the bundle.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the bundle contains no proofs.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The proofs of the bundle.
+    pub fn entries(&self) -> &[BundleEntry] {
+        &self.entries
+    }
+
+    /// The commitment at `index`, if it was generated with the digest `D`.
+    pub fn commitment<D: Hash>(&self, index: usize) -> Option<Commitment<D>> {
+        self.commitments
+            .get(index)
+            .and_then(|(base, bytes)| Commitment::deserialize(bytes, *base).ok())
+    }
+
+    /// Serialize the bundle.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = vec![BUNDLE_VERSION];
+        result.extend_from_slice(&i2osp_bounded(self.commitments.len(), 2));
+        result.extend_from_slice(&i2osp_bounded(self.entries.len(), 2));
+        for (base, commitment) in self.commitments.iter() {
+            result.extend_from_slice(&base.to_be_bytes());
+            result.extend_from_slice(&serialize_bounded(commitment, 2));
+        }
+
+        let proofs: Vec<Vec<u8>> = self.entries.iter().map(|e| e.proof.serialize()).collect();
+        for (entry, proof) in self.entries.iter().zip(proofs.iter()) {
+            result.extend_from_slice(&i2osp_bounded(entry.commitment_index, 2));
+            result.extend_from_slice(&serialize_bounded(&entry.threshold.to_bytes_be(), 2));
+            result.extend_from_slice(&i2osp_bounded(proof.len(), 4));
+        }
+        for proof in proofs.iter() {
+            result.extend_from_slice(proof);
+        }
+        result
+    }
+
+    /// Deserialize a bundle. Fails with [`HwError::ParameterError`] if a commitment has an
+    /// unsupported base.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let remainder = take_version(input, BUNDLE_VERSION)?;
+        let (commitments_count, remainder) = take_slice(remainder, 2)?;
+        let (entries_count, mut remainder) = take_slice(remainder, 2)?;
+        let commitments_count = os2ip(commitments_count)?;
+        let entries_count = os2ip(entries_count)?;
+
+        let mut commitments = Vec::with_capacity(commitments_count);
+        for _ in 0..commitments_count {
+            let (base, rest) = take_base(remainder)?;
+            let (commitment, rest) = tokenize_slice(rest, 2)?;
+            commitments.push((base, commitment.to_vec()));
+            remainder = rest;
+        }
+
+        let mut index = Vec::with_capacity(entries_count);
+        for _ in 0..entries_count {
+            let (commitment_index, rest) = take_slice(remainder, 2)?;
+            let (threshold, rest) = tokenize_slice(rest, 2)?;
+            let (proof_len, rest) = take_slice(rest, 4)?;
+            let commitment_index = os2ip(commitment_index)?;
+            if commitment_index >= commitments.len() {
+                return Err(HwError::SerializationError);
+            }
+            index.push((
+                commitment_index,
+                BigUint::from_bytes_be(threshold),
+                os2ip(proof_len)?,
+            ));
+            remainder = rest;
+        }
+
+        let mut entries = Vec::with_capacity(entries_count);
+        for (commitment_index, threshold, proof_len) in index.into_iter() {
+            let (proof, rest) = take_slice(remainder, proof_len)?;
+            entries.push(BundleEntry {
+                commitment_index,
+                threshold,
+                proof: Proof::deserialize(proof)?,
+            });
+            remainder = rest;
+        }
+        if !remainder.is_empty() {
+            return Err(HwError::SerializationError);
+        }
+
+        Ok(Self {
+            commitments,
+            entries,
+        })
+    }
+}
+
+/// Verify every proof of a bundle against its commitment, failing on the first invalid one.
+pub fn verify_bundle<D: Hash>(bundle: &ProofBundle) -> Result<(), HwError> {
+    for entry in bundle.entries.iter() {
+        let (base, bytes) = &bundle.commitments[entry.commitment_index];
+        Commitment::<D>::deserialize(bytes, *base)?.verify(&entry.proof, &entry.threshold)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::{Secret, PROOF_VERSION};
+    use crate::params::FramingPolicy;
+    use blake3::Hasher as Blake3;
+
+    fn sample_bundle() -> Result<ProofBundle, HwError> {
+        let mut bundle = ProofBundle::new();
+        for (seed, value, base) in [(1u8, 402u32, 4u32), (2, 3413, 16)].iter() {
+            let secret = Secret::<Blake3>::gen(&[*seed; 32], &BigUint::from(*value));
+            let commitment = secret.commit(*base, 32)?;
+            let index = bundle.add_commitment(&commitment)?;
+            assert_eq!(index, bundle.add_commitment(&commitment)?);
+            for threshold in [1u32, *value - 10, *value].iter() {
+                let threshold = BigUint::from(*threshold);
+                bundle.add_proof(index, &threshold, secret.prove(*base, 32, &threshold)?)?;
+            }
+        }
+        Ok(bundle)
+    }
+
+    #[test]
+    fn test_bundle_roundtrip() -> Result<(), HwError> {
+        let bundle = sample_bundle()?;
+        assert_eq!(bundle.len(), 6);
+        verify_bundle::<Blake3>(&bundle)?;
+
+        let bytes = bundle.serialize();
+        let deserialized = ProofBundle::deserialize(&bytes)?;
+        assert_eq!(deserialized.len(), 6);
+        assert_eq!(deserialized.serialize(), bytes);
+        verify_bundle::<Blake3>(&deserialized)
+    }
+
+    #[test]
+    fn test_bundle_rejects_malformed() -> Result<(), HwError> {
+        let mut bundle = sample_bundle()?;
+        assert!(bundle
+            .add_proof(
+                2,
+                &BigUint::from(1u32),
+                Proof::deserialize(
+                    &[
+                        &[PROOF_VERSION, FramingPolicy::Concatenated.as_u8(), 0, 16][..],
+                        &[0u8; 20]
+                    ]
+                    .concat()
+                )?
+            )
+            .is_err());
+
+        let proof = bundle.entries[0].proof.clone();
+        assert!(matches!(
+            bundle.add_proof(0, &(BigUint::from(1u32) << (8 * u16::MAX as usize)), proof),
+            Err(HwError::SerializationError)
+        ));
+
+        let bytes = bundle.serialize();
+        assert!(ProofBundle::deserialize(&bytes[..bytes.len() - 1]).is_err());
+        assert!(ProofBundle::deserialize(&[bytes.as_slice(), &[0]].concat()).is_err());
+        // the base of the first commitment follows the version and the counts
+        let mut unsupported = bytes.clone();
+        unsupported[5..9].copy_from_slice(&3u32.to_be_bytes());
+        assert!(matches!(
+            ProofBundle::deserialize(&unsupported),
+            Err(HwError::ParameterError)
+        ));
+
+        // A proof presented against a larger threshold does not verify.
+        bundle.entries[0].threshold = BigUint::from(403u32);
+        assert!(verify_bundle::<Blake3>(&bundle).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! The hash chains of HashWires, exposed for other constructions (e.g. one-time passwords)
+//!
+//! Node `i` of the chain of a 32-byte seed is `H^i(seed)`: node 0 is the seed itself, and each
+//! node is the hash of the previous one, without any salt or framing. These semantics are
+//! stable, as the commitments of HashWires depend on them. Chains are only defined for digests
+//! with a 32-byte output, which is checked at compile time.
+//!
+//! A [`CheckpointedChain`] stores one node every `interval` nodes, so that any node of a long
+//! chain is evaluated in less than `interval` hashes with `length / interval` stored nodes.
+use std::marker::PhantomData;
+
+use generic_array::typenum::Unsigned;
+
+use crate::errors::HwError;
+use crate::hashes::HashContext;
+use crate::traits::Hash;
+
+// Chain nodes are full digests, so the digest must have a 32-byte output.
+struct ChainOutput<D>(PhantomData<D>);
+
+impl<D: Hash> ChainOutput<D> {
+    const CHECK: () = assert!(
+        <D::OutputSize as Unsigned>::USIZE == 32,
+        "hash chains require a 32-byte digest"
+    );
+}
+
+/// Node `iterations` of the chain of `seed`, i.e. `H^iterations(seed)`.
+pub fn hash_chain<D: Hash>(seed: &[u8; 32], iterations: usize) -> [u8; 32] {
+    #[allow(clippy::let_unit_value)]
+    let () = ChainOutput::<D>::CHECK;
+    HashContext::<D>::new().hash_chain(seed, iterations)
+}
+
+/// The first `length` nodes of the chain of `seed`, starting with the seed itself.
+pub fn full_hash_chain<D: Hash>(seed: &[u8; 32], length: usize) -> Vec<[u8; 32]> {
+    #[allow(clippy::let_unit_value)]
+    let () = ChainOutput::<D>::CHECK;
+    let mut output = Vec::with_capacity(length);
+    if length > 0 {
+        HashContext::<D>::new().full_hash_chain_into(seed, length, &mut output);
+    }
+    output
+}
+
+/// A chain of `length` nodes storing one node every `interval` nodes.
+pub struct CheckpointedChain<D: Hash> {
+    length: usize,
+    interval: usize,
+    // nodes 0, interval, 2 * interval, ...
+    checkpoints: Vec<[u8; 32]>,
+    _d: PhantomData<D>,
+}
+
+impl<D: Hash> CheckpointedChain<D> {
+    /// Evaluate the chain of `seed` up to node `length - 1`, storing one node every `interval`
+    /// nodes. Fails if `length` or `interval` is zero.
+    pub fn new(seed: &[u8; 32], length: usize, interval: usize) -> Result<Self, HwError> {
+        #[allow(clippy::let_unit_value)]
+        let () = ChainOutput::<D>::CHECK;
+        if length == 0 || interval == 0 {
+            return Err(HwError::ParameterError);
+        }
+        let mut ctx = HashContext::<D>::new();
+        let mut checkpoints = Vec::with_capacity((length - 1) / interval + 1);
+        checkpoints.push(*seed);
+        while checkpoints.len() * interval < length {
+            let last = checkpoints[checkpoints.len() - 1];
+            checkpoints.push(ctx.hash_chain(&last, interval));
+        }
+        Ok(Self {
+            length,
+            interval,
+            checkpoints,
+            _d: PhantomData,
+        })
+    }
+
+    /// The number of nodes of the chain.
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Whether the chain has no node, which is never the case as `length` is positive.
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Node `index` of the chain, evaluated from the closest checkpoint below it. Fails if
+    /// `index` is not smaller than the length of the chain.
+    pub fn node(&self, index: usize) -> Result<[u8; 32], HwError> {
+        if index >= self.length {
+            return Err(HwError::ParameterError);
+        }
+        let checkpoint = &self.checkpoints[index / self.interval];
+        Ok(HashContext::<D>::new().hash_chain(checkpoint, index % self.interval))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+    use digest::Digest;
+
+    #[test]
+    fn test_chains() -> Result<(), HwError> {
+        let seed = [7u8; 32];
+        let mut expected: [u8; 32] = Blake3::digest(&seed).into();
+        expected = Blake3::digest(&expected).into();
+        assert_eq!(hash_chain::<Blake3>(&seed, 0), seed);
+        assert_eq!(hash_chain::<Blake3>(&seed, 2), expected);
+
+        let nodes = full_hash_chain::<Blake3>(&seed, 20);
+        assert_eq!(nodes.len(), 20);
+        assert!(full_hash_chain::<Blake3>(&seed, 0).is_empty());
+        let chain = CheckpointedChain::<Blake3>::new(&seed, 20, 6)?;
+        for (i, node) in nodes.iter().enumerate() {
+            assert_eq!(hash_chain::<Blake3>(&seed, i), *node);
+            assert_eq!(chain.node(i)?, *node);
+        }
+        assert!(chain.node(20).is_err());
+        assert!(CheckpointedChain::<Blake3>::new(&seed, 20, 0).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! The primitives of every HashWires wire format
+//!
+//! All of the formats of this crate are built from the same primitives, exposed here so that
+//! companion crates and ports encode their formats the same way:
+//!
+//! - integers are big-endian, encoded with [`i2osp`] and decoded with [`os2ip`] (RFC 8017);
+//! - variable-length fields are length-prefixed, `I2OSP(len, n) || field`, encoded with
+//!   [`serialize`] and decoded with [`tokenize_slice`];
+//! - fixed-length fields are decoded with [`take_slice`];
+//! - versioned formats start with a 1-byte version, checked with [`take_version`].
+//!
+//! Decoding never panics on malformed input, and fails with
+//! [`HwError::SerializationError`](crate::errors::HwError::SerializationError), as does encoding
+//! an integer or a length too large for its field.
+pub use crate::serialization::{i2osp, os2ip, serialize, take_slice, take_version, tokenize_slice};
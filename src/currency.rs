@@ -0,0 +1,193 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Commitments to amounts in a currency
+//!
+//! A [`CurrencyValue`] is an amount along with the ISO 4217 code of its currency (e.g.
+//! `"USD"`). A [`CurrencyCommitment`] commits to both jointly, so that a proof over an amount
+//! in one currency is never accepted against a threshold in another one: verification rejects
+//! thresholds in another currency with [`HwError::CurrencyError`] before verifying the proof.
+//!
+//! The root of a currency commitment, which the issuer publishes or signs, binds the
+//! HashWires commitment of the amount to the currency code:
+//!
+//! ```text
+//! root = H(CURRENCY_SALT || currency (3) || base (4) || commitment)
+//! ```
+//!
+//! A serialized currency commitment is laid out as `currency (3) || base (4) || commitment`,
+//! integers being big-endian.
+use digest::Digest;
+use num_bigint::BigUint;
+
+use crate::errors::HwError;
+use crate::hashwires::{Commitment, Proof, Secret};
+use crate::params::HwParams;
+use crate::serialization::take_slice;
+use crate::traits::Hash;
+use crate::value::HwValue;
+
+const CURRENCY_SALT: &[u8; 32] = b"q1234567890123456789012345678901";
+
+/// An amount in a currency, used both as committed value and as threshold.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CurrencyValue {
+    amount: BigUint,
+    currency: [u8; 3],
+}
+
+impl CurrencyValue {
+    /// An `amount` in `currency`, failing if the code is not three uppercase ASCII letters.
+    pub fn new<V: HwValue>(amount: &V, currency: &str) -> Result<Self, HwError> {
+        Ok(Self {
+            amount: amount.to_biguint()?,
+            currency: parse_currency(currency.as_bytes())?,
+        })
+    }
+
+    /// The amount.
+    pub fn amount(&self) -> &BigUint {
+        &self.amount
+    }
+
+    /// The currency code.
+    pub fn currency(&self) -> &str {
+        // the code was checked to be ASCII
+        core::str::from_utf8(&self.currency).unwrap()
+    }
+}
+
+fn parse_currency(code: &[u8]) -> Result<[u8; 3], HwError> {
+    match code {
+        [a, b, c] if code.iter().all(u8::is_ascii_uppercase) => Ok([*a, *b, *c]),
+        _ => Err(HwError::ParameterError),
+    }
+}
+
+/// The secret of a [`CurrencyCommitment`].
+pub struct CurrencySecret<D: Hash> {
+    secret: Secret<D>,
+    currency: [u8; 3],
+}
+
+impl<D: Hash> CurrencySecret<D> {
+    /// Generate the secret of `value`.
+    pub fn gen(seed: &[u8], value: &CurrencyValue) -> Self {
+        Self {
+            secret: Secret::gen(seed, &value.amount),
+            currency: value.currency,
+        }
+    }
+
+    /// Commit to the amount and currency of the secret under `params`.
+    pub fn commit_with_params(&self, params: &HwParams) -> Result<CurrencyCommitment<D>, HwError> {
+        Ok(CurrencyCommitment {
+            currency: self.currency,
+            commitment: self.secret.commit_with_params(params)?,
+        })
+    }
+
+    /// Prove that the amount of the secret is larger than or equal to `threshold`, failing
+    /// with [`HwError::CurrencyError`] if the threshold is in another currency.
+    pub fn prove(&self, params: &HwParams, threshold: &CurrencyValue) -> Result<Proof, HwError> {
+        if threshold.currency != self.currency {
+            return Err(HwError::CurrencyError);
+        }
+        self.secret
+            .prove(params.base, params.max_number_bits, &threshold.amount)
+    }
+}
+
+/// A commitment to an amount in a currency.
+pub struct CurrencyCommitment<D: Hash> {
+    currency: [u8; 3],
+    commitment: Commitment<D>,
+}
+
+impl<D: Hash> CurrencyCommitment<D> {
+    /// The currency code of the committed amount.
+    pub fn currency(&self) -> &str {
+        core::str::from_utf8(&self.currency).unwrap()
+    }
+
+    /// The HashWires commitment of the amount.
+    pub fn commitment(&self) -> &Commitment<D> {
+        &self.commitment
+    }
+
+    /// The root binding the commitment of the amount to its currency.
+    pub fn root(&self) -> [u8; 32] {
+        let mut hasher = D::new();
+        hasher.update(CURRENCY_SALT);
+        hasher.update(self.serialize());
+        let mut output = [0u8; 32];
+        output.copy_from_slice(&hasher.finalize()[..32]);
+        output
+    }
+
+    /// Verify a proof that the committed amount is larger than or equal to `threshold`,
+    /// failing with [`HwError::CurrencyError`] if the threshold is in another currency.
+    pub fn verify(&self, proof: &Proof, threshold: &CurrencyValue) -> Result<(), HwError> {
+        if threshold.currency != self.currency {
+            return Err(HwError::CurrencyError);
+        }
+        self.commitment.verify(proof, &threshold.amount)
+    }
+
+    /// Serialize the currency commitment.
+    pub fn serialize(&self) -> Vec<u8> {
+        [&self.currency[..], &self.commitment.serialize_with_base()].concat()
+    }
+
+    /// Deserialize a currency commitment.
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, HwError> {
+        let (currency, remainder) = take_slice(bytes, 3)?;
+        let currency = parse_currency(currency).map_err(|_| HwError::SerializationError)?;
+        Ok(Self {
+            currency,
+            commitment: Commitment::deserialize_with_base(remainder)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+
+    #[test]
+    fn test_currency_commitment() -> Result<(), HwError> {
+        assert!(CurrencyValue::new(&100u32, "usd").is_err());
+        assert!(CurrencyValue::new(&100u32, "USDT").is_err());
+
+        let params = HwParams::new(16, 32);
+        let secret =
+            CurrencySecret::<Blake3>::gen(&[4u8; 32], &CurrencyValue::new(&3413u32, "USD")?);
+        let commitment = secret.commit_with_params(&params)?;
+        let threshold = CurrencyValue::new(&3000u32, "USD")?;
+        let proof = secret.prove(&params, &threshold)?;
+        commitment.verify(&proof, &threshold)?;
+
+        // the same amount in another currency is rejected, by the prover and the verifier
+        let other = CurrencyValue::new(&3000u32, "EUR")?;
+        assert!(matches!(
+            secret.prove(&params, &other),
+            Err(HwError::CurrencyError)
+        ));
+        assert!(matches!(
+            commitment.verify(&proof, &other),
+            Err(HwError::CurrencyError)
+        ));
+
+        let deserialized = CurrencyCommitment::<Blake3>::deserialize(&commitment.serialize())?;
+        assert_eq!(deserialized.currency(), "USD");
+        assert_eq!(deserialized.root(), commitment.root());
+        deserialized.verify(&proof, &threshold)?;
+        let euros =
+            CurrencySecret::<Blake3>::gen(&[4u8; 32], &CurrencyValue::new(&3413u32, "EUR")?);
+        assert_ne!(euros.commit_with_params(&params)?.root(), commitment.root());
+        Ok(())
+    }
+}
@@ -0,0 +1,296 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Countersignatures of verified proofs, for chains of custody
+//!
+//! A verifier which verified a proof can [`countersign`] it: the resulting
+//! [`VerifiedReceipt`] holds the [`Proof::transcript_hash`] of the verification, its time,
+//! the key of the verifier and its signature. A downstream party then relies on the receipt
+//! instead of verifying the proof again, and may [`VerifiedReceipt::endorse`] it in turn, each
+//! receipt of the chain linking to the digest of the previous one:
+//!
+//! ```text
+//! digest = H(RECEIPT_SALT || serialized receipt)
+//! ```
+//!
+//! [`validate_receipt_chain`] checks such a chain against the transcript the downstream party
+//! expects and the verifier keys it trusts. Signatures are produced through the
+//! [`ManifestSigner`] trait. A serialized receipt is laid out as follows (all integers are
+//! big-endian), the signature being over everything before it:
+//!
+//! ```text
+//! transcript (32) || verified_at (8) || previous digest (32) || I2OSP(len, 2) || verifier key
+//! || I2OSP(len, 2) || signature
+//! ```
+//!
+//! The previous digest of the first receipt of a chain is all zeros.
+use digest::Digest;
+use num_bigint::BigUint;
+
+use crate::errors::HwError;
+use crate::hashwires::{Commitment, Proof};
+use crate::manifest::ManifestSigner;
+use crate::serialization::{os2ip_u64, serialize_bounded, take_slice, tokenize_slice};
+use crate::traits::Hash;
+
+const RECEIPT_SALT: &[u8; 32] = b"u1234567890123456789012345678901";
+
+/// Checks signatures of receipts against the verifier keys trusted by a downstream party.
+pub trait VerifierKeys {
+    /// Whether `verifier_key` is trusted and `signature` is a valid signature of `message`
+    /// under it.
+    fn verify(&self, verifier_key: &[u8], message: &[u8], signature: &[u8]) -> bool;
+}
+
+/// A verifier's countersignature of a verified proof.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifiedReceipt {
+    transcript: [u8; 32],
+    verified_at: u64,
+    previous: [u8; 32],
+    verifier_key: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+/// Verify a HashWires proof of `threshold` over `commitment` in `context` at time `now`, and
+/// countersign it with `signer`, the key of the verifier being `verifier_key`.
+#[allow(clippy::too_many_arguments)]
+pub fn countersign<D: Hash, S: ManifestSigner>(
+    commitment: &Commitment<D>,
+    proof: &Proof,
+    threshold: &BigUint,
+    context: &[u8],
+    now: u64,
+    verifier_key: &[u8],
+    signer: &S,
+) -> Result<VerifiedReceipt, HwError> {
+    commitment.verify(proof, threshold)?;
+    let transcript = proof.transcript_hash(commitment, threshold, context)?;
+    Ok(VerifiedReceipt::sign(
+        transcript,
+        now,
+        [0u8; 32],
+        verifier_key,
+        signer,
+    ))
+}
+
+impl VerifiedReceipt {
+    fn sign<S: ManifestSigner>(
+        transcript: [u8; 32],
+        verified_at: u64,
+        previous: [u8; 32],
+        verifier_key: &[u8],
+        signer: &S,
+    ) -> Self {
+        let mut receipt = Self {
+            transcript,
+            verified_at,
+            previous,
+            verifier_key: verifier_key.to_vec(),
+            signature: Vec::new(),
+        };
+        receipt.signature = signer.sign(&receipt.message());
+        receipt
+    }
+
+    // The signed part of the serialized receipt.
+    fn message(&self) -> Vec<u8> {
+        [
+            &self.transcript[..],
+            &self.verified_at.to_be_bytes(),
+            &self.previous,
+            &serialize_bounded(&self.verifier_key, 2),
+        ]
+        .concat()
+    }
+
+    /// Endorse this receipt at time `now` without verifying the proof again, returning the
+    /// next receipt of the chain, signed by `signer` with the key `verifier_key`. The chain
+    /// links receipts through their digest under `D`.
+    pub fn endorse<D: Hash, S: ManifestSigner>(
+        &self,
+        now: u64,
+        verifier_key: &[u8],
+        signer: &S,
+    ) -> Self {
+        Self::sign(
+            self.transcript,
+            now,
+            self.digest::<D>(),
+            verifier_key,
+            signer,
+        )
+    }
+
+    /// The transcript hash of the verification.
+    pub fn transcript_hash(&self) -> &[u8; 32] {
+        &self.transcript
+    }
+
+    /// The time of the verification, or of the endorsement.
+    pub fn verified_at(&self) -> u64 {
+        self.verified_at
+    }
+
+    /// The key of the verifier.
+    pub fn verifier_key(&self) -> &[u8] {
+        &self.verifier_key
+    }
+
+    /// The digest of the previous receipt of the chain, all zeros for the first one.
+    pub fn previous(&self) -> &[u8; 32] {
+        &self.previous
+    }
+
+    /// The digest of this receipt under `D`.
+    pub fn digest<D: Hash>(&self) -> [u8; 32] {
+        let mut hasher = D::new();
+        hasher.update(RECEIPT_SALT);
+        hasher.update(self.serialize());
+        let mut output = [0u8; 32];
+        output.copy_from_slice(&hasher.finalize()[..32]);
+        output
+    }
+
+    /// Serialize the receipt.
+    pub fn serialize(&self) -> Vec<u8> {
+        [self.message(), serialize_bounded(&self.signature, 2)].concat()
+    }
+
+    /// Deserialize a receipt.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (transcript, remainder) = take_slice(input, 32)?;
+        let (verified_at, remainder) = take_slice(remainder, 8)?;
+        let (previous, remainder) = take_slice(remainder, 32)?;
+        let (verifier_key, remainder) = tokenize_slice(remainder, 2)?;
+        let (signature, remainder) = tokenize_slice(remainder, 2)?;
+        if !remainder.is_empty() {
+            return Err(HwError::SerializationError);
+        }
+        let mut receipt = Self {
+            transcript: [0u8; 32],
+            verified_at: os2ip_u64(verified_at)?,
+            previous: [0u8; 32],
+            verifier_key: verifier_key.to_vec(),
+            signature: signature.to_vec(),
+        };
+        receipt.transcript.copy_from_slice(transcript);
+        receipt.previous.copy_from_slice(previous);
+        Ok(receipt)
+    }
+}
+
+/// Check that `receipts` is a chain of custody of the verification with transcript hash
+/// `transcript`: the first receipt starts the chain, each next one links to the digest under
+/// `D` of the previous one and is not older than it, and every signature is valid under a key
+/// trusted by `keys`.
+pub fn validate_receipt_chain<D: Hash, K: VerifierKeys>(
+    receipts: &[VerifiedReceipt],
+    transcript: &[u8; 32],
+    keys: &K,
+) -> Result<(), HwError> {
+    let mut previous: Option<&VerifiedReceipt> = None;
+    for receipt in receipts.iter() {
+        let linked = match previous {
+            None => receipt.previous == [0u8; 32],
+            Some(previous) => {
+                receipt.previous == previous.digest::<D>()
+                    && receipt.verified_at >= previous.verified_at
+            }
+        };
+        if !linked
+            || receipt.transcript != *transcript
+            || !keys.verify(
+                &receipt.verifier_key,
+                &receipt.message(),
+                &receipt.signature,
+            )
+        {
+            return Err(HwError::ProofVerificationError);
+        }
+        previous = Some(receipt);
+    }
+    match previous {
+        Some(_) => Ok(()),
+        None => Err(HwError::ProofVerificationError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::Secret;
+    use crate::params::HwParams;
+    use blake3::Hasher as Blake3;
+
+    // Keyed hashes standing in for the verifiers' signature scheme, the key being public.
+    struct TestKey([u8; 32]);
+
+    impl ManifestSigner for TestKey {
+        fn sign(&self, message: &[u8]) -> Vec<u8> {
+            blake3::keyed_hash(&self.0, message).as_bytes().to_vec()
+        }
+    }
+
+    struct TrustedKeys(Vec<[u8; 32]>);
+
+    impl VerifierKeys for TrustedKeys {
+        fn verify(&self, verifier_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+            self.0
+                .iter()
+                .any(|key| key[..] == *verifier_key && TestKey(*key).sign(message) == signature)
+        }
+    }
+
+    #[test]
+    fn test_receipt_chain() -> Result<(), HwError> {
+        let params = HwParams::new(16, 32);
+        let secret = Secret::<Blake3>::gen(&[5u8; 32], &BigUint::from(3413u32));
+        let threshold = BigUint::from(2999u32);
+        let (commitment, proof) = secret.commit_and_prove(&params, &threshold)?;
+        let (upstream, downstream) = ([1u8; 32], [2u8; 32]);
+
+        let first = countersign(
+            &commitment,
+            &proof,
+            &threshold,
+            b"rp",
+            1000,
+            &upstream,
+            &TestKey(upstream),
+        )?;
+        let first = VerifiedReceipt::deserialize(&first.serialize())?;
+        let second = first.endorse::<Blake3, _>(1010, &downstream, &TestKey(downstream));
+        let transcript = proof.transcript_hash(&commitment, &threshold, b"rp")?;
+        let keys = TrustedKeys(vec![upstream, downstream]);
+        let chain = [first.clone(), second.clone()];
+        validate_receipt_chain::<Blake3, _>(&chain, &transcript, &keys)?;
+
+        // untrusted keys, broken links and other transcripts are rejected
+        let upstream_only = TrustedKeys(vec![upstream]);
+        assert!(validate_receipt_chain::<Blake3, _>(&chain, &transcript, &upstream_only).is_err());
+        assert!(validate_receipt_chain::<Blake3, _>(&[second], &transcript, &keys).is_err());
+        assert!(validate_receipt_chain::<Blake3, _>(&chain, &[0u8; 32], &keys).is_err());
+        assert!(validate_receipt_chain::<Blake3, _>(&[], &transcript, &keys).is_err());
+        let earlier = first.endorse::<Blake3, _>(999, &downstream, &TestKey(downstream));
+        assert!(
+            validate_receipt_chain::<Blake3, _>(&[first, earlier], &transcript, &keys).is_err()
+        );
+        // a proof which does not verify is not countersigned
+        let other = BigUint::from(3414u32);
+        assert!(countersign(
+            &commitment,
+            &proof,
+            &other,
+            b"rp",
+            1000,
+            &upstream,
+            &TestKey(upstream)
+        )
+        .is_err());
+        Ok(())
+    }
+}
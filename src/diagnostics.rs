@@ -0,0 +1,130 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Offline diagnosis of proofs failing verification, for fraud analysis
+//!
+//! A verifier only learns whether a proof verifies: the chain nodes, the PLR padding, the MDP
+//! salt and the MDP tree inclusion proof all end up in the recomputed root, so a mismatch in
+//! any of them looks the same. Given the secret of the commitment, [`diagnose`] regenerates
+//! the honest commitment and proof, and compares them to the failing ones in the order the
+//! verifier consumes them, reporting the first inconsistent [`ProofComponent`].
+//!
+//! Telling which component is wrong would be an oracle if exposed to provers, so the module is
+//! only built with the `diagnostics` feature, which is off by default, and is meant for
+//! offline analysis by the issuer.
+use num_bigint::BigUint;
+
+use crate::errors::HwError;
+use crate::hashwires::{Commitment, Proof, Secret};
+use crate::params::HwParams;
+use crate::traits::Hash;
+
+/// A component of a commitment or proof, in the order of verification.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProofComponent {
+    /// The commitment, which was not generated from the secret under the parameters.
+    Commitment,
+    /// The proof header: its digest, framing policy or MDP salt size.
+    Header,
+    /// The chain node of the given digit of the threshold, most significant digit first, or
+    /// the number of chain nodes if the index is past the honest ones.
+    ChainNode(usize),
+    /// The PLR padding.
+    PlrPadding,
+    /// The MDP salt.
+    MdpSalt,
+    /// The inclusion proof in the MDP tree.
+    SmtPath,
+}
+
+/// Find the first component of `commitment` and `proof` which differs from the honest ones
+/// generated from `secret` under `params` for `threshold`, or `None` if the proof verifies.
+/// Fails with [`HwError::MdpError`] if the threshold is larger than the secret value, no
+/// honest proof existing then.
+pub fn diagnose<D: Hash>(
+    secret: &Secret<D>,
+    params: &HwParams,
+    commitment: &Commitment<D>,
+    proof: &Proof,
+    threshold: &BigUint,
+) -> Result<Option<ProofComponent>, HwError> {
+    if commitment.verify(proof, threshold).is_ok() {
+        return Ok(None);
+    }
+    let (honest_commitment, honest) = secret.commit_and_prove(params, threshold)?;
+    if honest_commitment.serialize() != commitment.serialize() {
+        return Ok(Some(ProofComponent::Commitment));
+    }
+    if proof.digest_id != honest.digest_id
+        || proof.framing != honest.framing
+        || proof.mdp_salt.len() != honest.mdp_salt.len()
+    {
+        return Ok(Some(ProofComponent::Header));
+    }
+    if let Some(index) = (0..proof.chain_nodes.len().max(honest.chain_nodes.len()))
+        .find(|i| proof.chain_nodes.get(*i) != honest.chain_nodes.get(*i))
+    {
+        return Ok(Some(ProofComponent::ChainNode(index)));
+    }
+    let component = if proof.plr_padding != honest.plr_padding {
+        ProofComponent::PlrPadding
+    } else if proof.mdp_salt != honest.mdp_salt {
+        ProofComponent::MdpSalt
+    } else {
+        ProofComponent::SmtPath
+    };
+    Ok(Some(component))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+
+    #[test]
+    fn test_diagnose() -> Result<(), HwError> {
+        let params = HwParams::new(16, 32);
+        let secret = Secret::<Blake3>::gen(&[2u8; 32], &BigUint::from(3413u32));
+        let threshold = BigUint::from(2999u32);
+        let (commitment, proof) = secret.commit_and_prove(&params, &threshold)?;
+        let bytes = proof.serialize();
+        assert_eq!(
+            diagnose(&secret, &params, &commitment, &proof, &threshold)?,
+            None
+        );
+
+        type Tamper = fn(&mut Proof);
+        let tampered: [(Tamper, ProofComponent); 4] = [
+            (|p| p.chain_nodes[1][0] ^= 1, ProofComponent::ChainNode(1)),
+            (
+                |p| p.plr_padding.as_mut().unwrap()[0] ^= 1,
+                ProofComponent::PlrPadding,
+            ),
+            (|p| p.mdp_salt[0] ^= 1, ProofComponent::MdpSalt),
+            (
+                |p| *p.smt_inclusion_proof.last_mut().unwrap() ^= 1,
+                ProofComponent::SmtPath,
+            ),
+        ];
+        for (tamper, component) in tampered.iter() {
+            let mut proof = Proof::deserialize(&bytes)?;
+            tamper(&mut proof);
+            assert_eq!(
+                diagnose(&secret, &params, &commitment, &proof, &threshold)?,
+                Some(*component)
+            );
+        }
+
+        // the commitment is checked against the secret first
+        let other = Secret::<Blake3>::gen(&[3u8; 32], &BigUint::from(3413u32));
+        let mut proof = Proof::deserialize(&bytes)?;
+        proof.mdp_salt[0] ^= 1;
+        assert_eq!(
+            diagnose(&other, &params, &commitment, &proof, &threshold)?,
+            Some(ProofComponent::Commitment)
+        );
+        Ok(())
+    }
+}
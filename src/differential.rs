@@ -0,0 +1,130 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Differential verification, for the CI of downstream integrations
+//!
+//! [`verify_differential`] verifies a proof with the optimized verifier, which reuses a single
+//! hasher across the whole verification, and with a reference verifier written after the
+//! paper, which hashes every value with a fresh hasher, and panics if they disagree. Both
+//! paths check the inclusion in the MDP tree through `smtree`, so only the HashWires part of
+//! the pipeline (hash chains, PLR root and salted MDP root) is compared.
+use digest::Digest;
+use num_bigint::BigUint;
+use smtree::node_template::HashWiresNodeSmt;
+use smtree::proof::MerkleProof;
+use smtree::traits::{InclusionProvable, Serializable};
+
+use crate::digest_id::DigestId;
+use crate::dp::value_split_per_base;
+use crate::errors::HwError;
+use crate::hashwires::{compute_bitlength, Commitment, Proof};
+use crate::params::FramingPolicy;
+use crate::traits::Hash;
+
+/// Verify a HashWires proof over a commitment with both the optimized and the reference
+/// verifiers, returning their common outcome.
+///
+/// # Panics
+///
+/// Panics if the verifiers disagree, with both outcomes in the message.
+pub fn verify_differential<D: Hash>(
+    commitment: &Commitment<D>,
+    proof: &Proof,
+    threshold: &BigUint,
+) -> Result<(), HwError> {
+    let optimized = commitment.verify(proof, threshold);
+    let reference = reference_verify(commitment, proof, threshold);
+    assert_eq!(
+        optimized.as_ref().map_err(HwError::code),
+        reference.as_ref().map_err(HwError::code),
+        "optimized and reference verifiers disagree"
+    );
+    optimized
+}
+
+// The reference verifier, hashing every value with a fresh hasher.
+fn reference_verify<D: Hash>(
+    commitment: &Commitment<D>,
+    proof: &Proof,
+    threshold: &BigUint,
+) -> Result<(), HwError> {
+    if proof.digest_id != DigestId::of::<D>() {
+        return Err(HwError::DigestMismatchError);
+    }
+    let digits = value_split_per_base(threshold, compute_bitlength(commitment.base()));
+    if proof.chain_nodes.len() != digits.len() {
+        return Err(HwError::ProofVerificationError);
+    }
+
+    // Advance each chain node by its digit, then accumulate the nodes from the most significant
+    // one, starting from the padding if any.
+    let framing = proof.framing_policy();
+    let mut plr_root = proof.plr_padding.as_ref().map(|p| p.to_vec());
+    for (node, digit) in proof.chain_nodes.iter().zip(digits.iter()) {
+        let mut chain_node = node.to_vec();
+        for _ in 0..*digit {
+            chain_node = hash::<D>(&[&chain_node]);
+        }
+        plr_root = Some(match plr_root {
+            Some(previous) => framed_hash::<D>(framing, &[&previous, &chain_node]),
+            None => framed_hash::<D>(framing, &[&chain_node]),
+        });
+    }
+    let plr_root = match (plr_root, digits.is_empty()) {
+        (Some(root), false) => root,
+        _ => vec![0u8; 32],
+    };
+    let mdp_root = framed_hash::<D>(framing, &[&proof.mdp_salt, &plr_root]);
+
+    let inclusion = MerkleProof::<HashWiresNodeSmt<D>>::deserialize(&proof.smt_inclusion_proof)?;
+    match inclusion.verify_inclusion_proof(
+        &[HashWiresNodeSmt::<D>::new(mdp_root)],
+        &HashWiresNodeSmt::<D>::new(commitment.root().to_vec()),
+    ) {
+        true => Ok(()),
+        false => Err(HwError::ProofVerificationError),
+    }
+}
+
+fn hash<D: Hash>(inputs: &[&[u8]]) -> Vec<u8> {
+    let mut hasher = D::new();
+    for input in inputs.iter() {
+        hasher.update(input);
+    }
+    hasher.finalize()[..32].to_vec()
+}
+
+// Hash the concatenation of `inputs`, preceded by its length under the prefix-free framing.
+fn framed_hash<D: Hash>(framing: FramingPolicy, inputs: &[&[u8]]) -> Vec<u8> {
+    let length: usize = inputs.iter().map(|input| input.len()).sum();
+    match framing {
+        FramingPolicy::Concatenated => hash::<D>(inputs),
+        FramingPolicy::PrefixFree => {
+            hash::<D>(&[&[&(length as u64).to_be_bytes()[..]], inputs].concat())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::Secret;
+    use blake3::Hasher as Blake3;
+
+    #[test]
+    fn test_verify_differential() -> Result<(), HwError> {
+        for (base, value) in [(2u32, 1u32), (4, 402), (16, 3413), (256, 70000)].iter() {
+            let secret = Secret::<Blake3>::gen(&[2u8; 32], &BigUint::from(*value));
+            let commitment = secret.commit(*base, 32)?;
+            for threshold in [0u32, 1, value / 3, *value].iter() {
+                let threshold = BigUint::from(*threshold);
+                let proof = secret.prove(*base, 32, &threshold)?;
+                verify_differential(&commitment, &proof, &threshold)?;
+                assert!(verify_differential(&commitment, &proof, &(threshold + 1u32)).is_err());
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Identifiers of the digests used to generate commitments and proofs
+//!
+//! Serialized commitments and proofs start with the [`DigestId`] of the digest they were
+//! generated with, so that verifying under another digest fails explicitly, and so that
+//! [`crate::hashwires::verify_any`] can select the right one at runtime.
+//!
+//! A digest is recognized by its output on the empty input, as the [`Hash`] bound does not
+//! carry any name. Digests outside of this list are [`DigestId::Unspecified`].
+use digest::Digest;
+
+use crate::errors::HwError;
+use crate::traits::Hash;
+
+/// The first 8 bytes of the digest of the empty input, for each known digest.
+const FINGERPRINTS: [(DigestId, [u8; 8]); 4] = [
+    (
+        DigestId::Sha256,
+        [0xe3, 0xb0, 0xc4, 0x42, 0x98, 0xfc, 0x1c, 0x14],
+    ),
+    (
+        DigestId::Sha512,
+        [0xcf, 0x83, 0xe1, 0x35, 0x7e, 0xef, 0xb8, 0xbd],
+    ),
+    (
+        DigestId::Sha3_256,
+        [0xa7, 0xff, 0xc6, 0xf8, 0xbf, 0x1e, 0xd7, 0x66],
+    ),
+    (
+        DigestId::Blake3,
+        [0xaf, 0x13, 0x49, 0xb9, 0xf5, 0xf9, 0xa1, 0xa6],
+    ),
+];
+
+/// The digest a commitment or proof was generated with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum DigestId {
+    /// A digest without an assigned identifier
+    Unspecified = 0,
+    /// SHA-256
+    Sha256 = 1,
+    /// SHA-512
+    Sha512 = 2,
+    /// SHA3-256
+    Sha3_256 = 3,
+    /// BLAKE3
+    Blake3 = 4,
+}
+
+impl DigestId {
+    /// The identifier of the digest `D`.
+    pub fn of<D: Hash>() -> Self {
+        let output = D::new().finalize();
+        FINGERPRINTS
+            .iter()
+            .find(|(_, fingerprint)| output.starts_with(fingerprint))
+            .map(|(id, _)| *id)
+            .unwrap_or(DigestId::Unspecified)
+    }
+
+    /// The serialized identifier.
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// Parse a serialized identifier.
+    pub fn from_u8(id: u8) -> Result<Self, HwError> {
+        match id {
+            0 => Ok(DigestId::Unspecified),
+            1 => Ok(DigestId::Sha256),
+            2 => Ok(DigestId::Sha512),
+            3 => Ok(DigestId::Sha3_256),
+            4 => Ok(DigestId::Blake3),
+            _ => Err(HwError::SerializationError),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+    use sha2::{Sha256, Sha512};
+    use sha3::{Keccak256, Sha3_256};
+
+    #[test]
+    fn test_digest_id() -> Result<(), HwError> {
+        assert_eq!(DigestId::of::<Sha256>(), DigestId::Sha256);
+        assert_eq!(DigestId::of::<Sha512>(), DigestId::Sha512);
+        assert_eq!(DigestId::of::<Sha3_256>(), DigestId::Sha3_256);
+        assert_eq!(DigestId::of::<Blake3>(), DigestId::Blake3);
+        assert_eq!(DigestId::of::<Keccak256>(), DigestId::Unspecified);
+
+        for id in 0..5 {
+            assert_eq!(DigestId::from_u8(id)?.as_u8(), id);
+        }
+        assert!(DigestId::from_u8(5).is_err());
+        Ok(())
+    }
+}
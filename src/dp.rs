@@ -15,7 +15,7 @@ pub(crate) fn find_mdp(value: &BigUint, base: u32) -> Vec<BigUint> {
     ret.push(value.clone());
     let mut prev = value.clone();
 
-    while exp < *value {
+    while exp <= *value {
         // optimizing out the unneeded values to get a minimal dominating partition
         if &val_plus1 % &exp != BigUint::zero() {
             //  (x//b^i - 1) * b^i + (b-1)
@@ -81,18 +81,118 @@ fn coef(msg: &[u8], index: usize, bitlength: usize) -> u8 {
 // TODO: it currently works for bases 2, 4, 16, 256 (bitlength 1, 2, 4, 8) only
 /// Split value, based on base in bitlength, (supports bitlength 1, 2, 4, 8).
 pub(crate) fn value_split_per_base(value: &BigUint, bitlength: usize) -> Vec<u8> {
+    let mut ret: Vec<u8> = Vec::new();
+    value_split_per_base_into(value, bitlength, &mut ret);
+    ret
+}
+
+/// Same as [`value_split_per_base`], but appends the digits to `output`.
+pub(crate) fn value_split_per_base_into(value: &BigUint, bitlength: usize, output: &mut Vec<u8>) {
     let v_bytes = value.to_bytes_be();
     let v = v_bytes.as_slice();
 
-    let mut ret: Vec<u8> = Vec::new();
+    let mut leading = true;
     for i in 0..v.len() * 8 / bitlength {
         let coef = coef(v, i, bitlength);
-        if !(ret.is_empty() && coef == 0) {
+        if !(leading && coef == 0) {
             // throw leading zeros
-            ret.push(coef);
+            leading = false;
+            output.push(coef);
         }
     }
-    ret
+    // zero is a single digit, so that it can be committed to and proven like any other value
+    if leading {
+        output.push(0);
+    }
+}
+
+/// Same as [`value_split_per_base`], but writes the digits to the start of `output` without
+/// allocating, returning their number, or `None` if `output` is too short.
+pub(crate) fn value_split_per_base_in_place(
+    value: &BigUint,
+    bitlength: usize,
+    output: &mut [u8],
+) -> Option<usize> {
+    // zero is a single digit, like in `value_split_per_base`
+    let len = ((value.bits() as usize + bitlength - 1) / bitlength).max(1);
+    let digits = output.get_mut(..len)?;
+    digits.fill(0);
+    let mask = (1u64 << bitlength) - 1;
+    // the least significant limb holds the last 64 / bitlength digits, and so on
+    for (limb, chunk) in value
+        .iter_u64_digits()
+        .zip(digits.rchunks_mut(64 / bitlength))
+    {
+        for (i, digit) in chunk.iter_mut().rev().enumerate() {
+            *digit = ((limb >> (i * bitlength)) & mask) as u8;
+        }
+    }
+    Some(len)
+}
+
+/// Find the minimal dominating partition of `value` in `base` (2, 4, 16 or 256), along with
+/// the digits of each of its elements, as [`value_split_per_base`] would output them.
+///
+/// The digits are derived while building the partition, instead of being converted back from
+/// each `BigUint` element.
+pub(crate) fn find_mdp_with_splits(value: &BigUint, base: u32) -> (Vec<BigUint>, Vec<Vec<u8>>) {
+    let mut digits = Vec::new();
+    let mut offsets = Vec::new();
+    let mdp = find_mdp_with_splits_into(value, base, &mut digits, &mut offsets);
+    let splits = offsets
+        .windows(2)
+        .map(|w| digits[w[0]..w[1]].to_vec())
+        .collect();
+    (mdp, splits)
+}
+
+/// Same as [`find_mdp_with_splits`], but the digits of all of the elements are written
+/// (flattened) to `digits`, where element `i` is `digits[offsets[i]..offsets[i + 1]]`.
+pub(crate) fn find_mdp_with_splits_into(
+    value: &BigUint,
+    base: u32,
+    digits: &mut Vec<u8>,
+    offsets: &mut Vec<usize>,
+) -> Vec<BigUint> {
+    let bitlength = base.trailing_zeros() as usize;
+    let max_digit = (base - 1) as u8;
+    digits.clear();
+    offsets.clear();
+    offsets.push(0);
+    value_split_per_base_into(value, bitlength, digits);
+    let n = digits.len();
+    offsets.push(n);
+    let mut mdp = vec![value.clone()];
+
+    for i in 1..n {
+        // skip if (x + 1) % b^i == 0, i.e. the i least significant digits are all (b - 1)
+        if digits[n - i..n].iter().all(|d| *d == max_digit) {
+            continue;
+        }
+        // (x//b^i - 1) * b^i + (b-1): decrement the prefix and fill the i digits with (b - 1)
+        let start = digits.len();
+        for j in 0..n - i {
+            digits.push(digits[j]);
+        }
+        let mut j = digits.len() - 1;
+        while digits[j] == 0 {
+            digits[j] = max_digit;
+            j -= 1;
+        }
+        digits[j] -= 1;
+        digits.resize(start + n, max_digit);
+        let leading = digits[start..].iter().take_while(|d| **d == 0).count();
+        digits.drain(start..start + leading);
+
+        let prev = offsets[offsets.len() - 2];
+        if digits[prev..start] == digits[start..] {
+            digits.truncate(start);
+            continue;
+        }
+        offsets.push(digits.len());
+        mdp.push(BigUint::from_radix_be(&digits[start..], base).unwrap());
+    }
+    mdp
 }
 
 /// For demonstration purposes only, not used in the main Hashwires implementation.
@@ -266,6 +366,29 @@ fn test_mdp() {
     );
 }
 
+#[test]
+fn test_find_mdp_with_splits() {
+    let mut values: Vec<BigUint> = (0u32..1100).map(BigUint::from).collect();
+    values.push(BigUint::from_str_radix("16777209", 10).unwrap());
+    values.push(BigUint::from_str_radix("18446744073709551614", 10).unwrap());
+    values.push(BigUint::from_str_radix("100000000", 16).unwrap());
+    // 512 and 1024-bit values
+    values.push((BigUint::one() << 512u32) - BigUint::from(2u32));
+    values.push((BigUint::one() << 1023u32) + BigUint::from(0xdead_beefu32));
+    for base in [2u32, 4, 16, 256].iter() {
+        let bitlength = base.trailing_zeros() as usize;
+        for value in values.iter() {
+            let (mdp, splits) = find_mdp_with_splits(value, *base);
+            assert_eq!(mdp, find_mdp(value, *base));
+            let expected: Vec<Vec<u8>> = mdp
+                .iter()
+                .map(|v| value_split_per_base(v, bitlength))
+                .collect();
+            assert_eq!(splits, expected);
+        }
+    }
+}
+
 #[test]
 fn test_coef() {
     // base2 = 2^1
@@ -291,6 +414,6 @@ fn test_coef() {
     // base256 = 2^8
     // 16777209 (decimal) = 1111_1111_1111_1111_1111_1001 (binary)
     let number = &BigUint::from_str_radix("16777209", 10).unwrap();
-    let splits = value_split_per_base(&number, 8);
+    let splits = value_split_per_base(number, 8);
     assert_eq!(splits, vec![255, 255, 249]);
 }
@@ -0,0 +1,148 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Composite commitments under two digests, for hash agility
+//!
+//! A [`DualCommitment`] holds two HashWires commitments to the same secret, one per digest
+//! (e.g. BLAKE3 and SHA-256). Each component only involves its own digest, so a proof under
+//! either component remains sound as long as that component's digest is, even if the other
+//! one is broken. The components do not share a seed: each is committed with its own seed,
+//! derived from the secret's seed under the component's digest (see [`DualCommitment::secrets`]),
+//! so the two commitments are not related by anything but the value.
+//!
+//! A serialized dual commitment is laid out as `I2OSP(len, 2) || first || second`.
+use generic_array::typenum::U32;
+use num_bigint::BigUint;
+
+use crate::errors::HwError;
+use crate::hashes::generate_subseeds;
+use crate::hashwires::{Commitment, Proof, Secret};
+use crate::serialization::{serialize_bounded, tokenize_slice};
+use crate::traits::Hash;
+
+const DUAL_SEED_SALT: &[u8; 32] = b"L1234567890123456789012345678901";
+
+/// A component of a [`DualCommitment`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DualComponent {
+    /// The commitment under the first digest.
+    First,
+    /// The commitment under the second digest.
+    Second,
+}
+
+/// Commitments to the same secret under the digests `D1` and `D2`.
+pub struct DualCommitment<D1: Hash, D2: Hash> {
+    first: Commitment<D1>,
+    second: Commitment<D2>,
+}
+
+impl<D1: Hash, D2: Hash> DualCommitment<D1, D2> {
+    /// The secrets of the two components for `secret`: the same value, with seeds
+    /// `D1(DUAL_SEED_SALT || LE64(0) || seed)` and `D2(DUAL_SEED_SALT || LE64(1) || seed)`.
+    pub fn secrets(secret: &Secret<D1>) -> (Secret<D1>, Secret<D2>) {
+        let first = generate_subseeds::<D1, U32>(DUAL_SEED_SALT, &secret.seed, 1);
+        let second = generate_subseeds::<D2, U32>(DUAL_SEED_SALT, &secret.seed, 2);
+        (
+            Secret::gen(&first[0], &secret.value),
+            Secret::gen(&second[1], &secret.value),
+        )
+    }
+
+    /// Commit to `secret` under both digests, with the component secrets of
+    /// [`DualCommitment::secrets`].
+    pub fn commit(secret: &Secret<D1>, base: u32, max_number_bits: usize) -> Result<Self, HwError> {
+        let (first, second) = Self::secrets(secret);
+        Ok(Self {
+            first: first.commit(base, max_number_bits)?,
+            second: second.commit(base, max_number_bits)?,
+        })
+    }
+
+    /// The commitment under `D1`.
+    pub fn first(&self) -> &Commitment<D1> {
+        &self.first
+    }
+
+    /// The commitment under `D2`.
+    pub fn second(&self) -> &Commitment<D2> {
+        &self.second
+    }
+
+    /// Verify a proof generated by `component`'s secret (see [`DualCommitment::secrets`]).
+    pub fn verify(
+        &self,
+        component: DualComponent,
+        proof: &Proof,
+        threshold: &BigUint,
+    ) -> Result<(), HwError> {
+        match component {
+            DualComponent::First => self.first.verify(proof, threshold),
+            DualComponent::Second => self.second.verify(proof, threshold),
+        }
+    }
+
+    /// Serialize the dual commitment.
+    pub fn serialize(&self) -> Vec<u8> {
+        [
+            serialize_bounded(&self.first.serialize(), 2),
+            self.second.serialize(),
+        ]
+        .concat()
+    }
+
+    /// Deserialize a dual commitment.
+    pub fn deserialize(bytes: &[u8], base: u32) -> Result<Self, HwError> {
+        let (first, second) = tokenize_slice(bytes, 2)?;
+        Ok(Self {
+            first: Commitment::deserialize(first, base)?,
+            second: Commitment::deserialize(second, base)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+    use sha2::Sha256;
+
+    #[test]
+    fn test_dual_commitment() -> Result<(), HwError> {
+        let secret = Secret::<Blake3>::gen(&[2u8; 32], &BigUint::from(3413u32));
+        let commitment = DualCommitment::<Blake3, Sha256>::commit(&secret, 16, 32)?;
+        let commitment =
+            DualCommitment::<Blake3, Sha256>::deserialize(&commitment.serialize(), 16)?;
+        let (first_secret, second_secret) = DualCommitment::<Blake3, Sha256>::secrets(&secret);
+        assert_eq!(
+            commitment.first().serialize(),
+            first_secret.commit(16, 32)?.serialize()
+        );
+        // Neither component reuses the secret's seed, nor the other component's.
+        assert_ne!(first_secret.seed, secret.seed);
+        assert_ne!(second_secret.seed, secret.seed);
+        assert_ne!(first_secret.seed, second_secret.seed);
+
+        let threshold = BigUint::from(2999u32);
+        let first = first_secret.prove(16, 32, &threshold)?;
+        let second = second_secret.prove(16, 32, &threshold)?;
+        assert!(commitment
+            .verify(
+                DualComponent::First,
+                &secret.prove(16, 32, &threshold)?,
+                &threshold
+            )
+            .is_err());
+        commitment.verify(DualComponent::First, &first, &threshold)?;
+        commitment.verify(DualComponent::Second, &second, &threshold)?;
+        assert!(commitment
+            .verify(DualComponent::Second, &first, &threshold)
+            .is_err());
+        assert!(commitment
+            .verify(DualComponent::First, &second, &threshold)
+            .is_err());
+        Ok(())
+    }
+}
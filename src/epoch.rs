@@ -0,0 +1,314 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Freshness of presentations through per-epoch public salts
+//!
+//! Instead of issuing a nonce per request (see [`crate::session`]), a verifier may only want
+//! proofs presented in the current epoch (e.g. this week). The issuer derives one public salt
+//! per epoch with an [`EpochSchedule`], publishes the anchor of the schedule once, and the
+//! salt of each epoch when it starts. The salts form a hash chain consumed backwards, so that
+//! the salt of an epoch cannot be predicted before it is published, and verifiers check it
+//! against the anchor with `epoch + 1` hashes:
+//!
+//! ```text
+//! salt_{n-1} = H(EPOCH_CHAIN_SALT || H(EPOCH_SEED_SALT || master key))
+//! salt_{e-1} = H(EPOCH_CHAIN_SALT || salt_e), down to salt_0
+//! anchor = H(EPOCH_CHAIN_SALT || salt_0)
+//! ```
+//!
+//! A holder folds the salt of the epoch into an [`EpochProof`], binding the presentation to
+//! the epoch through the transcript hash of the proof (see [`Proof::transcript_hash`]) with
+//! the context `EPOCH_CONTEXT_SALT || epoch (8) || salt || context`. An [`EpochVerifier`]
+//! only accepts presentations bound to the current epoch, or to the few previous ones it
+//! tolerates. As for nonces, HashWires proofs are deterministic, so the binding shows when the
+//! presentation was assembled and not who assembled it: epochs limit the replay of a
+//! presentation to its epoch.
+//!
+//! A serialized epoch proof is laid out as `epoch (8) || salt (32) || binding (32) || proof`.
+use std::marker::PhantomData;
+
+use digest::Digest;
+use num_bigint::BigUint;
+
+use crate::errors::HwError;
+use crate::hashwires::{Commitment, Proof};
+use crate::serialization::{os2ip_u64, take_slice};
+use crate::traits::Hash;
+
+const EPOCH_SEED_SALT: &[u8; 32] = b"D1234567890123456789012345678901";
+const EPOCH_CHAIN_SALT: &[u8; 32] = b"E1234567890123456789012345678901";
+const EPOCH_CONTEXT_SALT: &[u8; 32] = b"F1234567890123456789012345678901";
+
+/// The maximum number of epochs of a schedule, bounding the verification of its salts.
+pub const MAX_EPOCHS: u64 = 1 << 20;
+
+fn chain_step<D: Hash>(salt: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = D::new();
+    hasher.update(EPOCH_CHAIN_SALT);
+    hasher.update(salt);
+    let mut output = [0u8; 32];
+    output.copy_from_slice(&hasher.finalize()[..32]);
+    output
+}
+
+fn chain<D: Hash>(salt: &[u8; 32], steps: u64) -> [u8; 32] {
+    (0..steps).fold(*salt, |salt, _| chain_step::<D>(&salt))
+}
+
+/// The schedule of the epoch salts of an issuer.
+pub struct EpochSchedule<D: Hash> {
+    last: [u8; 32],
+    epochs: u64,
+    _d: PhantomData<D>,
+}
+
+impl<D: Hash> EpochSchedule<D> {
+    /// The schedule of `epochs` epochs under the secret `master` key of the issuer. Fails with
+    /// [`HwError::ParameterError`] for no epochs or more than [`MAX_EPOCHS`].
+    pub fn new(master: &[u8], epochs: u64) -> Result<Self, HwError> {
+        if epochs == 0 || epochs > MAX_EPOCHS {
+            return Err(HwError::ParameterError);
+        }
+        let mut hasher = D::new();
+        hasher.update(EPOCH_SEED_SALT);
+        hasher.update(master);
+        let mut seed = [0u8; 32];
+        seed.copy_from_slice(&hasher.finalize()[..32]);
+        Ok(Self {
+            last: chain_step::<D>(&seed),
+            epochs,
+            _d: PhantomData,
+        })
+    }
+
+    /// The anchor of the schedule, published once by the issuer.
+    pub fn anchor(&self) -> [u8; 32] {
+        chain::<D>(&self.last, self.epochs)
+    }
+
+    /// The salt of `epoch`, counting from 0, to be published when the epoch starts. Fails
+    /// with [`HwError::ParameterError`] past the last epoch.
+    pub fn salt(&self, epoch: u64) -> Result<[u8; 32], HwError> {
+        if epoch >= self.epochs {
+            return Err(HwError::ParameterError);
+        }
+        Ok(chain::<D>(&self.last, self.epochs - 1 - epoch))
+    }
+}
+
+/// Check that `salt` is the salt of `epoch` in the schedule of anchor `anchor`.
+pub fn verify_epoch_salt<D: Hash>(
+    anchor: &[u8; 32],
+    epoch: u64,
+    salt: &[u8; 32],
+) -> Result<(), HwError> {
+    if epoch >= MAX_EPOCHS || chain::<D>(salt, epoch + 1) != *anchor {
+        return Err(HwError::ProofVerificationError);
+    }
+    Ok(())
+}
+
+fn epoch_context(epoch: u64, salt: &[u8; 32], context: &[u8]) -> Vec<u8> {
+    [&EPOCH_CONTEXT_SALT[..], &epoch.to_be_bytes(), salt, context].concat()
+}
+
+/// A proof bound to the salt of an epoch.
+#[derive(Clone)]
+pub struct EpochProof {
+    epoch: u64,
+    salt: [u8; 32],
+    binding: [u8; 32],
+    proof: Proof,
+}
+
+impl EpochProof {
+    /// Bind `proof` of `threshold` over `commitment`, presented in `context`, to `epoch` of
+    /// published salt `salt`, failing if the transcript hash cannot be computed (see
+    /// [`Proof::transcript_hash`]).
+    pub fn new<D: Hash>(
+        commitment: &Commitment<D>,
+        proof: Proof,
+        threshold: &BigUint,
+        context: &[u8],
+        epoch: u64,
+        salt: &[u8; 32],
+    ) -> Result<Self, HwError> {
+        let binding =
+            proof.transcript_hash(commitment, threshold, &epoch_context(epoch, salt, context))?;
+        Ok(Self {
+            epoch,
+            salt: *salt,
+            binding,
+            proof,
+        })
+    }
+
+    /// The epoch the proof is bound to.
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// The HashWires proof.
+    pub fn proof(&self) -> &Proof {
+        &self.proof
+    }
+
+    /// Serialize the epoch proof.
+    pub fn serialize(&self) -> Vec<u8> {
+        [
+            &self.epoch.to_be_bytes()[..],
+            &self.salt,
+            &self.binding,
+            &self.proof.serialize(),
+        ]
+        .concat()
+    }
+
+    /// Deserialize an epoch proof.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (epoch, remainder) = take_slice(input, 8)?;
+        let (salt_bytes, remainder) = take_slice(remainder, 32)?;
+        let (binding_bytes, remainder) = take_slice(remainder, 32)?;
+        let (mut salt, mut binding) = ([0u8; 32], [0u8; 32]);
+        salt.copy_from_slice(salt_bytes);
+        binding.copy_from_slice(binding_bytes);
+        Ok(Self {
+            epoch: os2ip_u64(epoch)?,
+            salt,
+            binding,
+            proof: Proof::deserialize(remainder)?,
+        })
+    }
+}
+
+/// Accepts proofs bound to the current epoch of a schedule. Times are provided by the
+/// caller, in seconds.
+pub struct EpochVerifier<D: Hash> {
+    anchor: [u8; 32],
+    start: u64,
+    length: u64,
+    grace: u64,
+    _d: PhantomData<D>,
+}
+
+impl<D: Hash> EpochVerifier<D> {
+    /// A verifier of the schedule of anchor `anchor`, whose epoch 0 starts at `start` and
+    /// whose epochs last `length` seconds. Fails with [`HwError::ParameterError`] for epochs
+    /// of no length.
+    pub fn new(anchor: &[u8; 32], start: u64, length: u64) -> Result<Self, HwError> {
+        if length == 0 {
+            return Err(HwError::ParameterError);
+        }
+        Ok(Self {
+            anchor: *anchor,
+            start,
+            length,
+            grace: 0,
+            _d: PhantomData,
+        })
+    }
+
+    /// Also accept proofs bound to the `grace` epochs before the current one, e.g. 1 for
+    /// holders whose proofs were assembled just before the end of an epoch.
+    pub fn with_grace(mut self, grace: u64) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// The epoch at time `now`, or `None` before the start of the schedule.
+    pub fn current_epoch(&self, now: u64) -> Option<u64> {
+        now.checked_sub(self.start)
+            .map(|elapsed| elapsed / self.length)
+    }
+
+    /// Verify `proof` of `threshold` over `commitment`, presented in `context` at time `now`.
+    /// Fails with [`HwError::ReplayError`] if the proof is bound to another epoch than the
+    /// accepted ones, and with [`HwError::ProofVerificationError`] if its salt or binding is
+    /// invalid.
+    pub fn verify(
+        &self,
+        commitment: &Commitment<D>,
+        proof: &EpochProof,
+        threshold: &BigUint,
+        context: &[u8],
+        now: u64,
+    ) -> Result<(), HwError> {
+        let current = self.current_epoch(now).ok_or(HwError::ReplayError)?;
+        if proof.epoch > current || current - proof.epoch > self.grace {
+            return Err(HwError::ReplayError);
+        }
+        verify_epoch_salt::<D>(&self.anchor, proof.epoch, &proof.salt)?;
+        let binding = proof.proof.transcript_hash(
+            commitment,
+            threshold,
+            &epoch_context(proof.epoch, &proof.salt, context),
+        )?;
+        if binding != proof.binding {
+            return Err(HwError::ProofVerificationError);
+        }
+        commitment.verify(&proof.proof, threshold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::Secret;
+    use blake3::Hasher as Blake3;
+
+    #[test]
+    fn test_epoch_proof() -> Result<(), HwError> {
+        let schedule = EpochSchedule::<Blake3>::new(&[6u8; 32], 52)?;
+        let anchor = schedule.anchor();
+        for epoch in [0u64, 1, 51].iter() {
+            verify_epoch_salt::<Blake3>(&anchor, *epoch, &schedule.salt(*epoch)?)?;
+        }
+        assert!(schedule.salt(52).is_err());
+        assert!(verify_epoch_salt::<Blake3>(&anchor, 2, &schedule.salt(3)?).is_err());
+
+        let week = 7 * 24 * 3600;
+        let verifier = EpochVerifier::<Blake3>::new(&anchor, 1000, week)?;
+        let secret = Secret::<Blake3>::gen(&[7u8; 32], &BigUint::from(3413u32));
+        let commitment = secret.commit(16, 32)?;
+        let threshold = BigUint::from(2999u32);
+        let proof = secret.prove(16, 32, &threshold)?;
+        let now = 1000 + 3 * week + 5;
+        assert_eq!(verifier.current_epoch(now), Some(3));
+        let bound = EpochProof::new(
+            &commitment,
+            proof.clone(),
+            &threshold,
+            b"rp",
+            3,
+            &schedule.salt(3)?,
+        )?;
+        let bound = EpochProof::deserialize(&bound.serialize())?;
+        verifier.verify(&commitment, &bound, &threshold, b"rp", now)?;
+
+        // other contexts, thresholds or salts are rejected
+        assert!(verifier
+            .verify(&commitment, &bound, &threshold, b"other", now)
+            .is_err());
+        assert!(verifier
+            .verify(&commitment, &bound, &BigUint::from(2998u32), b"rp", now)
+            .is_err());
+        let forged = EpochProof::new(&commitment, proof.clone(), &threshold, b"rp", 3, &[0; 32])?;
+        assert!(matches!(
+            verifier.verify(&commitment, &forged, &threshold, b"rp", now),
+            Err(HwError::ProofVerificationError)
+        ));
+
+        // proofs of past epochs are only accepted within the grace period
+        assert!(matches!(
+            verifier.verify(&commitment, &bound, &threshold, b"rp", now + week),
+            Err(HwError::ReplayError)
+        ));
+        verifier
+            .with_grace(1)
+            .verify(&commitment, &bound, &threshold, b"rp", now + week)?;
+        assert!(EpochVerifier::<Blake3>::new(&anchor, 1000, 0).is_err());
+        Ok(())
+    }
+}
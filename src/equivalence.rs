@@ -0,0 +1,247 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Golden outputs, to check that wasm32 and native targets agree
+//!
+//! HashWires outputs must not depend on the platform, yet some steps handle `usize` values
+//! (the I2OSP and OS2IP encodings, the shuffled leaf positions), whose width differs between
+//! wasm32 and 64-bit targets, and the shuffling consumes its random generator word by word.
+//! This module, built with the `equivalence` feature, recomputes a small set of outputs and
+//! compares their SHA-256 digests to [`GOLDEN_OUTPUTS`], frozen on a native target. Downstream
+//! CI runs [`check_equivalence`] under both wasm32 (e.g. with `wasm-bindgen-test`) and
+//! native, and [`equivalence_outputs`] names the diverging outputs when it fails.
+//!
+//! The outputs are:
+//!
+//! - `i2osp`: I2OSP encodings of values up to `u32::MAX` over 1 to 9 bytes, and their OS2IP
+//!   decodings;
+//! - `shuffle`: the shuffled leaf positions for several numbers of MDP elements and leaves;
+//! - `commitment/base{b}/{bits}` and `proof/base{b}/{bits}`: a serialized commitment and proof
+//!   in each of the supported bases, for 32, 64 and 256-bit values. Past their format header,
+//!   they are byte for byte the outputs of the original implementation.
+use digest::Digest;
+use num_bigint::BigUint;
+use sha2::Sha256;
+
+use crate::errors::HwError;
+use crate::hashwires::Secret;
+use crate::params::SUPPORTED_BASES;
+use crate::serialization::{i2osp, os2ip};
+use crate::shuffle::deterministic_index_shuffling;
+
+const SEED: [u8; 32] = [0xe9; 32];
+
+/// The SHA-256 digests of the outputs, in hexadecimal, frozen on a native target.
+pub const GOLDEN_OUTPUTS: &[(&str, &str)] = &[
+    (
+        "i2osp",
+        "845cc6f6ea1c08fe36ea190bd0a36aa7dbf070e1e2ee17eb23224c1d0f55a279",
+    ),
+    (
+        "shuffle",
+        "7dd98d5af01426f4bf0a2b80e9e5aebbd753f2d93c60f99569055038a73fb818",
+    ),
+    (
+        "commitment/base2/32",
+        "8efb1d196278e4b4fd9724d8d6b5237fd58e7fc1881000de6fe05c423adc3d18",
+    ),
+    (
+        "proof/base2/32",
+        "beaff252d96dfb74de8b9cc00bc14d3a36a8274633aec8d247dcf4d1af3e5ef5",
+    ),
+    (
+        "commitment/base2/64",
+        "309a8bc623682a8d3f0a8869bf5c4651144b360c2186fbabddd22b6f887f4979",
+    ),
+    (
+        "proof/base2/64",
+        "bc5f93ea02ea48e575df8f3d090c2266bd7cb21b61ca9dd7bf2d0a6c95087b2f",
+    ),
+    (
+        "commitment/base2/256",
+        "a0ef2d8b594db70b042387a6a0df81ed5052323eee2dd13560c842b0acf26a5b",
+    ),
+    (
+        "proof/base2/256",
+        "e28e8521a6970f8437804d7cedf6bc02a2befcdac6fb6ca0b5389d0288f7ef3c",
+    ),
+    (
+        "commitment/base4/32",
+        "31c88fc485299a8db3a6587c80c30979d62544125f4cc6a2edf31f96cfd0915c",
+    ),
+    (
+        "proof/base4/32",
+        "4bde2d622bf31195d3376b28a607adde5064ec2641e57da80c3e20bd572f0257",
+    ),
+    (
+        "commitment/base4/64",
+        "33635f05814f81dd7222a0c29ed43394dd5fa9597dd68402af1a506df493bd0a",
+    ),
+    (
+        "proof/base4/64",
+        "9e9f12157d92839546c5b72cdfab5903280213897a007571071627a392ce5d16",
+    ),
+    (
+        "commitment/base4/256",
+        "e97b63daf2f34f71dbe0557ee572787eb5945320db31ff6bcff5ec37165557e1",
+    ),
+    (
+        "proof/base4/256",
+        "f459c7279d6ac0af138fd8f508c5bb67d332d030c70b9d40c6b9d1dd4df4ee76",
+    ),
+    (
+        "commitment/base16/32",
+        "2b55996197fb895d5820ecf7f8ae8ddb4043271b56d16f185e76739753401fe3",
+    ),
+    (
+        "proof/base16/32",
+        "d808e1ca95b632c75a6471b34a48c058593edd1d82bcd0277f2b3bd67c62370c",
+    ),
+    (
+        "commitment/base16/64",
+        "7d7ec35e0e28e29174534060bcd9be4c1ec98d5124d5646d6c492db99129033e",
+    ),
+    (
+        "proof/base16/64",
+        "c8f40b568287fa89314e27442fb0590f3e93f9981cec65795b4124a1a686e050",
+    ),
+    (
+        "commitment/base16/256",
+        "4c06206eb4b62066d9a5aebec20e760d537308eee3deb4ab6428d9b1fe37ee98",
+    ),
+    (
+        "proof/base16/256",
+        "cc347a0c912a749e952cb1dcd192c1c7a4a2587a09a757163fedb98b16b67ff3",
+    ),
+    (
+        "commitment/base256/32",
+        "1c33f23591f7e1b83cd3e63183f99e7197fcaa43bf96f31f7a4bc58a284e99ef",
+    ),
+    (
+        "proof/base256/32",
+        "e96d4df418aeb260d6adb49ac815979e5cf01e799405f0e5ee9b4092b9a053e6",
+    ),
+    (
+        "commitment/base256/64",
+        "24ef465d3ea655d88da3027325e10359a551aa1108ce5c88f86cde80def3cfb3",
+    ),
+    (
+        "proof/base256/64",
+        "eb99ca9c87fbe1364d992bff6a9b48f7bc50802639ac6074a24a5176e0621445",
+    ),
+    (
+        "commitment/base256/256",
+        "7bd12311cbec205c1c09aef89bfa394a1a3048e02b5df1dc57c202b37c614a5a",
+    ),
+    (
+        "proof/base256/256",
+        "fd3ba9ba87de9d1907c0f2195a8bbfcd8bfebd48ccb4ead919291403d530a0ae",
+    ),
+];
+
+fn digest(input: &[u8]) -> String {
+    hex::encode(Sha256::digest(input))
+}
+
+// The value, threshold and maximum number of bits of each proof.
+fn proof_inputs() -> Vec<(BigUint, BigUint, usize)> {
+    vec![
+        (BigUint::from(3413u32), BigUint::from(2999u32), 32),
+        (
+            BigUint::from(0x1234_5678_9abc_def0u64),
+            BigUint::from(0x1234_5678_0000_0000u64),
+            64,
+        ),
+        (
+            BigUint::from_bytes_be(&[0xa5; 31]),
+            BigUint::from_bytes_be(&[0x5a; 31]),
+            256,
+        ),
+    ]
+}
+
+/// Compute the outputs on this platform, along with their SHA-256 digests in hexadecimal.
+pub fn equivalence_outputs() -> Result<Vec<(String, String)>, HwError> {
+    let mut outputs = Vec::new();
+
+    let mut encodings = Vec::new();
+    for value in [
+        0usize,
+        1,
+        0xff,
+        0x100,
+        0xffff,
+        0x0123_4567,
+        u32::MAX as usize,
+    ]
+    .iter()
+    {
+        for length in 1..=9 {
+            if length < 4 && *value >> (8 * length) != 0 {
+                continue;
+            }
+            let encoded = i2osp(*value, length)?;
+            encodings.extend_from_slice(&encoded);
+            match os2ip(&encoded) {
+                Ok(decoded) => encodings.extend_from_slice(&(decoded as u64).to_be_bytes()),
+                Err(e) => encodings.extend_from_slice(&e.code().as_u16().to_be_bytes()),
+            }
+        }
+    }
+    outputs.push(("i2osp".to_string(), digest(&encodings)));
+
+    let mut positions = Vec::new();
+    for (required, max_num) in [(1usize, 1usize), (3, 16), (16, 32), (40, 256)].iter() {
+        for position in deterministic_index_shuffling(*required, *max_num, SEED)? {
+            positions.extend_from_slice(&(position as u64).to_be_bytes());
+        }
+    }
+    outputs.push(("shuffle".to_string(), digest(&positions)));
+
+    for base in SUPPORTED_BASES.iter() {
+        for (value, threshold, max_number_bits) in proof_inputs().iter() {
+            let secret = Secret::<Sha256>::gen(&SEED, value);
+            let commitment = secret.commit(*base, *max_number_bits)?;
+            let proof = secret.prove(*base, *max_number_bits, threshold)?;
+            let name = format!("base{}/{}", base, max_number_bits);
+            outputs.push((
+                format!("commitment/{}", name),
+                digest(&commitment.serialize()),
+            ));
+            outputs.push((format!("proof/{}", name), digest(&proof.serialize())));
+        }
+    }
+    Ok(outputs)
+}
+
+/// Check that the outputs on this platform match [`GOLDEN_OUTPUTS`], failing with
+/// [`HwError::KnownAnswerError`] otherwise.
+pub fn check_equivalence() -> Result<(), HwError> {
+    let outputs = equivalence_outputs()?;
+    let matches = outputs.len() == GOLDEN_OUTPUTS.len()
+        && outputs
+            .iter()
+            .zip(GOLDEN_OUTPUTS.iter())
+            .all(|((name, digest), (golden_name, golden))| name == golden_name && digest == golden);
+    match matches {
+        true => Ok(()),
+        false => Err(HwError::KnownAnswerError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equivalence() -> Result<(), HwError> {
+        let outputs = equivalence_outputs()?;
+        for ((name, digest), (golden_name, golden)) in outputs.iter().zip(GOLDEN_OUTPUTS.iter()) {
+            assert_eq!((name.as_str(), digest.as_str()), (*golden_name, *golden));
+        }
+        assert_eq!(outputs.len(), 2 + 6 * SUPPORTED_BASES.len());
+        check_equivalence()
+    }
+}
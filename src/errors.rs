@@ -5,6 +5,7 @@
 
 //! A list of error types which are produced during an execution of the protocol
 use displaydoc::Display;
+use smtree::error::DecodingError;
 use thiserror::Error;
 
 /// Represents an error in the manipulation of internal cryptographic data
@@ -18,10 +19,233 @@ pub enum HwError {
     SeedLengthError,
     /// Verification of proof failed
     ProofVerificationError,
-    /// Error in decoding merkle proof
-    MerkleProofDecodingError,
+    /// Error in decoding merkle proof: {0}
+    MerkleProofDecodingError(#[from] DecodingError),
     /// Proving value is bigger than the issued value
     MdpError,
     /// Error in serializing / deserializing bytestrings
     SerializationError,
+    /// Invalid or unsupported parameters
+    ParameterError,
+    /// Nonce is unknown, expired or already used
+    ReplayError,
+    /// Output differs from the known answer
+    KnownAnswerError,
+    /// Input was generated with another digest
+    DigestMismatchError,
+    /// Verification would exceed the work limit
+    WorkLimitError,
+    /// Request is bound to another context
+    ContextError,
+    /// Decoding or verification panicked
+    PanicError,
+    /// More MDP elements or chain nodes than the parameters allow
+    MdpSizeError,
+    /// Proof does not satisfy the policy of the verifier
+    PolicyError,
+    /// Threshold is in another currency than the committed amount
+    CurrencyError,
+    /// Attribute label maps to the position of another attribute
+    LabelCollisionError,
+    /// Secret store failed, or holds no seed for the credential
+    SecretStoreError,
+}
+
+/// Stable numeric codes for [`HwError`], for FFI, metrics and alerting. A code is never
+/// reassigned, so new errors get new codes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u16)]
+pub enum HwErrorCode {
+    /// See [`HwError::ShuffleError`]
+    ShuffleError = 1,
+    /// See [`HwError::InclusionProofError`]
+    InclusionProofError = 2,
+    /// See [`HwError::SeedLengthError`]
+    SeedLengthError = 3,
+    /// See [`HwError::ProofVerificationError`]
+    ProofVerificationError = 4,
+    /// See [`HwError::MerkleProofDecodingError`]
+    MerkleProofDecodingError = 5,
+    /// See [`HwError::MdpError`]
+    MdpError = 6,
+    /// See [`HwError::SerializationError`]
+    SerializationError = 7,
+    /// See [`HwError::ParameterError`]
+    ParameterError = 8,
+    /// See [`HwError::ReplayError`]
+    ReplayError = 9,
+    /// See [`HwError::KnownAnswerError`]
+    KnownAnswerError = 10,
+    /// See [`HwError::DigestMismatchError`]
+    DigestMismatchError = 11,
+    /// See [`HwError::WorkLimitError`]
+    WorkLimitError = 12,
+    /// See [`HwError::ContextError`]
+    ContextError = 13,
+    /// See [`HwError::PanicError`]
+    PanicError = 14,
+    /// See [`HwError::MdpSizeError`]
+    MdpSizeError = 15,
+    /// See [`HwError::PolicyError`]
+    PolicyError = 16,
+    /// See [`HwError::CurrencyError`]
+    CurrencyError = 17,
+    /// See [`HwError::LabelCollisionError`]
+    LabelCollisionError = 18,
+    /// See [`HwError::SecretStoreError`]
+    SecretStoreError = 19,
+}
+
+impl HwErrorCode {
+    /// The numeric value of the code.
+    pub fn as_u16(self) -> u16 {
+        self as u16
+    }
+
+    /// The category of the error.
+    pub fn category(self) -> HwErrorCategory {
+        match self {
+            HwErrorCode::ProofVerificationError => HwErrorCategory::InvalidProof,
+            HwErrorCode::SeedLengthError
+            | HwErrorCode::MerkleProofDecodingError
+            | HwErrorCode::SerializationError
+            | HwErrorCode::ParameterError
+            | HwErrorCode::DigestMismatchError
+            | HwErrorCode::MdpSizeError
+            | HwErrorCode::CurrencyError
+            | HwErrorCode::LabelCollisionError => HwErrorCategory::Malformed,
+            HwErrorCode::ReplayError
+            | HwErrorCode::WorkLimitError
+            | HwErrorCode::ContextError
+            | HwErrorCode::PolicyError => HwErrorCategory::PolicyViolation,
+            HwErrorCode::ShuffleError
+            | HwErrorCode::InclusionProofError
+            | HwErrorCode::MdpError
+            | HwErrorCode::KnownAnswerError
+            | HwErrorCode::PanicError
+            | HwErrorCode::SecretStoreError => HwErrorCategory::InternalError,
+        }
+    }
+
+    /// Whether the same request may succeed if retried. Only internal errors are retriable,
+    /// except panics: the other categories are determined by the input and the rules of the
+    /// verifier, and a panic caught on an input is raised again by the same input, so retrying
+    /// them is pointless.
+    pub fn is_retriable(self) -> bool {
+        self.category() == HwErrorCategory::InternalError && self != HwErrorCode::PanicError
+    }
+}
+
+/// The categories of [`HwError`], for gateways to pick a response (e.g. 400, 403 or 500)
+/// without matching on every error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HwErrorCategory {
+    /// The input could not be decoded, or does not fit the parameters.
+    Malformed,
+    /// The proof is well-formed, but does not verify.
+    InvalidProof,
+    /// The proof was rejected by a rule of the verifier (context, replay, cost or policy).
+    PolicyViolation,
+    /// The library failed, independently of the input.
+    InternalError,
+}
+
+impl From<&HwError> for HwErrorCode {
+    fn from(error: &HwError) -> Self {
+        match error {
+            HwError::ShuffleError => HwErrorCode::ShuffleError,
+            HwError::InclusionProofError => HwErrorCode::InclusionProofError,
+            HwError::SeedLengthError => HwErrorCode::SeedLengthError,
+            HwError::ProofVerificationError => HwErrorCode::ProofVerificationError,
+            HwError::MerkleProofDecodingError(_) => HwErrorCode::MerkleProofDecodingError,
+            HwError::MdpError => HwErrorCode::MdpError,
+            HwError::SerializationError => HwErrorCode::SerializationError,
+            HwError::ParameterError => HwErrorCode::ParameterError,
+            HwError::ReplayError => HwErrorCode::ReplayError,
+            HwError::KnownAnswerError => HwErrorCode::KnownAnswerError,
+            HwError::DigestMismatchError => HwErrorCode::DigestMismatchError,
+            HwError::WorkLimitError => HwErrorCode::WorkLimitError,
+            HwError::ContextError => HwErrorCode::ContextError,
+            HwError::PanicError => HwErrorCode::PanicError,
+            HwError::MdpSizeError => HwErrorCode::MdpSizeError,
+            HwError::PolicyError => HwErrorCode::PolicyError,
+            HwError::CurrencyError => HwErrorCode::CurrencyError,
+            HwError::LabelCollisionError => HwErrorCode::LabelCollisionError,
+            HwError::SecretStoreError => HwErrorCode::SecretStoreError,
+        }
+    }
+}
+
+impl From<HwError> for HwErrorCode {
+    fn from(error: HwError) -> Self {
+        HwErrorCode::from(&error)
+    }
+}
+
+impl HwError {
+    /// The stable numeric code of this error.
+    pub fn code(&self) -> HwErrorCode {
+        HwErrorCode::from(self)
+    }
+
+    /// The category of this error.
+    pub fn category(&self) -> HwErrorCategory {
+        self.code().category()
+    }
+
+    /// Whether the same request may succeed if retried (see [`HwErrorCode::is_retriable`]).
+    pub fn is_retriable(&self) -> bool {
+        self.code().is_retriable()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes() {
+        assert_eq!(HwError::ShuffleError.code().as_u16(), 1);
+        assert_eq!(HwError::ProofVerificationError.code().as_u16(), 4);
+        assert_eq!(
+            HwErrorCode::from(HwError::KnownAnswerError),
+            HwErrorCode::KnownAnswerError
+        );
+        assert_eq!(HwErrorCode::KnownAnswerError.as_u16(), 10);
+        assert_eq!(
+            HwError::SerializationError.category(),
+            HwErrorCategory::Malformed
+        );
+        assert_eq!(
+            HwError::ProofVerificationError.category(),
+            HwErrorCategory::InvalidProof
+        );
+        assert_eq!(
+            HwError::ContextError.category(),
+            HwErrorCategory::PolicyViolation
+        );
+        assert!(HwError::SecretStoreError.is_retriable());
+        assert!(!HwError::ReplayError.is_retriable());
+        assert!(!HwError::SerializationError.is_retriable());
+        assert_eq!(
+            HwError::PanicError.category(),
+            HwErrorCategory::InternalError
+        );
+        assert!(!HwError::PanicError.is_retriable());
+    }
+
+    #[test]
+    fn test_decoding_error_source() {
+        use std::error::Error;
+
+        let error = HwError::from(DecodingError::BytesNotEnough);
+        assert_eq!(error.code(), HwErrorCode::MerkleProofDecodingError);
+        assert_eq!(error.code().as_u16(), 5);
+        assert_eq!(
+            error
+                .source()
+                .and_then(|source| source.downcast_ref::<DecodingError>()),
+            Some(&DecodingError::BytesNotEnough)
+        );
+    }
 }
@@ -0,0 +1,146 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Exhaustive correctness checks over small domains
+//!
+//! For a small `max_number_bits`, [`check_exhaustive`] enumerates every (value, threshold) pair
+//! of the domain and checks that a proof is generated and verifies if and only if the
+//! threshold is lower than or equal to the value, and that no proof verifies against a
+//! threshold larger than the committed value. [`check_domain`] runs the same checks on the
+//! values below a bound, for parameters whose domain is too large to be enumerated, such as
+//! base 256, which needs 16 bits for two digits.
+//!
+//! The checks are enabled by the `exhaustive` feature, and are best run in release mode:
+//!
+//! ```text
+//! cargo test --release --features exhaustive exhaustive
+//! ```
+use num_bigint::BigUint;
+
+use crate::errors::HwError;
+use crate::hashwires::Secret;
+use crate::params::HwParams;
+use crate::prover::HwProver;
+use crate::traits::Hash;
+
+/// A (value, threshold) pair on which the checks failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Counterexample {
+    /// The committed value.
+    pub value: u64,
+    /// The proven threshold.
+    pub threshold: u64,
+}
+
+/// Check every (value, threshold) pair with `params`, returning the first pair on which the
+/// checks fail. Fails with [`HwError::ParameterError`] if `params` are invalid or their
+/// `max_number_bits` exceeds 16.
+pub fn check_exhaustive<D: Hash>(params: &HwParams) -> Result<Option<Counterexample>, HwError> {
+    if params.max_number_bits > MAX_DOMAIN_BITS {
+        return Err(HwError::ParameterError);
+    }
+    check_domain::<D>(params, 1 << params.max_number_bits)
+}
+
+/// Check every (value, threshold) pair below `domain` with `params`, returning the first pair
+/// on which the checks fail. Fails with [`HwError::ParameterError`] if `params` are invalid, or
+/// if `domain` exceeds `2^16` or the values of `params`.
+pub fn check_domain<D: Hash>(
+    params: &HwParams,
+    domain: u64,
+) -> Result<Option<Counterexample>, HwError> {
+    params.validate()?;
+    if domain > 1 << MAX_DOMAIN_BITS.min(params.max_number_bits) {
+        return Err(HwError::ParameterError);
+    }
+    match check_pairs::<D>(params, domain) {
+        Ok(()) => Ok(None),
+        Err(counterexample) => Ok(Some(counterexample)),
+    }
+}
+
+fn check_pairs<D: Hash>(params: &HwParams, domain: u64) -> Result<(), Counterexample> {
+    let mut prover = HwProver::<D>::new();
+    for value in 0..domain {
+        let secret = Secret::<D>::gen(&seed(value), &BigUint::from(value));
+        let commitment = secret
+            .commit(params.base, params.max_number_bits)
+            .map_err(|_| Counterexample {
+                value,
+                threshold: 0,
+            })?;
+        for threshold in 0..domain {
+            let counterexample = Counterexample { value, threshold };
+            let result = prover.prove(
+                &secret,
+                params.base,
+                params.max_number_bits,
+                &BigUint::from(threshold),
+            );
+            let proof = match result {
+                Ok(_) if threshold > value => return Err(counterexample),
+                Ok(proof) => proof,
+                Err(HwError::MdpError) if threshold > value => continue,
+                Err(_) => return Err(counterexample),
+            };
+            commitment
+                .verify(&proof, &BigUint::from(threshold))
+                .map_err(|_| counterexample)?;
+            // Soundness: no proof holds for a threshold larger than the value. Every larger
+            // threshold is only tried with the strongest proof, to keep the checks quadratic in
+            // the size of the domain.
+            let larger = match threshold == value {
+                true => value + 1..domain,
+                false => value + 1..(value + 2).min(domain),
+            };
+            if larger
+                .into_iter()
+                .any(|t| commitment.verify(&proof, &BigUint::from(t)).is_ok())
+            {
+                return Err(counterexample);
+            }
+        }
+    }
+    Ok(())
+}
+
+// The largest domain, in bits, which is enumerated.
+const MAX_DOMAIN_BITS: usize = 16;
+
+fn seed(value: u64) -> [u8; 32] {
+    let mut seed = [0u8; 32];
+    seed[..8].copy_from_slice(&value.to_be_bytes());
+    seed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+
+    #[test]
+    fn test_exhaustive() -> Result<(), HwError> {
+        for (base, max_number_bits) in [(2, 6), (4, 6), (16, 8)].iter() {
+            let params = HwParams::new(*base, *max_number_bits);
+            assert_eq!(check_exhaustive::<Blake3>(&params)?, None, "base {}", base);
+        }
+        // base 256 needs 16 bits for two digits: check the values up to the second digit
+        assert_eq!(check_domain::<Blake3>(&HwParams::new(256, 16), 272)?, None);
+
+        assert!(matches!(
+            check_exhaustive::<Blake3>(&HwParams::new(3, 8)),
+            Err(HwError::ParameterError)
+        ));
+        assert!(matches!(
+            check_exhaustive::<Blake3>(&HwParams::new(16, 32)),
+            Err(HwError::ParameterError)
+        ));
+        assert!(matches!(
+            check_domain::<Blake3>(&HwParams::new(16, 8), 257),
+            Err(HwError::ParameterError)
+        ));
+        Ok(())
+    }
+}
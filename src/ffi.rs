@@ -0,0 +1,84 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! C ABI entry points, for FFI and WASM consumers
+//!
+//! Only verification is exposed: it takes serialized inputs and returns `0` on success, or the
+//! [`HwErrorCode`] of the failure.
+use std::slice;
+
+use num_bigint::BigUint;
+
+use crate::errors::{HwError, HwErrorCode};
+use crate::hashwires::verify_any;
+
+/// Verify a serialized proof over a serialized commitment, for a big-endian `threshold`.
+/// Returns `0` on success, and the [`HwErrorCode`] of the failure otherwise.
+///
+/// # Safety
+///
+/// Each pointer must be valid for reads of its length, or be null with a length of zero.
+#[no_mangle]
+pub unsafe extern "C" fn hw_verify(
+    commitment: *const u8,
+    commitment_len: usize,
+    base: u32,
+    proof: *const u8,
+    proof_len: usize,
+    threshold: *const u8,
+    threshold_len: usize,
+) -> u16 {
+    let result = as_slice(commitment, commitment_len).and_then(|commitment| {
+        let proof = as_slice(proof, proof_len)?;
+        let threshold = BigUint::from_bytes_be(as_slice(threshold, threshold_len)?);
+        verify_any(commitment, base, proof, &threshold)
+    });
+    match result {
+        Ok(()) => 0,
+        Err(e) => HwErrorCode::from(e).as_u16(),
+    }
+}
+
+unsafe fn as_slice<'a>(data: *const u8, len: usize) -> Result<&'a [u8], HwError> {
+    match (data.is_null(), len) {
+        (true, 0) => Ok(&[]),
+        (true, _) => Err(HwError::SerializationError),
+        (false, _) => Ok(slice::from_raw_parts(data, len)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::Secret;
+    use std::ptr;
+
+    #[test]
+    fn test_hw_verify() -> Result<(), HwError> {
+        let secret = Secret::<sha2::Sha256>::gen(&[0u8; 32], &BigUint::from(3413u32));
+        let commitment = secret.commit(16, 32)?.serialize();
+        let proof = secret.prove(16, 32, &2999u32)?.serialize();
+        let verify = |threshold: &[u8]| unsafe {
+            hw_verify(
+                commitment.as_ptr(),
+                commitment.len(),
+                16,
+                proof.as_ptr(),
+                proof.len(),
+                threshold.as_ptr(),
+                threshold.len(),
+            )
+        };
+
+        assert_eq!(verify(&2999u32.to_be_bytes()), 0);
+        assert_eq!(
+            verify(&3000u32.to_be_bytes()),
+            HwErrorCode::ProofVerificationError.as_u16()
+        );
+        let null = unsafe { hw_verify(ptr::null(), 33, 16, ptr::null(), 0, ptr::null(), 0) };
+        assert_eq!(null, HwErrorCode::SerializationError.as_u16());
+        Ok(())
+    }
+}
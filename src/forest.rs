@@ -0,0 +1,359 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! A sparse Merkle forest: fixed-height trees sharded by index prefix, under a super-root
+//!
+//! A leaf index is split into a shard index (its `shard_bits` most significant bits) and an
+//! index within that shard (its `shard_height` least significant bits). Each shard is a sparse
+//! Merkle tree, and the roots of the non-empty shards are the leaves of the top tree, whose
+//! root is the super-root. Leaves and shard roots are hashed along with their index, so a
+//! proof cannot present a value under another index.
+//!
+//! A serialized [`ForestProof`] is laid out as follows (all integers are big-endian):
+//!
+//! ```text
+//! shard height (1) || #shards (2)
+//! for each shard: shard index (8) || I2OSP(len, 2) || shard root || I2OSP(len, 2) || shard proof
+//! I2OSP(len, 2) || top proof
+//! ```
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+use digest::Digest;
+use generic_array::typenum::U32;
+use smtree::index::TreeIndex;
+use smtree::node_template::HashWiresNodeSmt;
+use smtree::pad_secret::Secret as SmtSecret;
+use smtree::proof::MerkleProof;
+use smtree::traits::{InclusionProvable, Serializable};
+use smtree::tree::SparseMerkleTree;
+
+use crate::errors::HwError;
+use crate::hashes::generate_subseeds;
+use crate::serialization::{
+    i2osp_bounded, os2ip, os2ip_u64, serialize_bounded, take_slice, tokenize_slice,
+};
+use crate::traits::Hash;
+
+const FOREST_LEAF_SALT: &[u8; 32] = b"a1234567890123456789012345678901";
+const FOREST_SHARD_SALT: &[u8; 32] = b"b1234567890123456789012345678901";
+const FOREST_PADDING_SALT: &[u8; 32] = b"c1234567890123456789012345678901";
+
+type Node<D> = HashWiresNodeSmt<D>;
+
+/// Fixed-height sparse Merkle trees, sharded by index prefix under a single super-root.
+pub struct Forest<D: Hash> {
+    shard_bits: usize,
+    shard_height: usize,
+    seed: Vec<u8>,
+    shards: BTreeMap<u64, BTreeMap<u64, Vec<u8>>>,
+    _d: PhantomData<D>,
+}
+
+impl<D: Hash> Forest<D> {
+    /// Create an empty forest of `2^shard_bits` shards of height `shard_height`. The padding
+    /// nodes are derived from `seed`, which must be kept secret.
+    pub fn new(shard_bits: usize, shard_height: usize, seed: &[u8]) -> Result<Self, HwError> {
+        if shard_bits == 0 || shard_height == 0 || shard_bits + shard_height > 64 {
+            return Err(HwError::ParameterError);
+        }
+        Ok(Self {
+            shard_bits,
+            shard_height,
+            seed: seed.to_vec(),
+            shards: BTreeMap::new(),
+            _d: PhantomData,
+        })
+    }
+
+    /// Insert (or replace) the leaf at `index`.
+    pub fn insert(&mut self, index: u64, leaf: &[u8]) -> Result<(), HwError> {
+        let (shard, local) = self.split(index)?;
+        self.shards
+            .entry(shard)
+            .or_default()
+            .insert(local, leaf.to_vec());
+        Ok(())
+    }
+
+    /// The number of leaves in the forest.
+    pub fn len(&self) -> usize {
+        self.shards.values().map(|s| s.len()).sum()
+    }
+
+    /// Whether the forest is empty.
+    pub fn is_empty(&self) -> bool {
+        self.shards.is_empty()
+    }
+
+    /// The super-root of the forest.
+    pub fn root(&self) -> Vec<u8> {
+        self.top_tree().get_root_raw().serialize()
+    }
+
+    /// Prove the inclusion of the leaves at `indexes`, possibly spread across shards.
+    pub fn prove(&self, indexes: &[u64]) -> Result<ForestProof, HwError> {
+        let mut per_shard: BTreeMap<u64, Vec<TreeIndex>> = BTreeMap::new();
+        for index in indexes.iter() {
+            let (shard, local) = self.split(*index)?;
+            if !self
+                .shards
+                .get(&shard)
+                .into_iter()
+                .any(|s| s.contains_key(&local))
+            {
+                return Err(HwError::InclusionProofError);
+            }
+            per_shard
+                .entry(shard)
+                .or_default()
+                .push(TreeIndex::from_u64(self.shard_height, local));
+        }
+        if per_shard.is_empty() {
+            return Err(HwError::InclusionProofError);
+        }
+
+        let mut shards = Vec::with_capacity(per_shard.len());
+        for (shard, mut locals) in per_shard.into_iter() {
+            locals.sort();
+            locals.dedup();
+            let tree = self.shard_tree(shard);
+            let proof = MerkleProof::<Node<D>>::generate_inclusion_proof(&tree, &locals)
+                .ok_or(HwError::InclusionProofError)?;
+            shards.push(ShardProof {
+                shard,
+                root: tree.get_root_raw().serialize(),
+                proof: proof.serialize(),
+            });
+        }
+
+        let top_indexes: Vec<TreeIndex> = shards
+            .iter()
+            .map(|s| TreeIndex::from_u64(self.shard_bits, s.shard))
+            .collect();
+        let top = MerkleProof::<Node<D>>::generate_inclusion_proof(&self.top_tree(), &top_indexes)
+            .ok_or(HwError::InclusionProofError)?;
+        Ok(ForestProof {
+            shard_height: self.shard_height,
+            shards,
+            top: top.serialize(),
+        })
+    }
+
+    fn split(&self, index: u64) -> Result<(u64, u64), HwError> {
+        let height = self.shard_bits + self.shard_height;
+        if height < 64 && index >> height != 0 {
+            return Err(HwError::ParameterError);
+        }
+        Ok((
+            index >> self.shard_height,
+            index & ((1u64 << self.shard_height) - 1),
+        ))
+    }
+
+    fn shard_tree(&self, shard: u64) -> SparseMerkleTree<Node<D>> {
+        let leaves: Vec<(TreeIndex, Node<D>)> = self.shards[&shard]
+            .iter()
+            .map(|(local, leaf)| {
+                let index = (shard << self.shard_height) | local;
+                (
+                    TreeIndex::from_u64(self.shard_height, *local),
+                    Node::<D>::new(salted_node::<D>(FOREST_LEAF_SALT, index, leaf)),
+                )
+            })
+            .collect();
+        let seed = [&self.seed[..], &shard.to_be_bytes()].concat();
+        build::<D>(self.shard_height, &leaves, &seed)
+    }
+
+    fn top_tree(&self) -> SparseMerkleTree<Node<D>> {
+        let leaves: Vec<(TreeIndex, Node<D>)> = self
+            .shards
+            .keys()
+            .map(|shard| {
+                let root = self.shard_tree(*shard).get_root_raw().serialize();
+                (
+                    TreeIndex::from_u64(self.shard_bits, *shard),
+                    Node::<D>::new(salted_node::<D>(FOREST_SHARD_SALT, *shard, &root)),
+                )
+            })
+            .collect();
+        build::<D>(self.shard_bits, &leaves, &self.seed)
+    }
+}
+
+fn build<D: Hash>(
+    height: usize,
+    leaves: &[(TreeIndex, Node<D>)],
+    seed: &[u8],
+) -> SparseMerkleTree<Node<D>> {
+    let secret = generate_subseeds::<D, U32>(FOREST_PADDING_SALT, seed, 1);
+    let mut tree = SparseMerkleTree::new(height);
+    tree.build(leaves, &SmtSecret::from_bytes(&secret[0]).unwrap());
+    tree
+}
+
+fn salted_node<D: Hash>(salt: &[u8], index: u64, value: &[u8]) -> Vec<u8> {
+    let mut hasher = D::new();
+    hasher.update(salt);
+    hasher.update(index.to_be_bytes());
+    hasher.update(value);
+    hasher.finalize()[..32].to_vec()
+}
+
+struct ShardProof {
+    shard: u64,
+    root: Vec<u8>,
+    proof: Vec<u8>,
+}
+
+/// A proof of inclusion of leaves, possibly across shards, under the super-root of a
+/// [`Forest`].
+pub struct ForestProof {
+    shard_height: usize,
+    shards: Vec<ShardProof>,
+    top: Vec<u8>,
+}
+
+impl ForestProof {
+    /// Verify that `leaves` (pairs of index and value) are exactly the leaves this proof was
+    /// generated for, under the super-root `root`. Leaves claimed twice for the same index are
+    /// rejected, even with the same value.
+    pub fn verify<D: Hash>(&self, root: &[u8], leaves: &[(u64, &[u8])]) -> Result<(), HwError> {
+        let mut leaves = leaves.to_vec();
+        leaves.sort_by_key(|(index, _)| *index);
+        if leaves.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return Err(HwError::ProofVerificationError);
+        }
+
+        let mut remaining = &leaves[..];
+        let mut shard_nodes = Vec::with_capacity(self.shards.len());
+        for shard in self.shards.iter() {
+            let count = remaining
+                .iter()
+                .take_while(|(index, _)| index >> self.shard_height == shard.shard)
+                .count();
+            if count == 0 {
+                return Err(HwError::ProofVerificationError);
+            }
+            let nodes: Vec<Node<D>> = remaining[..count]
+                .iter()
+                .map(|(index, leaf)| {
+                    Node::<D>::new(salted_node::<D>(FOREST_LEAF_SALT, *index, leaf))
+                })
+                .collect();
+            remaining = &remaining[count..];
+
+            let proof = MerkleProof::<Node<D>>::deserialize(&shard.proof)?;
+            if !proof.verify_inclusion_proof(&nodes, &Node::<D>::new(shard.root.clone())) {
+                return Err(HwError::ProofVerificationError);
+            }
+            shard_nodes.push(Node::<D>::new(salted_node::<D>(
+                FOREST_SHARD_SALT,
+                shard.shard,
+                &shard.root,
+            )));
+        }
+        if !remaining.is_empty() {
+            return Err(HwError::ProofVerificationError);
+        }
+
+        let top = MerkleProof::<Node<D>>::deserialize(&self.top)?;
+        if !top.verify_inclusion_proof(&shard_nodes, &Node::<D>::new(root.to_vec())) {
+            return Err(HwError::ProofVerificationError);
+        }
+        Ok(())
+    }
+
+    /// Serialize the proof.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = vec![self.shard_height as u8];
+        result.extend_from_slice(&i2osp_bounded(self.shards.len(), 2));
+        for shard in self.shards.iter() {
+            result.extend_from_slice(&shard.shard.to_be_bytes());
+            result.extend_from_slice(&serialize_bounded(&shard.root, 2));
+            result.extend_from_slice(&serialize_bounded(&shard.proof, 2));
+        }
+        result.extend_from_slice(&serialize_bounded(&self.top, 2));
+        result
+    }
+
+    /// Deserialize a proof.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (shard_height, remainder) = take_slice(input, 1)?;
+        let (count, mut remainder) = take_slice(remainder, 2)?;
+        let count = os2ip(count)?;
+        let mut shards = Vec::with_capacity(count);
+        for _ in 0..count {
+            let (shard, rest) = take_slice(remainder, 8)?;
+            let (root, rest) = tokenize_slice(rest, 2)?;
+            let (proof, rest) = tokenize_slice(rest, 2)?;
+            shards.push(ShardProof {
+                shard: os2ip_u64(shard)?,
+                root: root.to_vec(),
+                proof: proof.to_vec(),
+            });
+            remainder = rest;
+        }
+        let (top, remainder) = tokenize_slice(remainder, 2)?;
+        if !remainder.is_empty() || shard_height[0] == 0 || shard_height[0] > 63 {
+            return Err(HwError::SerializationError);
+        }
+        Ok(Self {
+            shard_height: shard_height[0] as usize,
+            shards,
+            top: top.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+
+    #[test]
+    fn test_forest_cross_shard_proof() -> Result<(), HwError> {
+        let mut forest = Forest::<Blake3>::new(4, 8, &[4u8; 32])?;
+        let indexes = [3u64, 17, 300, 301, 4000];
+        for index in indexes.iter() {
+            forest.insert(*index, &index.to_be_bytes())?;
+        }
+        assert_eq!(forest.len(), 5);
+        assert!(forest.insert(1 << 12, &[0u8]).is_err());
+        let root = forest.root();
+
+        let values: Vec<[u8; 8]> = [300u64, 3, 4000].iter().map(|i| i.to_be_bytes()).collect();
+        let leaves = [
+            (300u64, &values[0][..]),
+            (3, &values[1][..]),
+            (4000, &values[2][..]),
+        ];
+        let proof = forest.prove(&[300, 3, 4000])?;
+        let proof = ForestProof::deserialize(&proof.serialize())?;
+        proof.verify::<Blake3>(&root, &leaves)?;
+
+        // Wrong values, indexes or root are rejected.
+        assert!(proof
+            .verify::<Blake3>(&root, &[(300, &values[1][..]), leaves[1], leaves[2]])
+            .is_err());
+        assert!(proof
+            .verify::<Blake3>(&root, &[(301, &values[0][..]), leaves[1], leaves[2]])
+            .is_err());
+        assert!(proof.verify::<Blake3>(&root, &leaves[..2]).is_err());
+        // A second claim for the same index is not ignored, whatever its value.
+        let conflicting = [leaves[0], leaves[1], leaves[2], (300, &values[1][..])];
+        assert!(matches!(
+            proof.verify::<Blake3>(&root, &conflicting),
+            Err(HwError::ProofVerificationError)
+        ));
+        assert!(proof
+            .verify::<Blake3>(&root, &[leaves[0], leaves[1], leaves[2], leaves[0]])
+            .is_err());
+        assert!(proof.verify::<Blake3>(&[0u8; 32], &leaves).is_err());
+        assert!(forest.prove(&[5]).is_err());
+        Ok(())
+    }
+}
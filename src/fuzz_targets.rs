@@ -0,0 +1,98 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Stable entry points for fuzzing harnesses
+//!
+//! Each function exercises a full decoding (and verification) path on arbitrary bytes, and
+//! converts panics into [`HwError::PanicError`], so that harnesses can tell rejected inputs
+//! apart from bugs:
+//!
+//! ```ignore
+//! fuzz_target!(|data: &[u8]| {
+//!     if let Err(HwError::PanicError) = hashwires::fuzz_targets::fuzz_proof_deserialize(data) {
+//!         panic!("proof decoding panicked");
+//!     }
+//! });
+//! ```
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use num_bigint::BigUint;
+
+use crate::errors::HwError;
+use crate::hashwires::{verify_any_with_limit, Proof};
+use crate::serialization::{os2ip, take_slice};
+
+/// The maximum verification cost of the proofs verified by [`fuzz_verify`], in hash
+/// iterations, so that fuzzers do not time out on valid but expensive inputs.
+pub const FUZZ_MAX_HASH_ITERATIONS: usize = 1 << 16;
+
+/// Deserialize a proof, and serialize it back.
+pub fn fuzz_proof_deserialize(bytes: &[u8]) -> Result<Vec<u8>, HwError> {
+    guard(|| Ok(Proof::deserialize(bytes)?.serialize()))
+}
+
+/// Verify a serialized proof for a big-endian threshold over a commitment, given as
+/// `base (4) || commitment`, selecting the digest from the commitment.
+pub fn fuzz_verify(
+    commitment_bytes: &[u8],
+    proof_bytes: &[u8],
+    threshold_bytes: &[u8],
+) -> Result<(), HwError> {
+    guard(|| {
+        let (base, commitment) = take_slice(commitment_bytes, 4)?;
+        verify_any_with_limit(
+            commitment,
+            os2ip(base)? as u32,
+            proof_bytes,
+            &BigUint::from_bytes_be(threshold_bytes),
+            FUZZ_MAX_HASH_ITERATIONS,
+        )
+    })
+}
+
+fn guard<T>(f: impl FnOnce() -> Result<T, HwError>) -> Result<T, HwError> {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(Err(HwError::PanicError))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::Secret;
+    use sha2::Sha256;
+
+    #[test]
+    fn test_fuzz_targets() -> Result<(), HwError> {
+        let secret = Secret::<Sha256>::gen(&[1u8; 32], &BigUint::from(3413u32));
+        let commitment = [
+            &16u32.to_be_bytes()[..],
+            &secret.commit(16, 32)?.serialize(),
+        ]
+        .concat();
+        let proof = secret.prove(16, 32, &2999u32)?.serialize();
+
+        assert_eq!(fuzz_proof_deserialize(&proof)?, proof);
+        fuzz_verify(&commitment, &proof, &2999u32.to_be_bytes())?;
+        assert!(fuzz_verify(&commitment, &proof, &3000u32.to_be_bytes()).is_err());
+        for len in 0..proof.len() {
+            assert!(!matches!(
+                fuzz_proof_deserialize(&proof[..len]),
+                Err(HwError::PanicError)
+            ));
+            assert!(!matches!(
+                fuzz_verify(
+                    &commitment[..len.min(commitment.len())],
+                    &proof[..len],
+                    &[1]
+                ),
+                Err(HwError::PanicError)
+            ));
+        }
+        assert!(matches!(
+            guard::<()>(|| panic!("bug")),
+            Err(HwError::PanicError)
+        ));
+        Ok(())
+    }
+}
@@ -3,135 +3,279 @@
 // This source code is licensed under the MIT license found in the
 // LICENSE file in the root directory of this source tree.
 
+//! The hash-based key derivation functions used by HashWires, exposed so that other protocols
+//! can reuse the exact same constructions.
+use std::marker::PhantomData;
+
 use crate::hashwires::PlrPaddingSize;
+use crate::params::{ByteOrderPolicy, ChainLengthPolicy, FramingPolicy};
 use crate::traits::Hash;
 use digest::Digest;
-use generic_array::{typenum::Unsigned, ArrayLength, GenericArray};
+use generic_array::{
+    typenum::{Unsigned, U32},
+    ArrayLength, GenericArray,
+};
 
+/// Salt deriving the seeds of the hash chains.
 pub const LEAF_SALT: &[u8; 32] = b"01234567890123456789012345678901";
+/// Salt deriving the salts of the PLR roots.
 pub const TOP_SALT: &[u8; 32] = b"11234567890123456789012345678901";
+/// Salt deriving the PLR padding nodes.
 pub const PADDING_SALT: &[u8; 32] = b"21234567890123456789012345678901";
+/// Salt deriving the secret of the MDP tree padding nodes.
 pub const SMTREE_PADDING_SALT: &[u8; 32] = b"31234567890123456789012345678901";
 
-/// Output padding node + PLR accumulator
-#[inline]
-pub(crate) fn plr_accumulator<D: Hash>(
-    seed: &[u8],
-    list: &[[u8; 32]],
-    max_length: usize,
-    desired_length: usize,
-) -> (
-    GenericArray<u8, PlrPaddingSize>,
-    Option<GenericArray<u8, PlrPaddingSize>>,
-) {
-    let mut hasher = D::new();
-    let mut plr_path_node = if list.len() < max_length {
-        hasher.update(PADDING_SALT);
-        hasher.update(seed);
-        let result = &hasher.finalize_reset()[..PlrPaddingSize::to_usize()];
-        hasher.update(&result);
-        Some(GenericArray::clone_from_slice(result))
-    } else {
-        None
-    };
-
-    let mut output = vec![0; PlrPaddingSize::to_usize()];
-    list.iter().enumerate().for_each(|(i, v)| {
-        if i != 0 {
-            hasher.update(&output);
-            if list.len() > desired_length && i == list.len() - desired_length {
-                plr_path_node = Some(GenericArray::clone_from_slice(
-                    &output[..PlrPaddingSize::to_usize()],
-                ));
-            }
-        }
-        hasher.update(v);
-        output.copy_from_slice(hasher.finalize_reset().as_slice());
-    });
+// Outputs of `N` bytes are truncated digests, so `N` must not exceed the digest's output size.
+// The check is evaluated at compile time, for each instantiation.
+struct OutputLength<D, N>(PhantomData<(D, N)>);
 
-    (GenericArray::clone_from_slice(&output), plr_path_node)
+impl<D: Hash, N: ArrayLength<u8>> OutputLength<D, N> {
+    const CHECK: () = assert!(
+        N::USIZE <= <D::OutputSize as Unsigned>::USIZE,
+        "output length exceeds the digest output size"
+    );
 }
 
-/// Computes a hash chain using a seed and number of iterations.
-#[inline]
-pub(crate) fn hash_chain<D: Hash>(seed: &[u8], iterations: usize) -> [u8; 32] {
-    // TODO: reuse hashers in single threaded applications, i.e. via finalize_reset()
-    let mut output = [0u8; 32];
-    let mut hasher = D::new();
-    output.copy_from_slice(seed);
-    for _i in 0..iterations {
-        hasher.update(&output);
-        output.copy_from_slice(hasher.finalize_reset().as_slice());
-    }
-    output
+/// A hashing context reusing a single hasher instance (via `finalize_reset()`) across all of
+/// the hash computations of one operation.
+#[derive(Clone, Default)]
+pub(crate) struct HashContext<D: Hash> {
+    hasher: D,
+    byte_order: ByteOrderPolicy,
+    framing: FramingPolicy,
 }
 
-/// Return all of the elements of the hash chain, where seed is at index = 0.
-#[inline]
-pub(crate) fn full_hash_chain<D: Hash>(seed: &[u8], size: usize) -> Vec<[u8; 32]> {
-    let mut hasher = D::new();
-    let mut output = Vec::with_capacity(size);
-    let mut temp = [0; 32];
-    temp.copy_from_slice(seed);
-    output.push(temp);
-    for _i in 1..size {
-        hasher.update(&temp);
-        temp.copy_from_slice(hasher.finalize_reset().as_slice());
+impl<D: Hash> HashContext<D> {
+    pub(crate) fn new() -> Self {
+        Self {
+            hasher: D::new(),
+            byte_order: ByteOrderPolicy::LittleEndian,
+            framing: FramingPolicy::Concatenated,
+        }
+    }
+
+    /// Encode the counters of the subseed derivations with `byte_order`.
+    pub(crate) fn set_byte_order(&mut self, byte_order: ByteOrderPolicy) {
+        self.byte_order = byte_order;
+    }
+
+    /// Frame the inputs of the salted hashes and of the PLR accumulators with `framing`.
+    pub(crate) fn set_framing(&mut self, framing: FramingPolicy) {
+        self.framing = framing;
+    }
+
+    // Hash `prefix || input` following the framing of the context.
+    #[inline]
+    fn framed_hash(&mut self, prefix: &[u8], input: &[u8]) -> [u8; 32] {
+        if let FramingPolicy::PrefixFree = self.framing {
+            let length = (prefix.len() + input.len()) as u64;
+            self.hasher.update(length.to_be_bytes());
+        }
+        self.hasher.update(prefix);
+        self.hasher.update(input);
+        let mut output = [0; 32];
+        output.copy_from_slice(self.hasher.finalize_reset().as_slice());
+        output
+    }
+
+    /// Output padding node + PLR accumulator
+    #[inline]
+    pub(crate) fn plr_accumulator(
+        &mut self,
+        seed: &[u8],
+        list: &[[u8; 32]],
+        max_length: usize,
+        desired_length: usize,
+    ) -> (
+        GenericArray<u8, PlrPaddingSize>,
+        Option<GenericArray<u8, PlrPaddingSize>>,
+    ) {
+        let padding = if list.len() < max_length {
+            let result = self.framed_hash(PADDING_SALT, seed);
+            Some(GenericArray::clone_from_slice(
+                &result[..PlrPaddingSize::to_usize()],
+            ))
+        } else {
+            None
+        };
+
+        let mut plr_path_node = padding;
+        let mut output = [0; 32];
+        list.iter().enumerate().for_each(|(i, v)| {
+            if i == 0 {
+                let padding = padding.as_ref().map(|p| &p[..]).unwrap_or(&[]);
+                output = self.framed_hash(padding, v);
+            } else {
+                if list.len() > desired_length && i == list.len() - desired_length {
+                    plr_path_node = Some(GenericArray::clone_from_slice(
+                        &output[..PlrPaddingSize::to_usize()],
+                    ));
+                }
+                output = self.framed_hash(&output, v);
+            }
+        });
+
+        (GenericArray::clone_from_slice(&output), plr_path_node)
+    }
+
+    /// Absorb the next list element into a PLR root recomputed by the verifier, `previous` being
+    /// the accumulator so far, or the (optional) padding node for the first element.
+    #[inline]
+    pub(crate) fn plr_absorb(&mut self, previous: Option<&[u8]>, element: &[u8]) -> [u8; 32] {
+        self.framed_hash(previous.unwrap_or(&[]), element)
+    }
+
+    /// Computes a hash chain using a seed and number of iterations.
+    #[inline]
+    pub(crate) fn hash_chain(&mut self, seed: &[u8], iterations: usize) -> [u8; 32] {
+        let mut output = [0u8; 32];
+        output.copy_from_slice(seed);
+        for _i in 0..iterations {
+            self.hasher.update(output);
+            output.copy_from_slice(self.hasher.finalize_reset().as_slice());
+        }
+        output
+    }
+
+    /// Append all of the elements of the hash chain to `output`, where seed comes first.
+    #[inline]
+    pub(crate) fn full_hash_chain_into(
+        &mut self,
+        seed: &[u8],
+        size: usize,
+        output: &mut Vec<[u8; 32]>,
+    ) {
+        let mut temp = [0; 32];
+        temp.copy_from_slice(seed);
         output.push(temp);
+        for _i in 1..size {
+            self.hasher.update(temp);
+            temp.copy_from_slice(self.hasher.finalize_reset().as_slice());
+            output.push(temp);
+        }
     }
-    output
-}
 
-/// Return the vector of hashchains for a base and seed (all of the elements in the chains).
-#[inline]
-pub(crate) fn compute_hash_chains<D: Hash>(
-    seed: &[u8],
-    size: usize,
-    base: u32,
-    most_significant_digit: u8,
-) -> Vec<Vec<[u8; 32]>> {
-    let mut output: Vec<Vec<[u8; 32]>> = Vec::with_capacity(size);
-    let seeds = generate_subseeds::<D, D::OutputSize>(LEAF_SALT, seed, size);
+    /// Simple KDF hash(salt, seed)
+    /// TODO: make it more generic to work for any seed size
+    #[inline]
+    pub(crate) fn salted_hash(&mut self, salt: &[u8], seed: &[u8]) -> [u8; 32] {
+        self.framed_hash(salt, seed)
+    }
 
-    // optimization: first chain might be shorter (up to most_significant_digit in selected base)
-    let first_chain = full_hash_chain::<D>(&seeds[0], most_significant_digit as usize + 1);
-    output.push(first_chain);
+    /// Generate num_of_seeds subseeds from salt and seed.
+    /// The counter is always hashed as a 64-bit integer, independently of the platform's pointer
+    /// width, in the byte order of the context (little-endian by default).
+    #[inline]
+    pub(crate) fn generate_subseeds<N: ArrayLength<u8>>(
+        &mut self,
+        salt: &[u8],
+        seed: &[u8],
+        num_of_seeds: usize,
+    ) -> Vec<GenericArray<u8, N>> {
+        #[allow(clippy::let_unit_value)]
+        let () = OutputLength::<D, N>::CHECK;
+        let mut seeds = Vec::with_capacity(num_of_seeds);
+        for i in 0..num_of_seeds {
+            self.hasher.update(salt);
+            self.hasher.update(self.byte_order.encode_counter(i));
+            self.hasher.update(seed);
+            let arr =
+                GenericArray::clone_from_slice(&self.hasher.finalize_reset()[..N::to_usize()]);
+            seeds.push(arr);
+        }
+        seeds
+    }
+
+    /// Generate num_of_salts salts of `size` bytes (at most 32) from salt and seed, as
+    /// [`HashContext::generate_subseeds`] would output them for a `size`-byte output type.
+    pub(crate) fn generate_salts(
+        &mut self,
+        salt: &[u8],
+        seed: &[u8],
+        num_of_salts: usize,
+        size: usize,
+    ) -> Vec<Vec<u8>> {
+        self.generate_subseeds::<U32>(salt, seed, num_of_salts)
+            .iter()
+            .map(|v| v[..size].to_vec())
+            .collect()
+    }
 
-    for elem in seeds.iter().skip(1) {
-        output.push(full_hash_chain::<D>(elem, base as usize));
+    /// Return the vector of hashchains for a base and seed (all of the elements in the chains),
+    /// the length of the first chain following `policy`.
+    #[inline]
+    pub(crate) fn compute_hash_chains(
+        &mut self,
+        seed: &[u8],
+        size: usize,
+        base: u32,
+        most_significant_digit: u8,
+        policy: ChainLengthPolicy,
+    ) -> Vec<Vec<[u8; 32]>> {
+        let mut output: Vec<Vec<[u8; 32]>> = Vec::with_capacity(size);
+        let seeds = self.generate_subseeds::<D::OutputSize>(LEAF_SALT, seed, size);
+
+        for (i, elem) in seeds.iter().enumerate() {
+            let chain_size = match i {
+                0 => policy.first_chain_length(base, most_significant_digit),
+                _ => base as usize,
+            };
+            let mut chain = Vec::with_capacity(chain_size);
+            self.full_hash_chain_into(elem, chain_size, &mut chain);
+            output.push(chain);
+        }
+        output
     }
-    output
 }
 
-/// Simple KDF hash(salt, i, seed)
-/// TODO: make it more generic to work for any seed size
-#[inline]
-pub(crate) fn salted_hash<D: Hash>(salt: &[u8], seed: &[u8]) -> [u8; 32] {
+/// Simple KDF hash(salt, seed), truncated to `N` bytes. `N` is checked at compile time
+/// against the output size of `D`.
+pub fn salted_hash<D: Hash, N: ArrayLength<u8>>(salt: &[u8], seed: &[u8]) -> GenericArray<u8, N> {
+    salted_hash_with_framing::<D, N>(salt, seed, FramingPolicy::default())
+}
+
+/// Simple KDF hash(salt, seed) with its input framed following `framing`, truncated to `N`
+/// bytes. This is the salting of the PLR roots.
+pub fn salted_hash_with_framing<D: Hash, N: ArrayLength<u8>>(
+    salt: &[u8],
+    seed: &[u8],
+    framing: FramingPolicy,
+) -> GenericArray<u8, N> {
+    #[allow(clippy::let_unit_value)]
+    let () = OutputLength::<D, N>::CHECK;
     let mut hasher = D::new();
+    if let FramingPolicy::PrefixFree = framing {
+        hasher.update(((salt.len() + seed.len()) as u64).to_be_bytes());
+    }
     hasher.update(salt);
     hasher.update(seed);
-    let mut output = [0; 32];
-    output.copy_from_slice(hasher.finalize().as_slice());
-    output
+    GenericArray::clone_from_slice(&hasher.finalize()[..N::USIZE])
 }
 
-/// Generate num_of_seeds subseeds from salt and seed.
+/// Generate num_of_seeds subseeds of `N` bytes from salt and seed, as
+/// hash(salt || LE64(i) || seed) truncated to `N` bytes. `N` is checked at compile time against
+/// the output size of `D`.
 #[inline]
-pub(crate) fn generate_subseeds<D: Hash, N: ArrayLength<u8>>(
+pub fn generate_subseeds<D: Hash, N: ArrayLength<u8>>(
     salt: &[u8],
     seed: &[u8],
     num_of_seeds: usize,
 ) -> Vec<GenericArray<u8, N>> {
-    let mut hasher = D::new();
-    let mut seeds = Vec::with_capacity(num_of_seeds);
-    for i in 0..num_of_seeds {
-        hasher.update(salt);
-        hasher.update(i.to_le_bytes());
-        hasher.update(seed);
-        let arr = GenericArray::clone_from_slice(&hasher.finalize_reset()[..N::to_usize()]);
-        seeds.push(arr);
-    }
-    seeds
+    generate_subseeds_with_byte_order::<D, N>(salt, seed, num_of_seeds, ByteOrderPolicy::default())
+}
+
+/// Generate num_of_seeds subseeds of `N` bytes from salt and seed, as
+/// hash(salt || counter || seed) truncated to `N` bytes, the counter being encoded following
+/// `byte_order`. This is the derivation of the hash chain seeds and of the MDP salts.
+pub fn generate_subseeds_with_byte_order<D: Hash, N: ArrayLength<u8>>(
+    salt: &[u8],
+    seed: &[u8],
+    num_of_seeds: usize,
+    byte_order: ByteOrderPolicy,
+) -> Vec<GenericArray<u8, N>> {
+    let mut ctx = HashContext::<D>::new();
+    ctx.set_byte_order(byte_order);
+    ctx.generate_subseeds(salt, seed, num_of_seeds)
 }
 
 #[cfg(test)]
@@ -143,7 +287,8 @@ mod tests {
     fn test_hash_chain() {
         use blake3::Hasher as Blake3;
 
-        let hash_chain_output = hash_chain::<Blake3>(b"01234567890123456789012345678901", 3);
+        let hash_chain_output =
+            HashContext::<Blake3>::new().hash_chain(b"01234567890123456789012345678901", 3);
         assert_eq!(
             hex::encode(hash_chain_output),
             "9dce6dd3c7e70a6e5052fe1626b97d5ff50f59764513950df43faf76f15efc5c"
@@ -154,7 +299,12 @@ mod tests {
     fn test_full_hash_chain() {
         use blake3::Hasher as Blake3;
 
-        let chain = full_hash_chain::<Blake3>(b"01234567890123456789012345678901", 3);
+        let mut chain = vec![];
+        HashContext::<Blake3>::new().full_hash_chain_into(
+            b"01234567890123456789012345678901",
+            3,
+            &mut chain,
+        );
 
         assert_eq!(chain.len(), 3);
         assert_eq!(
@@ -174,11 +324,66 @@ mod tests {
     #[test]
     fn test_compute_hashchains() {
         let seed = [0u8; 32];
-        let chains = compute_hash_chains::<Blake3>(&seed, 3, 4, 2);
+        let chains = HashContext::<Blake3>::new().compute_hash_chains(
+            &seed,
+            3,
+            4,
+            2,
+            ChainLengthPolicy::Optimized,
+        );
         assert_eq!(chains.len(), 3);
         assert_eq!(chains[0].len(), 3);
         assert_eq!(chains[1].len(), 4);
         assert_eq!(chains[2].len(), 4);
+
+        let uniform = HashContext::<Blake3>::new().compute_hash_chains(
+            &seed,
+            3,
+            4,
+            2,
+            ChainLengthPolicy::Uniform,
+        );
+        assert_eq!(uniform[0].len(), 4);
+        assert_eq!(uniform[0][..3], chains[0][..]);
+    }
+
+    #[test]
+    fn test_generate_subseeds() {
+        let seeds =
+            generate_subseeds::<Blake3, generic_array::typenum::U16>(LEAF_SALT, &[0u8; 32], 2);
+        assert_eq!(seeds.len(), 2);
+        assert_eq!(hex::encode(seeds[0]), "cf10550d4ed72b9c71c145fe2952be34");
+        assert_eq!(hex::encode(seeds[1]), "87ab29cd5fb7dbb379e187ef8dd207ff");
+
+        let big_endian = generate_subseeds_with_byte_order::<Blake3, generic_array::typenum::U16>(
+            LEAF_SALT,
+            &[0u8; 32],
+            2,
+            ByteOrderPolicy::BigEndian,
+        );
+        assert_eq!(big_endian[0], seeds[0]);
+        assert_ne!(big_endian[1], seeds[1]);
+    }
+
+    #[test]
+    fn test_salted_hash() {
+        let full = salted_hash::<Blake3, generic_array::typenum::U32>(TOP_SALT, &[0u8; 32]);
+        let short = salted_hash::<Blake3, generic_array::typenum::U16>(TOP_SALT, &[0u8; 32]);
+        assert_eq!(&full[..16], &short[..]);
+        assert_eq!(
+            full[..],
+            HashContext::<Blake3>::new().salted_hash(TOP_SALT, &[0u8; 32])
+        );
+
+        let framed = salted_hash_with_framing::<Blake3, generic_array::typenum::U32>(
+            TOP_SALT,
+            &[0u8; 32],
+            FramingPolicy::PrefixFree,
+        );
+        assert_ne!(framed, full);
+        let mut ctx = HashContext::<Blake3>::new();
+        ctx.set_framing(FramingPolicy::PrefixFree);
+        assert_eq!(framed[..], ctx.salted_hash(TOP_SALT, &[0u8; 32]));
     }
 
     #[test]
@@ -186,21 +391,21 @@ mod tests {
         let seed = [0u8; 32];
         let values = vec![[1u8; 32], [2u8; 32], [3u8; 32]];
 
-        let plr = plr_accumulator::<Blake3>(&seed, &values, 3, 3);
+        let plr = HashContext::<Blake3>::new().plr_accumulator(&seed, &values, 3, 3);
         assert!(plr.1.is_none());
         assert_eq!(
             hex::encode(plr.0),
             "0082c1dc66375f9ab20e8d699d48d9903fcae459330c03215a9909faaa0cf183"
         );
 
-        let plr = plr_accumulator::<Blake3>(&seed, &values, 4, 3);
+        let plr = HashContext::<Blake3>::new().plr_accumulator(&seed, &values, 4, 3);
         assert!(plr.1.is_some());
         assert_eq!(
-            hex::encode(&plr.0),
+            hex::encode(plr.0),
             "4accab47316eb7c538da7b940ce45e459572fd194ce25f4d5d42ab753cbf3fb4"
         );
         assert_eq!(
-            hex::encode(&plr.0),
+            hex::encode(plr.0),
             "4accab47316eb7c538da7b940ce45e459572fd194ce25f4d5d42ab753cbf3fb4"
         );
     }
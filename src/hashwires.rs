@@ -7,20 +7,30 @@ use std::convert::TryFrom;
 
 use digest::Digest;
 use generic_array::{
-    typenum::{Unsigned, U16, U32},
+    typenum::{Unsigned, U32},
     GenericArray,
 };
 use num_bigint::BigUint;
+use rand_core::{CryptoRng, RngCore};
 
-use crate::dp::{find_mdp, value_split_per_base};
+use crate::digest_id::DigestId;
+use crate::dp::{
+    find_mdp, find_mdp_with_splits, value_split_per_base, value_split_per_base_in_place,
+};
 use crate::errors::HwError;
-use crate::hashes::{
-    compute_hash_chains, generate_subseeds, hash_chain, plr_accumulator, salted_hash,
-    SMTREE_PADDING_SALT, TOP_SALT,
+use crate::hashes::{generate_subseeds, HashContext, LEAF_SALT, SMTREE_PADDING_SALT, TOP_SALT};
+use crate::metrics::{HwMetrics, NoopMetrics, VerifyStage};
+use crate::params::{
+    recommend_for_value, ChainLengthPolicy, FramingPolicy, HwParams, ProofSizeOrSpeed,
+    DEFAULT_MDP_SALT_SIZE, MAX_MDP_SALT_SIZE, MAX_NUMBER_BITS, MIN_MDP_SALT_SIZE, SUPPORTED_BASES,
+};
+use crate::prover::HwProver;
+use crate::serialization::{
+    os2ip, serialize, serialize_bounded, take_slice, tokenize, tokenize_slice,
 };
-use crate::serialization::{serialize, take_slice, tokenize};
 use crate::shuffle::deterministic_index_shuffling;
 use crate::traits::Hash;
+use crate::value::HwValue;
 use smtree::index::TreeIndex;
 use smtree::node_template::HashWiresNodeSmt;
 use smtree::pad_secret::Secret as SmtSecret;
@@ -29,25 +39,85 @@ use smtree::{
     node_template, proof::MerkleProof, traits::InclusionProvable, tree::SparseMerkleTree,
 };
 use std::marker::PhantomData;
+use std::sync::Mutex;
+use std::time::Instant;
 
 type Smt<P> = SparseMerkleTree<P>;
 
+/// The version of the serialized proofs, whatever their [`FramingPolicy`]. Versions 1 and 2
+/// were the proofs whose first byte was the code of their framing policy, and are rejected.
+pub const PROOF_VERSION: u8 = 3;
+// version (1) || framing policy (1) || digest identifier (1) || MDP salt size (1)
+const PROOF_HEADER_SIZE: usize = 4;
+// digest identifier (1) || chain-length policy (1) || MDP tree height (1)
+const COMMITMENT_HEADER_SIZE: usize = 3;
+const TRANSCRIPT_SALT: &[u8; 32] = b"p1234567890123456789012345678901";
+
 pub(crate) type PlrPaddingSize = U32;
 pub(crate) type ChainNodesSize = U32;
-pub(crate) type MdpSaltSize = U16;
 pub(crate) type SmtSecretSize = U32;
 
-/// HashWires commitment structure.
+/// HashWires commitment structure. A commitment does not hold a hasher, so it is `Send` and
+/// `Sync` whatever the digest, and can be shared across threads (see [`crate::verifier`]).
 pub struct Commitment<D: Hash> {
     base: u32,
     commitment: Vec<u8>,
-    _d: PhantomData<D>,
+    chain_length_policy: ChainLengthPolicy,
+    mdp_height: u8,
+    // The MDP tree leaves, only known to the issuer (never serialized).
+    leaves: Option<Vec<LeafHandle>>,
+    _d: PhantomData<fn() -> D>,
+}
+
+/// A leaf of the MDP tree, binding an MDP element to its (shuffled) position in the tree.
+///
+/// Handles are only produced by the commitment construction, and inclusion proofs are only
+/// generated for handles of the tree being built, so that proofs cannot refer to a wrong leaf.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LeafHandle {
+    mdp_index: usize,
+    position: usize,
+}
+
+impl LeafHandle {
+    /// The index of the MDP element, largest element first.
+    pub fn mdp_index(&self) -> usize {
+        self.mdp_index
+    }
+
+    /// The position of the leaf in the MDP tree.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+/// A structured breakdown of the internals of a HashWires commitment, intended for debugging
+/// diverging implementations. It does not contain the seed or any value derived from it other
+/// than the (public after proving) leaf positions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Explanation {
+    /// The base used for the hash chains.
+    pub base: u32,
+    /// The maximum number of bits supported by the commitment.
+    pub max_number_bits: usize,
+    /// The height of the top sparse Merkle tree.
+    pub mdp_smt_height: usize,
+    /// The minimum dominating partition of the committed value, largest element first.
+    pub mdp: Vec<BigUint>,
+    /// The per-base digits of each MDP element.
+    pub splits: Vec<Vec<u8>>,
+    /// The length of each hash chain, most significant digit first.
+    pub chain_lengths: Vec<usize>,
+    /// The sparse Merkle tree leaf position of each MDP element.
+    pub shuffled_indexes: Vec<usize>,
 }
 
 /// HashWires secret (value, seed) tuple.
 pub struct Secret<D: Hash> {
-    value: BigUint,
-    seed: Vec<u8>,
+    pub(crate) value: BigUint,
+    pub(crate) seed: Vec<u8>,
+    // Prover holding the derived state of the last commitment, see `with_cached_state`.
+    prover: Option<Mutex<HwProver<D>>>,
     _d: PhantomData<D>,
 }
 
@@ -57,56 +127,198 @@ impl<D: Hash> Secret<D> {
         Self {
             value: value.clone(),
             seed: seed.to_vec(),
+            prover: None,
             _d: PhantomData,
         }
     }
 
+    /// Generate a HashWires secret committing to any [`HwValue`], such as a `u64` or a
+    /// `(&str, radix)` pair.
+    pub fn from_value<V: HwValue>(seed: &[u8], value: &V) -> Result<Self, HwError> {
+        Ok(Self::gen(seed, &value.to_biguint()?))
+    }
+
+    /// Keep the state derived by commitments and proofs (MDP, hash chains, top salted roots and
+    /// shuffled indexes) in this secret, so that subsequent proofs for the same parameters skip
+    /// Steps 1 to 8. This trades memory for speed, for wallets keeping secrets in memory.
+    pub fn with_cached_state(mut self) -> Self {
+        self.prover = Some(Mutex::new(HwProver::new()));
+        self
+    }
+
+    /// Generate a HashWires secret with a fresh 32-byte seed sampled from `rng`.
+    ///
+    /// This is the only constructor consuming randomness, and it only uses the provided
+    /// generator; everything derived from a `Secret` is deterministic given its seed.
+    pub fn gen_with_rng<R: RngCore + CryptoRng>(rng: &mut R, value: &BigUint) -> Self {
+        let mut seed = vec![0u8; 32];
+        rng.fill_bytes(&mut seed);
+        Self::gen(&seed, value)
+    }
+
+    /// Generate a HashWires secret with a fresh 32-byte seed sampled from the OS generator.
+    #[cfg(feature = "std")]
+    pub fn gen_with_os_rng(value: &BigUint) -> Self {
+        Self::gen_with_rng(&mut rand_core::OsRng, value)
+    }
+
+    /// The same secret (value and seed), for use with another digest.
+    pub fn to_digest<E: Hash>(&self) -> Secret<E> {
+        Secret::gen(&self.seed, &self.value)
+    }
+
     /// Generate a HashWires commitment.
     pub fn commit(&self, base: u32, max_number_bits: usize) -> Result<Commitment<D>, HwError> {
-        let mdp_smt_height = compute_mdp_height(base, max_number_bits);
-        let commitment = commit_gen::<D>(
-            &self.value,
-            base,
-            &self.seed,
-            max_number_bits,
-            mdp_smt_height as usize,
+        self.commit_with_params(&HwParams::new(base, max_number_bits))
+    }
+
+    /// Generate a HashWires commitment with `params`, including non-default sizes.
+    pub fn commit_with_params(&self, params: &HwParams) -> Result<Commitment<D>, HwError> {
+        if let Some(prover) = &self.prover {
+            return prover.lock().unwrap().commit(self, params);
+        }
+        params.validate()?;
+        let mdp_smt_height = compute_mdp_height(params.base, params.max_number_bits);
+        let (commitment, leaves) =
+            commit_gen_with_params::<D>(&self.value, &self.seed, params, mdp_smt_height as usize)?;
+        Ok(Commitment::from_root(&commitment, params.base)
+            .with_chain_length_policy(params.chain_length_policy)
+            .with_mdp_height(mdp_smt_height)
+            .with_leaves(leaves))
+    }
+
+    /// Generate a HashWires commitment in the base best suited to the committed value, for
+    /// values of up to `max_number_bits` bits, optimizing for `target` (see
+    /// [`recommend_for_value`]). The base of the commitment is recorded by
+    /// [`Commitment::serialize_with_base`], and proofs must be generated in the same base.
+    pub fn commit_adaptive(
+        &self,
+        max_number_bits: usize,
+        target: ProofSizeOrSpeed,
+    ) -> Result<Commitment<D>, HwError> {
+        let base = recommend_for_value(&self.value, max_number_bits, target)?
+            .params
+            .base;
+        self.commit(base, max_number_bits)
+    }
+
+    /// Describe the commitment that `commit(base, max_number_bits)` would produce.
+    pub fn explain(&self, base: u32, max_number_bits: usize) -> Result<Explanation, HwError> {
+        HwParams::new(base, max_number_bits).validate()?;
+        let bitlength = compute_bitlength(base);
+        let (mdp, splits) = find_mdp_with_splits(&self.value, base);
+        let mut chain_lengths = vec![base as usize; splits[0].len()];
+        chain_lengths[0] = splits[0][0] as usize + 1;
+        let shuffled_indexes = deterministic_index_shuffling(
+            mdp.len(),
+            max_number_bits / bitlength,
+            <[u8; 32]>::try_from(&self.seed[..]).map_err(|_| HwError::SeedLengthError)?,
         )?;
-        Ok(Commitment {
+
+        Ok(Explanation {
             base,
-            commitment,
-            _d: PhantomData,
+            max_number_bits,
+            mdp_smt_height: compute_mdp_height(base, max_number_bits) as usize,
+            mdp,
+            splits,
+            chain_lengths,
+            shuffled_indexes,
         })
     }
 
-    /// Generate HashWires proof.
-    pub fn prove(
+    /// Generate a HashWires commitment with bounded memory usage, at the cost of extra hashing.
+    /// The output is identical to [`Secret::commit`].
+    pub fn commit_streaming(
         &self,
         base: u32,
         max_number_bits: usize,
-        threshold: &BigUint,
-    ) -> Result<Proof, HwError> {
+    ) -> Result<Commitment<D>, HwError> {
+        HwParams::new(base, max_number_bits).validate()?;
         let mdp_smt_height = compute_mdp_height(base, max_number_bits);
-        let result = larger_than_proof_gen::<D>(
-            threshold,
+        let (commitment, leaves) = commit_gen_streaming_with_leaves::<D>(
             &self.value,
             base,
             &self.seed,
             max_number_bits,
             mdp_smt_height as usize,
         )?;
-        Ok(Proof {
-            plr_padding: result.1,
-            chain_nodes: result.2,
-            mdp_salt: result.3,
-            smt_inclusion_proof: result.4,
-        })
+        Ok(Commitment::from_root(&commitment, base)
+            .with_mdp_height(mdp_smt_height)
+            .with_leaves(leaves))
+    }
+
+    /// Generate HashWires proof.
+    pub fn prove<T: HwValue>(
+        &self,
+        base: u32,
+        max_number_bits: usize,
+        threshold: &T,
+    ) -> Result<Proof, HwError> {
+        let threshold = &threshold.to_biguint()?;
+        match &self.prover {
+            Some(prover) => prover
+                .lock()
+                .unwrap()
+                .prove(self, base, max_number_bits, threshold),
+            None => HwProver::new().prove(self, base, max_number_bits, threshold),
+        }
+    }
+
+    /// Generate a HashWires commitment and a proof over it in a single pass, for issuance
+    /// flows needing both. The output is identical to calling [`Secret::commit`] and
+    /// [`Secret::prove`].
+    pub fn commit_and_prove<T: HwValue>(
+        &self,
+        params: &HwParams,
+        threshold: &T,
+    ) -> Result<(Commitment<D>, Proof), HwError> {
+        let threshold = &threshold.to_biguint()?;
+        match &self.prover {
+            Some(prover) => prover
+                .lock()
+                .unwrap()
+                .commit_and_prove(self, params, threshold),
+            None => HwProver::new().commit_and_prove(self, params, threshold),
+        }
     }
 }
 
 impl<D: Hash> Commitment<D> {
     /// Verify a HashWires proof over a commitment.
-    pub fn verify(&self, proof: &Proof, threshold: &BigUint) -> Result<(), HwError> {
-        let result = proof_verify::<D>(
+    pub fn verify<T: HwValue>(&self, proof: &Proof, threshold: &T) -> Result<(), HwError> {
+        self.verify_with_metrics(proof, &threshold.to_biguint()?, &NoopMetrics)
+    }
+
+    /// Verify a HashWires proof over a commitment, rejecting it with
+    /// [`HwError::WorkLimitError`] before any hashing if its [`Proof::verification_cost`]
+    /// exceeds `max_hash_iterations`. Public verification endpoints should use this method.
+    pub fn verify_with_limit<T: HwValue>(
+        &self,
+        proof: &Proof,
+        threshold: &T,
+        max_hash_iterations: usize,
+    ) -> Result<(), HwError> {
+        let threshold = &threshold.to_biguint()?;
+        if proof.verification_cost(self.base, threshold) > max_hash_iterations {
+            return Err(HwError::WorkLimitError);
+        }
+        self.verify_with_metrics(proof, threshold, &NoopMetrics)
+    }
+
+    /// Verify a HashWires proof over a commitment, reporting the outcome, the proof size and
+    /// the per-stage timings to `metrics`.
+    pub fn verify_with_metrics(
+        &self,
+        proof: &Proof,
+        threshold: &BigUint,
+        metrics: &dyn HwMetrics,
+    ) -> Result<(), HwError> {
+        metrics.on_proof_size(proof.serialized_size());
+        if proof.digest_id != DigestId::of::<D>() {
+            metrics.on_verification(false);
+            return Err(HwError::DigestMismatchError);
+        }
+        let result = proof_verify_with_framing::<D>(
             threshold,
             self.base,
             &self.commitment,
@@ -114,47 +326,409 @@ impl<D: Hash> Commitment<D> {
             &proof.chain_nodes,
             &proof.mdp_salt,
             &proof.smt_inclusion_proof,
+            proof.framing,
+            metrics,
         );
-        match result? {
+        let result = match result {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(HwError::ProofVerificationError),
+            Err(e) => Err(e),
+        };
+        metrics.on_verification(result.is_ok());
+        result
+    }
+
+    /// Verify a HashWires proof over a commitment without any heap allocation in the
+    /// HashWires stages (threshold digits, hash chains and MDP root), for latency-critical
+    /// pipelines. The digits of the threshold are written to `scratch`, sized with
+    /// [`scratch_size`]; fails with [`HwError::ParameterError`] if it is too short. The
+    /// inclusion proof in the MDP tree is still decoded and checked by `smtree`, which
+    /// allocates a number of times independent of the threshold.
+    pub fn verify_in_place(
+        &self,
+        proof: &Proof,
+        threshold: &BigUint,
+        scratch: &mut [u8],
+    ) -> Result<(), HwError> {
+        if proof.digest_id != DigestId::of::<D>() {
+            return Err(HwError::DigestMismatchError);
+        }
+        let bitlength = compute_bitlength(self.base);
+        let len = value_split_per_base_in_place(threshold, bitlength, scratch)
+            .ok_or(HwError::ParameterError)?;
+        let salted_mdp_root = salted_mdp_root::<D>(
+            &scratch[..len],
+            &proof.plr_padding,
+            &proof.chain_nodes,
+            &proof.mdp_salt,
+            proof.framing,
+        )
+        .ok_or(HwError::ProofVerificationError)?;
+
+        let inclusion_proof =
+            MerkleProof::<HashWiresNodeSmt<D>>::deserialize(&proof.smt_inclusion_proof)?;
+        let commitment_node = HashWiresNodeSmt::<D>::new(self.commitment.to_owned());
+        let smt_mdp_node = HashWiresNodeSmt::<D>::new(salted_mdp_root.to_vec());
+        match inclusion_proof.verify_inclusion_proof(&[smt_mdp_node], &commitment_node) {
+            true => Ok(()),
+            false => Err(HwError::ProofVerificationError),
+        }
+    }
+
+    /// Verify a HashWires proof over a commitment with the same work wherever verification
+    /// fails, for endpoints exposed to remote attackers measuring response times. Every hash
+    /// chain is walked to `base - 1` iterations and as many of them are walked as the MDP tree
+    /// has leaves, even past the chain nodes of the proof, and every failure is reported as
+    /// [`HwError::ProofVerificationError`] once all of the work is done. The work only depends
+    /// on the commitment and the length of the proof, which the attacker knows (see
+    /// [`Commitment::constant_work_cost`]). Thresholds with more digits than the MDP tree has
+    /// leaves are rejected with [`HwError::MdpSizeError`] before any hashing.
+    pub fn verify_constant_work<T: HwValue>(
+        &self,
+        proof: &Proof,
+        threshold: &T,
+    ) -> Result<(), HwError> {
+        let digits = value_split_per_base(&threshold.to_biguint()?, compute_bitlength(self.base));
+        let slots = self.constant_work_slots();
+        if digits.len() > slots {
+            return Err(HwError::MdpSizeError);
+        }
+        let height = slots.trailing_zeros();
+        let mut valid =
+            proof.digest_id == DigestId::of::<D>() && proof.chain_nodes.len() == digits.len();
+
+        let mut ctx = HashContext::<D>::new();
+        ctx.set_framing(proof.framing);
+        let mut mdp_root = [0u8; 32];
+        // the padding work goes through the hasher of `ctx`, reused by the actual work, so it
+        // is not optimized out
+        let dummy = GenericArray::<u8, ChainNodesSize>::default();
+        for i in 0..slots {
+            let node = proof.chain_nodes.get(i).unwrap_or(&dummy);
+            let digit = digits.get(i).copied().unwrap_or(0) as usize;
+            let chain_node = ctx.hash_chain(node, digit);
+            ctx.hash_chain(&chain_node, self.base as usize - 1 - digit);
+            let previous = match i {
+                0 => proof.plr_padding.as_ref().map(|p| &p[..]),
+                _ => Some(&mdp_root[..]),
+            };
+            let absorbed = ctx.plr_absorb(previous, &chain_node);
+            if i < digits.len() {
+                mdp_root = absorbed;
+            }
+        }
+        let salted_mdp_root = ctx.salted_hash(&proof.mdp_salt, &mdp_root);
+
+        let included =
+            match MerkleProof::<HashWiresNodeSmt<D>>::deserialize(&proof.smt_inclusion_proof) {
+                Ok(inclusion_proof) => inclusion_proof.verify_inclusion_proof(
+                    &[HashWiresNodeSmt::<D>::new(salted_mdp_root.to_vec())],
+                    &HashWiresNodeSmt::<D>::new(self.commitment.to_owned()),
+                ),
+                Err(_) => {
+                    // as many hashes as the path of the inclusion proof
+                    ctx.hash_chain(&salted_mdp_root, height as usize + 1);
+                    false
+                }
+            };
+        valid &= included;
+        match valid {
             true => Ok(()),
             false => Err(HwError::ProofVerificationError),
         }
     }
 
-    /// Serialize a HashWires commitment.
+    /// Like [`Commitment::verify_constant_work`], rejecting the proof with
+    /// [`HwError::WorkLimitError`] before any hashing if the [`Commitment::constant_work_cost`]
+    /// exceeds `max_hash_iterations`.
+    pub fn verify_constant_work_with_limit<T: HwValue>(
+        &self,
+        proof: &Proof,
+        threshold: &T,
+        max_hash_iterations: usize,
+    ) -> Result<(), HwError> {
+        if self.constant_work_cost() > max_hash_iterations {
+            return Err(HwError::WorkLimitError);
+        }
+        self.verify_constant_work(proof, threshold)
+    }
+
+    /// The number of hash chain iterations of [`Commitment::verify_constant_work`]: `base - 1`
+    /// for each leaf of the MDP tree, whatever the proof and the threshold.
+    pub fn constant_work_cost(&self) -> usize {
+        self.constant_work_slots() * (self.base as usize - 1)
+    }
+
+    // The number of leaves of the MDP tree, bounded by that of the largest supported tree.
+    fn constant_work_slots(&self) -> usize {
+        let max_height = compute_mdp_height(2, MAX_NUMBER_BITS);
+        1 << (self.mdp_height as u32).min(max_height)
+    }
+
+    /// The base this commitment was generated with.
+    pub fn base(&self) -> u32 {
+        self.base
+    }
+
+    /// The root of the commitment, without the header.
+    pub fn root(&self) -> &[u8] {
+        &self.commitment
+    }
+
+    /// The chain-length policy this commitment was generated with.
+    pub fn chain_length_policy(&self) -> ChainLengthPolicy {
+        self.chain_length_policy
+    }
+
+    /// The height of the MDP tree of this commitment, see [`compute_mdp_height`].
+    pub fn mdp_height(&self) -> usize {
+        self.mdp_height as usize
+    }
+
+    /// Serialize a HashWires commitment, as a header (its digest identifier, chain-length
+    /// policy and MDP tree height) followed by its root.
     pub fn serialize(&self) -> Vec<u8> {
-        self.commitment.clone()
+        [
+            &[
+                DigestId::of::<D>().as_u8(),
+                self.chain_length_policy.as_u8(),
+                self.mdp_height,
+            ],
+            &self.commitment[..],
+        ]
+        .concat()
     }
 
-    /// Deserialize a HashWires commitment.
-    pub fn deserialize(bytes: &[u8], base: u32) -> Self {
+    /// Deserialize a HashWires commitment, failing if it was generated with another digest or
+    /// an unknown chain-length policy, or if its root is not a digest of `D`.
+    pub fn deserialize(bytes: &[u8], base: u32) -> Result<Self, HwError> {
+        let (header, root) = take_slice(bytes, COMMITMENT_HEADER_SIZE)?;
+        if DigestId::from_u8(header[0])? != DigestId::of::<D>() {
+            return Err(HwError::DigestMismatchError);
+        }
+        if root.len() != D::OutputSize::to_usize() {
+            return Err(HwError::SerializationError);
+        }
+        Ok(Self::from_root(root, base)
+            .with_chain_length_policy(ChainLengthPolicy::from_u8(header[1])?)
+            .with_mdp_height(header[2] as u32))
+    }
+
+    /// Serialize a HashWires commitment along with its base, as `base (4) || commitment`, for
+    /// commitments whose base is not fixed by the deployment (see [`Secret::commit_adaptive`]).
+    pub fn serialize_with_base(&self) -> Vec<u8> {
+        [&self.base.to_be_bytes()[..], &self.serialize()].concat()
+    }
+
+    /// Deserialize a HashWires commitment serialized with [`Commitment::serialize_with_base`].
+    pub fn deserialize_with_base(bytes: &[u8]) -> Result<Self, HwError> {
+        let (base, commitment) = take_slice(bytes, 4)?;
+        let base = os2ip(base)? as u32;
+        if !SUPPORTED_BASES.contains(&base) {
+            return Err(HwError::ParameterError);
+        }
+        Self::deserialize(commitment, base)
+    }
+
+    /// The number of leaves of the MDP tree, i.e. the number of MDP elements. Only known to
+    /// the issuer: this is `None` for deserialized commitments.
+    pub fn leaf_count(&self) -> Option<usize> {
+        self.leaves.as_ref().map(Vec::len)
+    }
+
+    /// The positions of the MDP tree leaves, indexed by MDP element. Only known to the issuer:
+    /// this is `None` for deserialized commitments. Like [`Commitment::leaf_count`], it reveals
+    /// information on the committed value, so it is only meant for diagnostics.
+    pub fn mdp_positions(&self) -> Option<Vec<usize>> {
+        self.leaves
+            .as_ref()
+            .map(|leaves| leaves.iter().map(LeafHandle::position).collect())
+    }
+
+    /// The leaf of the MDP tree committing to MDP element `mdp_index`, if known.
+    pub fn leaf(&self, mdp_index: usize) -> Option<LeafHandle> {
+        self.leaves.as_ref()?.get(mdp_index).copied()
+    }
+
+    pub(crate) fn from_root(root: &[u8], base: u32) -> Self {
         Self {
             base,
-            commitment: bytes.to_vec(),
+            commitment: root.to_vec(),
+            chain_length_policy: ChainLengthPolicy::Optimized,
+            mdp_height: 0,
+            leaves: None,
             _d: PhantomData,
         }
     }
+
+    pub(crate) fn with_chain_length_policy(mut self, policy: ChainLengthPolicy) -> Self {
+        self.chain_length_policy = policy;
+        self
+    }
+
+    pub(crate) fn with_mdp_height(mut self, mdp_height: u32) -> Self {
+        self.mdp_height = mdp_height as u8;
+        self
+    }
+
+    pub(crate) fn with_leaves(mut self, leaves: Vec<LeafHandle>) -> Self {
+        self.leaves = Some(leaves);
+        self
+    }
 }
 
 /// HashWires Proof structure.
+#[derive(Clone)]
 pub struct Proof {
-    plr_padding: Option<GenericArray<u8, PlrPaddingSize>>,
-    chain_nodes: Vec<GenericArray<u8, ChainNodesSize>>,
-    mdp_salt: GenericArray<u8, MdpSaltSize>,
-    smt_inclusion_proof: Vec<u8>,
+    pub(crate) digest_id: DigestId,
+    pub(crate) plr_padding: Option<GenericArray<u8, PlrPaddingSize>>,
+    pub(crate) chain_nodes: Vec<GenericArray<u8, ChainNodesSize>>,
+    pub(crate) mdp_salt: Vec<u8>,
+    pub(crate) smt_inclusion_proof: Vec<u8>,
+    pub(crate) framing: FramingPolicy,
 }
 
 impl Proof {
-    /// Serializing a HashWires proof.
+    /// The digest this proof was generated with.
+    pub fn digest_id(&self) -> DigestId {
+        self.digest_id
+    }
+
+    /// The framing of the salted hash and of the PLR root of this proof.
+    pub fn framing_policy(&self) -> FramingPolicy {
+        self.framing
+    }
+
+    /// The padding of the proven PLR root, if any.
+    pub fn plr_padding(&self) -> Option<&[u8]> {
+        self.plr_padding.as_ref().map(|v| &v[..])
+    }
+
+    /// The hash chain nodes, one per digit of the proven MDP value.
+    pub fn chain_nodes(&self) -> Vec<&[u8]> {
+        self.chain_nodes.iter().map(|v| &v[..]).collect()
+    }
+
+    /// The salt of the proven MDP value.
+    pub fn mdp_salt(&self) -> &[u8] {
+        &self.mdp_salt
+    }
+
+    /// The serialized inclusion proof of the PLR root in the sparse Merkle tree.
+    pub fn smt_inclusion_proof(&self) -> &[u8] {
+        &self.smt_inclusion_proof
+    }
+
+    /// The inclusion proof of the PLR root, decoded under the digest `D` the proof was
+    /// generated with. HashWires inclusion proofs carry no padding proofs: the only padding is
+    /// that of [`Proof::plr_padding`].
+    pub fn smt_merkle_proof<D: Hash>(&self) -> Result<MerkleProof<HashWiresNodeSmt<D>>, HwError> {
+        if self.digest_id != DigestId::of::<D>() {
+            return Err(HwError::DigestMismatchError);
+        }
+        Ok(MerkleProof::<HashWiresNodeSmt<D>>::deserialize(
+            &self.smt_inclusion_proof,
+        )?)
+    }
+
+    /// The indexes of the leaves proven by the inclusion proof in the sparse Merkle tree.
+    pub fn smt_leaf_indexes<D: Hash>(&self) -> Result<Vec<TreeIndex>, HwError> {
+        Ok(self.smt_merkle_proof::<D>()?.get_indexes().to_vec())
+    }
+
+    /// The sibling hashes of the inclusion proof, from the child of the root to the sibling of
+    /// the leaf.
+    pub fn smt_sibling_hashes<D: Hash>(&self) -> Result<Vec<Vec<u8>>, HwError> {
+        Ok(self
+            .smt_merkle_proof::<D>()?
+            .get_path_siblings()
+            .iter()
+            .map(|sibling| sibling.serialize())
+            .collect())
+    }
+
+    /// An upper bound on the number of hash iterations needed to verify this proof against
+    /// `threshold` in base `base`: the hash chains, the PLR root, the salted root and the
+    /// inclusion proof.
+    pub fn verification_cost(&self, base: u32, threshold: &BigUint) -> usize {
+        let chain_iterations: usize = value_split_per_base(threshold, compute_bitlength(base))
+            .iter()
+            .map(|digit| *digit as usize)
+            .sum();
+        chain_iterations
+            + self.chain_nodes.len()
+            + 2
+            + self.smt_inclusion_proof.len() / ChainNodesSize::to_usize()
+    }
+
+    /// The canonical hash of the verification of this proof of `threshold` over `commitment`
+    /// in `context`, for external systems (HSMs, audit logs, countersignatures) to sign exactly
+    /// what was verified. All integers are big-endian:
+    ///
+    /// ```text
+    /// H(TRANSCRIPT_SALT || base (4) || I2OSP(len, 2) || commitment || I2OSP(len, 2) || threshold
+    ///   || I2OSP(len, 4) || context || I2OSP(len, 4) || proof)
+    /// ```
+    ///
+    /// Fails with [`HwError::SerializationError`] if a field is too long for its length prefix.
+    pub fn transcript_hash<D: Hash>(
+        &self,
+        commitment: &Commitment<D>,
+        threshold: &BigUint,
+        context: &[u8],
+    ) -> Result<[u8; 32], HwError> {
+        let mut hasher = D::new();
+        hasher.update(TRANSCRIPT_SALT);
+        hasher.update(commitment.base().to_be_bytes());
+        hasher.update(serialize(&commitment.serialize(), 2)?);
+        hasher.update(serialize(&threshold.to_bytes_be(), 2)?);
+        hasher.update(serialize(context, 4)?);
+        hasher.update(serialize(&self.serialize(), 4)?);
+        let mut output = [0u8; 32];
+        output.copy_from_slice(&hasher.finalize()[..32]);
+        Ok(output)
+    }
+
+    /// The length in bytes of the serialized proof, computed without serializing.
+    pub fn serialized_size(&self) -> usize {
+        let plr_padding_size = match self.plr_padding {
+            Some(_) => PlrPaddingSize::to_usize(),
+            None => 0,
+        };
+        PROOF_HEADER_SIZE
+            + 2
+            + self.chain_nodes.len() * ChainNodesSize::to_usize()
+            + self.mdp_salt.len()
+            + 2
+            + self.smt_inclusion_proof.len()
+            + plr_padding_size
+    }
+
+    /// Serializing a HashWires proof, laid out as follows (all integers are big-endian):
+    ///
+    /// ```text
+    /// version (1) || framing policy (1) || digest identifier (1) || MDP salt size (1)
+    /// || I2OSP(len, 2) || chain nodes || MDP salt || I2OSP(len, 2) || inclusion proof
+    /// || PLR padding (0 or 32)
+    /// ```
+    ///
+    /// The version is always [`PROOF_VERSION`], and the framing policy is the code of
+    /// [`Proof::framing_policy`].
     pub fn serialize(&self) -> Vec<u8> {
         let mut chain_nodes_flattened = vec![];
         for elem in self.chain_nodes.iter() {
             chain_nodes_flattened.extend_from_slice(elem);
         }
         let mut result = [
-            &serialize(&chain_nodes_flattened, 2),
-            &self.mdp_salt.to_vec()[..],
-            &serialize(&self.smt_inclusion_proof, 2),
+            &[
+                PROOF_VERSION,
+                self.framing.as_u8(),
+                self.digest_id.as_u8(),
+                self.mdp_salt.len() as u8,
+            ],
+            &serialize_bounded(&chain_nodes_flattened, 2)[..],
+            &self.mdp_salt[..],
+            &serialize_bounded(&self.smt_inclusion_proof, 2),
         ]
         .concat();
         if let Some(v) = &self.plr_padding {
@@ -166,8 +740,37 @@ impl Proof {
 
     /// Deserializing a HashWires proof.
     pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
-        let (chain_nodes_flattened, remainder) = tokenize(input, 2)?;
-        let (mdp_salt, remainder) = take_slice(&remainder, MdpSaltSize::to_usize())?;
+        Self::deserialize_bounded(input, usize::MAX)
+    }
+
+    /// Deserialize a HashWires proof generated with `params`, failing with
+    /// [`HwError::MdpSizeError`] before decoding the chain nodes if there are more of them than
+    /// the [`HwParams::max_mdp_size`] digits of a value.
+    pub fn deserialize_with_params(input: &[u8], params: &HwParams) -> Result<Self, HwError> {
+        Self::deserialize_bounded(input, params.max_mdp_size()?)
+    }
+
+    fn deserialize_bounded(input: &[u8], max_chain_nodes: usize) -> Result<Self, HwError> {
+        let (header, remainder) = take_slice(input, PROOF_HEADER_SIZE)?;
+        if header[0] != PROOF_VERSION {
+            return Err(HwError::SerializationError);
+        }
+        let framing = FramingPolicy::from_u8(header[1])?;
+        let mdp_salt_size = header[3] as usize;
+        if !(MIN_MDP_SALT_SIZE..=MAX_MDP_SALT_SIZE).contains(&mdp_salt_size) {
+            return Err(HwError::SerializationError);
+        }
+        let digest_id = DigestId::from_u8(header[2])?;
+        let (chain_nodes_flattened, remainder) = tokenize_slice(remainder, 2)?;
+        let chain_nodes = chain_nodes_flattened.chunks_exact(ChainNodesSize::to_usize());
+        if !chain_nodes.remainder().is_empty() {
+            return Err(HwError::SerializationError);
+        }
+        if chain_nodes.len() > max_chain_nodes {
+            return Err(HwError::MdpSizeError);
+        }
+        let chain_nodes = chain_nodes.map(GenericArray::clone_from_slice).collect();
+        let (mdp_salt, remainder) = take_slice(remainder, mdp_salt_size)?;
         let (smt_inclusion_proof, remainder) = tokenize(remainder, 2)?;
         let plr_padding = match remainder.is_empty() {
             true => None,
@@ -180,23 +783,116 @@ impl Proof {
             }
         };
 
-        let mut chain_nodes = vec![];
-        let mut cn_index = 0;
-        while cn_index < chain_nodes_flattened.len() {
-            chain_nodes.push(GenericArray::clone_from_slice(
-                &chain_nodes_flattened[cn_index..cn_index + ChainNodesSize::to_usize()],
-            ));
-            cn_index += ChainNodesSize::to_usize();
-        }
-        if cn_index != chain_nodes_flattened.len() {
-            return Err(HwError::SerializationError);
-        }
-
         Ok(Self {
+            digest_id,
             chain_nodes,
             plr_padding,
-            mdp_salt: GenericArray::clone_from_slice(mdp_salt),
+            mdp_salt: mdp_salt.to_vec(),
             smt_inclusion_proof,
+            framing,
+        })
+    }
+}
+
+/// Builder re-assembling a [`Proof`] from its parts, e.g. after inspection or re-encoding.
+pub struct ProofBuilder {
+    digest_id: DigestId,
+    plr_padding: Option<Vec<u8>>,
+    chain_nodes: Vec<Vec<u8>>,
+    mdp_salt: Vec<u8>,
+    smt_inclusion_proof: Vec<u8>,
+    framing: FramingPolicy,
+}
+
+impl ProofBuilder {
+    /// Create an empty builder for a proof generated with the digest `digest_id`.
+    pub fn new(digest_id: DigestId) -> Self {
+        Self {
+            digest_id,
+            plr_padding: None,
+            chain_nodes: Vec::new(),
+            mdp_salt: Vec::new(),
+            smt_inclusion_proof: Vec::new(),
+            framing: FramingPolicy::Concatenated,
+        }
+    }
+
+    /// Set the padding of the proven PLR root.
+    pub fn plr_padding(mut self, plr_padding: Option<&[u8]>) -> Self {
+        self.plr_padding = plr_padding.map(|v| v.to_vec());
+        self
+    }
+
+    /// Append a hash chain node.
+    pub fn chain_node(mut self, chain_node: &[u8]) -> Self {
+        self.chain_nodes.push(chain_node.to_vec());
+        self
+    }
+
+    /// Set the salt of the proven MDP value.
+    pub fn mdp_salt(mut self, mdp_salt: &[u8]) -> Self {
+        self.mdp_salt = mdp_salt.to_vec();
+        self
+    }
+
+    /// Set the serialized inclusion proof of the PLR root.
+    pub fn smt_inclusion_proof(mut self, smt_inclusion_proof: &[u8]) -> Self {
+        self.smt_inclusion_proof = smt_inclusion_proof.to_vec();
+        self
+    }
+
+    /// Set the inclusion proof of the PLR root from its leaf indexes and sibling hashes (see
+    /// [`Proof::smt_leaf_indexes`] and [`Proof::smt_sibling_hashes`]), encoded under `D`.
+    pub fn smt_inclusion_parts<D: Hash>(
+        self,
+        leaf_indexes: &[TreeIndex],
+        sibling_hashes: &[Vec<u8>],
+    ) -> Self {
+        let mut proof = MerkleProof::<HashWiresNodeSmt<D>>::new_batch(leaf_indexes);
+        proof.set_siblings(
+            sibling_hashes
+                .iter()
+                .map(|hash| HashWiresNodeSmt::<D>::new(hash.clone()))
+                .collect(),
+        );
+        self.smt_inclusion_proof(&proof.serialize())
+    }
+
+    /// Set the framing the proof was generated with.
+    pub fn framing_policy(mut self, framing: FramingPolicy) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// Build the proof, checking the length of each part, and that the chain nodes and the
+    /// inclusion proof fit their 2-byte length prefixes.
+    pub fn build(self) -> Result<Proof, HwError> {
+        if !(MIN_MDP_SALT_SIZE..=MAX_MDP_SALT_SIZE).contains(&self.mdp_salt.len())
+            || self.chain_nodes.len() * ChainNodesSize::to_usize() > u16::MAX as usize
+            || self.smt_inclusion_proof.len() > u16::MAX as usize
+            || self
+                .chain_nodes
+                .iter()
+                .any(|v| v.len() != ChainNodesSize::to_usize())
+            || self
+                .plr_padding
+                .as_ref()
+                .map(|v| v.len() != PlrPaddingSize::to_usize())
+                .unwrap_or(false)
+        {
+            return Err(HwError::SerializationError);
+        }
+        Ok(Proof {
+            digest_id: self.digest_id,
+            plr_padding: self.plr_padding.map(|v| GenericArray::clone_from_slice(&v)),
+            chain_nodes: self
+                .chain_nodes
+                .iter()
+                .map(|v| GenericArray::clone_from_slice(v))
+                .collect(),
+            mdp_salt: self.mdp_salt,
+            smt_inclusion_proof: self.smt_inclusion_proof,
+            framing: self.framing,
         })
     }
 }
@@ -215,47 +911,336 @@ pub fn larger_than_proof_gen<D: Hash>(
         Vec<u8>,
         Option<GenericArray<u8, PlrPaddingSize>>,
         Vec<GenericArray<u8, ChainNodesSize>>,
-        GenericArray<u8, MdpSaltSize>,
+        Vec<u8>,
         Vec<u8>,
     ),
     HwError,
 > {
+    HwProver::<D>::new().proof_gen(
+        proving_value,
+        value,
+        seed,
+        &HwParams::new(base, max_number_bits),
+        mdp_smt_height,
+    )
+}
+
+/// Verify HashWires proof.
+pub fn proof_verify<D: Hash>(
+    proving_value: &BigUint,
+    base: u32,
+    commitment: &[u8],
+    plr_padding: &Option<GenericArray<u8, PlrPaddingSize>>,
+    chain_nodes: &[GenericArray<u8, ChainNodesSize>],
+    mdp_salt: &[u8],
+    smt_inclusion_proof: &[u8],
+) -> Result<bool, HwError> {
+    proof_verify_with_metrics::<D>(
+        proving_value,
+        base,
+        commitment,
+        plr_padding,
+        chain_nodes,
+        mdp_salt,
+        smt_inclusion_proof,
+        &NoopMetrics,
+    )
+}
+
+/// Verify HashWires proof, reporting stage timings to `metrics`.
+#[allow(clippy::too_many_arguments)]
+pub fn proof_verify_with_metrics<D: Hash>(
+    proving_value: &BigUint,
+    base: u32,
+    commitment: &[u8],
+    plr_padding: &Option<GenericArray<u8, PlrPaddingSize>>,
+    chain_nodes: &[GenericArray<u8, ChainNodesSize>],
+    mdp_salt: &[u8],
+    smt_inclusion_proof: &[u8],
+    metrics: &dyn HwMetrics,
+) -> Result<bool, HwError> {
+    proof_verify_with_framing::<D>(
+        proving_value,
+        base,
+        commitment,
+        plr_padding,
+        chain_nodes,
+        mdp_salt,
+        smt_inclusion_proof,
+        FramingPolicy::Concatenated,
+        metrics,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn proof_verify_with_framing<D: Hash>(
+    proving_value: &BigUint,
+    base: u32,
+    commitment: &[u8],
+    plr_padding: &Option<GenericArray<u8, PlrPaddingSize>>,
+    chain_nodes: &[GenericArray<u8, ChainNodesSize>],
+    mdp_salt: &[u8],
+    smt_inclusion_proof: &[u8],
+    framing: FramingPolicy,
+    metrics: &dyn HwMetrics,
+) -> Result<bool, HwError> {
+    // the clock is only read for sinks timing the stages, see `HwMetrics::times_stages`
+    let timed = metrics.times_stages();
+    let on_stage = |stage: VerifyStage, start: Option<Instant>| {
+        if let Some(start) = start {
+            metrics.on_stage(stage, start.elapsed());
+        }
+    };
+    let start = timed.then(Instant::now);
+    let requested_value_split = value_split_per_base(proving_value, compute_bitlength(base));
+    let salted_mdp_root = match salted_mdp_root::<D>(
+        &requested_value_split,
+        plr_padding,
+        chain_nodes,
+        mdp_salt,
+        framing,
+    ) {
+        Some(root) => root,
+        None => return Ok(false),
+    };
+    on_stage(VerifyStage::ChainNodes, start);
+
+    // Decode the Merkle proof.
+    let start = timed.then(Instant::now);
+    let deserialized_proof = MerkleProof::<HashWiresNodeSmt<D>>::deserialize(smt_inclusion_proof)?;
+    on_stage(VerifyStage::SmtDecoding, start);
+
+    let start = timed.then(Instant::now);
+    let commitment_node = HashWiresNodeSmt::<D>::new(commitment.to_owned());
+    let smt_mdp_node = HashWiresNodeSmt::<D>::new(salted_mdp_root.to_vec());
+
+    let result = deserialized_proof.verify_inclusion_proof(&[smt_mdp_node], &commitment_node);
+    on_stage(VerifyStage::SmtInclusion, start);
+    Ok(result)
+}
+
+// Recompute the salted MDP root from the chain nodes of a proof, without allocating. Every
+// digit of the proving value must be covered by exactly one chain node, which is checked
+// before any hashing.
+fn salted_mdp_root<D: Hash>(
+    digits: &[u8],
+    plr_padding: &Option<GenericArray<u8, PlrPaddingSize>>,
+    chain_nodes: &[GenericArray<u8, ChainNodesSize>],
+    mdp_salt: &[u8],
+    framing: FramingPolicy,
+) -> Option<[u8; 32]> {
+    if chain_nodes.len() != digits.len() {
+        return None;
+    }
+    let mut ctx = HashContext::<D>::new();
+    ctx.set_framing(framing);
+    // Advance each chain node by its digit and absorb it into the MDP root right away, most
+    // significant digit first, so that no intermediate node is stored.
+    let mut mdp_root = [0u8; 32];
+    for (i, (node, digit)) in chain_nodes.iter().zip(digits).enumerate() {
+        let chain_node = ctx.hash_chain(node, *digit as usize);
+        let previous = match i {
+            0 => plr_padding.as_ref().map(|p| &p[..]),
+            _ => Some(&mdp_root[..]),
+        };
+        mdp_root = ctx.plr_absorb(previous, &chain_node);
+    }
+    Some(ctx.salted_hash(mdp_salt, &mdp_root))
+}
+
+/// The size of the scratch buffer of [`Commitment::verify_in_place`] for proofs under
+/// `params`, i.e. the maximum number of digits of a threshold.
+pub fn scratch_size(params: &HwParams) -> Result<usize, HwError> {
+    params.max_mdp_size()
+}
+
+/// Verify a HashWires proof against a raw 32-byte commitment root, such as one fetched from a
+/// ledger, without building a [`Commitment`] first. Unsupported bases are rejected with
+/// [`HwError::ParameterError`]. To check the inclusion proof alone against the root, see
+/// [`crate::traits::VerifyAgainstRootBytes`].
+pub fn proof_verify_root<D: Hash>(
+    root: &[u8],
+    base: u32,
+    proof: &Proof,
+    threshold: &BigUint,
+) -> Result<(), HwError> {
+    if !SUPPORTED_BASES.contains(&base) {
+        return Err(HwError::ParameterError);
+    }
+    if root.len() != 32 {
+        return Err(HwError::SerializationError);
+    }
+    Commitment::<D>::from_root(root, base).verify(proof, threshold)
+}
+
+/// Verify a serialized HashWires proof over a serialized commitment, selecting the digest from
+/// the identifier of the commitment. Verifying BLAKE3 commitments requires the `blake3` feature.
+/// Chain nodes are 32-byte digests, so commitments identified as SHA-512 are rejected with
+/// [`HwError::ParameterError`], as are unsupported bases.
+pub fn verify_any(
+    commitment: &[u8],
+    base: u32,
+    proof: &[u8],
+    threshold: &BigUint,
+) -> Result<(), HwError> {
+    verify_any_with_limit(commitment, base, proof, threshold, usize::MAX)
+}
+
+/// Like [`verify_any`], rejecting the proof with [`HwError::WorkLimitError`] before any
+/// hashing if its verification cost exceeds `max_hash_iterations`.
+pub fn verify_any_with_limit(
+    commitment: &[u8],
+    base: u32,
+    proof: &[u8],
+    threshold: &BigUint,
+    max_hash_iterations: usize,
+) -> Result<(), HwError> {
+    if !SUPPORTED_BASES.contains(&base) {
+        return Err(HwError::ParameterError);
+    }
+    let proof = Proof::deserialize(proof)?;
+    let digest_id = commitment.first().ok_or(HwError::SerializationError)?;
+    match DigestId::from_u8(*digest_id)? {
+        DigestId::Sha256 => Commitment::<sha2::Sha256>::deserialize(commitment, base)?
+            .verify_with_limit(&proof, threshold, max_hash_iterations),
+        DigestId::Sha3_256 => Commitment::<sha3::Sha3_256>::deserialize(commitment, base)?
+            .verify_with_limit(&proof, threshold, max_hash_iterations),
+        #[cfg(feature = "blake3")]
+        DigestId::Blake3 => Commitment::<blake3::Hasher>::deserialize(commitment, base)?
+            .verify_with_limit(&proof, threshold, max_hash_iterations),
+        _ => Err(HwError::ParameterError),
+    }
+}
+
+/// Like [`verify_any_with_limit`], verifying with
+/// [`Commitment::verify_constant_work_with_limit`] so that the work does not depend on where
+/// verification fails. The work limit applies to the [`Commitment::constant_work_cost`], and
+/// malformed inputs are still rejected before any hashing.
+pub fn verify_any_constant_work(
+    commitment: &[u8],
+    base: u32,
+    proof: &[u8],
+    threshold: &BigUint,
+    max_hash_iterations: usize,
+) -> Result<(), HwError> {
+    if !SUPPORTED_BASES.contains(&base) {
+        return Err(HwError::ParameterError);
+    }
+    let proof = Proof::deserialize(proof)?;
+    let digest_id = commitment.first().ok_or(HwError::SerializationError)?;
+    match DigestId::from_u8(*digest_id)? {
+        DigestId::Sha256 => Commitment::<sha2::Sha256>::deserialize(commitment, base)?
+            .verify_constant_work_with_limit(&proof, threshold, max_hash_iterations),
+        DigestId::Sha3_256 => Commitment::<sha3::Sha3_256>::deserialize(commitment, base)?
+            .verify_constant_work_with_limit(&proof, threshold, max_hash_iterations),
+        #[cfg(feature = "blake3")]
+        DigestId::Blake3 => Commitment::<blake3::Hasher>::deserialize(commitment, base)?
+            .verify_constant_work_with_limit(&proof, threshold, max_hash_iterations),
+        _ => Err(HwError::ParameterError),
+    }
+}
+
+/// Verify a serialized HashWires proof over a serialized commitment generated under `params`,
+/// selecting the digest from the identifier of the commitment, for integrations only holding
+/// byte blobs. The proof is rejected before any hashing with [`HwError::MdpSizeError`] if it
+/// has more chain nodes than the parameters allow, and with [`HwError::ParameterError`] if the
+/// proof or the commitment was not generated under `params` (MDP salt size, framing,
+/// chain-length policy or MDP tree height). Verifying BLAKE3 commitments requires the `blake3` feature.
+/// As for [`verify_any`], commitments identified as SHA-512 are rejected with
+/// [`HwError::ParameterError`].
+pub fn verify_bytes<T: HwValue>(
+    commitment: &[u8],
+    proof: &[u8],
+    threshold: &T,
+    params: &HwParams,
+) -> Result<(), HwError> {
+    let proof = Proof::deserialize_with_params(proof, params)?;
+    if proof.mdp_salt.len() != params.mdp_salt_size || proof.framing != params.framing_policy {
+        return Err(HwError::ParameterError);
+    }
+    let threshold = threshold.to_biguint()?;
+    let digest_id = commitment.first().ok_or(HwError::SerializationError)?;
+    match DigestId::from_u8(*digest_id)? {
+        DigestId::Sha256 => {
+            verify_deserialized::<sha2::Sha256>(commitment, &proof, &threshold, params)
+        }
+        DigestId::Sha3_256 => {
+            verify_deserialized::<sha3::Sha3_256>(commitment, &proof, &threshold, params)
+        }
+        #[cfg(feature = "blake3")]
+        DigestId::Blake3 => {
+            verify_deserialized::<blake3::Hasher>(commitment, &proof, &threshold, params)
+        }
+        _ => Err(HwError::ParameterError),
+    }
+}
+
+fn verify_deserialized<D: Hash>(
+    commitment: &[u8],
+    proof: &Proof,
+    threshold: &BigUint,
+    params: &HwParams,
+) -> Result<(), HwError> {
+    let commitment = Commitment::<D>::deserialize(commitment, params.base)?;
+    if commitment.chain_length_policy() != params.chain_length_policy
+        || commitment.mdp_height()
+            != compute_mdp_height(params.base, params.max_number_bits) as usize
+    {
+        return Err(HwError::ParameterError);
+    }
+    commitment.verify(proof, threshold)
+}
+
+/// Generate HashWires commitment.
+pub fn commit_gen<D: Hash>(
+    value: &BigUint,
+    base: u32,
+    seed: &[u8],
+    max_number_bits: usize,
+    mdp_smt_height: usize,
+) -> Result<Vec<u8>, HwError> {
+    let params = HwParams::new(base, max_number_bits);
+    commit_gen_with_params::<D>(value, seed, &params, mdp_smt_height).map(|(root, _)| root)
+}
+
+/// Generate HashWires commitment with `params`, including non-default sizes and chain-length
+/// policies, along with the leaves of its MDP tree.
+pub(crate) fn commit_gen_with_params<D: Hash>(
+    value: &BigUint,
+    seed: &[u8],
+    params: &HwParams,
+    mdp_smt_height: usize,
+) -> Result<(Vec<u8>, Vec<LeafHandle>), HwError> {
+    let (base, max_number_bits) = (params.base, params.max_number_bits);
     // Step 0: compute base's bitlength
     let bitlength = compute_bitlength(base);
 
-    // Step 1: find MDP
-    let mdp: Vec<BigUint> = find_mdp(value, base);
-
-    // Step 2: split MDP values per base (bitlength digits)
-    let splits: Vec<Vec<u8>> = mdp_splits(&mdp, bitlength);
+    // Steps 1 and 2: find MDP, along with its values split per base (bitlength digits)
+    let (_, splits) = find_mdp_with_splits(value, base);
 
-    // Step 3: compute required hashchains
-    let chains: Vec<Vec<[u8; 32]>> =
-        compute_hash_chains::<D>(seed, splits[0].len(), base, splits[0][0]);
+    // Step 3: compute required hash chains
+    let mut ctx = HashContext::<D>::new();
+    ctx.set_byte_order(params.byte_order_policy);
+    ctx.set_framing(params.framing_policy);
+    let chains: Vec<Vec<[u8; 32]>> = ctx.compute_hash_chains(
+        seed,
+        splits[0].len(),
+        base,
+        splits[0][0],
+        params.chain_length_policy,
+    );
 
     // Step 4: MDP to hashchain(s) position wiring
     let wires: Vec<Vec<[u8; 32]>> = wires(&splits, &chains);
 
-    // Step A: pick mdp index
-    let mdp_index = pick_mdp_index(proving_value, &mdp)?;
-
-    // Step B: split proving value per base (bitlength digits)
-    let proving_value_split = value_split_per_base(proving_value, bitlength);
-
-    // Step 5: PLR roots per MDP
-    let (plr_roots, plr_proof) = plr_roots_and_proof::<D>(
-        seed,
-        &wires,
-        max_number_bits / bitlength,
-        mdp_index,
-        proving_value_split.len(),
-    );
+    // Step 5: SMT roots per MDP
+    let plr_roots = plr_roots(&mut ctx, seed, &wires, max_number_bits / bitlength);
 
     // Step 6: compute top salts
-    let salts = generate_subseeds::<D, MdpSaltSize>(TOP_SALT, seed, plr_roots.len());
+    let salts = ctx.generate_salts(TOP_SALT, seed, plr_roots.len(), params.mdp_salt_size);
 
     // Step 7: KDF smt roots
-    let top_salted_roots = compute_plr_roots::<D>(&plr_roots, &salts);
+    let top_salted_roots = compute_plr_roots(&mut ctx, &plr_roots, &salts);
 
     // Step 8: get shuffled indexes
     let shuffled_indexes = deterministic_index_shuffling(
@@ -264,108 +1249,74 @@ pub fn larger_than_proof_gen<D: Hash>(
         <[u8; 32]>::try_from(seed).map_err(|_| HwError::SeedLengthError)?,
     );
 
+    let leaves = leaf_handles(shuffled_indexes?);
+
     // Step 9: Compute final root (HW commitment)
     let smt_secret = generate_subseeds::<D, SmtSecretSize>(SMTREE_PADDING_SALT, seed, 1);
-    let hw_commitment = final_smt_root_and_proof::<D>(
+    let hw_commitment = final_smt_root::<D>(
         &top_salted_roots,
-        &shuffled_indexes?,
+        &leaves,
         mdp_smt_height,
-        mdp_index,
         &SmtSecret::from_bytes(&smt_secret[0]).unwrap(),
     )?;
-
-    // Step C: pick hashchain nodes for the proving value
-    let chain_nodes = proving_value_chain_nodes(&chains, &splits, &proving_value_split, mdp_index);
-
-    Ok((
-        hw_commitment.0,
-        plr_proof,
-        chain_nodes,
-        salts[mdp_index],
-        hw_commitment.1,
-    ))
+    Ok((hw_commitment, leaves))
 }
 
-/// Verify HashWires proof.
-pub fn proof_verify<D: Hash>(
-    proving_value: &BigUint,
+/// Generate HashWires commitment without materializing the hash chains and wires.
+///
+/// Each MDP element is processed on its own: its wire nodes are recomputed from the chain seeds,
+/// accumulated and salted, and only the resulting top salted root is kept. This trades extra
+/// hashing for a peak memory of one wire plus the top SMT leaves, and produces the same
+/// commitment as [`commit_gen`].
+pub fn commit_gen_streaming<D: Hash>(
+    value: &BigUint,
     base: u32,
-    commitment: &[u8],
-    plr_padding: &Option<GenericArray<u8, PlrPaddingSize>>,
-    chain_nodes: &[GenericArray<u8, ChainNodesSize>],
-    mdp_salt: &GenericArray<u8, MdpSaltSize>,
-    smt_inclusion_proof: &[u8],
-) -> Result<bool, HwError> {
-    let bitlength = compute_bitlength(base);
-    let requested_value_split = value_split_per_base(proving_value, bitlength);
-    let mdp_chain_nodes: Vec<[u8; 32]> = chain_nodes
-        .iter()
-        .enumerate()
-        .map(|(i, v)| hash_chain::<D>(v, requested_value_split[i] as usize))
-        .collect();
-
-    let mut hasher = D::new();
-
-    match plr_padding {
-        Some(p) => {
-            hasher.update(&p);
-        }
-        None => {}
-    }
-
-    let mut mdp_root = [0; 32];
-    mdp_chain_nodes.iter().enumerate().for_each(|(i, v)| {
-        if i != 0 {
-            hasher.update(&mdp_root);
-        }
-        hasher.update(v);
-        mdp_root.copy_from_slice(hasher.finalize_reset().as_slice());
-    });
-
-    let salted_mdp_root = salted_hash::<D>(mdp_salt, &mdp_root);
-
-    // Decode the Merkle proof.
-    let deserialized_proof = MerkleProof::<HashWiresNodeSmt<D>>::deserialize(smt_inclusion_proof)
-        .map_err(|_| HwError::MerkleProofDecodingError)?;
-
-    let commitment_node = HashWiresNodeSmt::<D>::new(commitment.to_owned());
-    let smt_mdp_node = HashWiresNodeSmt::<D>::new(salted_mdp_root.to_vec());
-
-    Ok(deserialized_proof.verify_inclusion_proof(&[smt_mdp_node], &commitment_node))
+    seed: &[u8],
+    max_number_bits: usize,
+    mdp_smt_height: usize,
+) -> Result<Vec<u8>, HwError> {
+    commit_gen_streaming_with_leaves::<D>(value, base, seed, max_number_bits, mdp_smt_height)
+        .map(|(root, _)| root)
 }
 
-/// Generate HashWires commitment.
-pub fn commit_gen<D: Hash>(
+/// Generate HashWires commitment as [`commit_gen_streaming`], along with the leaves of its MDP
+/// tree.
+fn commit_gen_streaming_with_leaves<D: Hash>(
     value: &BigUint,
     base: u32,
     seed: &[u8],
     max_number_bits: usize,
     mdp_smt_height: usize,
-) -> Result<Vec<u8>, HwError> {
+) -> Result<(Vec<u8>, Vec<LeafHandle>), HwError> {
     // Step 0: compute base's bitlength
     let bitlength = compute_bitlength(base);
 
     // Step 1: find MDP
     let mdp: Vec<BigUint> = find_mdp(value, base);
 
-    // Step 2: split MDP values per base (bitlength digits)
-    let splits: Vec<Vec<u8>> = mdp_splits(&mdp, bitlength);
-
-    // Step 3: compute required hash chains
-    let chains: Vec<Vec<[u8; 32]>> =
-        compute_hash_chains::<D>(seed, splits[0].len(), base, splits[0][0]);
-
-    // Step 4: MDP to hashchain(s) position wiring
-    let wires: Vec<Vec<[u8; 32]>> = wires(&splits, &chains);
-
-    // Step 5: SMT roots per MDP
-    let plr_roots = plr_roots::<D>(seed, &wires, max_number_bits / bitlength);
+    // Step 2-3: derive one seed per hash chain (chains themselves are never stored)
+    let chains_count = value_split_per_base(&mdp[0], bitlength).len();
+    let mut ctx = HashContext::<D>::new();
+    let chain_seeds = ctx.generate_subseeds::<D::OutputSize>(LEAF_SALT, seed, chains_count);
 
     // Step 6: compute top salts
-    let salts = generate_subseeds::<D, MdpSaltSize>(TOP_SALT, seed, plr_roots.len());
-
-    // Step 7: KDF smt roots
-    let top_salted_roots = compute_plr_roots::<D>(&plr_roots, &salts);
+    let salts = ctx.generate_salts(TOP_SALT, seed, mdp.len(), DEFAULT_MDP_SALT_SIZE);
+
+    // Steps 4, 5 and 7 per MDP element: wire, PLR root and KDF smt root
+    let mut top_salted_roots = Vec::with_capacity(mdp.len());
+    for (mdp_value, salt) in mdp.iter().zip(salts.iter()) {
+        let split = value_split_per_base(mdp_value, bitlength);
+        let offset = chains_count - split.len();
+        let wire: Vec<[u8; 32]> = split
+            .iter()
+            .enumerate()
+            .map(|(i, s)| ctx.hash_chain(&chain_seeds[i + offset], *s as usize))
+            .collect();
+        let plr_root = ctx
+            .plr_accumulator(seed, &wire, max_number_bits / bitlength, wire.len())
+            .0;
+        top_salted_roots.push(ctx.salted_hash(salt, &plr_root));
+    }
 
     // Step 8: get shuffled indexes
     let shuffled_indexes = deterministic_index_shuffling(
@@ -374,15 +1325,17 @@ pub fn commit_gen<D: Hash>(
         <[u8; 32]>::try_from(seed).map_err(|_| HwError::SeedLengthError)?,
     );
 
+    let leaves = leaf_handles(shuffled_indexes?);
+
     // Step 9: Compute final root (HW commitment)
     let smt_secret = generate_subseeds::<D, SmtSecretSize>(SMTREE_PADDING_SALT, seed, 1);
     let hw_commitment = final_smt_root::<D>(
         &top_salted_roots,
-        &shuffled_indexes?,
+        &leaves,
         mdp_smt_height,
         &SmtSecret::from_bytes(&smt_secret[0]).unwrap(),
-    );
-    Ok(hw_commitment)
+    )?;
+    Ok((hw_commitment, leaves))
 }
 
 //////////////////////
@@ -391,39 +1344,20 @@ pub fn commit_gen<D: Hash>(
 
 // Compute plr roots; this function is reused, so we extracted it.
 fn compute_plr_roots<D: Hash>(
+    ctx: &mut HashContext<D>,
     plr_roots: &[GenericArray<u8, PlrPaddingSize>],
-    salts: &[GenericArray<u8, MdpSaltSize>],
+    salts: &[Vec<u8>],
 ) -> Vec<[u8; 32]> {
     plr_roots
         .iter()
         .enumerate()
-        .map(|(i, v)| salted_hash::<D>(&salts[i], v))
-        .collect()
-}
-
-// Get required chain nodes for proofs
-fn proving_value_chain_nodes(
-    chains: &[Vec<[u8; 32]>],
-    mdp_splits: &[Vec<u8>],
-    proving_value_split: &[u8],
-    mdp_index: usize,
-) -> Vec<GenericArray<u8, ChainNodesSize>> {
-    proving_value_split
-        .iter()
-        .enumerate()
-        .map(|(i, s)| {
-            let chain_index = i + chains.len() - proving_value_split.len();
-            let mdp_split_index = i + mdp_splits[mdp_index].len() - proving_value_split.len();
-            let result =
-                chains[chain_index][(mdp_splits[mdp_index][mdp_split_index] - *s) as usize];
-            GenericArray::clone_from_slice(&result[..])
-        })
+        .map(|(i, v)| ctx.salted_hash(&salts[i], v))
         .collect()
 }
 
 // find the mdp index where proving_value <= mdp[i]
 // TODO: use binary search
-fn pick_mdp_index(proving_value: &BigUint, mdp: &[BigUint]) -> Result<usize, HwError> {
+pub(crate) fn pick_mdp_index(proving_value: &BigUint, mdp: &[BigUint]) -> Result<usize, HwError> {
     for i in (0..mdp.len()).rev() {
         if proving_value <= &mdp[i] {
             return Ok(i);
@@ -433,7 +1367,7 @@ fn pick_mdp_index(proving_value: &BigUint, mdp: &[BigUint]) -> Result<usize, HwE
 }
 
 // Compute base's bitlength.
-fn compute_bitlength(base: u32) -> usize {
+pub(crate) fn compute_bitlength(base: u32) -> usize {
     match base {
         2 => 1,
         4 => 2,
@@ -443,50 +1377,77 @@ fn compute_bitlength(base: u32) -> usize {
     }
 }
 
-fn final_smt_root<D: Hash>(
+// The leaves of the MDP tree, MDP element `i` being at position `shuffled_indexes[i]`.
+pub(crate) fn leaf_handles(shuffled_indexes: Vec<usize>) -> Vec<LeafHandle> {
+    shuffled_indexes
+        .into_iter()
+        .enumerate()
+        .map(|(mdp_index, position)| LeafHandle {
+            mdp_index,
+            position,
+        })
+        .collect()
+}
+
+pub(crate) fn final_smt_root<D: Hash>(
+    top_salted_roots: &[[u8; 32]],
+    leaves: &[LeafHandle],
+    tree_height: usize,
+    smt_secret: &SmtSecret,
+) -> Result<Vec<u8>, HwError> {
+    let tree = build_mdp_tree::<D>(top_salted_roots, leaves, tree_height, smt_secret)?;
+    Ok(tree.get_root_raw().serialize())
+}
+
+// Build the MDP tree from its leaves in any order. `Smt::build` expects its leaves sorted by
+// position and silently diverges on duplicates, so they are sorted here and duplicate positions
+// are rejected with `HwError::ShuffleError`, the positions coming from the shuffling.
+fn build_mdp_tree<D: Hash>(
     top_salted_roots: &[[u8; 32]],
-    shuffled_indexes: &[usize],
+    leaves: &[LeafHandle],
     tree_height: usize,
     smt_secret: &SmtSecret,
-) -> Vec<u8> {
-    let mut smt_leaves = compute_smt_leaves(top_salted_roots, shuffled_indexes, tree_height);
-    smt_leaves.sort_by(|(t1, _), (t2, _)| t1.cmp(t2));
+) -> Result<Smt<node_template::HashWiresNodeSmt<D>>, HwError> {
+    let mut smt_leaves = compute_smt_leaves(top_salted_roots, leaves, tree_height);
+    smt_leaves.sort_by_key(|(t, _)| *t);
+    if smt_leaves.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+        return Err(HwError::ShuffleError);
+    }
     let mut tree: Smt<node_template::HashWiresNodeSmt<D>> = Smt::new(tree_height);
     tree.build(&smt_leaves, smt_secret);
-    tree.get_root_raw().serialize()
+    Ok(tree)
 }
 
 fn compute_smt_leaves<D: Hash>(
     top_salted_roots: &[[u8; 32]],
-    shuffled_indexes: &[usize],
+    leaves: &[LeafHandle],
     tree_height: usize,
 ) -> Vec<(TreeIndex, node_template::HashWiresNodeSmt<D>)> {
-    top_salted_roots
+    leaves
         .iter()
-        .enumerate()
-        .map(|(i, s)| {
+        .map(|leaf| {
             (
-                TreeIndex::from_u32(tree_height, shuffled_indexes[i] as u32),
-                node_template::HashWiresNodeSmt::<D>::new(s.to_vec()),
+                TreeIndex::from_u32(tree_height, leaf.position as u32),
+                node_template::HashWiresNodeSmt::<D>::new(
+                    top_salted_roots[leaf.mdp_index].to_vec(),
+                ),
             )
         })
         .collect()
 }
 
-fn final_smt_root_and_proof<D: Hash>(
+pub(crate) fn final_smt_root_and_proof<D: Hash>(
     top_salted_roots: &[[u8; 32]],
-    shuffled_indexes: &[usize],
+    leaves: &[LeafHandle],
     tree_height: usize,
-    leaf_index: usize,
+    leaf: &LeafHandle,
     smt_secret: &SmtSecret,
 ) -> Result<(Vec<u8>, Vec<u8>), HwError> {
-    let mut smt_leaves = compute_smt_leaves(top_salted_roots, shuffled_indexes, tree_height);
-
-    let node = smt_leaves[leaf_index].0;
-
-    smt_leaves.sort_by(|(t1, _), (t2, _)| t1.cmp(t2));
-    let mut tree: Smt<node_template::HashWiresNodeSmt<D>> = Smt::new(tree_height);
-    tree.build(&smt_leaves, smt_secret);
+    if !leaves.contains(leaf) {
+        return Err(HwError::InclusionProofError);
+    }
+    let tree = build_mdp_tree::<D>(top_salted_roots, leaves, tree_height, smt_secret)?;
+    let node = TreeIndex::from_u32(tree_height, leaf.position as u32);
 
     let inclusion_proof =
         MerkleProof::<node_template::HashWiresNodeSmt<D>>::generate_inclusion_proof(&tree, &[node])
@@ -496,42 +1457,15 @@ fn final_smt_root_and_proof<D: Hash>(
     Ok((tree.get_root_raw().serialize(), smt_proof))
 }
 
-fn mdp_splits(mdp: &[BigUint], bitlength: usize) -> Vec<Vec<u8>> {
-    mdp.iter()
-        .map(|v| value_split_per_base(v, bitlength))
-        .collect()
-}
-
-#[allow(clippy::type_complexity)]
-fn plr_roots_and_proof<D: Hash>(
-    seed: &[u8],
-    wires: &[Vec<[u8; 32]>],
-    max_length: usize,
-    mdp_index: usize,
-    proving_value_split_size: usize,
-) -> (
-    Vec<GenericArray<u8, PlrPaddingSize>>,
-    Option<GenericArray<u8, PlrPaddingSize>>,
-) {
-    let plr: Vec<(
-        GenericArray<u8, PlrPaddingSize>,
-        Option<GenericArray<u8, PlrPaddingSize>>,
-    )> = wires
-        .iter()
-        .map(|v| plr_accumulator::<D>(seed, v, max_length, proving_value_split_size))
-        .collect();
-
-    (plr.iter().map(|v| (*v).0).collect(), plr[mdp_index].1)
-}
-
 fn plr_roots<D: Hash>(
+    ctx: &mut HashContext<D>,
     seed: &[u8],
     wires: &[Vec<[u8; 32]>],
     max_length: usize,
 ) -> Vec<GenericArray<u8, PlrPaddingSize>> {
     wires
         .iter()
-        .map(|v| plr_accumulator::<D>(seed, v, max_length, v.len()).0)
+        .map(|v| ctx.plr_accumulator(seed, v, max_length, v.len()).0)
         .collect()
 }
 
@@ -562,13 +1496,32 @@ fn log_2(x: u32) -> u32 {
     num_bits::<u32>() as u32 - x.leading_zeros() - 1
 }
 
-fn compute_mdp_height(base: u32, max_number_bits: usize) -> u32 {
-    log_2(max_number_bits as u32 / log_2(base))
+/// The height of the MDP tree of commitments in `base` to values of up to `max_number_bits`
+/// bits. The tree has a leaf for each of the `max_number_bits / log2(base)` possible positions
+/// of an MDP element (rounded up, as for the digits of a value), so its height is the base-2
+/// logarithm of this number of positions, rounded up as well: e.g. 3 for the 8 positions of
+/// base 16 over 32 bits, and 4 for the 9 positions of base 16 over 36 bits, the tree having
+/// then 16 leaves. A single position gives a tree of height 0. The height is part of the
+/// serialized commitments, so that verifiers detect commitments generated under other
+/// parameters.
+///
+/// # Panics
+///
+/// Panics if `base` is smaller than 2.
+pub fn compute_mdp_height(base: u32, max_number_bits: usize) -> u32 {
+    // in usize, so that no number of bits is truncated
+    let bitlength = log_2(base) as usize;
+    let positions = (max_number_bits + bitlength - 1) / bitlength;
+    match positions {
+        0 | 1 => 0,
+        _ => usize::BITS - (positions - 1).leading_zeros(),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::traits::VerifyAgainstRootBytes;
     use blake3::Hasher as Blake3;
     use num_traits::{FromPrimitive, Num};
     use rand_core::{OsRng, RngCore};
@@ -588,7 +1541,7 @@ mod tests {
         rng.fill_bytes(&mut seed);
 
         // Generate secret.
-        let secret = Secret::<Blake3>::gen(&seed, &value);
+        let secret = Secret::<Blake3>::gen(&seed, value);
 
         // Generate and serialize commitment.
         let commitment = secret.commit(base, max_number_bits)?;
@@ -600,7 +1553,7 @@ mod tests {
 
         // Verify a range proof over a commitment.
         commitment.verify(&proof, &threshold)?;
-        Commitment::<Blake3>::deserialize(&commitment_bytes, base)
+        Commitment::<Blake3>::deserialize(&commitment_bytes, base)?
             .verify(&Proof::deserialize(&proof_bytes)?, &threshold)
     }
 
@@ -608,7 +1561,29 @@ mod tests {
     fn test_proof_success() -> Result<(), HwError> {
         let value = BigUint::from_u32(402).unwrap();
         let threshold = BigUint::from_u32(378).unwrap();
-        assert_eq!(true, prove_and_verify(4, 32, &value, &threshold).is_ok());
+        assert!(prove_and_verify(4, 32, &value, &threshold).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_proof_edge_values() -> Result<(), HwError> {
+        // Zero values and thresholds, a value equal to the base, and a number of MDP tree
+        // positions which is not a power of two.
+        for (base, max_number_bits, value, threshold) in [
+            (4, 32, 0u32, 0u32),
+            (4, 32, 402, 0),
+            (16, 32, 16, 15),
+            (2, 6, 13, 8),
+        ]
+        .iter()
+        {
+            prove_and_verify(
+                *base,
+                *max_number_bits,
+                &BigUint::from(*value),
+                &BigUint::from(*threshold),
+            )?;
+        }
         Ok(())
     }
 
@@ -616,7 +1591,20 @@ mod tests {
     fn test_proof_failure() -> Result<(), HwError> {
         let value = BigUint::from_u32(378).unwrap();
         let threshold = BigUint::from_u32(402).unwrap();
-        assert_eq!(true, prove_and_verify(4, 32, &value, &threshold).is_err());
+        assert!(prove_and_verify(4, 32, &value, &threshold).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_proof_for_shorter_threshold() -> Result<(), HwError> {
+        // A proof for a threshold with fewer digits must not verify for a larger threshold
+        // sharing its leading digits.
+        let secret = Secret::<Blake3>::gen(&[0u8; 32], &BigUint::from(402u32));
+        let commitment = secret.commit(4, 32)?;
+        let proof = secret.prove(4, 32, &BigUint::from(1u32))?;
+        commitment.verify(&proof, &BigUint::from(1u32))?;
+        assert!(commitment.verify(&proof, &BigUint::from(403u32)).is_err());
+        assert!(commitment.verify(&proof, &BigUint::from(0u32)).is_err());
         Ok(())
     }
 
@@ -713,6 +1701,101 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_deterministic_commit_and_prove() -> Result<(), HwError> {
+        use rand::SeedableRng;
+        use rand_chacha::ChaCha20Rng;
+
+        let value = BigUint::from(402u32);
+        let threshold = BigUint::from(378u32);
+        let mut rng = ChaCha20Rng::from_seed([7u8; 32]);
+        let secret = Secret::<Blake3>::gen_with_rng(&mut rng, &value);
+        let commitment = secret.commit(4, 32)?;
+        let proof = secret.prove(4, 32, &threshold)?;
+        commitment.verify(&proof, &threshold)?;
+
+        assert_eq!(
+            hex::encode(commitment.serialize()),
+            "040104be299c9a483a6c2517a78af45163860dba0a010e3ac0d69216fb995f99443d40"
+        );
+        assert_eq!(
+            hex::encode(proof.serialize()),
+            [
+                "0301041000a0ce8699f25d9d35a31786588b5187b44cfb8911afbce96921df19",
+                "cb83ea46464db61d25c917973a4de82c28add8038fae148993610a3dfd8fa4b9",
+                "7c602812dfe96960472e716736b603e4a250bea2cb3f2d8ff2f50a17bd95746c",
+                "2dfb553b4f292bac1886fcc3c8e62a57d2966d3613b7848970bcc956ce01d8a7",
+                "edf2416f415d1cee89c416519f5ce12a507350f232525acf73d79d0a1a6900ba",
+                "ed01a7e2c89fd0d8f51c54f1f0a449b7cf4b50e9136800930100000000000000",
+                "0400040400000000000000c332d937e9d51de30a19a6b8c97945cb9ac8a7bac4",
+                "4e1ae208d34a42f1534f83f05ce327e2e60090b6f0df4fff8c8c9898dca51826",
+                "42461a9fb4498a4d3fa6a608a43738d527593edfd4e2adc0d0f96d60a465f51f",
+                "bf3f5e29bf3a97af771b5ffd990168f71e1a1606a38f9f78490ec787be876ea6",
+                "ba0746c5d2c73d7fe9d1885983852e91818638d0422df7476451473560dc120a",
+                "52f9b1be45ef906dcc88e9",
+            ]
+            .concat()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_streaming() -> Result<(), HwError> {
+        let values = [
+            (4, 32, "402"),
+            (16, 32, "3413"),
+            (256, 64, "18446744073709551614"),
+            (2, 32, "1000"),
+        ];
+        for (base, max_number_bits, value) in values.iter() {
+            let value = BigUint::from_str_radix(value, 10).unwrap();
+            let secret = Secret::<Blake3>::gen(&[3u8; 32], &value);
+            assert_eq!(
+                secret.commit(*base, *max_number_bits)?.serialize(),
+                secret
+                    .commit_streaming(*base, *max_number_bits)?
+                    .serialize()
+            );
+        }
+
+        let secret = Secret::<Blake3>::gen(&[3u8; 32], &BigUint::from(402u32));
+        assert!(matches!(
+            secret.commit_streaming(3, 32),
+            Err(HwError::ParameterError)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_explain() -> Result<(), HwError> {
+        let value = BigUint::from_str_radix("312", 4).unwrap();
+        let secret = Secret::<Blake3>::gen(&[0u8; 32], &value);
+        let explanation = secret.explain(4, 32)?;
+
+        assert_eq!(explanation.mdp_smt_height, 4);
+        assert_eq!(
+            explanation.mdp,
+            vec![
+                BigUint::from_str_radix("312", 4).unwrap(),
+                BigUint::from_str_radix("303", 4).unwrap(),
+                BigUint::from_str_radix("233", 4).unwrap(),
+            ]
+        );
+        assert_eq!(
+            explanation.splits,
+            vec![vec![3, 1, 2], vec![3, 0, 3], vec![2, 3, 3]]
+        );
+        assert_eq!(explanation.chain_lengths, vec![4, 4, 4]);
+        assert_eq!(explanation.shuffled_indexes.len(), 3);
+        assert!(explanation.shuffled_indexes.iter().all(|i| *i < 16));
+        assert!(matches!(
+            secret.explain(3, 32),
+            Err(HwError::ParameterError)
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn test_pick_mdp_index() -> Result<(), HwError> {
         let mdp = vec![
@@ -735,4 +1818,597 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_proof_builder() -> Result<(), HwError> {
+        let secret = Secret::<Blake3>::gen(&[3u8; 32], &BigUint::from(3413u32));
+        let threshold = BigUint::from(2999u32);
+        let commitment = secret.commit(16, 32)?;
+        let proof = secret.prove(16, 32, &threshold)?;
+
+        let mut builder = ProofBuilder::new(proof.digest_id())
+            .plr_padding(proof.plr_padding())
+            .mdp_salt(proof.mdp_salt())
+            .smt_inclusion_proof(proof.smt_inclusion_proof());
+        for chain_node in proof.chain_nodes() {
+            builder = builder.chain_node(chain_node);
+        }
+        let rebuilt = builder.build()?;
+        assert_eq!(rebuilt.serialize(), proof.serialize());
+        commitment.verify(&rebuilt, &threshold)?;
+
+        // The inclusion proof re-encodes from its typed parts.
+        let leaf_indexes = proof.smt_leaf_indexes::<Blake3>()?;
+        let sibling_hashes = proof.smt_sibling_hashes::<Blake3>()?;
+        assert_eq!(leaf_indexes.len(), 1);
+        assert!(sibling_hashes.iter().all(|hash| hash.len() == 32));
+        let mut builder = ProofBuilder::new(proof.digest_id())
+            .plr_padding(proof.plr_padding())
+            .mdp_salt(proof.mdp_salt())
+            .smt_inclusion_parts::<Blake3>(&leaf_indexes, &sibling_hashes);
+        for chain_node in proof.chain_nodes() {
+            builder = builder.chain_node(chain_node);
+        }
+        assert_eq!(builder.build()?.serialize(), proof.serialize());
+        assert!(matches!(
+            proof.smt_leaf_indexes::<sha2::Sha256>(),
+            Err(HwError::DigestMismatchError)
+        ));
+
+        assert!(ProofBuilder::new(DigestId::Blake3)
+            .mdp_salt(&[0u8; 3])
+            .build()
+            .is_err());
+        assert!(matches!(
+            ProofBuilder::new(DigestId::Blake3)
+                .mdp_salt(proof.mdp_salt())
+                .smt_inclusion_proof(&[0u8; 65536])
+                .build(),
+            Err(HwError::SerializationError)
+        ));
+        let mut builder = ProofBuilder::new(DigestId::Blake3).mdp_salt(proof.mdp_salt());
+        for _ in 0..=u16::MAX as usize / ChainNodesSize::to_usize() {
+            builder = builder.chain_node(proof.chain_nodes()[0]);
+        }
+        assert!(matches!(builder.build(), Err(HwError::SerializationError)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_proof_verify_root() -> Result<(), HwError> {
+        let secret = Secret::<Blake3>::gen(&[5u8; 32], &BigUint::from(402u32));
+        let threshold = BigUint::from(378u32);
+        let root = secret.commit(4, 32)?.root().to_vec();
+        let proof = secret.prove(4, 32, &threshold)?;
+        proof_verify_root::<Blake3>(&root, 4, &proof, &threshold)?;
+        assert!(proof_verify_root::<Blake3>(&root[..31], 4, &proof, &threshold).is_err());
+        assert!(proof_verify_root::<Blake3>(&[0u8; 32], 4, &proof, &threshold).is_err());
+        assert!(matches!(
+            proof_verify_root::<Blake3>(&root, 3, &proof, &threshold),
+            Err(HwError::ParameterError)
+        ));
+
+        // The inclusion proof alone, against the root bytes.
+        let tree_height = 4;
+        let mut tree: Smt<HashWiresNodeSmt<Blake3>> = Smt::new(tree_height);
+        let leaves: Vec<_> = [0u32, 1, 15]
+            .iter()
+            .map(|position| {
+                (
+                    TreeIndex::from_u32(tree_height, *position),
+                    HashWiresNodeSmt::<Blake3>::new(vec![*position as u8 + 1; 32]),
+                )
+            })
+            .collect();
+        tree.build(&leaves, &ALL_ZEROS_SECRET);
+        let root = tree.get_root_raw().serialize();
+        let inclusion_proof = MerkleProof::<HashWiresNodeSmt<Blake3>>::generate_inclusion_proof(
+            &tree,
+            &[TreeIndex::from_u32(tree_height, 15)],
+        )
+        .ok_or(HwError::ProofVerificationError)?;
+        assert!(inclusion_proof.verify_against_root_bytes(&[16u8; 32], &root));
+        assert!(!inclusion_proof.verify_against_root_bytes(&[1u8; 32], &root));
+        assert!(!inclusion_proof.verify_against_root_bytes(&[16u8; 32], &root[..31]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_any() -> Result<(), HwError> {
+        use sha2::Sha256;
+        use sha3::Sha3_256;
+
+        let threshold = BigUint::from(2999u32);
+        let secret = Secret::<Sha256>::gen(&[6u8; 32], &BigUint::from(3413u32));
+        let sha256_commitment = secret.commit(16, 32)?.serialize();
+        let sha256_proof = secret.prove(16, 32, &threshold)?.serialize();
+        verify_any(&sha256_commitment, 16, &sha256_proof, &threshold)?;
+
+        let secret = secret.to_digest::<Sha3_256>();
+        let sha3_commitment = secret.commit(16, 32)?.serialize();
+        let sha3_proof = secret.prove(16, 32, &threshold)?.serialize();
+        verify_any(&sha3_commitment, 16, &sha3_proof, &threshold)?;
+
+        // Mixing digests is reported as such.
+        assert!(matches!(
+            verify_any(&sha256_commitment, 16, &sha3_proof, &threshold),
+            Err(HwError::DigestMismatchError)
+        ));
+        assert!(matches!(
+            Commitment::<Blake3>::deserialize(&sha256_commitment, 16),
+            Err(HwError::DigestMismatchError)
+        ));
+
+        // Digests without a 32-byte output and unsupported bases are rejected.
+        let mut sha512_commitment = sha256_commitment.clone();
+        sha512_commitment[0] = DigestId::Sha512.as_u8();
+        assert!(matches!(
+            verify_any(&sha512_commitment, 16, &sha256_proof, &threshold),
+            Err(HwError::ParameterError)
+        ));
+        assert!(matches!(
+            verify_any_constant_work(&sha512_commitment, 16, &sha256_proof, &threshold, 1 << 16),
+            Err(HwError::ParameterError)
+        ));
+        assert!(matches!(
+            verify_bytes(
+                &sha512_commitment,
+                &sha256_proof,
+                &2999u32,
+                &HwParams::new(16, 32)
+            ),
+            Err(HwError::ParameterError)
+        ));
+        assert!(matches!(
+            verify_any(&sha256_commitment, 3, &sha256_proof, &threshold),
+            Err(HwError::ParameterError)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_mdp_size() -> Result<(), HwError> {
+        let params = HwParams::new(16, 32);
+        assert_eq!(params.max_mdp_size()?, 8);
+        assert!(HwParams::new(3, 32).max_mdp_size().is_err());
+
+        let secret = Secret::<Blake3>::gen(&[0u8; 32], &BigUint::from(3413u32));
+        let proof = secret.prove(16, 32, &2999u32)?.serialize();
+        Proof::deserialize_with_params(&proof, &params)?;
+        assert!(matches!(
+            Proof::deserialize_with_params(&proof, &HwParams::new(16, 8)),
+            Err(HwError::MdpSizeError)
+        ));
+
+        // a chain node length which is not a multiple of the node size is rejected
+        let mut misaligned = proof[..PROOF_HEADER_SIZE].to_vec();
+        misaligned.extend_from_slice(&[0, 33]);
+        misaligned.extend_from_slice(&[0u8; 33 + 16 + 2]);
+        assert!(matches!(
+            Proof::deserialize(&misaligned),
+            Err(HwError::SerializationError)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_mdp_height() -> Result<(), HwError> {
+        assert_eq!(compute_mdp_height(16, 32), 3);
+        assert_eq!(compute_mdp_height(16, 36), 4);
+        assert_eq!(compute_mdp_height(2, 64), 6);
+        assert_eq!(compute_mdp_height(256, 8), 0);
+        // positions are rounded up as digits are
+        assert_eq!(compute_mdp_height(16, 34), 4);
+
+        let params = HwParams::new(16, 36);
+        let secret = Secret::<sha2::Sha256>::gen(&[0u8; 32], &BigUint::from(3413u32));
+        let (commitment, proof) = secret.commit_and_prove(&params, &2999u32)?;
+        assert_eq!(commitment.mdp_height(), 4);
+        let bytes = commitment.serialize();
+        let deserialized = Commitment::<sha2::Sha256>::deserialize(&bytes, 16)?;
+        assert_eq!(deserialized.mdp_height(), 4);
+        verify_bytes(&bytes, &proof.serialize(), &2999u32, &params)?;
+        assert!(matches!(
+            verify_bytes(&bytes, &proof.serialize(), &2999u32, &HwParams::new(16, 32)),
+            Err(HwError::ParameterError)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_commitment_root_length() -> Result<(), HwError> {
+        let secret = Secret::<Blake3>::gen(&[0u8; 32], &BigUint::from(3413u32));
+        let bytes = secret.commit(16, 32)?.serialize();
+        Commitment::<Blake3>::deserialize(&bytes, 16)?;
+
+        // roots of any other length than a digest are rejected, before they reach the
+        // length-prefixed encodings of the transcript hash
+        let mut oversized = bytes.clone();
+        oversized.resize(COMMITMENT_HEADER_SIZE + 70_000, 0);
+        assert!(matches!(
+            Commitment::<Blake3>::deserialize(&oversized, 16),
+            Err(HwError::SerializationError)
+        ));
+        assert!(matches!(
+            Commitment::<Blake3>::deserialize(&bytes[..bytes.len() - 1], 16),
+            Err(HwError::SerializationError)
+        ));
+        assert!(verify_any(&oversized, 16, &[], &BigUint::from(1u32)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_long_values() -> Result<(), HwError> {
+        assert_eq!(compute_mdp_height(2, 1024), 10);
+        assert_eq!(compute_mdp_height(256, 1024), 7);
+        assert_eq!(compute_mdp_height(16, 512), 7);
+        for max_number_bits in [512usize, 1024].iter() {
+            let value = (BigUint::from(1u32) << (max_number_bits - 1)) + 0xdead_beefu32;
+            let threshold = (BigUint::from(1u32) << (max_number_bits - 2)) + 12345u32;
+            for base in SUPPORTED_BASES.iter() {
+                prove_and_verify(*base, *max_number_bits, &value, &threshold)?;
+                assert!(prove_and_verify(*base, *max_number_bits, &threshold, &value).is_err());
+            }
+        }
+        let secret = Secret::<Blake3>::gen(&[0u8; 32], &BigUint::from(3413u32));
+        assert!(matches!(
+            secret.commit(16, 2048),
+            Err(HwError::ParameterError)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_with_limit() -> Result<(), HwError> {
+        let secret = Secret::<Blake3>::gen(&[4u8; 32], &BigUint::from(0xABCDEFu32));
+        let threshold = BigUint::from(0xAB0000u32);
+        let commitment = secret.commit(256, 32)?;
+        let proof = secret.prove(256, 32, &threshold)?;
+
+        let cost = proof.verification_cost(256, &threshold);
+        assert!(cost >= 0xAB);
+        commitment.verify_with_limit(&proof, &threshold, cost)?;
+        assert!(matches!(
+            commitment.verify_with_limit(&proof, &threshold, cost - 1),
+            Err(HwError::WorkLimitError)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_mdp_salt_size() -> Result<(), HwError> {
+        let secret = Secret::<Blake3>::gen(&[7u8; 32], &BigUint::from(3413u32));
+        let threshold = BigUint::from(2999u32);
+        let params = HwParams::new(16, 32);
+        assert_eq!(
+            secret.commit_with_params(&params)?.serialize(),
+            secret.commit(16, 32)?.serialize()
+        );
+
+        for size in [MIN_MDP_SALT_SIZE, MAX_MDP_SALT_SIZE].iter() {
+            let params = params.with_mdp_salt_size(*size);
+            let (commitment, proof) = secret.commit_and_prove(&params, &threshold)?;
+            assert_eq!(
+                commitment.serialize(),
+                secret.commit_with_params(&params)?.serialize()
+            );
+            let proof = Proof::deserialize(&proof.serialize())?;
+            assert_eq!(proof.mdp_salt().len(), *size);
+            commitment.verify(&proof, &threshold)?;
+        }
+        assert!(secret
+            .commit_with_params(&params.with_mdp_salt_size(MAX_MDP_SALT_SIZE + 1))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hw_value() -> Result<(), HwError> {
+        let secret = Secret::<Blake3>::from_value(&[0u8; 32], &("d55", 16))?;
+        assert_eq!(secret.value, BigUint::from(3413u32));
+        let commitment = secret.commit(16, 32)?;
+
+        let proof = secret.prove(16, 32, &2999u64)?;
+        commitment.verify(&proof, &2999u32)?;
+        commitment.verify(&proof, &("2999", 10))?;
+        commitment.verify(&proof, &BigUint::from(2999u32))?;
+        assert!(commitment.verify(&proof, &("bb8", 16)).is_err());
+        assert!(secret.prove(16, 32, &("xyz", 10)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_leaf_handles() -> Result<(), HwError> {
+        let secret = Secret::<Blake3>::gen(&[0u8; 32], &BigUint::from(3413u32));
+        let explanation = secret.explain(16, 32)?;
+        let commitment = secret.commit(16, 32)?;
+        assert_eq!(commitment.leaf_count(), Some(explanation.mdp.len()));
+        assert_eq!(
+            commitment.mdp_positions(),
+            Some(explanation.shuffled_indexes.clone())
+        );
+        assert_eq!(
+            secret.commit_streaming(16, 32)?.mdp_positions(),
+            commitment.mdp_positions()
+        );
+        let leaf = commitment.leaf(1).unwrap();
+        assert_eq!(leaf.mdp_index(), 1);
+        assert_eq!(leaf.position(), explanation.shuffled_indexes[1]);
+        assert!(commitment.leaf(explanation.mdp.len()).is_none());
+
+        let deserialized = Commitment::<Blake3>::deserialize(&commitment.serialize(), 16)?;
+        assert_eq!(deserialized.leaf_count(), None);
+        assert_eq!(deserialized.mdp_positions(), None);
+
+        // Inclusion proofs are only generated for leaves of the tree.
+        let leaves = leaf_handles(vec![3, 5]);
+        let foreign = LeafHandle {
+            mdp_index: 1,
+            position: 4,
+        };
+        assert!(final_smt_root_and_proof::<Blake3>(
+            &[[1u8; 32], [2u8; 32]],
+            &leaves,
+            3,
+            &foreign,
+            &ALL_ZEROS_SECRET,
+        )
+        .is_err());
+
+        // The root does not depend on the order of the leaves, and duplicate positions are
+        // rejected.
+        let roots = [[1u8; 32], [2u8; 32]];
+        let reversed: Vec<LeafHandle> = leaves.iter().rev().copied().collect();
+        assert_eq!(
+            final_smt_root::<Blake3>(&roots, &leaves, 3, &ALL_ZEROS_SECRET)?,
+            final_smt_root::<Blake3>(&roots, &reversed, 3, &ALL_ZEROS_SECRET)?
+        );
+        let duplicates = leaf_handles(vec![3, 3]);
+        assert!(final_smt_root::<Blake3>(&roots, &duplicates, 3, &ALL_ZEROS_SECRET).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_chain_length_policy() -> Result<(), HwError> {
+        let secret = Secret::<Blake3>::gen(&[5u8; 32], &BigUint::from(3413u32));
+        let threshold = BigUint::from(2999u32);
+        let params = HwParams::new(16, 32);
+        let optimized = secret.commit_with_params(&params)?;
+        let uniform = secret
+            .commit_with_params(&params.with_chain_length_policy(ChainLengthPolicy::Uniform))?;
+        assert_eq!(
+            optimized.chain_length_policy(),
+            ChainLengthPolicy::Optimized
+        );
+        assert_eq!(uniform.root(), optimized.root());
+
+        // The policy is carried in the commitment header.
+        let bytes = uniform.serialize();
+        assert_eq!(bytes[1], ChainLengthPolicy::Uniform.as_u8());
+        let deserialized = Commitment::<Blake3>::deserialize(&bytes, 16)?;
+        assert_eq!(
+            deserialized.chain_length_policy(),
+            ChainLengthPolicy::Uniform
+        );
+        deserialized.verify(&secret.prove(16, 32, &threshold)?, &threshold)?;
+
+        let mut unknown = bytes;
+        unknown[1] = 0;
+        assert!(Commitment::<Blake3>::deserialize(&unknown, 16).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_transcript_hash() -> Result<(), HwError> {
+        let secret = Secret::<Blake3>::gen(&[3u8; 32], &BigUint::from(402u32));
+        let commitment = secret.commit(4, 32)?;
+        let threshold = BigUint::from(378u32);
+        let proof = secret.prove(4, 32, &threshold)?;
+        let transcript = proof.transcript_hash(&commitment, &threshold, b"rp")?;
+
+        let decoded = Proof::deserialize(&proof.serialize())?;
+        assert_eq!(
+            decoded.transcript_hash(&commitment, &threshold, b"rp")?,
+            transcript
+        );
+        assert_ne!(
+            proof.transcript_hash(&commitment, &threshold, b"rq")?,
+            transcript
+        );
+        assert_ne!(
+            proof.transcript_hash(&commitment, &BigUint::from(377u32), b"rp")?,
+            transcript
+        );
+        let other = secret.commit(16, 32)?;
+        assert_ne!(
+            proof.transcript_hash(&other, &threshold, b"rp")?,
+            transcript
+        );
+
+        // a threshold too large for its length prefix is an error, not a panic
+        let huge = BigUint::from(1u32) << (8 * 70_000);
+        assert!(matches!(
+            proof.transcript_hash(&commitment, &huge, b"rp"),
+            Err(HwError::SerializationError)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_framing_policy() -> Result<(), HwError> {
+        let params = HwParams::new(16, 32).with_framing_policy(FramingPolicy::PrefixFree);
+        let secret = Secret::<sha2::Sha256>::gen(&[7u8; 32], &BigUint::from(3413u32));
+        let threshold = BigUint::from(2999u32);
+        let (commitment, proof) = secret.commit_and_prove(&params, &threshold)?;
+        let (default_commitment, _) =
+            secret.commit_and_prove(&HwParams::new(16, 32), &threshold)?;
+        assert_ne!(commitment.root(), default_commitment.root());
+
+        let mut bytes = proof.serialize();
+        assert_eq!(bytes[0], PROOF_VERSION);
+        assert_eq!(bytes[1], FramingPolicy::PrefixFree.as_u8());
+        let decoded = Proof::deserialize(&bytes)?;
+        assert_eq!(decoded.framing_policy(), FramingPolicy::PrefixFree);
+        commitment.verify(&decoded, &threshold)?;
+        crate::differential::verify_differential(&commitment, &decoded, &threshold)?;
+
+        // the framing is part of the proof
+        bytes[1] = FramingPolicy::Concatenated.as_u8();
+        assert!(commitment
+            .verify(&Proof::deserialize(&bytes)?, &threshold)
+            .is_err());
+        // and is not a format version
+        bytes[0] = FramingPolicy::PrefixFree.as_u8();
+        assert!(matches!(
+            Proof::deserialize(&bytes),
+            Err(HwError::SerializationError)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_in_place() -> Result<(), HwError> {
+        for bitlength in [1, 2, 4, 8].iter() {
+            for value in [0u128, 1, 255, 256, 3413, u64::MAX as u128 + 1, u128::MAX].iter() {
+                let value = BigUint::from(*value);
+                let mut scratch = [0xffu8; 128];
+                let len = value_split_per_base_in_place(&value, *bitlength, &mut scratch)
+                    .ok_or(HwError::ParameterError)?;
+                assert_eq!(scratch[..len], value_split_per_base(&value, *bitlength)[..]);
+            }
+        }
+
+        let params = HwParams::new(16, 32);
+        let secret = Secret::<sha2::Sha256>::gen(&[8u8; 32], &BigUint::from(3413u32));
+        let threshold = BigUint::from(2999u32);
+        let (commitment, proof) = secret.commit_and_prove(&params, &threshold)?;
+        let mut scratch = vec![0u8; scratch_size(&params)?];
+        commitment.verify_in_place(&proof, &threshold, &mut scratch)?;
+        assert!(commitment
+            .verify_in_place(&proof, &BigUint::from(3000u32), &mut scratch)
+            .is_err());
+        assert!(matches!(
+            commitment.verify_in_place(&proof, &threshold, &mut scratch[..2]),
+            Err(HwError::ParameterError)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_bytes() -> Result<(), HwError> {
+        let params = HwParams::new(16, 32);
+        let secret = Secret::<sha2::Sha256>::gen(&[9u8; 32], &BigUint::from(3413u32));
+        let (commitment, proof) = secret.commit_and_prove(&params, &2999u32)?;
+        let (commitment, proof) = (commitment.serialize(), proof.serialize());
+        verify_bytes(&commitment, &proof, &2999u32, &params)?;
+        assert!(verify_bytes(&commitment, &proof, &3000u32, &params).is_err());
+        assert!(matches!(
+            verify_bytes(&commitment, &proof, &2999u32, &HwParams::new(16, 8)),
+            Err(HwError::MdpSizeError)
+        ));
+        let prefix_free = params.with_framing_policy(FramingPolicy::PrefixFree);
+        assert!(matches!(
+            verify_bytes(&commitment, &proof, &2999u32, &prefix_free),
+            Err(HwError::ParameterError)
+        ));
+        assert!(verify_bytes(&[], &proof, &2999u32, &params).is_err());
+        Ok(())
+    }
+
+    thread_local! {
+        static HASHED_BYTES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    // SHA-256, counting the bytes it hashes on this thread.
+    #[derive(Clone, Default)]
+    struct CountingSha256(sha2::Sha256);
+
+    impl digest::Update for CountingSha256 {
+        fn update(&mut self, data: impl AsRef<[u8]>) {
+            HASHED_BYTES.with(|count| count.set(count.get() + data.as_ref().len()));
+            digest::Update::update(&mut self.0, data);
+        }
+    }
+
+    impl digest::BlockInput for CountingSha256 {
+        type BlockSize = <sha2::Sha256 as digest::BlockInput>::BlockSize;
+    }
+
+    impl digest::FixedOutput for CountingSha256 {
+        type OutputSize = <sha2::Sha256 as digest::FixedOutput>::OutputSize;
+
+        fn finalize_into(self, out: &mut GenericArray<u8, Self::OutputSize>) {
+            self.0.finalize_into(out);
+        }
+
+        fn finalize_into_reset(&mut self, out: &mut GenericArray<u8, Self::OutputSize>) {
+            self.0.finalize_into_reset(out);
+        }
+    }
+
+    impl digest::Reset for CountingSha256 {
+        fn reset(&mut self) {
+            digest::Reset::reset(&mut self.0);
+        }
+    }
+
+    #[test]
+    fn test_verify_constant_work() -> Result<(), HwError> {
+        let params = HwParams::new(16, 32);
+        let secret = Secret::<CountingSha256>::gen(&[10u8; 32], &BigUint::from(3413u32));
+        let threshold = BigUint::from(2999u32);
+        let (commitment, proof) = secret.commit_and_prove(&params, &threshold)?;
+        let work = |proof: &Proof, threshold: &BigUint| {
+            HASHED_BYTES.with(|count| count.set(0));
+            let result = commitment.verify_constant_work(proof, threshold);
+            (result, HASHED_BYTES.with(|count| count.get()))
+        };
+        let (result, honest) = work(&proof, &threshold);
+        assert!(result.is_ok());
+
+        type Tamper = fn(&mut Proof);
+        let tampered: [Tamper; 4] = [
+            |p| p.chain_nodes[0][0] ^= 1,
+            |p| p.plr_padding.as_mut().unwrap()[0] ^= 1,
+            |p| p.mdp_salt[0] ^= 1,
+            |p| *p.smt_inclusion_proof.last_mut().unwrap() ^= 1,
+        ];
+        for tamper in tampered.iter() {
+            let mut forged = proof.clone();
+            tamper(&mut forged);
+            assert!(commitment.verify(&forged, &threshold).is_err());
+            let (result, forged_work) = work(&forged, &threshold);
+            assert!(matches!(result, Err(HwError::ProofVerificationError)));
+            assert_eq!(forged_work, honest);
+        }
+        let (result, forged_work) = work(&proof, &BigUint::from(3000u32));
+        assert!(matches!(result, Err(HwError::ProofVerificationError)));
+        assert_eq!(forged_work, honest);
+
+        // a proof with another number of chain nodes is rejected too
+        let mut forged = proof.clone();
+        forged.chain_nodes.pop();
+        assert!(matches!(
+            work(&forged, &threshold).0,
+            Err(HwError::ProofVerificationError)
+        ));
+
+        // a threshold with more digits than the MDP tree has leaves is rejected before any
+        // hashing, whatever its verification cost
+        let huge = BigUint::from(1u32) << (4 * 9);
+        assert!(proof.verification_cost(16, &huge) < 16);
+        let (result, forged_work) = work(&proof, &huge);
+        assert!(matches!(result, Err(HwError::MdpSizeError)));
+        assert_eq!(forged_work, 0);
+
+        // the work limit applies to the constant work, not to the digits of the threshold
+        assert_eq!(commitment.constant_work_cost(), 8 * 15);
+        let (commitment, proof) = (commitment.serialize(), proof.serialize());
+        assert!(matches!(
+            verify_any_constant_work(&commitment, 16, &proof, &threshold, 8 * 15 - 1),
+            Err(HwError::WorkLimitError)
+        ));
+        verify_any_constant_work(&commitment, 16, &proof, &threshold, 8 * 15)?;
+        Ok(())
+    }
 }
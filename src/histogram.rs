@@ -0,0 +1,275 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Histograms of committed values over a ladder of thresholds
+//!
+//! A verifier collecting distribution statistics (e.g. how many account holders hold more
+//! than 1k, 10k and 100k) publishes a [`ThresholdLadder`], and each holder answers with a
+//! [`LadderContribution`] proving the highest threshold of the ladder its value reaches, or
+//! proving nothing if its value is below all of them. A [`HistogramTally`] verifies the
+//! contributions and counts them per bucket, so the verifier learns the bucket of each holder
+//! but never a value.
+//!
+//! Bucket 0 counts the holders below the first threshold, and bucket `i + 1` the holders
+//! proving the threshold `i` of the ladder. Holders may always prove a lower threshold than
+//! their value reaches, so the histogram bounds the values from below. Each holder is counted
+//! once under a key chosen by the verifier (e.g. the serialized commitment, or an identifier
+//! tag of the [`crate::linking`] module): a later contribution of the same holder replaces
+//! the previous one if it proves a higher threshold, and is ignored otherwise.
+//!
+//! A serialized contribution is laid out as `0 (1)` below the ladder, and as
+//! `1 (1) || rung (2) || proof` otherwise, the rung being the big-endian index of the proven
+//! threshold.
+use std::collections::HashMap;
+
+use num_bigint::BigUint;
+
+use crate::errors::HwError;
+use crate::hashwires::{Commitment, Proof, Secret};
+use crate::params::HwParams;
+use crate::serialization::{i2osp_bounded, os2ip, take_slice};
+use crate::traits::Hash;
+
+// The rung of a contribution is serialized over 2 bytes.
+const MAX_RUNGS: usize = 1 << 16;
+
+/// The thresholds of a histogram, in increasing order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ThresholdLadder {
+    thresholds: Vec<BigUint>,
+}
+
+impl ThresholdLadder {
+    /// A ladder of `thresholds`, sorted and deduplicated. Fails with
+    /// [`HwError::ParameterError`] if there are none or more than 65536 of them.
+    pub fn new(thresholds: &[BigUint]) -> Result<Self, HwError> {
+        let mut thresholds = thresholds.to_vec();
+        thresholds.sort();
+        thresholds.dedup();
+        if thresholds.is_empty() || thresholds.len() > MAX_RUNGS {
+            return Err(HwError::ParameterError);
+        }
+        Ok(Self { thresholds })
+    }
+
+    /// A ladder of `count` thresholds, from `start` by steps of `step`.
+    pub fn uniform(start: &BigUint, step: &BigUint, count: usize) -> Result<Self, HwError> {
+        let thresholds: Vec<BigUint> = (0..count)
+            .map(|i| start + step * BigUint::from(i))
+            .collect();
+        Self::new(&thresholds)
+    }
+
+    /// The thresholds of the ladder, in increasing order.
+    pub fn thresholds(&self) -> &[BigUint] {
+        &self.thresholds
+    }
+
+    /// The number of buckets of the histogram, one more than the number of thresholds.
+    pub fn bucket_count(&self) -> usize {
+        self.thresholds.len() + 1
+    }
+
+    /// The bucket of `value`: 0 if it is below the first threshold, `i + 1` if the threshold
+    /// `i` is the highest one it reaches.
+    pub fn bucket_of(&self, value: &BigUint) -> usize {
+        self.thresholds
+            .iter()
+            .take_while(|threshold| *threshold <= value)
+            .count()
+    }
+}
+
+/// The answer of a holder to a ladder.
+#[derive(Clone)]
+pub enum LadderContribution {
+    /// The value of the holder is below the first threshold.
+    Below,
+    /// A proof of the threshold `rung` of the ladder.
+    Proven {
+        /// The index of the proven threshold.
+        rung: usize,
+        /// The proof of the threshold.
+        proof: Proof,
+    },
+}
+
+impl LadderContribution {
+    /// Prove the highest threshold of `ladder` reached by the value of `secret`, over its
+    /// commitment under `params`.
+    pub fn prove<D: Hash>(
+        secret: &Secret<D>,
+        params: &HwParams,
+        ladder: &ThresholdLadder,
+    ) -> Result<Self, HwError> {
+        match ladder.bucket_of(&secret.value) {
+            0 => Ok(LadderContribution::Below),
+            bucket => {
+                let rung = bucket - 1;
+                let proof = secret.commit_and_prove(params, &ladder.thresholds[rung])?.1;
+                Ok(LadderContribution::Proven { rung, proof })
+            }
+        }
+    }
+
+    /// The bucket the contribution counts in.
+    pub fn bucket(&self) -> usize {
+        match self {
+            LadderContribution::Below => 0,
+            LadderContribution::Proven { rung, .. } => rung + 1,
+        }
+    }
+
+    /// Serialize the contribution.
+    pub fn serialize(&self) -> Vec<u8> {
+        match self {
+            LadderContribution::Below => vec![0],
+            LadderContribution::Proven { rung, proof } => {
+                [&[1u8][..], &i2osp_bounded(*rung, 2), &proof.serialize()].concat()
+            }
+        }
+    }
+
+    /// Deserialize a contribution.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (tag, remainder) = take_slice(input, 1)?;
+        match tag[0] {
+            0 if remainder.is_empty() => Ok(LadderContribution::Below),
+            1 => {
+                let (rung, remainder) = take_slice(remainder, 2)?;
+                Ok(LadderContribution::Proven {
+                    rung: os2ip(rung)?,
+                    proof: Proof::deserialize(remainder)?,
+                })
+            }
+            _ => Err(HwError::SerializationError),
+        }
+    }
+}
+
+/// The verifier side of a histogram, counting verified contributions per bucket.
+pub struct HistogramTally {
+    ladder: ThresholdLadder,
+    counts: Vec<u64>,
+    // the bucket each holder is counted in
+    holders: HashMap<Vec<u8>, usize>,
+}
+
+impl HistogramTally {
+    /// Start a histogram over `ladder`.
+    pub fn new(ladder: ThresholdLadder) -> Self {
+        Self {
+            counts: vec![0; ladder.bucket_count()],
+            ladder,
+            holders: HashMap::new(),
+        }
+    }
+
+    /// Verify the contribution of the holder `holder` over its commitment, and count it.
+    /// Returns the bucket the holder is counted in, which is the previous one if the holder
+    /// already contributed a higher or equal threshold. Fails with
+    /// [`HwError::ParameterError`] if the rung is not in the ladder, or with the error of
+    /// the verification, in which case nothing is counted.
+    pub fn add<D: Hash>(
+        &mut self,
+        holder: &[u8],
+        commitment: &Commitment<D>,
+        contribution: &LadderContribution,
+    ) -> Result<usize, HwError> {
+        if let LadderContribution::Proven { rung, proof } = contribution {
+            let threshold = self
+                .ladder
+                .thresholds
+                .get(*rung)
+                .ok_or(HwError::ParameterError)?;
+            commitment.verify(proof, threshold)?;
+        }
+        let bucket = contribution.bucket();
+        match self.holders.get_mut(holder) {
+            Some(previous) if *previous >= bucket => Ok(*previous),
+            Some(previous) => {
+                self.counts[*previous] -= 1;
+                self.counts[bucket] += 1;
+                *previous = bucket;
+                Ok(bucket)
+            }
+            None => {
+                self.counts[bucket] += 1;
+                self.holders.insert(holder.to_vec(), bucket);
+                Ok(bucket)
+            }
+        }
+    }
+
+    /// The ladder of the histogram.
+    pub fn ladder(&self) -> &ThresholdLadder {
+        &self.ladder
+    }
+
+    /// The number of holders per bucket.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// The number of distinct holders counted.
+    pub fn holder_count(&self) -> usize {
+        self.holders.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+
+    #[test]
+    fn test_histogram() -> Result<(), HwError> {
+        let params = HwParams::new(16, 32);
+        let ladder = ThresholdLadder::uniform(&BigUint::from(1000u32), &BigUint::from(1000u32), 3)?;
+        assert_eq!(ladder.bucket_count(), 4);
+        assert_eq!(
+            ladder,
+            ThresholdLadder::new(&[3000u32, 1000, 2000, 1000].map(BigUint::from))?
+        );
+        assert!(ThresholdLadder::new(&[]).is_err());
+
+        let mut tally = HistogramTally::new(ladder.clone());
+        for (i, value) in [500u32, 1500, 2500, 2999, 3000, 100_000].iter().enumerate() {
+            let secret = Secret::<Blake3>::gen(&[i as u8; 32], &BigUint::from(*value));
+            let commitment = secret.commit_with_params(&params)?;
+            let contribution = LadderContribution::prove(&secret, &params, &ladder)?;
+            let contribution = LadderContribution::deserialize(&contribution.serialize())?;
+            let bucket = tally.add(&commitment.serialize(), &commitment, &contribution)?;
+            assert_eq!(bucket, ladder.bucket_of(&BigUint::from(*value)));
+        }
+        assert_eq!(tally.counts(), &[1, 1, 2, 2]);
+
+        // a holder is counted once, in the highest bucket it proved
+        let secret = Secret::<Blake3>::gen(&[1u8; 32], &BigUint::from(1500u32));
+        let commitment = secret.commit_with_params(&params)?;
+        let holder = commitment.serialize();
+        assert_eq!(
+            tally.add(&holder, &commitment, &LadderContribution::Below)?,
+            1
+        );
+        assert_eq!(tally.holder_count(), 6);
+        assert_eq!(tally.counts(), &[1, 1, 2, 2]);
+
+        // forged rungs are not counted
+        let proof = secret.commit_and_prove(&params, &BigUint::from(1000u32))?.1;
+        let forged = LadderContribution::Proven { rung: 1, proof };
+        assert!(tally.add(&holder, &commitment, &forged).is_err());
+        let forged = LadderContribution::Proven {
+            rung: 3,
+            proof: secret.commit_and_prove(&params, &BigUint::from(1000u32))?.1,
+        };
+        assert!(matches!(
+            tally.add(&holder, &commitment, &forged),
+            Err(HwError::ParameterError)
+        ));
+        assert_eq!(tally.counts(), &[1, 1, 2, 2]);
+        Ok(())
+    }
+}
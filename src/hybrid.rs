@@ -0,0 +1,268 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! A unified range proof interface backed by either HashWires or Bulletproofs
+//!
+//! HashWires requires the commitment to be generated by a trusted issuer, while Bulletproofs
+//! (over Pedersen commitments) do not, at the cost of much slower proving and verification.
+//! A [`SchemePolicy`] selects the scheme per credential, and [`RangeSecret`],
+//! [`RangeCommitment`] and [`RangeProof`] expose one `commit`/`prove`/`verify` interface.
+//!
+//! A Bulletproofs proof of `value >= threshold` is a range proof of `value - threshold` in
+//! `[0, 2^bit_size)` over the commitment `V - threshold * B`, which the verifier derives from
+//! the commitment `V` to `value`.
+use bulletproofs::{BulletproofGens, PedersenGens};
+use curve25519_dalek_ng::{ristretto::CompressedRistretto, scalar::Scalar};
+use generic_array::typenum::U32;
+use merlin::Transcript;
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+use crate::errors::HwError;
+use crate::hashes::generate_subseeds;
+use crate::hashwires::{Commitment, Proof, Secret};
+use crate::traits::Hash;
+
+const BLINDING_SALT: &[u8; 32] = b"41234567890123456789012345678901";
+const PROVER_RNG_SALT: &[u8; 32] = b"51234567890123456789012345678901";
+const TRANSCRIPT_LABEL: &[u8] = b"hashwires-hybrid-range-proof";
+
+/// The range proof scheme (and its parameters) used for a credential.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SchemePolicy {
+    /// HashWires, for commitments generated by a trusted issuer.
+    HashWires {
+        /// The base of the hash chains.
+        base: u32,
+        /// The maximum number of bits of the committed values.
+        max_number_bits: usize,
+    },
+    /// Bulletproofs, for values of up to `bit_size` bits (8, 16, 32 or 64).
+    Bulletproofs {
+        /// The bit size of the range proofs.
+        bit_size: usize,
+    },
+}
+
+impl SchemePolicy {
+    /// Select HashWires (in base 16) when the commitment is generated by a trusted issuer, and
+    /// Bulletproofs otherwise, for values of up to `max_number_bits` bits.
+    pub fn select(trusted_issuer: bool, max_number_bits: usize) -> Result<Self, HwError> {
+        match (trusted_issuer, max_number_bits) {
+            (true, _) => Ok(SchemePolicy::HashWires {
+                base: 16,
+                max_number_bits,
+            }),
+            (false, 0..=8) => Ok(SchemePolicy::Bulletproofs { bit_size: 8 }),
+            (false, 9..=16) => Ok(SchemePolicy::Bulletproofs { bit_size: 16 }),
+            (false, 17..=32) => Ok(SchemePolicy::Bulletproofs { bit_size: 32 }),
+            (false, 33..=64) => Ok(SchemePolicy::Bulletproofs { bit_size: 64 }),
+            _ => Err(HwError::ParameterError),
+        }
+    }
+}
+
+/// A commitment for either scheme.
+pub enum RangeCommitment<D: Hash> {
+    /// A HashWires commitment.
+    HashWires(Commitment<D>),
+    /// A Pedersen commitment to a value of up to `bit_size` bits.
+    Bulletproofs {
+        /// The compressed Pedersen commitment.
+        commitment: CompressedRistretto,
+        /// The bit size of the range proofs.
+        bit_size: usize,
+    },
+}
+
+/// A range proof for either scheme.
+pub enum RangeProof {
+    /// A HashWires proof.
+    HashWires(Proof),
+    /// A Bulletproofs range proof.
+    Bulletproofs(Box<bulletproofs::RangeProof>),
+}
+
+/// A secret value committed under the scheme selected by a [`SchemePolicy`].
+pub struct RangeSecret<D: Hash> {
+    secret: Secret<D>,
+    policy: SchemePolicy,
+}
+
+impl<D: Hash> RangeSecret<D> {
+    /// Generate a secret for `value`. Both schemes derive everything (including the Pedersen
+    /// blinding factor) deterministically from `seed`.
+    pub fn gen(seed: &[u8], value: &BigUint, policy: SchemePolicy) -> Self {
+        Self {
+            secret: Secret::gen(seed, value),
+            policy,
+        }
+    }
+
+    /// The policy this secret was generated with.
+    pub fn policy(&self) -> SchemePolicy {
+        self.policy
+    }
+
+    /// Generate a commitment.
+    pub fn commit(&self) -> Result<RangeCommitment<D>, HwError> {
+        match self.policy {
+            SchemePolicy::HashWires {
+                base,
+                max_number_bits,
+            } => Ok(RangeCommitment::HashWires(
+                self.secret.commit(base, max_number_bits)?,
+            )),
+            SchemePolicy::Bulletproofs { bit_size } => {
+                let value = bounded_u64(&self.secret.value, bit_size)?;
+                let commitment = PedersenGens::default()
+                    .commit(Scalar::from(value), self.blinding())
+                    .compress();
+                Ok(RangeCommitment::Bulletproofs {
+                    commitment,
+                    bit_size,
+                })
+            }
+        }
+    }
+
+    /// Generate a proof that the committed value is larger than or equal to `threshold`.
+    pub fn prove(&self, threshold: &BigUint) -> Result<RangeProof, HwError> {
+        match self.policy {
+            SchemePolicy::HashWires {
+                base,
+                max_number_bits,
+            } => Ok(RangeProof::HashWires(self.secret.prove(
+                base,
+                max_number_bits,
+                threshold,
+            )?)),
+            SchemePolicy::Bulletproofs { bit_size } => {
+                let value = bounded_u64(&self.secret.value, bit_size)?;
+                let threshold = bounded_u64(threshold, bit_size)?;
+                let difference = value.checked_sub(threshold).ok_or(HwError::MdpError)?;
+
+                // The prover randomness is derived from the seed and the threshold.
+                let mut rng_seed = [0u8; 32];
+                let rng_input = [&self.secret.seed[..], &threshold.to_be_bytes()].concat();
+                rng_seed.copy_from_slice(
+                    &generate_subseeds::<D, U32>(PROVER_RNG_SALT, &rng_input, 1)[0],
+                );
+
+                let (proof, _) = bulletproofs::RangeProof::prove_single_with_rng(
+                    &BulletproofGens::new(bit_size, 1),
+                    &PedersenGens::default(),
+                    &mut Transcript::new(TRANSCRIPT_LABEL),
+                    difference,
+                    &self.blinding(),
+                    bit_size,
+                    &mut ChaCha20Rng::from_seed(rng_seed),
+                )
+                .map_err(|_| HwError::ParameterError)?;
+                Ok(RangeProof::Bulletproofs(Box::new(proof)))
+            }
+        }
+    }
+
+    fn blinding(&self) -> Scalar {
+        let halves = generate_subseeds::<D, U32>(BLINDING_SALT, &self.secret.seed, 2);
+        let mut wide = [0u8; 64];
+        wide[..32].copy_from_slice(&halves[0]);
+        wide[32..].copy_from_slice(&halves[1]);
+        Scalar::from_bytes_mod_order_wide(&wide)
+    }
+}
+
+impl<D: Hash> RangeCommitment<D> {
+    /// Verify that `proof` shows the committed value is larger than or equal to `threshold`.
+    pub fn verify(&self, proof: &RangeProof, threshold: &BigUint) -> Result<(), HwError> {
+        match (self, proof) {
+            (RangeCommitment::HashWires(commitment), RangeProof::HashWires(proof)) => {
+                commitment.verify(proof, threshold)
+            }
+            (
+                RangeCommitment::Bulletproofs {
+                    commitment,
+                    bit_size,
+                },
+                RangeProof::Bulletproofs(proof),
+            ) => {
+                let threshold = bounded_u64(threshold, *bit_size)?;
+                let pc_gens = PedersenGens::default();
+                let shifted = commitment
+                    .decompress()
+                    .ok_or(HwError::ProofVerificationError)?
+                    - Scalar::from(threshold) * pc_gens.B;
+                proof
+                    .verify_single(
+                        &BulletproofGens::new(*bit_size, 1),
+                        &pc_gens,
+                        &mut Transcript::new(TRANSCRIPT_LABEL),
+                        &shifted.compress(),
+                        *bit_size,
+                    )
+                    .map_err(|_| HwError::ProofVerificationError)
+            }
+            _ => Err(HwError::ProofVerificationError),
+        }
+    }
+}
+
+// Convert `value` to a u64, checking it fits in `bit_size` bits.
+fn bounded_u64(value: &BigUint, bit_size: usize) -> Result<u64, HwError> {
+    match value.to_u64() {
+        Some(v) if bit_size == 64 || v >> bit_size == 0 => Ok(v),
+        _ => Err(HwError::ParameterError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+
+    fn prove_and_verify(policy: SchemePolicy) -> Result<(), HwError> {
+        let value = BigUint::from(402u32);
+        let secret = RangeSecret::<Blake3>::gen(&[1u8; 32], &value, policy);
+        let commitment = secret.commit()?;
+
+        let threshold = BigUint::from(378u32);
+        let proof = secret.prove(&threshold)?;
+        commitment.verify(&proof, &threshold)?;
+        assert!(commitment.verify(&proof, &BigUint::from(379u32)).is_err());
+        assert!(secret.prove(&BigUint::from(403u32)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hybrid_hashwires() -> Result<(), HwError> {
+        prove_and_verify(SchemePolicy::select(true, 32)?)
+    }
+
+    #[test]
+    fn test_hybrid_bulletproofs() -> Result<(), HwError> {
+        prove_and_verify(SchemePolicy::select(false, 16)?)
+    }
+
+    #[test]
+    fn test_hybrid_mismatched_schemes() -> Result<(), HwError> {
+        let value = BigUint::from(402u32);
+        let threshold = BigUint::from(378u32);
+        let hw = RangeSecret::<Blake3>::gen(&[1u8; 32], &value, SchemePolicy::select(true, 32)?);
+        let bp = RangeSecret::<Blake3>::gen(&[1u8; 32], &value, SchemePolicy::select(false, 16)?);
+        assert!(hw
+            .commit()?
+            .verify(&bp.prove(&threshold)?, &threshold)
+            .is_err());
+        assert!(
+            RangeSecret::<Blake3>::gen(&[1u8; 32], &value, SchemePolicy::select(false, 8)?)
+                .commit()
+                .is_err()
+        );
+        Ok(())
+    }
+}
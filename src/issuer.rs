@@ -0,0 +1,220 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Issuer-side derivation of the credential seeds from a master key
+//!
+//! An [`IssuerKey`] derives the seed of each credential as `KDF(master, credential_id)`, so that
+//! the issuer only has to store its master key, and can re-derive any secret later on.
+//!
+//! The issuer registers the [`IssuerKey::fingerprint`] of its key with an auditor, who holds the
+//! master key in escrow. For dispute resolution, the issuer discloses a [`DerivationProof`] for
+//! a credential, which the auditor checks against the commitment in dispute by re-deriving it.
+//!
+//! Despite its name, a derivation proof is not a cryptographic proof: it is the opening of the
+//! credential (identifier, value and parameters), in the clear, and it is only checked by
+//! re-deriving the commitment with the master key. Only a holder of the master key can check it,
+//! and it discloses the committed value to whoever sees it.
+//!
+//! A serialized derivation proof is laid out as follows (all integers are big-endian):
+//!
+//! ```text
+//! credential_id (8) || base (4) || max_number_bits (8) || mdp_salt_size (1)
+//! || chain_length_policy (1) || byte_order_policy (1) || framing_policy (1) || value
+//! ```
+use std::marker::PhantomData;
+
+use digest::Digest;
+use num_bigint::BigUint;
+
+use crate::errors::HwError;
+use crate::hashwires::{Commitment, Secret};
+use crate::params::HwParams;
+use crate::serialization::{os2ip_u64, take_slice};
+use crate::traits::Hash;
+
+const ISSUER_SEED_SALT: &[u8; 32] = b"e1234567890123456789012345678901";
+const ISSUER_KEY_SALT: &[u8; 32] = b"f1234567890123456789012345678901";
+
+/// The minimum length in bytes of a master key.
+pub const MIN_MASTER_KEY_LEN: usize = 32;
+
+/// A master key deriving the seeds of all the credentials of an issuer.
+pub struct IssuerKey<D: Hash> {
+    master: Vec<u8>,
+    _d: PhantomData<D>,
+}
+
+impl<D: Hash> IssuerKey<D> {
+    /// Create an issuer key from a master key of at least [`MIN_MASTER_KEY_LEN`] bytes.
+    pub fn new(master: &[u8]) -> Result<Self, HwError> {
+        if master.len() < MIN_MASTER_KEY_LEN {
+            return Err(HwError::SeedLengthError);
+        }
+        Ok(Self {
+            master: master.to_vec(),
+            _d: PhantomData,
+        })
+    }
+
+    /// The fingerprint of the master key, to be registered with the auditor.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        let mut hasher = D::new();
+        hasher.update(ISSUER_KEY_SALT);
+        hasher.update(&self.master);
+        let mut output = [0u8; 32];
+        output.copy_from_slice(&hasher.finalize()[..32]);
+        output
+    }
+
+    /// The seed of credential `credential_id`.
+    pub fn derive_seed(&self, credential_id: u64) -> [u8; 32] {
+        let mut hasher = D::new();
+        hasher.update(ISSUER_SEED_SALT);
+        hasher.update(&self.master);
+        hasher.update(credential_id.to_be_bytes());
+        let mut output = [0u8; 32];
+        output.copy_from_slice(&hasher.finalize()[..32]);
+        output
+    }
+
+    /// The secret of credential `credential_id`, committing to `value`.
+    pub fn secret(&self, credential_id: u64, value: &BigUint) -> Secret<D> {
+        Secret::gen(&self.derive_seed(credential_id), value)
+    }
+
+    /// The opening of the commitment of credential `credential_id` to `value` under `params`,
+    /// for the auditor to re-derive it from this key (see [`DerivationProof::verify`]).
+    pub fn prove_derivation(
+        &self,
+        credential_id: u64,
+        value: &BigUint,
+        params: &HwParams,
+    ) -> DerivationProof {
+        DerivationProof {
+            credential_id,
+            value: value.clone(),
+            params: *params,
+        }
+    }
+}
+
+/// The opening of a credential, for the auditor to check its commitment against the
+/// registered master key. It discloses the committed value, and is checked by re-derivation
+/// with the master key rather than verified as a proof.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DerivationProof {
+    credential_id: u64,
+    value: BigUint,
+    params: HwParams,
+}
+
+impl DerivationProof {
+    /// The credential identifier.
+    pub fn credential_id(&self) -> u64 {
+        self.credential_id
+    }
+
+    /// The committed value.
+    pub fn value(&self) -> &BigUint {
+        &self.value
+    }
+
+    /// The parameters of the commitment.
+    pub fn params(&self) -> &HwParams {
+        &self.params
+    }
+
+    /// Check that `commitment` was derived from `key`, whose fingerprint must be the registered
+    /// one, by re-deriving the commitment of this opening with `key`.
+    pub fn verify<D: Hash>(
+        &self,
+        key: &IssuerKey<D>,
+        fingerprint: &[u8; 32],
+        commitment: &Commitment<D>,
+    ) -> Result<(), HwError> {
+        if key.fingerprint() != *fingerprint || commitment.base() != self.params.base {
+            return Err(HwError::ProofVerificationError);
+        }
+        let expected = key
+            .secret(self.credential_id, &self.value)
+            .commit_with_params(&self.params)?;
+        if expected.serialize() != commitment.serialize() {
+            return Err(HwError::ProofVerificationError);
+        }
+        Ok(())
+    }
+
+    /// Serialize the derivation proof.
+    pub fn serialize(&self) -> Vec<u8> {
+        [
+            &self.credential_id.to_be_bytes()[..],
+            &self.params.serialize(),
+            &self.value.to_bytes_be(),
+        ]
+        .concat()
+    }
+
+    /// Deserialize a derivation proof, rejecting invalid parameters with
+    /// [`HwError::ParameterError`].
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (credential_id, remainder) = take_slice(input, 8)?;
+        let (params, value) = HwParams::deserialize_prefix(remainder)?;
+        params.validate()?;
+        Ok(Self {
+            credential_id: os2ip_u64(credential_id)?,
+            value: BigUint::from_bytes_be(value),
+            params,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+
+    #[test]
+    fn test_issuer_key() -> Result<(), HwError> {
+        let key = IssuerKey::<Blake3>::new(&[1u8; 32])?;
+        let fingerprint = key.fingerprint();
+        assert!(IssuerKey::<Blake3>::new(&[1u8; 16]).is_err());
+        assert_ne!(key.derive_seed(1), key.derive_seed(2));
+
+        let params = HwParams::new(16, 32);
+        let value = BigUint::from(3413u32);
+        let commitment = key
+            .secret(7, &value)
+            .commit(params.base, params.max_number_bits)?;
+
+        let proof = key.prove_derivation(7, &value, &params);
+        let proof = DerivationProof::deserialize(&proof.serialize())?;
+        assert_eq!(proof.credential_id(), 7);
+        proof.verify(&key, &fingerprint, &commitment)?;
+
+        // Invalid parameters are rejected on deserialization.
+        let mut serialized = proof.serialize();
+        serialized[8..12].copy_from_slice(&3u32.to_be_bytes());
+        assert!(matches!(
+            DerivationProof::deserialize(&serialized),
+            Err(HwError::ParameterError)
+        ));
+
+        // Another credential, value or key does not match.
+        let other = IssuerKey::<Blake3>::new(&[2u8; 32])?;
+        assert!(key
+            .prove_derivation(8, &value, &params)
+            .verify(&key, &fingerprint, &commitment)
+            .is_err());
+        assert!(key
+            .prove_derivation(7, &BigUint::from(3414u32), &params)
+            .verify(&key, &fingerprint, &commitment)
+            .is_err());
+        assert!(proof.verify(&other, &fingerprint, &commitment).is_err());
+        assert!(proof
+            .verify(&other, &other.fingerprint(), &commitment)
+            .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Known-answer tests, to validate implementations against frozen outputs
+//!
+//! A fixture file holds one known answer per line, as whitespace-separated fields:
+//!
+//! ```text
+//! seed (hex) value (decimal) base max_number_bits threshold (decimal) commitment (hex) proof (hex)
+//! ```
+//!
+//! Empty lines and lines starting with `#` are ignored.
+//!
+//! The frozen known answers of this crate ship in the `kats` directory, one fixture file per
+//! digest, and are embedded as [`BLAKE3_KATS`] and [`SHA256_KATS`]. They are never regenerated:
+//! a change of output is a breaking change of the format.
+use std::io::{BufRead, Write};
+
+use num_bigint::BigUint;
+use num_traits::Num;
+
+use crate::errors::HwError;
+use crate::hashwires::{Commitment, Proof, Secret};
+use crate::params::HwParams;
+use crate::traits::Hash;
+
+/// The frozen known answers under BLAKE3, as a fixture file.
+pub const BLAKE3_KATS: &str = include_str!("../kats/blake3.txt");
+
+/// The frozen known answers under SHA-256, as a fixture file.
+pub const SHA256_KATS: &str = include_str!("../kats/sha256.txt");
+
+/// Inputs and expected outputs of one commitment and proof generation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KnownAnswer {
+    /// The seed of the secret.
+    pub seed: Vec<u8>,
+    /// The committed value.
+    pub value: BigUint,
+    /// The base of the hash chains.
+    pub base: u32,
+    /// The maximum number of bits of the committed values.
+    pub max_number_bits: usize,
+    /// The proven threshold.
+    pub threshold: BigUint,
+    /// The expected serialized commitment.
+    pub commitment: Vec<u8>,
+    /// The expected serialized proof.
+    pub proof: Vec<u8>,
+}
+
+impl KnownAnswer {
+    /// Compute the known answer for the given inputs with this implementation.
+    pub fn generate<D: Hash>(
+        seed: &[u8],
+        value: &BigUint,
+        base: u32,
+        max_number_bits: usize,
+        threshold: &BigUint,
+    ) -> Result<Self, HwError> {
+        let secret = Secret::<D>::gen(seed, value);
+        Ok(Self {
+            seed: seed.to_vec(),
+            value: value.clone(),
+            base,
+            max_number_bits,
+            threshold: threshold.clone(),
+            commitment: secret.commit(base, max_number_bits)?.serialize(),
+            proof: secret.prove(base, max_number_bits, threshold)?.serialize(),
+        })
+    }
+
+    /// Check that this implementation produces the expected outputs, and that the expected
+    /// proof verifies over the expected commitment.
+    pub fn check<D: Hash>(&self) -> Result<(), HwError> {
+        let actual = Self::generate::<D>(
+            &self.seed,
+            &self.value,
+            self.base,
+            self.max_number_bits,
+            &self.threshold,
+        )?;
+        if actual != *self {
+            return Err(HwError::KnownAnswerError);
+        }
+        Commitment::<D>::deserialize(&self.commitment, self.base)?
+            .verify(&Proof::deserialize(&self.proof)?, &self.threshold)
+    }
+
+    fn parse(line: &str) -> Result<Self, HwError> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() != 7 {
+            return Err(HwError::SerializationError);
+        }
+        let hex = |s: &str| hex::decode(s).map_err(|_| HwError::SerializationError);
+        let decimal =
+            |s: &str| BigUint::from_str_radix(s, 10).map_err(|_| HwError::SerializationError);
+        let base = fields[2].parse().map_err(|_| HwError::SerializationError)?;
+        let max_number_bits = fields[3].parse().map_err(|_| HwError::SerializationError)?;
+        HwParams::new(base, max_number_bits).validate()?;
+        Ok(Self {
+            seed: hex(fields[0])?,
+            value: decimal(fields[1])?,
+            base,
+            max_number_bits,
+            threshold: decimal(fields[4])?,
+            commitment: hex(fields[5])?,
+            proof: hex(fields[6])?,
+        })
+    }
+}
+
+/// Load the known answers of a fixture file.
+pub fn load<R: BufRead>(reader: R) -> Result<Vec<KnownAnswer>, HwError> {
+    let mut kats = Vec::new();
+    for line in reader.lines() {
+        let line = line.map_err(|_| HwError::SerializationError)?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        kats.push(KnownAnswer::parse(line)?);
+    }
+    Ok(kats)
+}
+
+/// Save known answers as a fixture file.
+pub fn save<W: Write>(mut writer: W, kats: &[KnownAnswer]) -> Result<(), HwError> {
+    for kat in kats.iter() {
+        writeln!(
+            writer,
+            "{} {} {} {} {} {} {}",
+            hex::encode(&kat.seed),
+            kat.value,
+            kat.base,
+            kat.max_number_bits,
+            kat.threshold,
+            hex::encode(&kat.commitment),
+            hex::encode(&kat.proof)
+        )
+        .map_err(|_| HwError::SerializationError)?;
+    }
+    Ok(())
+}
+
+/// Check every known answer, failing on the first mismatch.
+pub fn run_kats<D: Hash>(kats: &[KnownAnswer]) -> Result<(), HwError> {
+    kats.iter().try_for_each(|kat| kat.check::<D>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+
+    #[test]
+    fn test_kat_roundtrip() -> Result<(), HwError> {
+        let mut kats = Vec::new();
+        for (base, value, threshold) in
+            [(4u32, 402u32, 378u32), (16, 3413, 2999), (256, 9999, 1)].iter()
+        {
+            kats.push(KnownAnswer::generate::<Blake3>(
+                &[*base as u8; 32],
+                &BigUint::from(*value),
+                *base,
+                32,
+                &BigUint::from(*threshold),
+            )?);
+        }
+
+        let mut fixture = b"# HashWires known answers\n\n".to_vec();
+        save(&mut fixture, &kats)?;
+        let loaded = load(&fixture[..])?;
+        assert_eq!(loaded, kats);
+        run_kats::<Blake3>(&loaded)?;
+
+        let mut tampered = loaded;
+        tampered[1].proof[0] ^= 1;
+        assert!(run_kats::<Blake3>(&tampered).is_err());
+        assert!(load(&b"00 1 4 32"[..]).is_err());
+        assert!(matches!(
+            load(&b"00 1 3 32 0 00 00"[..]),
+            Err(HwError::ParameterError)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_frozen_kats() -> Result<(), HwError> {
+        let kats = load(BLAKE3_KATS.as_bytes())?;
+        assert!(!kats.is_empty());
+        run_kats::<Blake3>(&kats)?;
+        let kats = load(SHA256_KATS.as_bytes())?;
+        assert!(!kats.is_empty());
+        run_kats::<sha2::Sha256>(&kats)
+    }
+}
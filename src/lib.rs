@@ -3,12 +3,95 @@
 // This source code is licensed under the MIT license found in the
 // LICENSE file in the root directory of this source tree.
 
+//! HashWires is a range proof system for credentials issued by a trusted third party.
+//!
+//! The algorithms (commitments, proofs and verification) are always built. The rest of the
+//! crate is layered with features, so that downstream users depend on the surface they need:
+//!
+//! - `std` (default): the OS randomness helpers, and the modules built on I/O, threads and
+//!   unwinding ([`fuzz_targets`], [`kat`] and [`verifier`]);
+//! - `bindings`: the C ABI entry points of the `ffi` module, for FFI and WASM consumers;
+//! - `server`: the framework-agnostic request handling of the `server` module, for
+//!   verification endpoints;
+//! - `audit-log`: the structured events of rejected proofs of the `reject_log` module;
+//! - `diagnostics`: the offline diagnosis of failing proofs of the `diagnostics` module, off by
+//!   default since it tells which component of a proof is wrong;
+//! - `equivalence`: the golden outputs of the `equivalence` module, for downstream CI to check
+//!   that wasm32 and native targets agree;
+//! - `digest10`: the `Digest10` adapter of the prelude, for hashers implementing the traits of
+//!   `digest` 0.10 rather than 0.9;
+//! - `sealed-audit`: the openings of commitments sealed to regulators of the `sealed` module.
+//!
+//! The minimum supported Rust version is 1.65, as declared in the manifest.
+//!
+//! The crate does not build under `no_std`, since the `smtree` dependency requires the standard
+//! library: disabling `std` only drops the modules above.
+
+pub mod audit;
+pub mod audit_path;
+pub mod bitfield;
+pub mod bundle;
+pub mod chains;
+pub mod codec;
+pub mod currency;
+pub mod custody;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+pub mod differential;
+pub mod digest_id;
 mod dp;
-mod hashes;
+pub mod dual;
+pub mod epoch;
+#[cfg(feature = "equivalence")]
+pub mod equivalence;
+#[cfg(feature = "exhaustive")]
+pub mod exhaustive;
+#[cfg(feature = "bindings")]
+pub mod ffi;
+pub mod forest;
+#[cfg(feature = "std")]
+pub mod fuzz_targets;
+pub mod hashes;
 pub mod hashwires;
+pub mod histogram;
+#[cfg(feature = "hybrid")]
+pub mod hybrid;
+pub mod issuer;
+#[cfg(feature = "std")]
+pub mod kat;
+pub mod linking;
+pub mod manifest;
+mod merkle;
+pub mod metrics;
+pub mod migration;
+pub mod multi_issuer;
+pub mod narrowing;
+pub mod offline;
+pub mod params;
+pub mod policy;
+pub mod prelude;
+pub mod profiles;
+pub mod prover;
+pub mod registry;
+#[cfg(feature = "audit-log")]
+pub mod reject_log;
+#[cfg(feature = "sealed-audit")]
+pub mod sealed;
+pub mod selftest;
 mod serialization;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod session;
 mod shuffle;
+pub mod store;
+pub mod sum;
+pub mod timeseries;
 mod traits;
+pub mod value;
+#[cfg(feature = "std")]
+pub mod verifier;
 
 // Error types
 pub mod errors;
+
+pub use crate::selftest::selftest;
@@ -0,0 +1,191 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Proofs that commitments of different issuers were issued to the same identifier
+//!
+//! A holder aggregating credentials across institutions (e.g. the balances of the same
+//! account holder at two banks) shows that they refer to the same underlying identifier
+//! (e.g. a national identity number) without revealing it. The holder derives a tag of the
+//! identifier under a secret 32-byte link key, which hides the identifier, and hands the key
+//! to each issuer, which checks the identifier as part of its usual onboarding. Each issuer
+//! then binds its commitment to the tag with a fresh nonce given to the holder, and signs the
+//! [`BoundCommitment::digest`]:
+//!
+//! ```text
+//! tag = H(ID_TAG_SALT || link key || identifier)
+//! binding = H(ID_BINDING_SALT || nonce || tag)
+//! digest = H(BOUND_SALT || binding || base (4) || commitment)
+//! ```
+//!
+//! An [`IdentifierLinkProof`] opens two bindings to the same tag, so the tag is revealed to
+//! the verifier while the nonces keep the bindings unlinkable until then. The tag is a stable
+//! pseudonym of the holder for a given link key: holders use a fresh link key per set of
+//! credentials they want to present together.
+//!
+//! A serialized bound commitment is laid out as `binding (32) || base (4) || commitment`, and
+//! a serialized identifier link proof as `tag (32) || first nonce (32) || second nonce (32)`.
+use digest::Digest;
+
+use crate::errors::HwError;
+use crate::hashwires::Commitment;
+use crate::serialization::take_slice;
+use crate::traits::Hash;
+
+const ID_TAG_SALT: &[u8; 32] = b"r1234567890123456789012345678901";
+const ID_BINDING_SALT: &[u8; 32] = b"s1234567890123456789012345678901";
+const BOUND_SALT: &[u8; 32] = b"t1234567890123456789012345678901";
+
+fn salted_digest<D: Hash>(salt: &[u8; 32], inputs: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = D::new();
+    hasher.update(salt);
+    for input in inputs {
+        hasher.update(input);
+    }
+    let mut output = [0u8; 32];
+    output.copy_from_slice(&hasher.finalize()[..32]);
+    output
+}
+
+/// The tag of `identifier` under the secret `link_key` of the holder.
+pub fn identifier_tag<D: Hash>(link_key: &[u8; 32], identifier: &[u8]) -> [u8; 32] {
+    salted_digest::<D>(ID_TAG_SALT, &[link_key, identifier])
+}
+
+/// A commitment bound by its issuer to the tag of an identifier.
+pub struct BoundCommitment<D: Hash> {
+    commitment: Commitment<D>,
+    binding: [u8; 32],
+}
+
+impl<D: Hash> BoundCommitment<D> {
+    /// Bind `commitment` to `tag` with a fresh random `nonce`, to be kept by the holder.
+    pub fn bind(commitment: Commitment<D>, tag: &[u8; 32], nonce: &[u8; 32]) -> Self {
+        Self {
+            commitment,
+            binding: salted_digest::<D>(ID_BINDING_SALT, &[nonce, tag]),
+        }
+    }
+
+    /// The bound commitment.
+    pub fn commitment(&self) -> &Commitment<D> {
+        &self.commitment
+    }
+
+    /// The digest of the commitment and its binding, to be signed by the issuer.
+    pub fn digest(&self) -> [u8; 32] {
+        salted_digest::<D>(BOUND_SALT, &[&self.serialize()])
+    }
+
+    /// Serialize the bound commitment.
+    pub fn serialize(&self) -> Vec<u8> {
+        [&self.binding[..], &self.commitment.serialize_with_base()].concat()
+    }
+
+    /// Deserialize a bound commitment.
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, HwError> {
+        let (binding, commitment) = take_slice(bytes, 32)?;
+        let mut output = [0u8; 32];
+        output.copy_from_slice(binding);
+        Ok(Self {
+            commitment: Commitment::deserialize_with_base(commitment)?,
+            binding: output,
+        })
+    }
+}
+
+/// A proof that two bound commitments were bound to the same identifier tag.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdentifierLinkProof {
+    tag: [u8; 32],
+    nonces: [[u8; 32]; 2],
+}
+
+impl IdentifierLinkProof {
+    /// Open the bindings of two commitments to `tag`, with the nonces received from their
+    /// issuers.
+    pub fn new(tag: &[u8; 32], first_nonce: &[u8; 32], second_nonce: &[u8; 32]) -> Self {
+        Self {
+            tag: *tag,
+            nonces: [*first_nonce, *second_nonce],
+        }
+    }
+
+    /// The identifier tag, a pseudonym of the holder.
+    pub fn tag(&self) -> &[u8; 32] {
+        &self.tag
+    }
+
+    /// Check that `first` and `second` are both bound to the tag of this proof. The
+    /// commitments must be authenticated separately, e.g. by the issuers' signatures of their
+    /// digests.
+    pub fn verify<D: Hash, E: Hash>(
+        &self,
+        first: &BoundCommitment<D>,
+        second: &BoundCommitment<E>,
+    ) -> Result<(), HwError> {
+        if salted_digest::<D>(ID_BINDING_SALT, &[&self.nonces[0], &self.tag]) != first.binding
+            || salted_digest::<E>(ID_BINDING_SALT, &[&self.nonces[1], &self.tag]) != second.binding
+        {
+            return Err(HwError::ProofVerificationError);
+        }
+        Ok(())
+    }
+
+    /// Serialize the identifier link proof.
+    pub fn serialize(&self) -> Vec<u8> {
+        [&self.tag[..], &self.nonces[0], &self.nonces[1]].concat()
+    }
+
+    /// Deserialize an identifier link proof.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        if input.len() != 96 {
+            return Err(HwError::SerializationError);
+        }
+        let mut proof = Self::new(&[0u8; 32], &[0u8; 32], &[0u8; 32]);
+        proof.tag.copy_from_slice(&input[..32]);
+        proof.nonces[0].copy_from_slice(&input[32..64]);
+        proof.nonces[1].copy_from_slice(&input[64..]);
+        Ok(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::Secret;
+    use crate::params::HwParams;
+    use blake3::Hasher as Blake3;
+    use num_bigint::BigUint;
+    use sha2::Sha256;
+
+    #[test]
+    fn test_identifier_link_proof() -> Result<(), HwError> {
+        let params = HwParams::new(16, 32);
+        let link_key = [9u8; 32];
+        let tag = identifier_tag::<Blake3>(&link_key, b"AB123456");
+        let first = Secret::<Blake3>::gen(&[1u8; 32], &BigUint::from(3413u32));
+        let first = BoundCommitment::bind(first.commit_with_params(&params)?, &tag, &[2u8; 32]);
+        let second = Secret::<Sha256>::gen(&[3u8; 32], &BigUint::from(1234u32));
+        let second = BoundCommitment::bind(second.commit_with_params(&params)?, &tag, &[4u8; 32]);
+        let second = BoundCommitment::<Sha256>::deserialize(&second.serialize())?;
+
+        let proof = IdentifierLinkProof::new(&tag, &[2u8; 32], &[4u8; 32]);
+        let proof = IdentifierLinkProof::deserialize(&proof.serialize())?;
+        proof.verify(&first, &second)?;
+        assert!(proof.verify(&first, &first).is_err());
+
+        // another identifier, or the same identifier under another link key
+        for tag in [
+            identifier_tag::<Blake3>(&link_key, b"AB123457"),
+            identifier_tag::<Blake3>(&[8u8; 32], b"AB123456"),
+        ]
+        .iter()
+        {
+            let proof = IdentifierLinkProof::new(tag, &[2u8; 32], &[4u8; 32]);
+            assert!(proof.verify(&first, &second).is_err());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,395 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Signed manifests of batch issuances, with per-holder receipts
+//!
+//! When issuing many commitments at once, the issuer collects them in a [`BatchIssuance`] and
+//! signs a [`Manifest`] holding the parameters, the issuance time, the number of commitments
+//! and the root of a Merkle tree over them, in issuance order. Each holder receives a
+//! [`Receipt`] proving its commitment is part of the manifest, so that a verifier can check
+//! that a commitment was issued recently without contacting the issuer.
+//!
+//! The tree hashes leaves and nodes with distinct salts, and a level with an odd number of
+//! nodes promotes its last node unchanged:
+//!
+//! ```text
+//! leaf = H(LEAF_SALT || serialized commitment)
+//! node = H(NODE_SALT || left || right)
+//! ```
+//!
+//! Signatures are produced and checked by the issuer's own key management, through the
+//! [`ManifestSigner`] and [`SignatureVerifier`] traits. A serialized signed manifest is laid
+//! out as follows (all integers are big-endian):
+//!
+//! ```text
+//! digest identifier (1) || base (4) || max_number_bits (8) || mdp_salt_size (1)
+//! || chain_length_policy (1) || byte_order_policy (1) || framing_policy (1) || issuance time (8)
+//! || #commitments (8) || root (32) || I2OSP(len, 2) || signature
+//! ```
+//!
+//! and a serialized receipt as `index (8) || path`, the path being a concatenation of 32-byte
+//! siblings, leaf level first.
+use std::marker::PhantomData;
+
+use digest::Digest;
+
+use crate::digest_id::DigestId;
+use crate::errors::HwError;
+use crate::hashwires::Commitment;
+use crate::merkle::{root_from_path, MerkleTree};
+use crate::params::HwParams;
+use crate::serialization::{
+    i2osp_bounded, os2ip, os2ip_u64, serialize_bounded, take_slice, tokenize_slice,
+};
+use crate::traits::Hash;
+
+const LEAF_SALT: &[u8; 32] = b"g1234567890123456789012345678901";
+const NODE_SALT: &[u8; 32] = b"h1234567890123456789012345678901";
+
+/// Signs serialized manifests with the issuer's key, or receipts with a verifier's key (see
+/// [`crate::custody`]).
+pub trait ManifestSigner {
+    /// Sign `message`.
+    fn sign(&self, message: &[u8]) -> Vec<u8>;
+}
+
+/// Checks signatures of serialized manifests against the issuer's public key.
+pub trait SignatureVerifier {
+    /// Whether `signature` is a valid signature of `message`.
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool;
+}
+
+/// The commitments of a batch issuance, in issuance order.
+pub struct BatchIssuance<D: Hash> {
+    params: HwParams,
+    issued_at: u64,
+    leaves: Vec<[u8; 32]>,
+    _d: PhantomData<D>,
+}
+
+impl<D: Hash> BatchIssuance<D> {
+    /// Start a batch of commitments generated with `params`, issued at time `issued_at`.
+    pub fn new(params: &HwParams, issued_at: u64) -> Result<Self, HwError> {
+        params.validate()?;
+        Ok(Self {
+            params: *params,
+            issued_at,
+            leaves: Vec::new(),
+            _d: PhantomData,
+        })
+    }
+
+    /// Add an issued commitment to the batch, returning its index.
+    pub fn add(&mut self, commitment: &Commitment<D>) -> Result<usize, HwError> {
+        if commitment.base() != self.params.base {
+            return Err(HwError::ParameterError);
+        }
+        self.leaves.push(leaf::<D>(commitment));
+        Ok(self.leaves.len() - 1)
+    }
+
+    /// The number of commitments.
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Whether the batch is empty.
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// The manifest of the batch, as it currently is.
+    pub fn manifest(&self) -> Manifest {
+        Manifest {
+            digest_id: DigestId::of::<D>(),
+            params: self.params,
+            issued_at: self.issued_at,
+            count: self.leaves.len(),
+            root: MerkleTree::new::<D>(&self.leaves, NODE_SALT).root(),
+        }
+    }
+
+    /// Sign the manifest of the batch with `signer`.
+    pub fn sign<S: ManifestSigner>(&self, signer: &S) -> SignedManifest {
+        let manifest = self.manifest();
+        let signature = signer.sign(&manifest.serialize());
+        SignedManifest {
+            manifest,
+            signature,
+        }
+    }
+
+    /// The receipts of all of the commitments, in issuance order.
+    pub fn receipts(&self) -> Vec<Receipt> {
+        let tree = MerkleTree::new::<D>(&self.leaves, NODE_SALT);
+        (0..self.leaves.len())
+            .map(|index| Receipt {
+                index,
+                path: tree.path(index),
+            })
+            .collect()
+    }
+
+    /// The receipt of the commitment at `index`.
+    pub fn receipt(&self, index: usize) -> Result<Receipt, HwError> {
+        if index >= self.leaves.len() {
+            return Err(HwError::InclusionProofError);
+        }
+        Ok(Receipt {
+            index,
+            path: MerkleTree::new::<D>(&self.leaves, NODE_SALT).path(index),
+        })
+    }
+}
+
+/// The public description of a batch issuance.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Manifest {
+    digest_id: DigestId,
+    params: HwParams,
+    issued_at: u64,
+    count: usize,
+    root: [u8; 32],
+}
+
+impl Manifest {
+    /// The parameters of the commitments.
+    pub fn params(&self) -> &HwParams {
+        &self.params
+    }
+
+    /// The issuance time.
+    pub fn issued_at(&self) -> u64 {
+        self.issued_at
+    }
+
+    /// The number of commitments.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The root of the Merkle tree over the commitments.
+    pub fn root(&self) -> &[u8; 32] {
+        &self.root
+    }
+
+    /// Serialize the manifest, as signed by the issuer.
+    pub fn serialize(&self) -> Vec<u8> {
+        [
+            &[self.digest_id.as_u8()][..],
+            &self.params.serialize(),
+            &self.issued_at.to_be_bytes(),
+            &i2osp_bounded(self.count, 8),
+            &self.root,
+        ]
+        .concat()
+    }
+
+    /// Deserialize a manifest, outputting the remainder of the input.
+    fn deserialize_prefix(input: &[u8]) -> Result<(Self, &[u8]), HwError> {
+        let (digest_id, remainder) = take_slice(input, 1)?;
+        let (params, remainder) = HwParams::deserialize_prefix(remainder)?;
+        let (issued_at, remainder) = take_slice(remainder, 8)?;
+        let (count, remainder) = take_slice(remainder, 8)?;
+        let (root_bytes, remainder) = take_slice(remainder, 32)?;
+        let mut root = [0u8; 32];
+        root.copy_from_slice(root_bytes);
+        let manifest = Self {
+            digest_id: DigestId::from_u8(digest_id[0])?,
+            params,
+            issued_at: os2ip_u64(issued_at)?,
+            count: os2ip(count)?,
+            root,
+        };
+        Ok((manifest, remainder))
+    }
+}
+
+/// A manifest along with the issuer's signature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedManifest {
+    manifest: Manifest,
+    signature: Vec<u8>,
+}
+
+impl SignedManifest {
+    /// The manifest.
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Check the signature of the manifest with `verifier`, and that it was issued at most
+    /// `max_age` before `now`, returning the manifest.
+    pub fn verify<V: SignatureVerifier>(
+        &self,
+        verifier: &V,
+        now: u64,
+        max_age: u64,
+    ) -> Result<&Manifest, HwError> {
+        if !verifier.verify(&self.manifest.serialize(), &self.signature) {
+            return Err(HwError::ProofVerificationError);
+        }
+        match now.checked_sub(self.manifest.issued_at) {
+            Some(age) if age <= max_age => Ok(&self.manifest),
+            _ => Err(HwError::ProofVerificationError),
+        }
+    }
+
+    /// Serialize the signed manifest.
+    pub fn serialize(&self) -> Vec<u8> {
+        [
+            self.manifest.serialize(),
+            serialize_bounded(&self.signature, 2),
+        ]
+        .concat()
+    }
+
+    /// Deserialize a signed manifest.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (manifest, remainder) = Manifest::deserialize_prefix(input)?;
+        let (signature, remainder) = tokenize_slice(remainder, 2)?;
+        if !remainder.is_empty() {
+            return Err(HwError::SerializationError);
+        }
+        Ok(Self {
+            manifest,
+            signature: signature.to_vec(),
+        })
+    }
+}
+
+/// A proof that a commitment is part of a batch issuance.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Receipt {
+    index: usize,
+    path: Vec<[u8; 32]>,
+}
+
+impl Receipt {
+    /// The index of the commitment in the batch.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Verify that `commitment` is part of the batch described by `manifest`. The manifest
+    /// itself is checked with [`SignedManifest::verify`].
+    pub fn verify<D: Hash>(
+        &self,
+        manifest: &Manifest,
+        commitment: &Commitment<D>,
+    ) -> Result<(), HwError> {
+        if manifest.digest_id != DigestId::of::<D>() {
+            return Err(HwError::DigestMismatchError);
+        }
+        if commitment.base() != manifest.params.base {
+            return Err(HwError::ProofVerificationError);
+        }
+        let root = root_from_path::<D>(
+            NODE_SALT,
+            leaf::<D>(commitment),
+            self.index,
+            manifest.count,
+            &self.path,
+        )?;
+        if root != manifest.root {
+            return Err(HwError::ProofVerificationError);
+        }
+        Ok(())
+    }
+
+    /// Serialize the receipt.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = i2osp_bounded(self.index, 8);
+        for sibling in self.path.iter() {
+            result.extend_from_slice(sibling);
+        }
+        result
+    }
+
+    /// Deserialize a receipt.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (index, remainder) = take_slice(input, 8)?;
+        if remainder.len() % 32 != 0 {
+            return Err(HwError::SerializationError);
+        }
+        Ok(Self {
+            index: os2ip(index)?,
+            path: remainder
+                .chunks(32)
+                .map(|c| {
+                    let mut sibling = [0u8; 32];
+                    sibling.copy_from_slice(c);
+                    sibling
+                })
+                .collect(),
+        })
+    }
+}
+
+fn leaf<D: Hash>(commitment: &Commitment<D>) -> [u8; 32] {
+    let mut hasher = D::new();
+    hasher.update(LEAF_SALT);
+    hasher.update(commitment.serialize());
+    let mut output = [0u8; 32];
+    output.copy_from_slice(&hasher.finalize()[..32]);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::Secret;
+    use blake3::Hasher as Blake3;
+    use num_bigint::BigUint;
+
+    // A keyed hash standing in for the issuer's signature scheme.
+    struct TestKey([u8; 32]);
+
+    impl ManifestSigner for TestKey {
+        fn sign(&self, message: &[u8]) -> Vec<u8> {
+            blake3::keyed_hash(&self.0, message).as_bytes().to_vec()
+        }
+    }
+
+    impl SignatureVerifier for TestKey {
+        fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+            self.sign(message) == signature
+        }
+    }
+
+    #[test]
+    fn test_manifest() -> Result<(), HwError> {
+        let params = HwParams::new(16, 32);
+        let key = TestKey([7u8; 32]);
+        let commitments = (0..5u8)
+            .map(|i| {
+                Secret::<Blake3>::gen(&[i; 32], &BigUint::from(1000u32 + i as u32)).commit(16, 32)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut batch = BatchIssuance::<Blake3>::new(&params, 1000)?;
+        for commitment in commitments.iter() {
+            batch.add(commitment)?;
+        }
+        let signed = SignedManifest::deserialize(&batch.sign(&key).serialize())?;
+        let manifest = signed.verify(&key, 1500, 3600)?;
+        assert_eq!(manifest.count(), 5);
+
+        for (i, receipt) in batch.receipts().iter().enumerate() {
+            let receipt = Receipt::deserialize(&receipt.serialize())?;
+            assert_eq!(receipt.index(), i);
+            receipt.verify(manifest, &commitments[i])?;
+            assert!(receipt.verify(manifest, &commitments[(i + 1) % 5]).is_err());
+        }
+        assert_eq!(batch.receipt(3)?, batch.receipts()[3]);
+        assert!(batch.receipt(5).is_err());
+
+        // Stale, future or forged manifests are rejected.
+        assert!(signed.verify(&key, 5000, 3600).is_err());
+        assert!(signed.verify(&key, 999, 3600).is_err());
+        assert!(signed.verify(&TestKey([8u8; 32]), 1500, 3600).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Dense binary Merkle trees over 32-byte leaves, in insertion order
+//!
+//! Nodes are hashed with a salt specific to each use, and a level with an odd number of nodes
+//! promotes its last node unchanged:
+//!
+//! ```text
+//! node = H(salt || left || right)
+//! ```
+use digest::Digest;
+
+use crate::errors::HwError;
+use crate::serialization::{i2osp_bounded, os2ip, take_slice};
+use crate::traits::Hash;
+
+/// A Merkle tree, holding all of its levels.
+pub(crate) struct MerkleTree {
+    // leaves first; the last level holds the root
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl MerkleTree {
+    pub(crate) fn new<D: Hash>(leaves: &[[u8; 32]], salt: &[u8]) -> Self {
+        let mut levels = vec![leaves.to_vec()];
+        while levels[levels.len() - 1].len() > 1 {
+            let next = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node::<D>(salt, left, right),
+                    _ => pair[0],
+                })
+                .collect();
+            levels.push(next);
+        }
+        Self { levels }
+    }
+
+    /// The root of the tree, or zeros if it is empty.
+    pub(crate) fn root(&self) -> [u8; 32] {
+        self.levels[self.levels.len() - 1]
+            .first()
+            .copied()
+            .unwrap_or([0u8; 32])
+    }
+
+    /// The siblings of the leaf at `index`, leaf level first.
+    pub(crate) fn path(&self, index: usize) -> Vec<[u8; 32]> {
+        let mut path = Vec::new();
+        let mut current = index;
+        for level in self.levels[..self.levels.len() - 1].iter() {
+            if let Some(sibling) = level.get(current ^ 1) {
+                path.push(*sibling);
+            }
+            current /= 2;
+        }
+        path
+    }
+}
+
+/// The root of a tree of `count` leaves, computed from the leaf at `index` and its `path`.
+pub(crate) fn root_from_path<D: Hash>(
+    salt: &[u8],
+    leaf: [u8; 32],
+    index: usize,
+    count: usize,
+    path: &[[u8; 32]],
+) -> Result<[u8; 32], HwError> {
+    if index >= count {
+        return Err(HwError::ProofVerificationError);
+    }
+    let mut current = leaf;
+    let mut siblings = path.iter();
+    let (mut index, mut len) = (index, count);
+    while len > 1 {
+        if index ^ 1 < len {
+            let sibling = siblings.next().ok_or(HwError::ProofVerificationError)?;
+            current = match index & 1 {
+                0 => node::<D>(salt, &current, sibling),
+                _ => node::<D>(salt, sibling, &current),
+            };
+        }
+        index /= 2;
+        len -= len / 2;
+    }
+    if siblings.next().is_some() {
+        return Err(HwError::ProofVerificationError);
+    }
+    Ok(current)
+}
+
+/// Serialize a path, as `I2OSP(#siblings, 1) || siblings`.
+pub(crate) fn serialize_path(path: &[[u8; 32]]) -> Vec<u8> {
+    let mut result = i2osp_bounded(path.len(), 1);
+    for sibling in path.iter() {
+        result.extend_from_slice(sibling);
+    }
+    result
+}
+
+/// Deserialize a path, outputting the remainder of the input.
+pub(crate) fn deserialize_path(input: &[u8]) -> Result<(Vec<[u8; 32]>, &[u8]), HwError> {
+    let (len, mut remainder) = take_slice(input, 1)?;
+    let mut path = Vec::new();
+    for _ in 0..os2ip(len)? {
+        let (sibling, rest) = take_slice(remainder, 32)?;
+        let mut node = [0u8; 32];
+        node.copy_from_slice(sibling);
+        path.push(node);
+        remainder = rest;
+    }
+    Ok((path, remainder))
+}
+
+fn node<D: Hash>(salt: &[u8], left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = D::new();
+    hasher.update(salt);
+    hasher.update(left);
+    hasher.update(right);
+    let mut output = [0u8; 32];
+    output.copy_from_slice(&hasher.finalize()[..32]);
+    output
+}
@@ -0,0 +1,130 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Hooks for collecting verification metrics (e.g., Prometheus counters)
+use std::time::Duration;
+
+/// The stages of a HashWires proof verification, in execution order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VerifyStage {
+    /// Advancing the hash chains and accumulating the MDP (PLR) root
+    ChainNodes,
+    /// Decoding the sparse Merkle tree inclusion proof
+    SmtDecoding,
+    /// Checking the sparse Merkle tree inclusion proof against the commitment
+    SmtInclusion,
+}
+
+/// Callbacks invoked during verification. All methods have empty default implementations,
+/// so implementors only override the events they are interested in.
+pub trait HwMetrics {
+    /// Called once per verification with its final outcome.
+    fn on_verification(&self, _success: bool) {}
+
+    /// Called with the serialized size (in bytes) of every verified proof.
+    fn on_proof_size(&self, _bytes: usize) {}
+
+    /// Called after each verification stage completes, with the time it took. Only called if
+    /// [`HwMetrics::times_stages`] holds.
+    fn on_stage(&self, _stage: VerifyStage, _elapsed: Duration) {}
+
+    /// Whether to time the verification stages for [`HwMetrics::on_stage`]. Timing reads the
+    /// system clock, which panics on `wasm32-unknown-unknown`, so sinks for that target should
+    /// return `false`. The clock is never read for sinks returning `false`.
+    fn times_stages(&self) -> bool {
+        true
+    }
+}
+
+/// A metrics sink that discards all events, without reading the clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoopMetrics;
+
+impl HwMetrics for NoopMetrics {
+    fn times_stages(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::Secret;
+    use blake3::Hasher as Blake3;
+    use num_bigint::BigUint;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct Counters {
+        success: AtomicUsize,
+        failure: AtomicUsize,
+        bytes: AtomicUsize,
+        stages: AtomicUsize,
+    }
+
+    impl HwMetrics for Counters {
+        fn on_verification(&self, success: bool) {
+            match success {
+                true => self.success.fetch_add(1, Ordering::Relaxed),
+                false => self.failure.fetch_add(1, Ordering::Relaxed),
+            };
+        }
+
+        fn on_proof_size(&self, bytes: usize) {
+            self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        }
+
+        fn on_stage(&self, _stage: VerifyStage, _elapsed: Duration) {
+            self.stages.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_metrics_hooks() {
+        let secret = Secret::<Blake3>::gen(&[0u8; 32], &BigUint::from(402u32));
+        let commitment = secret.commit(4, 32).unwrap();
+        let proof = secret.prove(4, 32, &BigUint::from(378u32)).unwrap();
+
+        let counters = Counters::default();
+        assert!(commitment
+            .verify_with_metrics(&proof, &BigUint::from(378u32), &counters)
+            .is_ok());
+        assert!(commitment
+            .verify_with_metrics(&proof, &BigUint::from(379u32), &counters)
+            .is_err());
+
+        assert_eq!(counters.success.load(Ordering::Relaxed), 1);
+        assert_eq!(counters.failure.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            counters.bytes.load(Ordering::Relaxed),
+            2 * proof.serialize().len()
+        );
+        assert_eq!(counters.stages.load(Ordering::Relaxed), 6);
+
+        // Without stage timing, the clock is never read and no stage is reported.
+        struct Untimed(Counters);
+
+        impl HwMetrics for Untimed {
+            fn on_stage(&self, stage: VerifyStage, elapsed: Duration) {
+                self.0.on_stage(stage, elapsed);
+            }
+
+            fn times_stages(&self) -> bool {
+                false
+            }
+        }
+
+        let untimed = Untimed(Counters::default());
+        assert!(commitment
+            .verify_with_metrics(&proof, &BigUint::from(378u32), &untimed)
+            .is_ok());
+        assert_eq!(untimed.0.stages.load(Ordering::Relaxed), 0);
+        assert!(!NoopMetrics.times_stages());
+        assert!(commitment
+            .verify_with_metrics(&proof, &BigUint::from(378u32), &NoopMetrics)
+            .is_ok());
+        assert!(commitment.verify(&proof, &BigUint::from(378u32)).is_ok());
+    }
+}
@@ -0,0 +1,173 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Re-issuance of commitments under new parameters
+//!
+//! [`migrate`] recomputes the commitment of a secret under its old parameters and commits to
+//! the same value under the new parameters (base, maximum number of bits and digest), with a
+//! fresh seed: the new commitment never reuses the old seed under another digest. The
+//! resulting [`LinkingProof`] ties both commitments together; its [`LinkingProof::digest`] is
+//! the message the issuer countersigns, since only the issuer can attest the two commitments
+//! hide the same value.
+//!
+//! A serialized linking proof is laid out as follows (all integers are big-endian):
+//!
+//! ```text
+//! for the old and then the new commitment:
+//!     base (4) || max_number_bits (8) || mdp_salt_size (1) || chain_length_policy (1)
+//!     || byte_order_policy (1) || framing_policy (1) || I2OSP(len, 2) || commitment
+//! ```
+use digest::Digest;
+
+use crate::errors::HwError;
+use crate::hashwires::{Commitment, Secret};
+use crate::params::HwParams;
+use crate::serialization::{serialize_bounded, tokenize_slice};
+use crate::traits::Hash;
+
+const LINK_SALT: &[u8; 32] = b"d1234567890123456789012345678901";
+
+/// Commit to the value of `secret` with `new_seed` under `new_params` and the digest `E`,
+/// returning the new secret and commitment along with a proof linking the commitment under
+/// `old_params` to the new one. `new_seed` must be freshly sampled; reusing the seed of
+/// `secret` is rejected with a [`HwError::ParameterError`].
+pub fn migrate<D: Hash, E: Hash>(
+    secret: &Secret<D>,
+    new_seed: &[u8],
+    old_params: &HwParams,
+    new_params: &HwParams,
+) -> Result<(Secret<E>, Commitment<E>, LinkingProof), HwError> {
+    if new_seed == secret.seed.as_slice() {
+        return Err(HwError::ParameterError);
+    }
+    let old_commitment = secret.commit_with_params(old_params)?;
+    let new_secret = Secret::<E>::gen(new_seed, &secret.value);
+    let new_commitment = new_secret.commit_with_params(new_params)?;
+    let proof = LinkingProof {
+        old_params: *old_params,
+        old_commitment: old_commitment.serialize(),
+        new_params: *new_params,
+        new_commitment: new_commitment.serialize(),
+    };
+    Ok((new_secret, new_commitment, proof))
+}
+
+/// A statement that two commitments, under possibly different parameters and digests, were
+/// issued for the same secret.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinkingProof {
+    old_params: HwParams,
+    old_commitment: Vec<u8>,
+    new_params: HwParams,
+    new_commitment: Vec<u8>,
+}
+
+impl LinkingProof {
+    /// The parameters of the old commitment.
+    pub fn old_params(&self) -> &HwParams {
+        &self.old_params
+    }
+
+    /// The parameters of the new commitment.
+    pub fn new_params(&self) -> &HwParams {
+        &self.new_params
+    }
+
+    /// Check that this proof links `old` to `new`.
+    pub fn verify<D: Hash, E: Hash>(
+        &self,
+        old: &Commitment<D>,
+        new: &Commitment<E>,
+    ) -> Result<(), HwError> {
+        if old.base() != self.old_params.base
+            || new.base() != self.new_params.base
+            || old.serialize() != self.old_commitment
+            || new.serialize() != self.new_commitment
+        {
+            return Err(HwError::ProofVerificationError);
+        }
+        Ok(())
+    }
+
+    /// The digest of this proof under `D`, to be countersigned by the issuer.
+    pub fn digest<D: Hash>(&self) -> [u8; 32] {
+        let mut hasher = D::new();
+        hasher.update(LINK_SALT);
+        hasher.update(self.serialize());
+        let mut output = [0u8; 32];
+        output.copy_from_slice(&hasher.finalize()[..32]);
+        output
+    }
+
+    /// Serialize the linking proof.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        for (params, commitment) in [
+            (&self.old_params, &self.old_commitment),
+            (&self.new_params, &self.new_commitment),
+        ]
+        .iter()
+        {
+            result.extend_from_slice(&params.serialize());
+            result.extend_from_slice(&serialize_bounded(commitment, 2));
+        }
+        result
+    }
+
+    /// Deserialize a linking proof.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (old_params, old_commitment, remainder) = deserialize_commitment(input)?;
+        let (new_params, new_commitment, remainder) = deserialize_commitment(remainder)?;
+        if !remainder.is_empty() {
+            return Err(HwError::SerializationError);
+        }
+        Ok(Self {
+            old_params,
+            old_commitment: old_commitment.to_vec(),
+            new_params,
+            new_commitment: new_commitment.to_vec(),
+        })
+    }
+}
+
+fn deserialize_commitment(input: &[u8]) -> Result<(HwParams, &[u8], &[u8]), HwError> {
+    let (params, remainder) = HwParams::deserialize_prefix(input)?;
+    let (commitment, remainder) = tokenize_slice(remainder, 2)?;
+    Ok((params, commitment, remainder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+    use num_bigint::BigUint;
+    use sha2::Sha256;
+
+    #[test]
+    fn test_migrate() -> Result<(), HwError> {
+        let old_params = HwParams::new(4, 32);
+        let new_params = HwParams::new(256, 64);
+        let secret = Secret::<Blake3>::gen(&[8u8; 32], &BigUint::from(3413u32));
+        let old = secret.commit(old_params.base, old_params.max_number_bits)?;
+        assert!(matches!(
+            migrate::<Blake3, Sha256>(&secret, &[8u8; 32], &old_params, &new_params),
+            Err(HwError::ParameterError)
+        ));
+        let (new_secret, new, link) =
+            migrate::<Blake3, Sha256>(&secret, &[9u8; 32], &old_params, &new_params)?;
+
+        let link = LinkingProof::deserialize(&link.serialize())?;
+        link.verify(&old, &new)?;
+        assert_eq!(*link.new_params(), new_params);
+        assert!(link.verify(&new, &old).is_err());
+        assert_ne!(link.digest::<Sha256>(), [0u8; 32]);
+
+        let threshold = BigUint::from(3000u32);
+        new.verify(
+            &new_secret.prove(new_params.base, new_params.max_number_bits, &threshold)?,
+            &threshold,
+        )
+    }
+}
@@ -0,0 +1,221 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! k-of-n issuer attestations
+//!
+//! Whoever knows the seed of a HashWires commitment can prove anything about it, so a single
+//! seed cannot be shared among issuers. Instead, each of the `n` issuers derives its own seed
+//! and commits to the value independently; the [`CombinedCommitment`] gathers these
+//! contributions, and a [`CombinedProof`] only verifies if at least `k` distinct issuers'
+//! contributions are proven and all of the presented ones verify. Hence fewer than `k`
+//! colluding issuers cannot forge an attestation.
+//!
+//! A serialized combined commitment is laid out as follows (all integers are big-endian):
+//!
+//! ```text
+//! k (2) || n (2) || for each contribution: issuer id (2) || base (4) || I2OSP(len, 2) || commitment
+//! ```
+//!
+//! and a serialized combined proof as:
+//!
+//! ```text
+//! #proofs (2) || for each proof: issuer id (2) || I2OSP(len, 2) || proof
+//! ```
+use num_bigint::BigUint;
+
+use crate::errors::HwError;
+use crate::hashwires::{Commitment, Proof, Secret};
+use crate::params::{take_base, HwParams};
+use crate::serialization::{i2osp_bounded, os2ip, serialize_bounded, take_slice, tokenize_slice};
+use crate::traits::Hash;
+
+/// The commitments of `n` issuers to the same value, of which `k` must be proven.
+pub struct CombinedCommitment<D: Hash> {
+    k: usize,
+    contributions: Vec<(u16, Commitment<D>)>,
+}
+
+impl<D: Hash> CombinedCommitment<D> {
+    /// Combine the commitments of distinct issuers, requiring `k` of them to be proven.
+    pub fn new(k: usize, contributions: Vec<(u16, Commitment<D>)>) -> Result<Self, HwError> {
+        let mut ids: Vec<u16> = contributions.iter().map(|(id, _)| *id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        if k == 0 || k > contributions.len() || ids.len() != contributions.len() {
+            return Err(HwError::ParameterError);
+        }
+        Ok(Self { k, contributions })
+    }
+
+    /// The number of issuers which must be proven.
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// The issuers' commitments, with their identifiers.
+    pub fn contributions(&self) -> &[(u16, Commitment<D>)] {
+        &self.contributions
+    }
+
+    /// Verify that at least `k` distinct issuers' commitments are proven larger than or equal
+    /// to `threshold`, and that every proof presented verifies.
+    pub fn verify(&self, proof: &CombinedProof, threshold: &BigUint) -> Result<(), HwError> {
+        let mut proven: Vec<u16> = Vec::with_capacity(proof.proofs.len());
+        for (id, p) in proof.proofs.iter() {
+            if proven.contains(id) {
+                return Err(HwError::ProofVerificationError);
+            }
+            let (_, commitment) = self
+                .contributions
+                .iter()
+                .find(|(c, _)| c == id)
+                .ok_or(HwError::ProofVerificationError)?;
+            commitment.verify(p, threshold)?;
+            proven.push(*id);
+        }
+        if proven.len() < self.k {
+            return Err(HwError::ProofVerificationError);
+        }
+        Ok(())
+    }
+
+    /// Serialize the combined commitment.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = i2osp_bounded(self.k, 2);
+        result.extend_from_slice(&i2osp_bounded(self.contributions.len(), 2));
+        for (id, commitment) in self.contributions.iter() {
+            result.extend_from_slice(&id.to_be_bytes());
+            result.extend_from_slice(&commitment.base().to_be_bytes());
+            result.extend_from_slice(&serialize_bounded(&commitment.serialize(), 2));
+        }
+        result
+    }
+
+    /// Deserialize a combined commitment. Fails with [`HwError::ParameterError`] if a
+    /// contribution has an unsupported base.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (k, remainder) = take_slice(input, 2)?;
+        let (n, mut remainder) = take_slice(remainder, 2)?;
+        let n = os2ip(n)?;
+        let mut contributions = Vec::with_capacity(n);
+        for _ in 0..n {
+            let (id, rest) = take_slice(remainder, 2)?;
+            let (base, rest) = take_base(rest)?;
+            let (commitment, rest) = tokenize_slice(rest, 2)?;
+            contributions.push((
+                os2ip(id)? as u16,
+                Commitment::deserialize(commitment, base)?,
+            ));
+            remainder = rest;
+        }
+        if !remainder.is_empty() {
+            return Err(HwError::SerializationError);
+        }
+        Self::new(os2ip(k)?, contributions).map_err(|_| HwError::SerializationError)
+    }
+}
+
+/// Proofs over the commitments of several issuers.
+pub struct CombinedProof {
+    proofs: Vec<(u16, Proof)>,
+}
+
+impl CombinedProof {
+    /// Generate proofs of `threshold` over the commitments of each of the issuers in
+    /// `secrets`, identified by their issuer identifier.
+    pub fn prove<D: Hash>(
+        secrets: &[(u16, Secret<D>)],
+        params: &HwParams,
+        threshold: &BigUint,
+    ) -> Result<Self, HwError> {
+        let proofs = secrets
+            .iter()
+            .map(|(id, secret)| {
+                secret
+                    .prove(params.base, params.max_number_bits, threshold)
+                    .map(|p| (*id, p))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { proofs })
+    }
+
+    /// Serialize the combined proof.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = i2osp_bounded(self.proofs.len(), 2);
+        for (id, proof) in self.proofs.iter() {
+            result.extend_from_slice(&id.to_be_bytes());
+            result.extend_from_slice(&serialize_bounded(&proof.serialize(), 2));
+        }
+        result
+    }
+
+    /// Deserialize a combined proof.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (count, mut remainder) = take_slice(input, 2)?;
+        let count = os2ip(count)?;
+        let mut proofs = Vec::with_capacity(count);
+        for _ in 0..count {
+            let (id, rest) = take_slice(remainder, 2)?;
+            let (proof, rest) = tokenize_slice(rest, 2)?;
+            proofs.push((os2ip(id)? as u16, Proof::deserialize(proof)?));
+            remainder = rest;
+        }
+        if !remainder.is_empty() {
+            return Err(HwError::SerializationError);
+        }
+        Ok(Self { proofs })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+
+    #[test]
+    fn test_two_of_three_issuers() -> Result<(), HwError> {
+        let params = HwParams::new(16, 32);
+        let value = BigUint::from(3413u32);
+        let threshold = BigUint::from(2999u32);
+        let secrets: Vec<(u16, Secret<Blake3>)> = (1..=3u16)
+            .map(|id| (id, Secret::gen(&[id as u8; 32], &value)))
+            .collect();
+        let contributions = secrets
+            .iter()
+            .map(|(id, s)| Ok((*id, s.commit(params.base, params.max_number_bits)?)))
+            .collect::<Result<Vec<_>, HwError>>()?;
+        let commitment = CombinedCommitment::<Blake3>::deserialize(
+            &CombinedCommitment::new(2, contributions)?.serialize(),
+        )?;
+        assert_eq!(commitment.k(), 2);
+
+        let proof = CombinedProof::prove(&secrets[1..], &params, &threshold)?;
+        let proof = CombinedProof::deserialize(&proof.serialize())?;
+        commitment.verify(&proof, &threshold)?;
+
+        // A single issuer is not enough, and issuers cannot be counted twice.
+        let single = CombinedProof::prove(&secrets[..1], &params, &threshold)?;
+        assert!(commitment.verify(&single, &threshold).is_err());
+        let mut twice = CombinedProof::prove(&secrets[..1], &params, &threshold)?;
+        twice.proofs.extend(single.proofs);
+        assert!(commitment.verify(&twice, &threshold).is_err());
+
+        // An issuer proof presented under another issuer's identifier does not verify.
+        let mut swapped = CombinedProof::prove(&secrets[..2], &params, &threshold)?;
+        swapped.proofs[0].0 = 3;
+        assert!(commitment.verify(&swapped, &threshold).is_err());
+
+        assert!(CombinedCommitment::<Blake3>::new(0, vec![]).is_err());
+
+        // the base of the first contribution follows k, n and its issuer id
+        let mut unsupported = commitment.serialize();
+        unsupported[6..10].copy_from_slice(&3u32.to_be_bytes());
+        assert!(matches!(
+            CombinedCommitment::<Blake3>::deserialize(&unsupported),
+            Err(HwError::ParameterError)
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,308 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Interactive narrowing of a threshold, with incremental proofs
+//!
+//! A verifier which wants to learn a band of the committed value (e.g. by binary search)
+//! asks for proofs of several thresholds in turn. Consecutive proofs often pick the same MDP
+//! element, and then share their PLR padding, MDP salt and inclusion proof, while the chain
+//! node of a digit `t_i` of the threshold derives from the node sent for a larger or equal
+//! digit `r_i` of a previous threshold by hashing it `r_i - t_i` times.
+//!
+//! A [`HolderSession`] answers each threshold with an [`IncrementalProof`], which either is a
+//! full proof, or refers to a previous proof of the session and only carries the chain nodes
+//! which the verifier cannot derive. A [`VerifierSession`] rebuilds and verifies the full
+//! proof, and keeps it as a reference for the next ones. Both sessions must see the same
+//! thresholds in the same order.
+//!
+//! A serialized incremental proof is laid out as `0 (1) || proof` for a full proof, and as
+//! follows otherwise (all integers are big-endian):
+//!
+//! ```text
+//! 1 (1) || reference (2) || #chain nodes (2)
+//! for each chain node: 0 (1) if derived from the reference, 1 (1) || node (32) otherwise
+//! ```
+use generic_array::GenericArray;
+use num_bigint::BigUint;
+
+use crate::dp::value_split_per_base;
+use crate::errors::HwError;
+use crate::hashes::HashContext;
+use crate::hashwires::{compute_bitlength, ChainNodesSize, Commitment, Proof, Secret};
+use crate::params::HwParams;
+use crate::serialization::{i2osp_bounded, os2ip, take_slice};
+use crate::traits::Hash;
+
+type ChainNode = GenericArray<u8, ChainNodesSize>;
+
+/// The answer of a holder to a threshold of a narrowing session.
+pub enum IncrementalProof {
+    /// A full proof, sharing nothing with the previous ones.
+    Full(Proof),
+    /// A proof reusing the PLR padding, MDP salt and inclusion proof of the previous proof
+    /// `reference` of the session, along with the chain nodes which are not derived from it.
+    Reuse {
+        /// The index of the previous proof in the session.
+        reference: usize,
+        /// The chain nodes, most significant digit first, `None` when derived from the
+        /// reference.
+        chain_nodes: Vec<Option<ChainNode>>,
+    },
+}
+
+impl IncrementalProof {
+    /// Serialize the incremental proof.
+    pub fn serialize(&self) -> Vec<u8> {
+        match self {
+            IncrementalProof::Full(proof) => [&[0u8][..], &proof.serialize()].concat(),
+            IncrementalProof::Reuse {
+                reference,
+                chain_nodes,
+            } => {
+                let mut result = [
+                    &[1u8][..],
+                    &i2osp_bounded(*reference, 2),
+                    &i2osp_bounded(chain_nodes.len(), 2),
+                ]
+                .concat();
+                for node in chain_nodes.iter() {
+                    match node {
+                        Some(node) => {
+                            result.push(1);
+                            result.extend_from_slice(node);
+                        }
+                        None => result.push(0),
+                    }
+                }
+                result
+            }
+        }
+    }
+
+    /// Deserialize an incremental proof.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (tag, remainder) = take_slice(input, 1)?;
+        if tag[0] == 0 {
+            return Ok(IncrementalProof::Full(Proof::deserialize(remainder)?));
+        }
+        if tag[0] != 1 {
+            return Err(HwError::SerializationError);
+        }
+        let (reference, remainder) = take_slice(remainder, 2)?;
+        let (count, mut remainder) = take_slice(remainder, 2)?;
+        let mut chain_nodes = Vec::new();
+        for _ in 0..os2ip(count)? {
+            let (flag, rest) = take_slice(remainder, 1)?;
+            remainder = match flag[0] {
+                0 => {
+                    chain_nodes.push(None);
+                    rest
+                }
+                1 => {
+                    let (node, rest) = take_slice(rest, 32)?;
+                    chain_nodes.push(Some(GenericArray::clone_from_slice(node)));
+                    rest
+                }
+                _ => return Err(HwError::SerializationError),
+            };
+        }
+        if !remainder.is_empty() {
+            return Err(HwError::SerializationError);
+        }
+        Ok(IncrementalProof::Reuse {
+            reference: os2ip(reference)?,
+            chain_nodes,
+        })
+    }
+}
+
+// The chain node of digit `digit` derived from the node of a previous digit `reference`.
+fn derive_node<D: Hash>(node: &ChainNode, reference: u8, digit: u8) -> Option<ChainNode> {
+    let iterations = reference.checked_sub(digit)? as usize;
+    let node = HashContext::<D>::new().hash_chain(node, iterations);
+    Some(GenericArray::clone_from_slice(&node))
+}
+
+// Whether `proof` and `reference` share everything but their chain nodes, i.e. prove the same
+// MDP element for thresholds of the same number of digits.
+fn shares_element(proof: &Proof, reference: &Proof) -> bool {
+    proof.digest_id == reference.digest_id
+        && proof.framing == reference.framing
+        && proof.chain_nodes.len() == reference.chain_nodes.len()
+        && proof.plr_padding == reference.plr_padding
+        && proof.mdp_salt == reference.mdp_salt
+        && proof.smt_inclusion_proof == reference.smt_inclusion_proof
+}
+
+/// The holder side of a narrowing session.
+pub struct HolderSession<'a, D: Hash> {
+    secret: &'a Secret<D>,
+    params: HwParams,
+    // the proofs sent so far, with the digits of their thresholds
+    sent: Vec<(Vec<u8>, Proof)>,
+}
+
+impl<'a, D: Hash> HolderSession<'a, D> {
+    /// Start a session proving thresholds over the commitment of `secret` under `params`.
+    pub fn new(secret: &'a Secret<D>, params: &HwParams) -> Self {
+        Self {
+            secret,
+            params: *params,
+            sent: Vec::new(),
+        }
+    }
+
+    /// Prove `threshold`, reusing the previous proof of the session from which most chain
+    /// nodes derive.
+    pub fn respond(&mut self, threshold: &BigUint) -> Result<IncrementalProof, HwError> {
+        let proof = self.secret.commit_and_prove(&self.params, threshold)?.1;
+        let digits = value_split_per_base(threshold, compute_bitlength(self.params.base));
+        let mut best: Option<(usize, Vec<Option<ChainNode>>)> = None;
+        for (reference, (reference_digits, reference_proof)) in self.sent.iter().enumerate() {
+            if !shares_element(&proof, reference_proof) {
+                continue;
+            }
+            let chain_nodes: Vec<Option<ChainNode>> = proof
+                .chain_nodes
+                .iter()
+                .zip(reference_proof.chain_nodes.iter())
+                .zip(digits.iter().zip(reference_digits.iter()))
+                .map(|((node, previous), (digit, previous_digit))| {
+                    match derive_node::<D>(previous, *previous_digit, *digit) {
+                        Some(derived) if derived == *node => None,
+                        _ => Some(*node),
+                    }
+                })
+                .collect();
+            let sent = chain_nodes.iter().filter(|node| node.is_some()).count();
+            let fewer = match best.as_ref() {
+                Some((_, nodes)) => sent <= nodes.iter().filter(|node| node.is_some()).count(),
+                None => true,
+            };
+            if fewer {
+                best = Some((reference, chain_nodes));
+            }
+        }
+        let answer = match best {
+            Some((reference, chain_nodes)) => IncrementalProof::Reuse {
+                reference,
+                chain_nodes,
+            },
+            None => IncrementalProof::Full(proof.clone()),
+        };
+        self.sent.push((digits, proof));
+        Ok(answer)
+    }
+}
+
+/// The verifier side of a narrowing session.
+pub struct VerifierSession<'a, D: Hash> {
+    commitment: &'a Commitment<D>,
+    // the proofs accepted so far, with the digits of their thresholds
+    accepted: Vec<(Vec<u8>, Proof)>,
+}
+
+impl<'a, D: Hash> VerifierSession<'a, D> {
+    /// Start a session verifying thresholds over `commitment`.
+    pub fn new(commitment: &'a Commitment<D>) -> Self {
+        Self {
+            commitment,
+            accepted: Vec::new(),
+        }
+    }
+
+    /// Rebuild the full proof of `threshold` from `proof` and the previous proofs of the
+    /// session, and verify it. A rejected proof does not become a reference, so the holder
+    /// session must be restarted after a failure.
+    pub fn verify(&mut self, proof: IncrementalProof, threshold: &BigUint) -> Result<(), HwError> {
+        let digits = value_split_per_base(threshold, compute_bitlength(self.commitment.base()));
+        let proof = match proof {
+            IncrementalProof::Full(proof) => proof,
+            IncrementalProof::Reuse {
+                reference,
+                chain_nodes,
+            } => {
+                let (reference_digits, reference) = self
+                    .accepted
+                    .get(reference)
+                    .ok_or(HwError::ProofVerificationError)?;
+                if chain_nodes.len() != reference.chain_nodes.len()
+                    || digits.len() != reference_digits.len()
+                {
+                    return Err(HwError::ProofVerificationError);
+                }
+                let chain_nodes = chain_nodes
+                    .into_iter()
+                    .zip(reference.chain_nodes.iter())
+                    .zip(digits.iter().zip(reference_digits.iter()))
+                    .map(|((node, previous), (digit, previous_digit))| match node {
+                        Some(node) => Some(node),
+                        None => derive_node::<D>(previous, *previous_digit, *digit),
+                    })
+                    .collect::<Option<Vec<ChainNode>>>()
+                    .ok_or(HwError::ProofVerificationError)?;
+                Proof {
+                    chain_nodes,
+                    ..reference.clone()
+                }
+            }
+        };
+        self.commitment.verify(&proof, threshold)?;
+        self.accepted.push((digits, proof));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+
+    #[test]
+    fn test_narrowing() -> Result<(), HwError> {
+        let params = HwParams::new(16, 32);
+        let secret = Secret::<Blake3>::gen(&[4u8; 32], &BigUint::from(0x3d5au32));
+        let commitment = secret.commit_with_params(&params)?;
+        let mut holder = HolderSession::new(&secret, &params);
+        let mut verifier = VerifierSession::new(&commitment);
+
+        // binary search of the value between 0x3000 and 0x3fff
+        let (mut low, mut high) = (0x3000u32, 0x3fffu32);
+        let (mut incremental, mut full) = (0, 0);
+        while low < high {
+            let middle = (low + high + 1) / 2;
+            let threshold = BigUint::from(middle);
+            match holder.respond(&threshold) {
+                Ok(answer) => {
+                    let bytes = answer.serialize();
+                    incremental += bytes.len();
+                    full += secret.prove(16, 32, &threshold)?.serialize().len();
+                    verifier.verify(IncrementalProof::deserialize(&bytes)?, &threshold)?;
+                    low = middle;
+                }
+                Err(_) => high = middle - 1,
+            }
+        }
+        assert_eq!(low, 0x3d5a);
+        // the search proves three MDP elements, each of them fully once
+        assert!(incremental * 3 < full * 2);
+
+        // a lower threshold only sends the nodes of its larger digits
+        match holder.respond(&BigUint::from(0x3a05u32))? {
+            IncrementalProof::Reuse { chain_nodes, .. } => {
+                assert_eq!(chain_nodes.iter().filter(|node| node.is_some()).count(), 1)
+            }
+            IncrementalProof::Full(_) => panic!("expected an incremental proof"),
+        }
+
+        // nodes of larger digits cannot be derived from the reference
+        let forged = IncrementalProof::Reuse {
+            reference: verifier.accepted.len() - 1,
+            chain_nodes: vec![None; 4],
+        };
+        assert!(verifier.verify(forged, &BigUint::from(0x3d5bu32)).is_err());
+        Ok(())
+    }
+}
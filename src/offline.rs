@@ -0,0 +1,504 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Offline verification against signed verifier bundles
+//!
+//! Verifiers with intermittent connectivity (e.g. at border control) cannot reach issuers when
+//! a holder presents a proof. A trust authority periodically publishes a [`VerifierBundle`],
+//! signed with its own key, holding everything a verifier needs to check presentations
+//! offline: the public keys of the issuers whose manifests (see [`crate::manifest`]) are
+//! trusted, the parameters of the accepted profiles, the revocation root, and the validity
+//! window of the bundle. An [`OfflineVerifier`] keeps the latest bundle it checked, and only
+//! replaces it with bundles of a larger sequence number, so that an older bundle (e.g. with
+//! an older revocation root) cannot be replayed to it.
+//!
+//! An [`OfflinePresentation`] carries the commitment of the holder, the signed manifest of
+//! its issuance and its receipt, the range proof and an absence proof of the commitment in
+//! the revocation list. Issuers revoke a commitment by inserting it in a
+//! [`CommitmentSet`](crate::registry::CommitmentSet) of height 64 under its
+//! [`revocation_id`], whose identifier root is the revocation root:
+//!
+//! ```text
+//! revocation id = the 8 most significant bytes of H(REVOCATION_SALT || serialized commitment)
+//! ```
+//!
+//! A serialized signed bundle is laid out as follows (all integers are big-endian), the
+//! signature covering all of the preceding bytes:
+//!
+//! ```text
+//! version (1) || digest identifier (1) || sequence (8) || not before (8) || not after (8)
+//! || revocation root (32) || #issuer keys (2) || for each key: I2OSP(len, 2) || key
+//! || #params (2) || for each: params || I2OSP(len, 2) || signature
+//! ```
+use digest::Digest;
+use num_bigint::BigUint;
+
+use crate::digest_id::DigestId;
+use crate::errors::HwError;
+use crate::hashwires::{Commitment, Proof};
+use crate::manifest::{ManifestSigner, Receipt, SignatureVerifier, SignedManifest};
+use crate::params::HwParams;
+use crate::registry::{verify_absence, AbsenceProof};
+use crate::serialization::{
+    i2osp, os2ip, os2ip_u64, serialize, take_slice, take_version, tokenize_slice,
+};
+use crate::traits::Hash;
+
+const VERIFIER_BUNDLE_VERSION: u8 = 1;
+const MAX_BUNDLE_ENTRIES: usize = u16::MAX as usize;
+const REVOCATION_SALT: &[u8; 32] = b"C1234567890123456789012345678901";
+
+/// Checks signatures against a public key carried by a [`VerifierBundle`].
+pub trait PublicKeyVerifier {
+    /// Whether `signature` is a valid signature of `message` under `public_key`.
+    fn verify(&self, public_key: &[u8], message: &[u8], signature: &[u8]) -> bool;
+}
+
+// A public key of a bundle, along with the scheme checking its signatures.
+struct BundleKey<'a, P: PublicKeyVerifier> {
+    scheme: &'a P,
+    public_key: &'a [u8],
+}
+
+impl<'a, P: PublicKeyVerifier> SignatureVerifier for BundleKey<'a, P> {
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        self.scheme.verify(self.public_key, message, signature)
+    }
+}
+
+/// The identifier of `commitment` in revocation lists.
+pub fn revocation_id<D: Hash>(commitment: &Commitment<D>) -> u64 {
+    let mut hasher = D::new();
+    hasher.update(REVOCATION_SALT);
+    hasher.update(commitment.serialize());
+    let mut output = [0u8; 8];
+    output.copy_from_slice(&hasher.finalize()[..8]);
+    u64::from_be_bytes(output)
+}
+
+/// The trust material of offline verifiers, as published by a trust authority.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifierBundle {
+    digest_id: DigestId,
+    sequence: u64,
+    not_before: u64,
+    not_after: u64,
+    revocation_root: [u8; 32],
+    issuer_keys: Vec<Vec<u8>>,
+    params: Vec<HwParams>,
+}
+
+impl VerifierBundle {
+    /// An empty bundle for commitments of digest `D`, numbered `sequence` and valid from
+    /// `not_before` to `not_after` included, with the revocation root `revocation_root`.
+    pub fn new<D: Hash>(
+        sequence: u64,
+        not_before: u64,
+        not_after: u64,
+        revocation_root: &[u8; 32],
+    ) -> Self {
+        Self {
+            digest_id: DigestId::of::<D>(),
+            sequence,
+            not_before,
+            not_after,
+            revocation_root: *revocation_root,
+            issuer_keys: Vec::new(),
+            params: Vec::new(),
+        }
+    }
+
+    /// Trust the manifests signed under the issuer public key `public_key`.
+    pub fn with_issuer_key(mut self, public_key: &[u8]) -> Self {
+        self.issuer_keys.push(public_key.to_vec());
+        self
+    }
+
+    /// Accept commitments generated under `params`, e.g. the parameters of a profile.
+    pub fn with_params(mut self, params: &HwParams) -> Self {
+        self.params.push(*params);
+        self
+    }
+
+    /// The sequence number of the bundle.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// The validity window of the bundle, bounds included.
+    pub fn validity(&self) -> (u64, u64) {
+        (self.not_before, self.not_after)
+    }
+
+    /// The revocation root.
+    pub fn revocation_root(&self) -> &[u8; 32] {
+        &self.revocation_root
+    }
+
+    /// The trusted issuer public keys.
+    pub fn issuer_keys(&self) -> &[Vec<u8>] {
+        &self.issuer_keys
+    }
+
+    /// The accepted parameters.
+    pub fn params(&self) -> &[HwParams] {
+        &self.params
+    }
+
+    /// Serialize the bundle, as signed by the trust authority. Fails with
+    /// [`HwError::SerializationError`] if it has more than 65535 issuer keys or parameters,
+    /// or a key longer than 65535 bytes.
+    pub fn serialize(&self) -> Result<Vec<u8>, HwError> {
+        if self.issuer_keys.len() > MAX_BUNDLE_ENTRIES
+            || self.params.len() > MAX_BUNDLE_ENTRIES
+            || self
+                .issuer_keys
+                .iter()
+                .any(|key| key.len() > u16::MAX as usize)
+        {
+            return Err(HwError::SerializationError);
+        }
+        let mut result = [
+            &[VERIFIER_BUNDLE_VERSION, self.digest_id.as_u8()][..],
+            &self.sequence.to_be_bytes(),
+            &self.not_before.to_be_bytes(),
+            &self.not_after.to_be_bytes(),
+            &self.revocation_root,
+            &i2osp(self.issuer_keys.len(), 2)?,
+        ]
+        .concat();
+        for key in self.issuer_keys.iter() {
+            result.extend_from_slice(&serialize(key, 2)?);
+        }
+        result.extend_from_slice(&i2osp(self.params.len(), 2)?);
+        for params in self.params.iter() {
+            result.extend_from_slice(&params.serialize());
+        }
+        Ok(result)
+    }
+
+    /// Deserialize a bundle, outputting the remainder of the input.
+    fn deserialize_prefix(input: &[u8]) -> Result<(Self, &[u8]), HwError> {
+        let remainder = take_version(input, VERIFIER_BUNDLE_VERSION)?;
+        let (digest_id, remainder) = take_slice(remainder, 1)?;
+        let (sequence, remainder) = take_slice(remainder, 8)?;
+        let (not_before, remainder) = take_slice(remainder, 8)?;
+        let (not_after, remainder) = take_slice(remainder, 8)?;
+        let (revocation_root, remainder) = take_slice(remainder, 32)?;
+        let (key_count, mut remainder) = take_slice(remainder, 2)?;
+        let mut issuer_keys = Vec::new();
+        for _ in 0..os2ip(key_count)? {
+            let (key, rest) = tokenize_slice(remainder, 2)?;
+            issuer_keys.push(key.to_vec());
+            remainder = rest;
+        }
+        let (params_count, mut remainder) = take_slice(remainder, 2)?;
+        let mut params = Vec::new();
+        for _ in 0..os2ip(params_count)? {
+            let (entry, rest) = HwParams::deserialize_prefix(remainder)?;
+            params.push(entry);
+            remainder = rest;
+        }
+        let mut root = [0u8; 32];
+        root.copy_from_slice(revocation_root);
+        let bundle = Self {
+            digest_id: DigestId::from_u8(digest_id[0])?,
+            sequence: os2ip_u64(sequence)?,
+            not_before: os2ip_u64(not_before)?,
+            not_after: os2ip_u64(not_after)?,
+            revocation_root: root,
+            issuer_keys,
+            params,
+        };
+        Ok((bundle, remainder))
+    }
+
+    /// Sign the bundle with the key of the trust authority.
+    pub fn sign<S: ManifestSigner>(self, signer: &S) -> Result<SignedVerifierBundle, HwError> {
+        let signature = signer.sign(&self.serialize()?);
+        Ok(SignedVerifierBundle {
+            bundle: self,
+            signature,
+        })
+    }
+}
+
+/// A verifier bundle along with the signature of the trust authority.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedVerifierBundle {
+    bundle: VerifierBundle,
+    signature: Vec<u8>,
+}
+
+impl SignedVerifierBundle {
+    /// The bundle.
+    pub fn bundle(&self) -> &VerifierBundle {
+        &self.bundle
+    }
+
+    /// Check the signature of the bundle with `authority`, and that it is valid at `now`,
+    /// returning the bundle. Fails with [`HwError::ProofVerificationError`] for an invalid
+    /// signature, and with [`HwError::PolicyError`] outside of the validity window.
+    pub fn verify<V: SignatureVerifier>(
+        &self,
+        authority: &V,
+        now: u64,
+    ) -> Result<&VerifierBundle, HwError> {
+        if !authority.verify(&self.bundle.serialize()?, &self.signature) {
+            return Err(HwError::ProofVerificationError);
+        }
+        if now < self.bundle.not_before || now > self.bundle.not_after {
+            return Err(HwError::PolicyError);
+        }
+        Ok(&self.bundle)
+    }
+
+    /// Serialize the signed bundle.
+    pub fn serialize(&self) -> Result<Vec<u8>, HwError> {
+        Ok([self.bundle.serialize()?, serialize(&self.signature, 2)?].concat())
+    }
+
+    /// Deserialize a signed bundle.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (bundle, remainder) = VerifierBundle::deserialize_prefix(input)?;
+        let (signature, remainder) = tokenize_slice(remainder, 2)?;
+        if !remainder.is_empty() {
+            return Err(HwError::SerializationError);
+        }
+        Ok(Self {
+            bundle,
+            signature: signature.to_vec(),
+        })
+    }
+}
+
+/// What a holder presents to an offline verifier.
+pub struct OfflinePresentation<'a, D: Hash> {
+    /// The commitment of the holder.
+    pub commitment: &'a Commitment<D>,
+    /// The signed manifest of the issuance of the commitment.
+    pub manifest: &'a SignedManifest,
+    /// The receipt of the commitment in the manifest.
+    pub receipt: &'a Receipt,
+    /// The range proof.
+    pub proof: &'a Proof,
+    /// The proven threshold.
+    pub threshold: &'a BigUint,
+    /// The proof that the commitment is not in the revocation list of the bundle.
+    pub absence: &'a AbsenceProof,
+}
+
+/// A verifier checking presentations against the latest bundle of a trust authority.
+pub struct OfflineVerifier<V: SignatureVerifier, P: PublicKeyVerifier> {
+    authority: V,
+    scheme: P,
+    bundle: Option<VerifierBundle>,
+}
+
+impl<V: SignatureVerifier, P: PublicKeyVerifier> OfflineVerifier<V, P> {
+    /// A verifier trusting the bundles signed by `authority`, checking the signatures of
+    /// issuer manifests with `scheme`, and without any bundle yet.
+    pub fn new(authority: V, scheme: P) -> Self {
+        Self {
+            authority,
+            scheme,
+            bundle: None,
+        }
+    }
+
+    /// The current bundle, if any.
+    pub fn bundle(&self) -> Option<&VerifierBundle> {
+        self.bundle.as_ref()
+    }
+
+    /// Replace the current bundle with `signed` if it verifies at `now` (see
+    /// [`SignedVerifierBundle::verify`]). Fails with [`HwError::ReplayError`] if its sequence
+    /// number is not larger than the one of the current bundle, which is then kept.
+    pub fn refresh(&mut self, signed: &SignedVerifierBundle, now: u64) -> Result<(), HwError> {
+        let bundle = signed.verify(&self.authority, now)?;
+        if let Some(current) = &self.bundle {
+            if bundle.sequence <= current.sequence {
+                return Err(HwError::ReplayError);
+            }
+        }
+        self.bundle = Some(bundle.clone());
+        Ok(())
+    }
+
+    /// Verify `presentation` at `now` against the current bundle. Fails with
+    /// [`HwError::PolicyError`] without a bundle valid at `now`, or for parameters the bundle
+    /// does not accept, and with [`HwError::ProofVerificationError`] if the manifest is not
+    /// signed by a trusted issuer or the commitment is revoked.
+    pub fn verify<D: Hash>(
+        &self,
+        presentation: &OfflinePresentation<D>,
+        now: u64,
+    ) -> Result<(), HwError> {
+        let bundle = self.bundle.as_ref().ok_or(HwError::PolicyError)?;
+        if now < bundle.not_before || now > bundle.not_after {
+            return Err(HwError::PolicyError);
+        }
+        if bundle.digest_id != DigestId::of::<D>() {
+            return Err(HwError::DigestMismatchError);
+        }
+        let manifest = bundle
+            .issuer_keys
+            .iter()
+            .find_map(|public_key| {
+                let key = BundleKey {
+                    scheme: &self.scheme,
+                    public_key,
+                };
+                presentation.manifest.verify(&key, now, u64::MAX).ok()
+            })
+            .ok_or(HwError::ProofVerificationError)?;
+        if !bundle.params.contains(manifest.params()) {
+            return Err(HwError::PolicyError);
+        }
+        presentation
+            .receipt
+            .verify(manifest, presentation.commitment)?;
+        verify_absence::<D>(
+            &bundle.revocation_root,
+            revocation_id(presentation.commitment),
+            presentation.absence,
+        )?;
+        presentation
+            .commitment
+            .verify(presentation.proof, presentation.threshold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::Secret;
+    use crate::manifest::BatchIssuance;
+    use crate::registry::CommitmentSet;
+    use blake3::Hasher as Blake3;
+
+    // A keyed hash standing in for the signature schemes, the public key being the key.
+    struct TestKey([u8; 32]);
+
+    impl ManifestSigner for TestKey {
+        fn sign(&self, message: &[u8]) -> Vec<u8> {
+            blake3::keyed_hash(&self.0, message).as_bytes().to_vec()
+        }
+    }
+
+    impl SignatureVerifier for TestKey {
+        fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+            self.sign(message) == signature
+        }
+    }
+
+    struct TestScheme;
+
+    impl PublicKeyVerifier for TestScheme {
+        fn verify(&self, public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+            let mut key = [0u8; 32];
+            key.copy_from_slice(public_key);
+            TestKey(key).verify(message, signature)
+        }
+    }
+
+    #[test]
+    fn test_offline_verifier() -> Result<(), HwError> {
+        let params = HwParams::new(16, 32);
+        let (authority, issuer) = (TestKey([1u8; 32]), TestKey([2u8; 32]));
+        let secrets: Vec<Secret<Blake3>> = (0..3u8)
+            .map(|i| Secret::gen(&[i; 32], &BigUint::from(3413u32)))
+            .collect();
+        let commitments = secrets
+            .iter()
+            .map(|secret| secret.commit_with_params(&params))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut batch = BatchIssuance::<Blake3>::new(&params, 1000)?;
+        for commitment in commitments.iter() {
+            batch.add(commitment)?;
+        }
+        let manifest = batch.sign(&issuer);
+
+        // the last commitment is revoked
+        let mut revoked = CommitmentSet::<Blake3>::new(64, &[3u8; 32])?;
+        revoked.insert(revocation_id(&commitments[2]), &commitments[2])?;
+        let bundle = VerifierBundle::new::<Blake3>(1, 1000, 2000, &revoked.identifier_root())
+            .with_issuer_key(&[9u8; 32])
+            .with_issuer_key(&issuer.0)
+            .with_params(&params)
+            .sign(&authority)?;
+        let bundle = SignedVerifierBundle::deserialize(&bundle.serialize()?)?;
+
+        let mut verifier = OfflineVerifier::new(TestKey([1u8; 32]), TestScheme);
+        let threshold = BigUint::from(2999u32);
+        let receipts = batch.receipts();
+        let verify = |verifier: &OfflineVerifier<TestKey, TestScheme>, i: usize, now: u64| {
+            let proof = secrets[i].commit_and_prove(&params, &threshold)?.1;
+            // a revoked holder can only present the absence proof of another commitment
+            let absence = match revoked.prove_absent(revocation_id(&commitments[i])) {
+                Ok(absence) => absence,
+                Err(_) => revoked.prove_absent(revocation_id(&commitments[0]))?,
+            };
+            let presentation = OfflinePresentation {
+                commitment: &commitments[i],
+                manifest: &manifest,
+                receipt: &receipts[i],
+                proof: &proof,
+                threshold: &threshold,
+                absence: &absence,
+            };
+            verifier.verify(&presentation, now)
+        };
+        assert!(matches!(
+            verify(&verifier, 0, 1500),
+            Err(HwError::PolicyError)
+        ));
+
+        verifier.refresh(&bundle, 1500)?;
+        verify(&verifier, 0, 1500)?;
+        verify(&verifier, 1, 1500)?;
+        assert!(matches!(
+            verify(&verifier, 2, 1500),
+            Err(HwError::ProofVerificationError)
+        ));
+        assert!(matches!(
+            verify(&verifier, 0, 2500),
+            Err(HwError::PolicyError)
+        ));
+
+        // bundles are only replaced by newer ones signed by the authority
+        assert!(matches!(
+            verifier.refresh(&bundle, 1500),
+            Err(HwError::ReplayError)
+        ));
+        let unsigned = VerifierBundle::new::<Blake3>(2, 1000, 3000, &revoked.identifier_root())
+            .with_issuer_key(&issuer.0)
+            .with_params(&params)
+            .sign(&issuer)?;
+        assert!(verifier.refresh(&unsigned, 1500).is_err());
+        assert!(verifier.refresh(&bundle, 2500).is_err());
+
+        // an issuer or parameters dropped from the bundle are no longer accepted
+        let untrusted = VerifierBundle::new::<Blake3>(2, 1000, 3000, &revoked.identifier_root())
+            .with_params(&params)
+            .sign(&authority)?;
+        verifier.refresh(&untrusted, 1500)?;
+        assert!(matches!(
+            verify(&verifier, 0, 2500),
+            Err(HwError::ProofVerificationError)
+        ));
+        let other_params = VerifierBundle::new::<Blake3>(3, 1000, 3000, &revoked.identifier_root())
+            .with_issuer_key(&issuer.0)
+            .with_params(&HwParams::new(16, 64))
+            .sign(&authority)?;
+        verifier.refresh(&other_params, 1500)?;
+        assert!(matches!(
+            verify(&verifier, 0, 2500),
+            Err(HwError::PolicyError)
+        ));
+        assert_eq!(verifier.bundle().map(|bundle| bundle.sequence()), Some(3));
+        Ok(())
+    }
+}
@@ -0,0 +1,552 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! HashWires parameters, and an advisor suggesting them for a maximum value
+//!
+//! The estimates come from closed-form models counting the hash evaluations of each operation
+//! in the worst case, where `n = max_number_bits / log2(base)` digits are used:
+//!
+//! - commitment: `n * (base - 1)` chain hashes, at most `n^2` PLR hashes over up to `n` MDPs,
+//!   and about `8n` hashes for the salts, the top roots and the MDP tree;
+//! - verification: at most `n * (base - 1)` chain hashes, `n` PLR hashes and one hash per
+//!   level of the MDP tree.
+//!
+//! Hash evaluations are converted to time using the Blake3 figures of the `hw_bench`
+//! benchmarks, so the estimates are only meaningful relative to each other.
+use std::time::Duration;
+
+use num_bigint::BigUint;
+
+use crate::dp::value_split_per_base;
+use crate::errors::HwError;
+use crate::hashwires::{compute_bitlength, compute_mdp_height, ChainNodesSize};
+use crate::serialization::{os2ip, take_slice};
+use crate::traits::Hash;
+use digest::Digest;
+use generic_array::typenum::Unsigned;
+
+/// The supported bases, in increasing order.
+pub const SUPPORTED_BASES: [u32; 4] = [2, 4, 16, 256];
+
+// Take a base serialized on 4 bytes (big-endian), failing with `HwError::ParameterError` if it
+// is not supported.
+pub(crate) fn take_base(input: &[u8]) -> Result<(u32, &[u8]), HwError> {
+    let (base, remainder) = take_slice(input, 4)?;
+    let mut base_bytes = [0u8; 4];
+    base_bytes.copy_from_slice(base);
+    let base = u32::from_be_bytes(base_bytes);
+    if !SUPPORTED_BASES.contains(&base) {
+        return Err(HwError::ParameterError);
+    }
+    Ok((base, remainder))
+}
+
+/// The maximum number of bits of the committed values, e.g. for large monetary or scientific
+/// values. It bounds the work and memory of commitments under untrusted parameters.
+pub const MAX_NUMBER_BITS: usize = 1024;
+
+/// The default size in bytes of the MDP salts.
+pub const DEFAULT_MDP_SALT_SIZE: usize = 16;
+/// The minimum size in bytes of the MDP salts.
+pub const MIN_MDP_SALT_SIZE: usize = 8;
+/// The maximum size in bytes of the MDP salts.
+pub const MAX_MDP_SALT_SIZE: usize = 32;
+
+const PARAMS_SALT: &[u8; 32] = b"81234567890123456789012345678901";
+// Calibrated from the 64-bit Blake3 figures of hw_bench (base 16 and 256).
+const NANOS_PER_HASH: u64 = 120;
+// Approximate size of a sparse Merkle tree inclusion proof: a header plus one node per level.
+const SMT_PROOF_HEADER_SIZE: usize = 4;
+const SMT_PROOF_NODE_SIZE: usize = 32;
+
+/// How long the hash chains are, recorded in the commitment header.
+///
+/// Both policies yield the same chain nodes, hence the same commitments and proofs: they only
+/// differ in the hashes the prover computes. The policies are versioned, so a policy keeps its
+/// code, and any change to the chain lengths gets a new policy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum ChainLengthPolicy {
+    /// The first chain only goes up to the most significant digit of the value, as it is never
+    /// used beyond it (version 1).
+    Optimized = 1,
+    /// All of the chains have `base` elements (version 1).
+    Uniform = 2,
+}
+
+impl ChainLengthPolicy {
+    /// The code of the policy.
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// The policy with code `code`.
+    pub fn from_u8(code: u8) -> Result<Self, HwError> {
+        match code {
+            1 => Ok(ChainLengthPolicy::Optimized),
+            2 => Ok(ChainLengthPolicy::Uniform),
+            _ => Err(HwError::SerializationError),
+        }
+    }
+
+    // The length of the first chain, for a value whose most significant digit is
+    // `most_significant_digit`.
+    pub(crate) fn first_chain_length(self, base: u32, most_significant_digit: u8) -> usize {
+        match self {
+            ChainLengthPolicy::Optimized => most_significant_digit as usize + 1,
+            ChainLengthPolicy::Uniform => base as usize,
+        }
+    }
+}
+
+/// How the key derivation functions encode their counters.
+///
+/// HashWires derives the seeds of the hash chains and the MDP salts as
+/// `H(salt || counter || seed)`, the counter being encoded on 8 bytes with this byte order. As
+/// the first counter is zero, derivations of a single output are the same under both policies.
+/// The policies are versioned, so a policy keeps its code, and any change to the encoding gets
+/// a new policy.
+///
+/// The shuffling of the MDP leaves does not depend on the policy: it seeds ChaCha12 with the
+/// 32-byte seed as is, ChaCha reads its key and outputs its words in little-endian order by
+/// specification, and positions are sampled from 64-bit words.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum ByteOrderPolicy {
+    /// Counters are encoded as 64-bit little-endian integers (version 1).
+    #[default]
+    LittleEndian = 1,
+    /// Counters are encoded as 64-bit big-endian integers, like every integer of the
+    /// serialization formats (version 1).
+    BigEndian = 2,
+}
+
+impl ByteOrderPolicy {
+    /// The code of the policy.
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// The policy with code `code`.
+    pub fn from_u8(code: u8) -> Result<Self, HwError> {
+        match code {
+            1 => Ok(ByteOrderPolicy::LittleEndian),
+            2 => Ok(ByteOrderPolicy::BigEndian),
+            _ => Err(HwError::SerializationError),
+        }
+    }
+
+    /// The encoding of the KDF counter `counter`.
+    pub fn encode_counter(self, counter: usize) -> [u8; 8] {
+        match self {
+            ByteOrderPolicy::LittleEndian => (counter as u64).to_le_bytes(),
+            ByteOrderPolicy::BigEndian => (counter as u64).to_be_bytes(),
+        }
+    }
+}
+
+/// How the salted hashes and the PLR accumulators frame their inputs.
+///
+/// These hashes are computed over concatenations such as `salt || data`, which digests of the
+/// Merkle-Damgard family (e.g. SHA-256) extend without knowing the input. Under the prefix-free
+/// framing, each input is preceded by its length on 8 bytes (big-endian), so that no framed
+/// input is a prefix of another. The hash chains and the key derivation functions hash inputs
+/// of fixed lengths, and are not affected.
+///
+/// The framing of a proof is encoded in its own header field, after the format version, as the
+/// code of its policy. The policies are versioned, so a policy keeps its code, and any change
+/// to the framing gets a new policy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum FramingPolicy {
+    /// Inputs are hashed as plain concatenations (version 1).
+    #[default]
+    Concatenated = 1,
+    /// Inputs are prefixed with their length (version 2).
+    PrefixFree = 2,
+}
+
+impl FramingPolicy {
+    /// The code of the policy.
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// The policy with code `code`.
+    pub fn from_u8(code: u8) -> Result<Self, HwError> {
+        match code {
+            1 => Ok(FramingPolicy::Concatenated),
+            2 => Ok(FramingPolicy::PrefixFree),
+            _ => Err(HwError::SerializationError),
+        }
+    }
+}
+
+/// The parameters of a HashWires commitment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HwParams {
+    /// The base of the hash chains, one of [`SUPPORTED_BASES`].
+    pub base: u32,
+    /// The maximum number of bits of the committed values, up to [`MAX_NUMBER_BITS`].
+    pub max_number_bits: usize,
+    /// The size in bytes of the MDP salts, between [`MIN_MDP_SALT_SIZE`] and
+    /// [`MAX_MDP_SALT_SIZE`]. Larger salts cost bandwidth, as one is sent in every proof.
+    pub mdp_salt_size: usize,
+    /// The length of the hash chains.
+    pub chain_length_policy: ChainLengthPolicy,
+    /// The encoding of the counters of the key derivation functions.
+    pub byte_order_policy: ByteOrderPolicy,
+    /// The framing of the inputs of the salted hashes and of the PLR accumulators.
+    pub framing_policy: FramingPolicy,
+}
+
+impl HwParams {
+    /// Parameters with the given base and maximum number of bits, and default sizes.
+    pub fn new(base: u32, max_number_bits: usize) -> Self {
+        Self {
+            base,
+            max_number_bits,
+            mdp_salt_size: DEFAULT_MDP_SALT_SIZE,
+            chain_length_policy: ChainLengthPolicy::Optimized,
+            byte_order_policy: ByteOrderPolicy::LittleEndian,
+            framing_policy: FramingPolicy::Concatenated,
+        }
+    }
+
+    /// The same parameters, with MDP salts of `mdp_salt_size` bytes.
+    pub fn with_mdp_salt_size(mut self, mdp_salt_size: usize) -> Self {
+        self.mdp_salt_size = mdp_salt_size;
+        self
+    }
+
+    /// The same parameters, with the chain-length policy `chain_length_policy`.
+    pub fn with_chain_length_policy(mut self, chain_length_policy: ChainLengthPolicy) -> Self {
+        self.chain_length_policy = chain_length_policy;
+        self
+    }
+
+    /// The same parameters, with the byte-order policy `byte_order_policy`.
+    pub fn with_byte_order_policy(mut self, byte_order_policy: ByteOrderPolicy) -> Self {
+        self.byte_order_policy = byte_order_policy;
+        self
+    }
+
+    /// The same parameters, with the framing policy `framing_policy`.
+    pub fn with_framing_policy(mut self, framing_policy: FramingPolicy) -> Self {
+        self.framing_policy = framing_policy;
+        self
+    }
+
+    /// Check that the parameters are supported. Values must have room for at least two digits,
+    /// as the MDP tree of a single digit has no height, e.g. base 256 needs 16 bits or more.
+    pub fn validate(&self) -> Result<(), HwError> {
+        if !SUPPORTED_BASES.contains(&self.base)
+            || self.max_number_bits == 0
+            || self.max_number_bits > MAX_NUMBER_BITS
+            // bitlengths are powers of two
+            || self.max_number_bits & (compute_bitlength(self.base) - 1) != 0
+            || compute_mdp_height(self.base, self.max_number_bits) == 0
+            || !(MIN_MDP_SALT_SIZE..=MAX_MDP_SALT_SIZE).contains(&self.mdp_salt_size)
+        {
+            return Err(HwError::ParameterError);
+        }
+        Ok(())
+    }
+
+    /// The maximum cardinality of the MDP of a value, which is also the maximum number of chain
+    /// nodes of a proof: a value has at most `max_number_bits / log2(base)` digits, and its MDP
+    /// at most one element per digit.
+    pub fn max_mdp_size(&self) -> Result<usize, HwError> {
+        self.validate()?;
+        Ok(self.max_number_bits / compute_bitlength(self.base))
+    }
+
+    /// The canonical digits of `value` in the base of the parameters, as used by commitments
+    /// and proofs: most significant digit first, without leading zeros, zero being the single
+    /// digit `[0]`. Ports of HashWires must decompose values exactly this way, e.g. 256 in base
+    /// 16 is `[1, 0, 0]` and not `[0, 1, 0, 0]`. Fails if `value` has more than
+    /// `max_number_bits` bits.
+    pub fn canonical_digits(&self, value: &BigUint) -> Result<Vec<u8>, HwError> {
+        self.validate()?;
+        if value.bits() > self.max_number_bits as u64 {
+            return Err(HwError::ParameterError);
+        }
+        Ok(value_split_per_base(value, compute_bitlength(self.base)))
+    }
+
+    /// A hash of the parameters, binding the base, the maximum number of bits, the sizes and
+    /// the policies.
+    pub fn digest<D: Hash>(&self) -> [u8; 32] {
+        let mut hasher = D::new();
+        hasher.update(PARAMS_SALT);
+        hasher.update(self.serialize());
+        let mut output = [0u8; 32];
+        output.copy_from_slice(&hasher.finalize()[..32]);
+        output
+    }
+
+    // base (4) || max_number_bits (8) || mdp_salt_size (1) || chain_length_policy (1)
+    // || byte_order_policy (1) || framing_policy (1)
+    pub(crate) fn serialize(&self) -> Vec<u8> {
+        [
+            &self.base.to_be_bytes()[..],
+            &(self.max_number_bits as u64).to_be_bytes(),
+            &[
+                self.mdp_salt_size as u8,
+                self.chain_length_policy.as_u8(),
+                self.byte_order_policy.as_u8(),
+                self.framing_policy.as_u8(),
+            ],
+        ]
+        .concat()
+    }
+
+    pub(crate) fn deserialize_prefix(input: &[u8]) -> Result<(Self, &[u8]), HwError> {
+        let (base, remainder) = take_slice(input, 4)?;
+        let (max_number_bits, remainder) = take_slice(remainder, 8)?;
+        let (sizes, remainder) = take_slice(remainder, 4)?;
+        let params = Self::new(os2ip(base)? as u32, os2ip(max_number_bits)?)
+            .with_mdp_salt_size(sizes[0] as usize)
+            .with_chain_length_policy(ChainLengthPolicy::from_u8(sizes[1])?)
+            .with_byte_order_policy(ByteOrderPolicy::from_u8(sizes[2])?)
+            .with_framing_policy(FramingPolicy::from_u8(sizes[3])?);
+        Ok((params, remainder))
+    }
+}
+
+/// What [`recommend`] should optimize for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProofSizeOrSpeed {
+    /// The smallest proofs.
+    ProofSize,
+    /// The fastest commitment generation and verification.
+    Speed,
+}
+
+/// Estimated (worst case) costs of HashWires for some parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Estimate {
+    /// The parameters these estimates are for.
+    pub params: HwParams,
+    /// The estimated commitment generation time (proof generation costs about the same).
+    pub commit_time: Duration,
+    /// The estimated serialized proof size, in bytes.
+    pub proof_size: usize,
+    /// The estimated proof verification time.
+    pub verify_time: Duration,
+}
+
+/// Estimate the worst-case costs of HashWires for `params`.
+pub fn estimate(params: &HwParams) -> Estimate {
+    let digits = params.max_number_bits / compute_bitlength(params.base);
+    estimate_digits(params, digits, true)
+}
+
+/// Estimate the costs of HashWires for `params` and a commitment to `value`. Unlike
+/// [`estimate`], the hash chains and the proofs only cover the digits of `value`, and proofs
+/// only carry a PLR padding if `value` has fewer digits than the maximum.
+pub fn estimate_for_value(params: &HwParams, value: &BigUint) -> Result<Estimate, HwError> {
+    let digits = params.canonical_digits(value)?.len();
+    Ok(estimate_digits(
+        params,
+        digits,
+        digits < params.max_mdp_size()?,
+    ))
+}
+
+/// Suggest the base for committing to `value` with values of up to `max_number_bits` bits,
+/// optimizing for `target` (see [`crate::hashwires::Secret::commit_adaptive`]). Bases whose
+/// digits do not divide `max_number_bits` are skipped.
+pub fn recommend_for_value(
+    value: &BigUint,
+    max_number_bits: usize,
+    target: ProofSizeOrSpeed,
+) -> Result<Estimate, HwError> {
+    let mut estimates = Vec::with_capacity(SUPPORTED_BASES.len());
+    for base in SUPPORTED_BASES.iter() {
+        let params = HwParams::new(*base, max_number_bits);
+        if params.validate().is_ok() {
+            estimates.push(estimate_for_value(&params, value)?);
+        }
+    }
+    match target {
+        ProofSizeOrSpeed::ProofSize => estimates
+            .into_iter()
+            .min_by_key(|e| (e.proof_size, e.commit_time + e.verify_time)),
+        ProofSizeOrSpeed::Speed => estimates
+            .into_iter()
+            .min_by_key(|e| (e.commit_time + e.verify_time, e.proof_size)),
+    }
+    .ok_or(HwError::ParameterError)
+}
+
+// Estimate the costs of HashWires for `params` over `digits` digits, with or without the PLR
+// padding in proofs.
+fn estimate_digits(params: &HwParams, digits: usize, padded: bool) -> Estimate {
+    let digits = digits as u64;
+    let mdp_smt_height = compute_mdp_height(params.base, params.max_number_bits) as u64;
+    let chain_hashes = digits * (params.base as u64 - 1);
+
+    let commit_hashes = chain_hashes + digits * digits + 8 * digits;
+    let verify_hashes = chain_hashes + digits + mdp_smt_height + 1;
+    // header, chain nodes, MDP salt, SMT proof and PLR padding, with their length prefixes
+    let proof_size = 4
+        + 2
+        + digits as usize * ChainNodesSize::to_usize()
+        + params.mdp_salt_size
+        + 2
+        + SMT_PROOF_HEADER_SIZE
+        + mdp_smt_height as usize * SMT_PROOF_NODE_SIZE
+        + if padded { 32 } else { 0 };
+
+    Estimate {
+        params: *params,
+        commit_time: Duration::from_nanos(commit_hashes * NANOS_PER_HASH),
+        proof_size,
+        verify_time: Duration::from_nanos(verify_hashes * NANOS_PER_HASH),
+    }
+}
+
+/// Suggest parameters for values up to `max_value`, optimizing for `target`.
+///
+/// `max_number_bits` is the smallest power of two (and at least 8) covering `max_value`, so
+/// that the number of digits is a power of two in every supported base. Bases in which values
+/// would have a single digit are skipped (see [`HwParams::validate`]), e.g. base 256 for
+/// values of up to 8 bits. Fails with [`HwError::ParameterError`] if `max_value` is longer
+/// than [`MAX_NUMBER_BITS`].
+pub fn recommend(max_value: &BigUint, target: ProofSizeOrSpeed) -> Result<Estimate, HwError> {
+    let max_number_bits = (max_value.bits() as usize).max(8).next_power_of_two();
+    let estimates = SUPPORTED_BASES
+        .iter()
+        .map(|base| HwParams::new(*base, max_number_bits))
+        .filter(|params| params.validate().is_ok())
+        .map(|params| estimate(&params));
+    match target {
+        ProofSizeOrSpeed::ProofSize => {
+            estimates.min_by_key(|e| (e.proof_size, e.commit_time + e.verify_time))
+        }
+        ProofSizeOrSpeed::Speed => {
+            estimates.min_by_key(|e| (e.commit_time + e.verify_time, e.proof_size))
+        }
+    }
+    .ok_or(HwError::ParameterError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::Secret;
+    use blake3::Hasher as Blake3;
+
+    #[test]
+    fn test_recommend() {
+        let max_value = BigUint::from(u64::MAX);
+        let small = recommend(&max_value, ProofSizeOrSpeed::ProofSize).unwrap();
+        let fast = recommend(&max_value, ProofSizeOrSpeed::Speed).unwrap();
+        assert_eq!(small.params, HwParams::new(256, 64));
+        assert_eq!(fast.params, HwParams::new(16, 64));
+        assert!(small.proof_size < fast.proof_size);
+        assert!(fast.commit_time + fast.verify_time < small.commit_time + small.verify_time);
+
+        // The advised parameters must be usable.
+        for max_value in [1u32, 200, 300, 70000].iter() {
+            let max_value = BigUint::from(*max_value);
+            for target in [ProofSizeOrSpeed::ProofSize, ProofSizeOrSpeed::Speed].iter() {
+                let params = recommend(&max_value, *target).unwrap().params;
+                assert!(max_value.bits() as usize <= params.max_number_bits);
+                params.validate().unwrap();
+                let secret = Secret::<Blake3>::gen(&[0u8; 32], &max_value);
+                let commitment = secret.commit(params.base, params.max_number_bits).unwrap();
+                let proof = secret
+                    .prove(params.base, params.max_number_bits, &max_value)
+                    .unwrap();
+                commitment.verify(&proof, &max_value).unwrap();
+            }
+        }
+
+        let too_long = BigUint::from(1u32) << MAX_NUMBER_BITS;
+        assert_eq!(too_long.bits(), 1025);
+        assert!(matches!(
+            recommend(&too_long, ProofSizeOrSpeed::Speed),
+            Err(HwError::ParameterError)
+        ));
+    }
+
+    #[test]
+    fn test_recommend_for_value() -> Result<(), HwError> {
+        let fast = |value: u64| {
+            recommend_for_value(&BigUint::from(value), 64, ProofSizeOrSpeed::Speed)
+                .map(|e| e.params.base)
+        };
+        assert_eq!(fast(5)?, 4);
+        assert_eq!(fast(1 << 63)?, 16);
+        assert!(recommend_for_value(&BigUint::from(256u32), 4, ProofSizeOrSpeed::Speed).is_err());
+
+        let secret = Secret::<Blake3>::gen(&[0u8; 32], &BigUint::from(5u32));
+        let commitment = secret.commit_adaptive(64, ProofSizeOrSpeed::Speed)?;
+        let bytes = commitment.serialize_with_base();
+        let commitment = crate::hashwires::Commitment::<Blake3>::deserialize_with_base(&bytes)?;
+        assert_eq!(commitment.base(), 4);
+        let threshold = BigUint::from(3u32);
+        commitment.verify(
+            &secret.prove(commitment.base(), 64, &threshold)?,
+            &threshold,
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_byte_order_policy() -> Result<(), HwError> {
+        assert_eq!(
+            ByteOrderPolicy::BigEndian.encode_counter(1),
+            [0, 0, 0, 0, 0, 0, 0, 1]
+        );
+        let params = HwParams::new(16, 32).with_byte_order_policy(ByteOrderPolicy::BigEndian);
+        let (deserialized, _) = HwParams::deserialize_prefix(&params.serialize())?;
+        assert_eq!(deserialized, params);
+        assert!(HwParams::deserialize_prefix(&[0u8; 15]).is_err());
+
+        let secret = Secret::<Blake3>::gen(&[0u8; 32], &BigUint::from(3413u32));
+        let threshold = BigUint::from(2999u32);
+        let (commitment, proof) = secret.commit_and_prove(&params, &threshold)?;
+        commitment.verify(&proof, &threshold)?;
+        assert_ne!(
+            commitment.serialize(),
+            secret
+                .commit_with_params(&HwParams::new(16, 32))?
+                .serialize()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_canonical_digits() -> Result<(), HwError> {
+        let digits =
+            |base: u32, value: u32| HwParams::new(base, 32).canonical_digits(&BigUint::from(value));
+        assert_eq!(digits(16, 0)?, vec![0]);
+        assert_eq!(digits(16, 256)?, vec![1, 0, 0]);
+        assert_eq!(digits(2, 5)?, vec![1, 0, 1]);
+        assert_eq!(digits(4, 402)?, vec![1, 2, 1, 0, 2]);
+        assert_eq!(digits(256, 0x0100_00ff)?, vec![1, 0, 0, 255]);
+        assert_eq!(digits(256, u32::MAX)?.len(), 4);
+        assert!(HwParams::new(16, 8)
+            .canonical_digits(&BigUint::from(256u32))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_single_digit() -> Result<(), HwError> {
+        HwParams::new(256, 16).validate()?;
+        HwParams::new(16, 8).validate()?;
+        for (base, max_number_bits) in [(256u32, 8usize), (16, 4), (4, 2), (2, 1)].iter() {
+            let params = HwParams::new(*base, *max_number_bits);
+            assert!(matches!(params.validate(), Err(HwError::ParameterError)));
+            let secret = Secret::<Blake3>::gen(&[0u8; 32], &BigUint::from(1u32));
+            assert!(secret.commit_with_params(&params).is_err());
+        }
+        Ok(())
+    }
+}
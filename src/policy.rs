@@ -0,0 +1,279 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Declarative acceptance rules for verifiers
+//!
+//! A [`Policy`] gathers the rules a verifier applies on top of the cryptographic verification
+//! of a proof: the minimum threshold it accepts, the parameters it allows, the context the
+//! proof must be presented in and the maximum age of the proof. [`verify_with_policy`] checks
+//! a [`Presentation`] against these rules before verifying the proof, and rejects it with
+//! [`HwError::ContextError`] for another context, and [`HwError::PolicyError`] for any other
+//! rule.
+//!
+//! The claimed parameters must be those of the commitment and of the proof: base, MDP tree
+//! height, chain-length policy, MDP salt size and framing. The context and the creation time
+//! are claimed by the holder, and HashWires proofs are deterministic, so a policy with a
+//! context or a maximum age only accepts presentations signed by the holder: the signature
+//! covers the transcript hash of the proof (see [`Proof::transcript_hash`]) with the context
+//! `POLICY_CONTEXT_SALT || created_at (8) || context`, and is checked against the key the
+//! verifier associates with the holder. Times are provided by the caller, in seconds.
+use num_bigint::BigUint;
+
+use crate::errors::HwError;
+use crate::hashwires::{compute_mdp_height, Commitment, Proof};
+use crate::manifest::SignatureVerifier;
+use crate::params::HwParams;
+use crate::traits::Hash;
+
+const POLICY_CONTEXT_SALT: &[u8; 32] = b"K1234567890123456789012345678901";
+
+/// The acceptance rules of a verifier. The default policy accepts any valid proof.
+#[derive(Clone, Debug, Default)]
+pub struct Policy {
+    min_threshold: BigUint,
+    // an empty list allows any parameters
+    allowed_params: Vec<HwParams>,
+    context: Option<Vec<u8>>,
+    max_age: Option<u64>,
+}
+
+impl Policy {
+    /// A policy accepting any valid proof.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only accept proofs of thresholds larger than or equal to `min_threshold`.
+    pub fn with_min_threshold(mut self, min_threshold: &BigUint) -> Self {
+        self.min_threshold = min_threshold.clone();
+        self
+    }
+
+    /// Accept proofs over commitments generated under `params`. Once some parameters are
+    /// allowed, proofs under any other parameters are rejected.
+    pub fn allow_params(mut self, params: &HwParams) -> Self {
+        self.allowed_params.push(*params);
+        self
+    }
+
+    /// Only accept proofs presented in `context` (e.g. the identifier of the relying party),
+    /// in presentations signed by the holder.
+    pub fn with_context(mut self, context: &[u8]) -> Self {
+        self.context = Some(context.to_vec());
+        self
+    }
+
+    /// Only accept proofs created at most `max_age` seconds ago, in presentations signed by
+    /// the holder.
+    pub fn with_max_age(mut self, max_age: u64) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Check a presentation against the rules of the policy at time `now`, without verifying
+    /// its proof. The signature of the holder is checked if the policy has a context or a
+    /// maximum age.
+    pub fn check<D: Hash>(
+        &self,
+        commitment: &Commitment<D>,
+        presentation: &Presentation,
+        now: u64,
+    ) -> Result<(), HwError> {
+        if let Some(context) = &self.context {
+            if context.as_slice() != presentation.context {
+                return Err(HwError::ContextError);
+            }
+        }
+        if presentation.threshold < &self.min_threshold {
+            return Err(HwError::PolicyError);
+        }
+        if let Some(max_age) = self.max_age {
+            match now.checked_sub(presentation.created_at) {
+                Some(age) if age <= max_age => (),
+                _ => return Err(HwError::PolicyError),
+            }
+        }
+        if self.context.is_some() || self.max_age.is_some() {
+            let (holder, signature) = presentation.holder.ok_or(HwError::PolicyError)?;
+            if !holder.verify(&presentation.transcript_hash(commitment)?, signature) {
+                return Err(HwError::PolicyError);
+            }
+        }
+
+        let params = presentation.params;
+        if !self.allowed_params.is_empty() && !self.allowed_params.contains(params) {
+            return Err(HwError::PolicyError);
+        }
+        // the commitment and the proof must have been generated under the claimed parameters
+        if params.validate().is_err()
+            || params.base != commitment.base()
+            || commitment.mdp_height()
+                != compute_mdp_height(params.base, params.max_number_bits) as usize
+            || params.chain_length_policy != commitment.chain_length_policy()
+            || presentation.proof.mdp_salt().len() != params.mdp_salt_size
+            || presentation.proof.framing_policy() != params.framing_policy
+            || presentation.proof.chain_nodes().len() > params.max_mdp_size()?
+        {
+            return Err(HwError::PolicyError);
+        }
+        Ok(())
+    }
+}
+
+/// A proof as presented to a verifier, along with the statement it proves and the
+/// circumstances of its presentation.
+pub struct Presentation<'a> {
+    proof: &'a Proof,
+    threshold: &'a BigUint,
+    params: &'a HwParams,
+    context: &'a [u8],
+    created_at: u64,
+    holder: Option<(&'a dyn SignatureVerifier, &'a [u8])>,
+}
+
+impl<'a> Presentation<'a> {
+    /// A presentation of `proof`, proving `threshold` over a commitment generated under
+    /// `params`, with an empty context and created at time zero.
+    pub fn new(proof: &'a Proof, threshold: &'a BigUint, params: &'a HwParams) -> Self {
+        Self {
+            proof,
+            threshold,
+            params,
+            context: &[],
+            created_at: 0,
+            holder: None,
+        }
+    }
+
+    /// The same presentation, in `context`.
+    pub fn with_context(mut self, context: &'a [u8]) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// The same presentation, created at time `created_at`.
+    pub fn with_created_at(mut self, created_at: u64) -> Self {
+        self.created_at = created_at;
+        self
+    }
+
+    /// The same presentation, with the `signature` of [`Presentation::transcript_hash`] by
+    /// the holder, checked against the key of `holder`.
+    pub fn with_holder_signature(
+        mut self,
+        holder: &'a dyn SignatureVerifier,
+        signature: &'a [u8],
+    ) -> Self {
+        self.holder = Some((holder, signature));
+        self
+    }
+
+    /// The transcript hash binding the proof, the threshold, the context and the creation time
+    /// of the presentation to `commitment`, which the holder signs.
+    pub fn transcript_hash<D: Hash>(
+        &self,
+        commitment: &Commitment<D>,
+    ) -> Result<[u8; 32], HwError> {
+        let context = [
+            &POLICY_CONTEXT_SALT[..],
+            &self.created_at.to_be_bytes(),
+            self.context,
+        ]
+        .concat();
+        self.proof
+            .transcript_hash(commitment, self.threshold, &context)
+    }
+}
+
+/// Verify a presented HashWires proof over a commitment at time `now`, if it satisfies
+/// `policy`.
+pub fn verify_with_policy<D: Hash>(
+    commitment: &Commitment<D>,
+    presentation: &Presentation,
+    policy: &Policy,
+    now: u64,
+) -> Result<(), HwError> {
+    policy.check(commitment, presentation, now)?;
+    commitment.verify(presentation.proof, presentation.threshold)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::Secret;
+    use crate::manifest::ManifestSigner;
+    use blake3::Hasher as Blake3;
+
+    // A keyed hash standing in for the holder's signature scheme.
+    struct TestKey([u8; 32]);
+
+    impl ManifestSigner for TestKey {
+        fn sign(&self, message: &[u8]) -> Vec<u8> {
+            blake3::keyed_hash(&self.0, message).as_bytes().to_vec()
+        }
+    }
+
+    impl SignatureVerifier for TestKey {
+        fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+            self.sign(message) == signature
+        }
+    }
+
+    #[test]
+    fn test_verify_with_policy() -> Result<(), HwError> {
+        let params = HwParams::new(16, 32);
+        let secret = Secret::<Blake3>::gen(&[6u8; 32], &BigUint::from(3413u32));
+        let (commitment, proof) = secret.commit_and_prove(&params, &BigUint::from(3000u32))?;
+        let threshold = BigUint::from(3000u32);
+        let policy = Policy::new()
+            .with_min_threshold(&BigUint::from(2500u32))
+            .allow_params(&params)
+            .with_context(b"rp")
+            .with_max_age(60);
+        let holder = TestKey([9u8; 32]);
+        let unsigned = Presentation::new(&proof, &threshold, &params)
+            .with_context(b"rp")
+            .with_created_at(1000);
+        let signature = holder.sign(&unsigned.transcript_hash(&commitment)?);
+        let presentation = Presentation::new(&proof, &threshold, &params)
+            .with_context(b"rp")
+            .with_created_at(1000)
+            .with_holder_signature(&holder, &signature);
+
+        verify_with_policy(&commitment, &presentation, &policy, 1060)?;
+        verify_with_policy(&commitment, &presentation, &Policy::new(), 0)?;
+        assert!(verify_with_policy(&commitment, &presentation, &policy, 1061).is_err());
+        assert!(verify_with_policy(&commitment, &presentation, &policy, 999).is_err());
+        let strict = policy.clone().with_min_threshold(&BigUint::from(3001u32));
+        assert!(verify_with_policy(&commitment, &presentation, &strict, 1000).is_err());
+        let other = Presentation::new(&proof, &threshold, &params).with_created_at(1000);
+        assert!(matches!(
+            verify_with_policy(&commitment, &other, &policy, 1000),
+            Err(HwError::ContextError)
+        ));
+
+        // the context and the creation time must be signed by the holder
+        assert!(verify_with_policy(&commitment, &unsigned, &policy, 1000).is_err());
+        let refreshed = Presentation::new(&proof, &threshold, &params)
+            .with_context(b"rp")
+            .with_created_at(2000)
+            .with_holder_signature(&holder, &signature);
+        assert!(verify_with_policy(&commitment, &refreshed, &policy, 2000).is_err());
+        let impostor = TestKey([8u8; 32]);
+        let forged = Presentation::new(&proof, &threshold, &params)
+            .with_context(b"rp")
+            .with_created_at(1000)
+            .with_holder_signature(&impostor, &signature);
+        assert!(verify_with_policy(&commitment, &forged, &policy, 1000).is_err());
+
+        // the claimed parameters must be those of the commitment
+        let claimed = HwParams::new(16, 64);
+        let claimed = Presentation::new(&proof, &threshold, &claimed);
+        assert!(verify_with_policy(&commitment, &claimed, &Policy::new(), 0).is_err());
+        let restricted = Policy::new().allow_params(&params);
+        assert!(verify_with_policy(&commitment, &claimed, &restricted, 0).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! The types and traits needed by most HashWires flows, to be imported with
+//! `use hashwires::prelude::*;`
+//!
+//! Along with the HashWires API, the prelude re-exports the `smtree` items needed to build and
+//! check MDP trees directly, so that downstream crates do not depend on `smtree` themselves and
+//! always use the version HashWires was built with.
+pub use crate::digest_id::DigestId;
+pub use crate::errors::{HwError, HwErrorCategory, HwErrorCode};
+pub use crate::hashwires::{
+    verify_any, verify_any_with_limit, verify_bytes, Commitment, LeafHandle, Proof, Secret,
+};
+pub use crate::params::{ByteOrderPolicy, ChainLengthPolicy, HwParams};
+#[cfg(feature = "digest10")]
+pub use crate::traits::Digest10;
+pub use crate::traits::{Hash, VerifyAgainstRootBytes};
+pub use crate::value::HwValue;
+
+pub use smtree::index::TreeIndex;
+pub use smtree::node_template::HashWiresNodeSmt;
+pub use smtree::pad_secret::Secret as SmtSecret;
+pub use smtree::proof::MerkleProof;
+pub use smtree::traits::{InclusionProvable, Serializable};
+pub use smtree::tree::SparseMerkleTree;
@@ -0,0 +1,11 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Preset profiles for common attestations
+//!
+//! A profile fixes the encoding of the committed values, the parameters and the context
+//! labels of a use case, so that the proofs of different integrators interoperate.
+pub mod age_over;
+pub mod kyc_tier;
@@ -0,0 +1,188 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Proofs that the holder of a credential is at least some years old
+//!
+//! The credential commits to the birthdate of its holder, encoded as
+//! `65535 - days since 1900-01-01` so that earlier birthdates are larger values, under the
+//! [`params`] of the profile (base 16, 16-bit values). Birthdates thus range from [`MIN_DATE`]
+//! to [`MAX_DATE`].
+//!
+//! Being at least `years` old on a given day means being born on or before the same day
+//! `years` years earlier, February 29 becoming February 28 in non-leap years, so the
+//! threshold of a proof is the encoding of that day. Verification requests of this profile
+//! should be bound to [`CONTEXT`], e.g. through [`policy`].
+use num_bigint::BigUint;
+
+use crate::errors::HwError;
+use crate::hashwires::{Commitment, Proof, Secret};
+use crate::params::HwParams;
+use crate::policy::Policy;
+use crate::traits::Hash;
+
+/// The context label of the verification requests of this profile.
+pub const CONTEXT: &[u8] = b"hashwires/profiles/age_over/v1";
+/// The earliest supported date, 1900-01-01.
+pub const MIN_DATE: Date = Date {
+    year: 1900,
+    month: 1,
+    day: 1,
+};
+/// The latest supported date, 2079-06-06, which is 65535 days after [`MIN_DATE`].
+pub const MAX_DATE: Date = Date {
+    year: 2079,
+    month: 6,
+    day: 6,
+};
+
+const MAX_DAY: u32 = u16::MAX as u32;
+
+/// The parameters of the credentials of this profile: base 16 over 16-bit values.
+pub fn params() -> HwParams {
+    HwParams::new(16, 16)
+}
+
+/// A day of the Gregorian calendar, between [`MIN_DATE`] and [`MAX_DATE`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    year: u16,
+    month: u8,
+    day: u8,
+}
+
+impl Date {
+    /// The date `year-month-day`, failing if it does not exist or is out of the supported
+    /// range.
+    pub fn new(year: u16, month: u8, day: u8) -> Result<Self, HwError> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return Err(HwError::ParameterError);
+        }
+        let date = Self { year, month, day };
+        if date < MIN_DATE || date > MAX_DATE {
+            return Err(HwError::ParameterError);
+        }
+        Ok(date)
+    }
+
+    // The number of days since MIN_DATE.
+    fn days(self) -> u32 {
+        let years: u32 = (MIN_DATE.year..self.year)
+            .map(|year| if is_leap(year) { 366 } else { 365 })
+            .sum();
+        let months: u32 = (1..self.month)
+            .map(|month| days_in_month(self.year, month) as u32)
+            .sum();
+        years + months + self.day as u32 - 1
+    }
+
+    // The committed value of a birthdate.
+    fn encode(self) -> BigUint {
+        BigUint::from(MAX_DAY - self.days())
+    }
+}
+
+fn is_leap(year: u16) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The threshold proving an age of at least `years` on `today`: the encoding of the latest
+/// birthdate of such holders. Fails if this birthdate precedes [`MIN_DATE`].
+pub fn threshold(years: u16, today: Date) -> Result<BigUint, HwError> {
+    let year = today
+        .year
+        .checked_sub(years)
+        .ok_or(HwError::ParameterError)?;
+    let day = today.day.min(days_in_month(year, today.month));
+    Ok(Date::new(year, today.month, day)?.encode())
+}
+
+/// Issue the credential of a holder born on `birthdate`, from a secret `seed`.
+pub fn issue_age_credential<D: Hash>(
+    seed: &[u8],
+    birthdate: Date,
+) -> Result<(Secret<D>, Commitment<D>), HwError> {
+    let secret = Secret::gen(seed, &birthdate.encode());
+    let commitment = secret.commit_with_params(&params())?;
+    Ok((secret, commitment))
+}
+
+/// Prove that the holder of `secret` is at least `years` old on `today`.
+pub fn prove_age_over<D: Hash>(
+    secret: &Secret<D>,
+    years: u16,
+    today: Date,
+) -> Result<Proof, HwError> {
+    let params = params();
+    secret.prove(
+        params.base,
+        params.max_number_bits,
+        &threshold(years, today)?,
+    )
+}
+
+/// Verify that the holder of the credential `commitment` is at least `years` old on `today`.
+pub fn verify_age_over<D: Hash>(
+    commitment: &Commitment<D>,
+    proof: &Proof,
+    years: u16,
+    today: Date,
+) -> Result<(), HwError> {
+    if commitment.base() != params().base {
+        return Err(HwError::ParameterError);
+    }
+    commitment.verify(proof, &threshold(years, today)?)
+}
+
+/// The verifier policy of this profile, accepting proofs of an age of at least `years` on
+/// `today`, under the parameters and in the context of the profile. Presentations must be
+/// signed by the holder, see [`crate::policy`].
+pub fn policy(years: u16, today: Date) -> Result<Policy, HwError> {
+    Ok(Policy::new()
+        .with_min_threshold(&threshold(years, today)?)
+        .allow_params(&params())
+        .with_context(CONTEXT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+
+    #[test]
+    fn test_age_over() -> Result<(), HwError> {
+        assert_eq!(MAX_DATE.days(), MAX_DAY);
+        assert!(Date::new(2021, 2, 29).is_err());
+        assert!(Date::new(2079, 6, 7).is_err());
+
+        let (secret, commitment) =
+            issue_age_credential::<Blake3>(&[1u8; 32], Date::new(2004, 2, 29)?)?;
+        // born on February 29, 18 years old on March 1 of a non-leap year
+        let birthday = Date::new(2022, 3, 1)?;
+        let day_before = Date::new(2022, 2, 28)?;
+        let proof = prove_age_over(&secret, 18, birthday)?;
+        verify_age_over(&commitment, &proof, 18, birthday)?;
+        assert!(prove_age_over(&secret, 18, day_before).is_err());
+        assert!(verify_age_over(&commitment, &proof, 18, day_before).is_err());
+        assert!(verify_age_over(&commitment, &proof, 21, birthday).is_err());
+        // on February 29, the cutoff of a non-leap year is February 28
+        let (secret, commitment) =
+            issue_age_credential::<Blake3>(&[2u8; 32], Date::new(2006, 2, 28)?)?;
+        let leap_day = Date::new(2024, 2, 29)?;
+        let proof = prove_age_over(&secret, 18, leap_day)?;
+        verify_age_over(&commitment, &proof, 18, leap_day)?;
+        assert!(threshold(200, birthday).is_err());
+        assert_eq!(threshold(0, MIN_DATE)?, BigUint::from(MAX_DAY));
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Proofs that a financial amount of the holder of a credential reaches some tier
+//!
+//! The credential commits to an amount (e.g. a balance or a turnover) in minor units of its
+//! currency (e.g. cents), under the [`params`] of the profile (base 16, 64-bit values). The
+//! amount reaches a [`Tier`] when it is at least the threshold of the tier, which is a fixed
+//! number of major units, 100 minor units each, so proofs of a tier are interoperable across
+//! institutions. Verification requests of this profile should be bound to the context of the
+//! [`Metric`] of the amount, e.g. through [`policy`].
+use num_bigint::BigUint;
+
+use crate::errors::HwError;
+use crate::hashwires::{Commitment, Proof, Secret};
+use crate::params::HwParams;
+use crate::policy::Policy;
+use crate::traits::Hash;
+
+const MINOR_UNITS: u64 = 100;
+
+/// The parameters of the credentials of this profile: base 16 over 64-bit values.
+pub fn params() -> HwParams {
+    HwParams::new(16, 64)
+}
+
+/// The kind of amount committed to by a credential.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    /// The balance of an account.
+    Balance,
+    /// The turnover of an account over a period.
+    Turnover,
+}
+
+impl Metric {
+    /// The context label of the verification requests of this metric.
+    pub fn context(self) -> &'static [u8] {
+        match self {
+            Metric::Balance => b"hashwires/profiles/kyc_tier/balance/v1",
+            Metric::Turnover => b"hashwires/profiles/kyc_tier/turnover/v1",
+        }
+    }
+}
+
+/// The standardized amount bands, in increasing order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Tier {
+    /// At least 1,000 major units.
+    Tier1,
+    /// At least 10,000 major units.
+    Tier2,
+    /// At least 100,000 major units.
+    Tier3,
+    /// At least 1,000,000 major units.
+    Tier4,
+}
+
+impl Tier {
+    /// All the tiers, in increasing order.
+    pub const ALL: [Tier; 4] = [Tier::Tier1, Tier::Tier2, Tier::Tier3, Tier::Tier4];
+
+    /// The smallest amount reaching the tier, in minor units.
+    pub fn threshold(self) -> u64 {
+        let major_units = match self {
+            Tier::Tier1 => 1_000,
+            Tier::Tier2 => 10_000,
+            Tier::Tier3 => 100_000,
+            Tier::Tier4 => 1_000_000,
+        };
+        major_units * MINOR_UNITS
+    }
+
+    /// The highest tier reached by `amount`, in minor units, if any.
+    pub fn for_amount(amount: u64) -> Option<Tier> {
+        Self::ALL
+            .iter()
+            .rev()
+            .find(|tier| amount >= tier.threshold())
+            .copied()
+    }
+}
+
+/// Issue the credential of an `amount` in minor units, from a secret `seed`.
+pub fn issue_kyc_credential<D: Hash>(
+    seed: &[u8],
+    amount: u64,
+) -> Result<(Secret<D>, Commitment<D>), HwError> {
+    let secret = Secret::gen(seed, &BigUint::from(amount));
+    let commitment = secret.commit_with_params(&params())?;
+    Ok((secret, commitment))
+}
+
+/// Prove that the amount of `secret` reaches `tier`.
+pub fn prove_tier<D: Hash>(secret: &Secret<D>, tier: Tier) -> Result<Proof, HwError> {
+    let params = params();
+    secret.prove(
+        params.base,
+        params.max_number_bits,
+        &BigUint::from(tier.threshold()),
+    )
+}
+
+/// Verify that the amount of the credential `commitment` reaches `tier`.
+pub fn verify_tier<D: Hash>(
+    commitment: &Commitment<D>,
+    proof: &Proof,
+    tier: Tier,
+) -> Result<(), HwError> {
+    if commitment.base() != params().base {
+        return Err(HwError::ParameterError);
+    }
+    commitment.verify(proof, &BigUint::from(tier.threshold()))
+}
+
+/// The verifier policy of this profile, accepting proofs that an amount of `metric` reaches
+/// `tier`, under the parameters and in the context of the profile. Presentations must be
+/// signed by the holder, see [`crate::policy`].
+pub fn policy(metric: Metric, tier: Tier) -> Policy {
+    Policy::new()
+        .with_min_threshold(&BigUint::from(tier.threshold()))
+        .allow_params(&params())
+        .with_context(metric.context())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::{ManifestSigner, SignatureVerifier};
+    use crate::policy::{verify_with_policy, Presentation};
+    use blake3::Hasher as Blake3;
+
+    // A keyed hash standing in for the holder's signature scheme.
+    struct TestKey([u8; 32]);
+
+    impl ManifestSigner for TestKey {
+        fn sign(&self, message: &[u8]) -> Vec<u8> {
+            blake3::keyed_hash(&self.0, message).as_bytes().to_vec()
+        }
+    }
+
+    impl SignatureVerifier for TestKey {
+        fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+            self.sign(message) == signature
+        }
+    }
+
+    #[test]
+    fn test_kyc_tier() -> Result<(), HwError> {
+        assert_eq!(Tier::for_amount(99_999), None);
+        assert_eq!(Tier::for_amount(100_000), Some(Tier::Tier1));
+        assert_eq!(Tier::for_amount(u64::MAX), Some(Tier::Tier4));
+
+        // a balance of 25,000.50
+        let (secret, commitment) = issue_kyc_credential::<Blake3>(&[3u8; 32], 2_500_050)?;
+        let proof = prove_tier(&secret, Tier::Tier2)?;
+        verify_tier(&commitment, &proof, Tier::Tier2)?;
+        assert!(verify_tier(&commitment, &proof, Tier::Tier3).is_err());
+        assert!(prove_tier(&secret, Tier::Tier3).is_err());
+
+        let threshold = BigUint::from(Tier::Tier2.threshold());
+        let params = params();
+        let holder = TestKey([4u8; 32]);
+        let signature = holder.sign(
+            &Presentation::new(&proof, &threshold, &params)
+                .with_context(Metric::Balance.context())
+                .transcript_hash(&commitment)?,
+        );
+        let presentation = Presentation::new(&proof, &threshold, &params)
+            .with_context(Metric::Balance.context())
+            .with_holder_signature(&holder, &signature);
+        verify_with_policy(
+            &commitment,
+            &presentation,
+            &policy(Metric::Balance, Tier::Tier1),
+            0,
+        )?;
+        assert!(matches!(
+            verify_with_policy(
+                &commitment,
+                &presentation,
+                &policy(Metric::Turnover, Tier::Tier2),
+                0
+            ),
+            Err(HwError::ContextError)
+        ));
+        assert!(verify_with_policy(
+            &commitment,
+            &presentation,
+            &policy(Metric::Balance, Tier::Tier3),
+            0
+        )
+        .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,805 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! A reusable HashWires prover, which keeps its intermediate buffers across proofs
+//!
+//! The state a prover holds for a secret can be exported with [`HwProver::export_state`], so
+//! that a wallet persists it across restarts instead of recomputing the hash chains and the
+//! MDP tree at every launch. The exported state holds the secret, and is encrypted and
+//! authenticated under a 32-byte key of the wallet, with a fresh 32-byte nonce:
+//!
+//! ```text
+//! keystream block i = H(STATE_KEYSTREAM_SALT || key || nonce || i (8))
+//! tag = H(STATE_MAC_SALT || key || nonce || len(ciphertext) (8) || ciphertext)
+//! envelope = nonce (32) || plaintext XOR keystream || tag (32)
+//! ```
+//!
+//! The hash chains are kept as tables of one node out of a stride, set with
+//! [`HwProver::with_chain_table_stride`]: every other node is recomputed from the closest
+//! stored node below it, with fewer hashes than the stride. The default stride of 1 keeps every
+//! node; larger strides trade hashing for memory and exported state size in bases 16 and 256,
+//! whose chains have 16 and 256 nodes (e.g. a stride of 16 in base 256 stores 16 nodes per
+//! chain, for at most 15 hashes per node).
+//!
+//! The plaintext is laid out as follows (all integers are big-endian), the MDP digits being
+//! recomputed from the value on import:
+//!
+//! ```text
+//! params (16) || I2OSP(len, 1) || seed || I2OSP(len, 2) || value || stride (2)
+//! || #chains (2) || for each chain: #stored nodes (2) || stored nodes (32 each)
+//! || #MDP elements (2) || for each MDP element: I2OSP(len, 1) || salt || salted root (32)
+//!     || leaf position (2) || I2OSP(len, 2) || inclusion proof, empty if not generated yet
+//! || I2OSP(len, 1) || MDP tree root, empty if not generated yet
+//! ```
+use std::convert::TryFrom;
+
+use digest::Digest;
+use generic_array::GenericArray;
+use num_bigint::BigUint;
+use rand_core::{CryptoRng, RngCore};
+
+use crate::digest_id::DigestId;
+use crate::dp::{find_mdp_with_splits_into, value_split_per_base};
+use crate::errors::HwError;
+use crate::hashes::{generate_subseeds, HashContext, LEAF_SALT, SMTREE_PADDING_SALT, TOP_SALT};
+use crate::hashwires::{
+    compute_bitlength, compute_mdp_height, final_smt_root, final_smt_root_and_proof, leaf_handles,
+    pick_mdp_index, ChainNodesSize, Commitment, LeafHandle, PlrPaddingSize, Proof, Secret,
+    SmtSecretSize,
+};
+use crate::params::{ChainLengthPolicy, HwParams};
+use crate::serialization::{i2osp, os2ip, serialize, take_slice, tokenize_slice};
+use crate::shuffle::deterministic_index_shuffling;
+use crate::traits::Hash;
+use smtree::pad_secret::Secret as SmtSecret;
+
+const STATE_KEYSTREAM_SALT: &[u8; 32] = b"A1234567890123456789012345678901";
+const STATE_MAC_SALT: &[u8; 32] = b"B1234567890123456789012345678901";
+
+/// HashWires prover holding a preallocated workspace.
+///
+/// Proving through the same `HwProver` reuses the buffers holding the MDP digits, the hash
+/// chains, the wires and the top salted roots, instead of allocating them for every proof.
+/// Proving again for the same secret and parameters also reuses their contents, skipping
+/// Steps 1 to 8 of the commitment, as well as the MDP tree root and the inclusion proofs already
+/// generated: thresholds picking the same MDP element share its inclusion proof, which is the
+/// dominant cost of a proof after the hash chains in base 256.
+pub struct HwProver<D: Hash> {
+    // MDP digits, flattened; split `i` is `digits[split_offsets[i]..split_offsets[i + 1]]`.
+    digits: Vec<u8>,
+    split_offsets: Vec<usize>,
+    // Hash chains, flattened, keeping one node out of `table_stride`; chain `i` starts at
+    // `chains[chain_offsets[i]]`.
+    chains: Vec<[u8; 32]>,
+    chain_offsets: Vec<usize>,
+    table_stride: u16,
+    wire: Vec<[u8; 32]>,
+    plr_roots: Vec<GenericArray<u8, PlrPaddingSize>>,
+    top_salted_roots: Vec<[u8; 32]>,
+    mdp: Vec<BigUint>,
+    salts: Vec<Vec<u8>>,
+    leaves: Vec<LeafHandle>,
+    // The MDP tree root and the inclusion proofs generated so far, per MDP index.
+    smt_root: Option<Vec<u8>>,
+    inclusion_proofs: Vec<Option<Vec<u8>>>,
+    // The secret and parameters the workspace currently holds Steps 0 to 8 for.
+    state: Option<StateKey>,
+    ctx: HashContext<D>,
+}
+
+#[derive(PartialEq)]
+struct StateKey {
+    seed: Vec<u8>,
+    value: BigUint,
+    params: HwParams,
+}
+
+impl<D: Hash> Default for HwProver<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D: Hash> HwProver<D> {
+    /// Create a prover with an empty workspace.
+    pub fn new() -> Self {
+        Self {
+            digits: Vec::new(),
+            split_offsets: Vec::new(),
+            chains: Vec::new(),
+            chain_offsets: Vec::new(),
+            table_stride: 1,
+            wire: Vec::new(),
+            plr_roots: Vec::new(),
+            top_salted_roots: Vec::new(),
+            mdp: Vec::new(),
+            salts: Vec::new(),
+            leaves: Vec::new(),
+            smt_root: None,
+            inclusion_proofs: Vec::new(),
+            state: None,
+            ctx: HashContext::new(),
+        }
+    }
+
+    /// Only keep one node out of `stride` of the hash chains, see the
+    /// [module documentation](self). A stride of 0 is taken as 1, which keeps every node.
+    /// Changing the stride discards the state held so far.
+    pub fn with_chain_table_stride(mut self, stride: u16) -> Self {
+        let stride = stride.max(1);
+        if stride != self.table_stride {
+            self.table_stride = stride;
+            self.state = None;
+        }
+        self
+    }
+
+    /// The stride of the tables of the hash chains.
+    pub fn chain_table_stride(&self) -> u16 {
+        self.table_stride
+    }
+
+    /// Generate HashWires proof, reusing this prover's workspace.
+    pub fn prove(
+        &mut self,
+        secret: &Secret<D>,
+        base: u32,
+        max_number_bits: usize,
+        threshold: &BigUint,
+    ) -> Result<Proof, HwError> {
+        let params = HwParams::new(base, max_number_bits);
+        Ok(self.commit_and_prove(secret, &params, threshold)?.1)
+    }
+
+    /// Generate a HashWires commitment along with a proof over it, reusing this prover's
+    /// workspace. The hash chains, PLR roots and MDP tree are only computed once.
+    pub fn commit_and_prove(
+        &mut self,
+        secret: &Secret<D>,
+        params: &HwParams,
+        threshold: &BigUint,
+    ) -> Result<(Commitment<D>, Proof), HwError> {
+        params.validate()?;
+        let mdp_smt_height = compute_mdp_height(params.base, params.max_number_bits);
+        let result = self.proof_gen(
+            threshold,
+            &secret.value,
+            &secret.seed,
+            params,
+            mdp_smt_height as usize,
+        )?;
+        let proof = Proof {
+            digest_id: DigestId::of::<D>(),
+            plr_padding: result.1,
+            chain_nodes: result.2,
+            mdp_salt: result.3,
+            smt_inclusion_proof: result.4,
+            framing: params.framing_policy,
+        };
+        let commitment = Commitment::from_root(&result.0, params.base)
+            .with_chain_length_policy(params.chain_length_policy)
+            .with_mdp_height(mdp_smt_height)
+            .with_leaves(self.leaves.clone());
+        Ok((commitment, proof))
+    }
+
+    /// Generate a HashWires commitment, reusing this prover's workspace. A subsequent proof for
+    /// the same secret and parameters skips Steps 1 to 8.
+    pub fn commit(
+        &mut self,
+        secret: &Secret<D>,
+        params: &HwParams,
+    ) -> Result<Commitment<D>, HwError> {
+        params.validate()?;
+        self.ensure_prepared(&secret.value, &secret.seed, params)?;
+        let mdp_smt_height = compute_mdp_height(params.base, params.max_number_bits);
+        if self.smt_root.is_none() {
+            let smt_secret =
+                generate_subseeds::<D, SmtSecretSize>(SMTREE_PADDING_SALT, &secret.seed, 1);
+            self.smt_root = Some(final_smt_root::<D>(
+                &self.top_salted_roots,
+                &self.leaves,
+                mdp_smt_height as usize,
+                &SmtSecret::from_bytes(&smt_secret[0]).unwrap(),
+            )?);
+        }
+        let root = self.smt_root.as_ref().unwrap();
+        Ok(Commitment::from_root(root, params.base)
+            .with_chain_length_policy(params.chain_length_policy)
+            .with_mdp_height(mdp_smt_height)
+            .with_leaves(self.leaves.clone()))
+    }
+
+    /// Export the state held for the last secret and parameters, encrypted and authenticated
+    /// under `key` with a nonce sampled from `rng`, see the [module documentation](self).
+    /// Fails with [`HwError::ParameterError`] if the prover holds no state.
+    pub fn export_state<R: RngCore + CryptoRng>(
+        &self,
+        key: &[u8; 32],
+        rng: &mut R,
+    ) -> Result<Vec<u8>, HwError> {
+        let state = self.state.as_ref().ok_or(HwError::ParameterError)?;
+        let mut plaintext = [
+            &state.params.serialize()[..],
+            &serialize(&state.seed, 1)?,
+            &serialize(&state.value.to_bytes_be(), 2)?,
+            &i2osp(self.table_stride as usize, 2)?,
+            &i2osp(self.chain_offsets.len(), 2)?,
+        ]
+        .concat();
+        for (i, start) in self.chain_offsets.iter().enumerate() {
+            let end = match self.chain_offsets.get(i + 1) {
+                Some(end) => *end,
+                None => self.chains.len(),
+            };
+            plaintext.extend_from_slice(&i2osp(end - start, 2)?);
+            for node in self.chains[*start..end].iter() {
+                plaintext.extend_from_slice(node);
+            }
+        }
+        plaintext.extend_from_slice(&i2osp(self.mdp.len(), 2)?);
+        for i in 0..self.mdp.len() {
+            let inclusion_proof = self.inclusion_proofs[i].as_deref().unwrap_or(&[]);
+            plaintext.extend_from_slice(&serialize(&self.salts[i], 1)?);
+            plaintext.extend_from_slice(&self.top_salted_roots[i]);
+            plaintext.extend_from_slice(&i2osp(self.leaves[i].position(), 2)?);
+            plaintext.extend_from_slice(&serialize(inclusion_proof, 2)?);
+        }
+        plaintext.extend_from_slice(&serialize(self.smt_root.as_deref().unwrap_or(&[]), 1)?);
+
+        let mut nonce = [0u8; 32];
+        rng.fill_bytes(&mut nonce);
+        apply_keystream::<D>(key, &nonce, &mut plaintext);
+        let tag = state_tag::<D>(key, &nonce, &plaintext);
+        Ok([&nonce[..], &plaintext, &tag].concat())
+    }
+
+    /// Create a prover from a state exported with [`HwProver::export_state`] under `key`.
+    /// Fails with [`HwError::SerializationError`] if the envelope was not sealed under `key`,
+    /// was altered, or does not hold a consistent state.
+    pub fn import_state(envelope: &[u8], key: &[u8; 32]) -> Result<Self, HwError> {
+        let (nonce, remainder) = take_slice(envelope, 32)?;
+        let tag_offset = remainder
+            .len()
+            .checked_sub(32)
+            .ok_or(HwError::SerializationError)?;
+        let (ciphertext, tag) = remainder.split_at(tag_offset);
+        let expected = state_tag::<D>(key, nonce, ciphertext);
+        if tag
+            .iter()
+            .zip(expected.iter())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            != 0
+        {
+            return Err(HwError::SerializationError);
+        }
+        let mut plaintext = ciphertext.to_vec();
+        apply_keystream::<D>(key, nonce, &mut plaintext);
+
+        let (params, remainder) = HwParams::deserialize_prefix(&plaintext)?;
+        params.validate()?;
+        let (seed, remainder) = tokenize_slice(remainder, 1)?;
+        let (value, remainder) = tokenize_slice(remainder, 2)?;
+        if seed.len() != 32 {
+            return Err(HwError::SerializationError);
+        }
+        let value = BigUint::from_bytes_be(value);
+        let (stride, remainder) = take_slice(remainder, 2)?;
+        let stride = os2ip(stride)?;
+        if stride == 0 {
+            return Err(HwError::SerializationError);
+        }
+        let mut prover = Self::new();
+        prover.table_stride = stride as u16;
+        prover.ctx.set_byte_order(params.byte_order_policy);
+        prover.ctx.set_framing(params.framing_policy);
+        prover.mdp = find_mdp_with_splits_into(
+            &value,
+            params.base,
+            &mut prover.digits,
+            &mut prover.split_offsets,
+        );
+
+        // the tables of the hash chains, of the lengths computed in Step 3
+        let (count, mut remainder) = take_slice(remainder, 2)?;
+        if os2ip(count)? != prover.split_offsets[1] {
+            return Err(HwError::SerializationError);
+        }
+        for i in 0..prover.split_offsets[1] {
+            let (size, rest) = take_slice(remainder, 2)?;
+            let expected = match i {
+                0 => params
+                    .chain_length_policy
+                    .first_chain_length(params.base, prover.digits[0]),
+                _ => params.base as usize,
+            };
+            let expected = (expected + stride - 1) / stride;
+            if os2ip(size)? != expected {
+                return Err(HwError::SerializationError);
+            }
+            let (nodes, rest) = take_slice(rest, expected * 32)?;
+            prover.chain_offsets.push(prover.chains.len());
+            for node in nodes.chunks_exact(32) {
+                prover.chains.push(<[u8; 32]>::try_from(node).unwrap());
+            }
+            remainder = rest;
+        }
+
+        // the salted roots, leaf positions and inclusion proofs of the MDP elements
+        let (count, mut remainder) = take_slice(remainder, 2)?;
+        if os2ip(count)? != prover.mdp.len() {
+            return Err(HwError::SerializationError);
+        }
+        let positions_count = params.max_number_bits / compute_bitlength(params.base);
+        let mut positions = Vec::with_capacity(prover.mdp.len());
+        for _ in 0..prover.mdp.len() {
+            let (salt, rest) = tokenize_slice(remainder, 1)?;
+            let (root, rest) = take_slice(rest, 32)?;
+            let (position, rest) = take_slice(rest, 2)?;
+            let (inclusion_proof, rest) = tokenize_slice(rest, 2)?;
+            let position = os2ip(position)?;
+            if salt.len() != params.mdp_salt_size
+                || position >= positions_count
+                || positions.contains(&position)
+            {
+                return Err(HwError::SerializationError);
+            }
+            prover.salts.push(salt.to_vec());
+            prover
+                .top_salted_roots
+                .push(<[u8; 32]>::try_from(root).unwrap());
+            positions.push(position);
+            prover
+                .inclusion_proofs
+                .push(Some(inclusion_proof.to_vec()).filter(|p| !p.is_empty()));
+            remainder = rest;
+        }
+        prover.leaves = leaf_handles(positions);
+        let (smt_root, remainder) = tokenize_slice(remainder, 1)?;
+        if !remainder.is_empty() {
+            return Err(HwError::SerializationError);
+        }
+        prover.smt_root = Some(smt_root.to_vec()).filter(|root| !root.is_empty());
+        prover.state = Some(StateKey {
+            seed: seed.to_vec(),
+            value,
+            params,
+        });
+        Ok(prover)
+    }
+
+    /// Generate larger than proof, see [`crate::hashwires::larger_than_proof_gen`].
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn proof_gen(
+        &mut self,
+        proving_value: &BigUint,
+        value: &BigUint,
+        seed: &[u8],
+        params: &HwParams,
+        mdp_smt_height: usize,
+    ) -> Result<
+        (
+            Vec<u8>,
+            Option<GenericArray<u8, PlrPaddingSize>>,
+            Vec<GenericArray<u8, ChainNodesSize>>,
+            Vec<u8>,
+            Vec<u8>,
+        ),
+        HwError,
+    > {
+        self.ensure_prepared(value, seed, params)?;
+        let bitlength = compute_bitlength(params.base);
+
+        // Step A: pick mdp index
+        let mdp_index = pick_mdp_index(proving_value, &self.mdp)?;
+
+        // Step B: split proving value per base (bitlength digits)
+        let proving_value_split = value_split_per_base(proving_value, bitlength);
+
+        // PLR proof of the picked MDP element, for the length of the proving value
+        self.load_wire(mdp_index);
+        let plr_proof = self
+            .ctx
+            .plr_accumulator(
+                seed,
+                &self.wire,
+                params.max_number_bits / bitlength,
+                proving_value_split.len(),
+            )
+            .1;
+
+        // Step 9: Compute final root (HW commitment), unless the inclusion proof of the picked
+        // MDP element was already generated
+        if self.inclusion_proofs[mdp_index].is_none() {
+            let smt_secret = generate_subseeds::<D, SmtSecretSize>(SMTREE_PADDING_SALT, seed, 1);
+            let (root, inclusion_proof) = final_smt_root_and_proof::<D>(
+                &self.top_salted_roots,
+                &self.leaves,
+                mdp_smt_height,
+                &self.leaves[mdp_index],
+                &SmtSecret::from_bytes(&smt_secret[0]).unwrap(),
+            )?;
+            self.smt_root = Some(root);
+            self.inclusion_proofs[mdp_index] = Some(inclusion_proof);
+        }
+
+        // Step C: pick hashchain nodes for the proving value
+        let chain_nodes = self.chain_nodes(&proving_value_split, mdp_index);
+
+        Ok((
+            self.smt_root.clone().unwrap(),
+            plr_proof,
+            chain_nodes,
+            self.salts[mdp_index].clone(),
+            self.inclusion_proofs[mdp_index].clone().unwrap(),
+        ))
+    }
+
+    // Steps 0 to 8, unless already done for this secret and these parameters.
+    fn ensure_prepared(
+        &mut self,
+        value: &BigUint,
+        seed: &[u8],
+        params: &HwParams,
+    ) -> Result<(), HwError> {
+        let key = StateKey {
+            seed: seed.to_vec(),
+            value: value.clone(),
+            params: *params,
+        };
+        if self.state.as_ref() != Some(&key) {
+            self.state = None;
+            self.prepare(value, seed, params)?;
+            self.smt_root = None;
+            self.inclusion_proofs.clear();
+            self.inclusion_proofs.resize(self.mdp.len(), None);
+            self.state = Some(key);
+        }
+        Ok(())
+    }
+
+    // Steps 0 to 8, which only depend on the secret and the parameters.
+    fn prepare(&mut self, value: &BigUint, seed: &[u8], params: &HwParams) -> Result<(), HwError> {
+        let (base, max_number_bits) = (params.base, params.max_number_bits);
+        // Step 0: compute base's bitlength
+        let bitlength = compute_bitlength(base);
+        self.ctx.set_byte_order(params.byte_order_policy);
+        self.ctx.set_framing(params.framing_policy);
+
+        // Steps 1 and 2: find MDP, along with its values split per base (bitlength digits)
+        self.mdp =
+            find_mdp_with_splits_into(value, base, &mut self.digits, &mut self.split_offsets);
+
+        // Step 3: compute required hashchains
+        self.compute_chains(seed, base, params.chain_length_policy);
+
+        // Steps 4 and 5: MDP to hashchain(s) position wiring and PLR roots per MDP
+        self.plr_roots.clear();
+        for i in 0..self.mdp.len() {
+            self.load_wire(i);
+            let (root, _) = self.ctx.plr_accumulator(
+                seed,
+                &self.wire,
+                max_number_bits / bitlength,
+                self.wire.len(),
+            );
+            self.plr_roots.push(root);
+        }
+
+        // Step 6: compute top salts
+        self.salts = self
+            .ctx
+            .generate_salts(TOP_SALT, seed, self.mdp.len(), params.mdp_salt_size);
+
+        // Step 7: KDF smt roots
+        self.top_salted_roots.clear();
+        for (root, salt) in self.plr_roots.iter().zip(self.salts.iter()) {
+            self.top_salted_roots.push(self.ctx.salted_hash(salt, root));
+        }
+
+        // Step 8: get shuffled indexes
+        self.leaves = leaf_handles(deterministic_index_shuffling(
+            self.mdp.len(),
+            max_number_bits / bitlength,
+            <[u8; 32]>::try_from(seed).map_err(|_| HwError::SeedLengthError)?,
+        )?);
+        Ok(())
+    }
+
+    // The length of the first chain follows the chain-length policy.
+    fn compute_chains(&mut self, seed: &[u8], base: u32, policy: ChainLengthPolicy) {
+        let chains_count = self.split_offsets[1];
+        let seeds = self
+            .ctx
+            .generate_subseeds::<D::OutputSize>(LEAF_SALT, seed, chains_count);
+        self.chains.clear();
+        self.chain_offsets.clear();
+        let stride = self.table_stride as usize;
+        let mut chain = Vec::with_capacity(base as usize);
+        for (i, chain_seed) in seeds.iter().enumerate() {
+            let size = match i {
+                0 => policy.first_chain_length(base, self.digits[0]),
+                _ => base as usize,
+            };
+            self.chain_offsets.push(self.chains.len());
+            if stride == 1 {
+                self.ctx
+                    .full_hash_chain_into(chain_seed, size, &mut self.chains);
+            } else {
+                chain.clear();
+                self.ctx.full_hash_chain_into(chain_seed, size, &mut chain);
+                self.chains.extend(chain.iter().step_by(stride));
+            }
+        }
+    }
+
+    // Node `index` of chain `chain`, recomputed from the closest stored node below it.
+    fn chain_node(&mut self, chain: usize, index: usize) -> [u8; 32] {
+        let stride = self.table_stride as usize;
+        let stored = self.chains[self.chain_offsets[chain] + index / stride];
+        self.ctx.hash_chain(&stored, index % stride)
+    }
+
+    // Shorter MDP values are wired to the least significant chains.
+    fn load_wire(&mut self, mdp_index: usize) {
+        let (start, end) = (
+            self.split_offsets[mdp_index],
+            self.split_offsets[mdp_index + 1],
+        );
+        let offset = self.chain_offsets.len() - (end - start);
+        self.wire.clear();
+        for i in 0..end - start {
+            let node = self.chain_node(i + offset, self.digits[start + i] as usize);
+            self.wire.push(node);
+        }
+    }
+
+    fn chain_nodes(
+        &mut self,
+        proving_value_split: &[u8],
+        mdp_index: usize,
+    ) -> Vec<GenericArray<u8, ChainNodesSize>> {
+        let split_end = self.split_offsets[mdp_index + 1];
+        let chains_count = self.chain_offsets.len();
+        let count = proving_value_split.len();
+        proving_value_split
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let digit = self.digits[split_end - count + i];
+                let node = self.chain_node(i + chains_count - count, (digit - *s) as usize);
+                GenericArray::clone_from_slice(&node[..])
+            })
+            .collect()
+    }
+}
+
+// XOR `data` with the keystream of the prover state envelope.
+fn apply_keystream<D: Hash>(key: &[u8; 32], nonce: &[u8], data: &mut [u8]) {
+    for (i, block) in data.chunks_mut(32).enumerate() {
+        let mut hasher = D::new();
+        hasher.update(STATE_KEYSTREAM_SALT);
+        hasher.update(key);
+        hasher.update(nonce);
+        hasher.update((i as u64).to_be_bytes());
+        for (byte, k) in block.iter_mut().zip(hasher.finalize()) {
+            *byte ^= k;
+        }
+    }
+}
+
+// The authentication tag of the prover state envelope.
+fn state_tag<D: Hash>(key: &[u8; 32], nonce: &[u8], ciphertext: &[u8]) -> [u8; 32] {
+    let mut hasher = D::new();
+    hasher.update(STATE_MAC_SALT);
+    hasher.update(key);
+    hasher.update(nonce);
+    hasher.update((ciphertext.len() as u64).to_be_bytes());
+    hasher.update(ciphertext);
+    let mut output = [0u8; 32];
+    output.copy_from_slice(&hasher.finalize()[..32]);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::commit_gen;
+    use blake3::Hasher as Blake3;
+    use rand_core::OsRng;
+
+    #[test]
+    fn test_prover_reuse() -> Result<(), HwError> {
+        let mut prover = HwProver::<Blake3>::new();
+        let values = [
+            (4, 32, 402u32, 378u32),
+            (16, 32, 3413, 2999),
+            (256, 64, 9999, 1),
+        ];
+        for (base, max_number_bits, value, threshold) in values.iter() {
+            let secret = Secret::<Blake3>::gen(&[5u8; 32], &BigUint::from(*value));
+            let commitment = secret.commit(*base, *max_number_bits)?;
+            let threshold = BigUint::from(*threshold);
+            let proof = prover.prove(&secret, *base, *max_number_bits, &threshold)?;
+            commitment.verify(&proof, &threshold)?;
+            assert_eq!(
+                proof.serialize(),
+                HwProver::new()
+                    .prove(&secret, *base, *max_number_bits, &threshold)?
+                    .serialize()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_and_prove() -> Result<(), HwError> {
+        let secret = Secret::<Blake3>::gen(&[7u8; 32], &BigUint::from(3413u32));
+        let threshold = BigUint::from(2999u32);
+        let mut prover = HwProver::new();
+        for base in [4u32, 16, 256].iter() {
+            let params = HwParams::new(*base, 32);
+            let (commitment, proof) = prover.commit_and_prove(&secret, &params, &threshold)?;
+            commitment.verify(&proof, &threshold)?;
+            assert_eq!(
+                commitment.serialize(),
+                secret.commit(*base, 32)?.serialize()
+            );
+            assert_eq!(
+                proof.serialize(),
+                secret.prove(*base, 32, &threshold)?.serialize()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_proof_gen_commitment() -> Result<(), HwError> {
+        let value = BigUint::from(3413u32);
+        let seed = [0u8; 32];
+        let result = HwProver::<Blake3>::new().proof_gen(
+            &BigUint::from(10u32),
+            &value,
+            &seed,
+            &HwParams::new(16, 32),
+            3,
+        )?;
+        assert_eq!(result.0, commit_gen::<Blake3>(&value, 16, &seed, 32, 3)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cached_inclusion_proofs() -> Result<(), HwError> {
+        let secret = Secret::<Blake3>::gen(&[8u8; 32], &BigUint::from(3413u32));
+        let params = HwParams::new(256, 32);
+        let mut prover = HwProver::new();
+        let commitment = prover.commit(&secret, &params)?;
+        // 2999 and 3000 both pick the MDP element 3327, 3413 picks 3413
+        for threshold in [2999u32, 3000, 3413].iter() {
+            let threshold = BigUint::from(*threshold);
+            let proof = prover.commit_and_prove(&secret, &params, &threshold)?.1;
+            commitment.verify(&proof, &threshold)?;
+            assert_eq!(
+                proof.serialize(),
+                secret.prove(256, 32, &threshold)?.serialize()
+            );
+        }
+        let cached = prover.inclusion_proofs.iter().filter(|p| p.is_some());
+        assert_eq!(cached.count(), 2);
+
+        // another secret starts over
+        let other = Secret::<Blake3>::gen(&[8u8; 32], &BigUint::from(3414u32));
+        prover.commit(&other, &params)?;
+        assert!(prover.inclusion_proofs.iter().all(Option::is_none));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cached_state() -> Result<(), HwError> {
+        let value = BigUint::from(3413u32);
+        let secret = Secret::<Blake3>::gen(&[6u8; 32], &value);
+        let cached = Secret::<Blake3>::gen(&[6u8; 32], &value).with_cached_state();
+        for base in [16u32, 256, 16].iter() {
+            let commitment = cached.commit(*base, 32)?;
+            assert_eq!(
+                commitment.serialize(),
+                secret.commit(*base, 32)?.serialize()
+            );
+            for threshold in [1u32, 15, 2999, 3413].iter() {
+                let threshold = BigUint::from(*threshold);
+                let proof = cached.prove(*base, 32, &threshold)?;
+                assert_eq!(
+                    proof.serialize(),
+                    secret.prove(*base, 32, &threshold)?.serialize()
+                );
+                commitment.verify(&proof, &threshold)?;
+            }
+        }
+        assert!(cached.prove(16, 32, &BigUint::from(3414u32)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_state() -> Result<(), HwError> {
+        let secret = Secret::<Blake3>::gen(&[9u8; 32], &BigUint::from(3413u32));
+        let params = HwParams::new(16, 32);
+        let key = [1u8; 32];
+        let mut prover = HwProver::new();
+        assert!(prover.export_state(&key, &mut OsRng).is_err());
+        let commitment = prover.commit(&secret, &params)?;
+        prover.commit_and_prove(&secret, &params, &BigUint::from(2999u32))?;
+        let envelope = prover.export_state(&key, &mut OsRng)?;
+
+        let mut restored = HwProver::<Blake3>::import_state(&envelope, &key)?;
+        assert_eq!(restored.inclusion_proofs, prover.inclusion_proofs);
+        assert_eq!(
+            restored.export_state(&key, &mut OsRng)?.len(),
+            envelope.len()
+        );
+        for threshold in [1u32, 2999, 3413].iter() {
+            let threshold = BigUint::from(*threshold);
+            let (restored_commitment, proof) =
+                restored.commit_and_prove(&secret, &params, &threshold)?;
+            assert_eq!(restored_commitment.serialize(), commitment.serialize());
+            assert_eq!(
+                proof.serialize(),
+                secret.prove(16, 32, &threshold)?.serialize()
+            );
+        }
+
+        // another key, or any altered byte, is rejected
+        assert!(HwProver::<Blake3>::import_state(&envelope, &[2u8; 32]).is_err());
+        for i in [0, 40, envelope.len() - 1].iter() {
+            let mut altered = envelope.clone();
+            altered[*i] ^= 1;
+            assert!(HwProver::<Blake3>::import_state(&altered, &key).is_err());
+        }
+        assert!(HwProver::<Blake3>::import_state(&envelope[..envelope.len() - 1], &key).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_chain_table_stride() -> Result<(), HwError> {
+        let secret = Secret::<Blake3>::gen(&[10u8; 32], &BigUint::from(3413u32));
+        let key = [1u8; 32];
+        for base in [16u32, 256].iter() {
+            let params = HwParams::new(*base, 32);
+            let mut envelope_sizes = Vec::new();
+            for stride in [0u16, 4, 16].iter() {
+                let mut prover = HwProver::<Blake3>::new().with_chain_table_stride(*stride);
+                assert_eq!(prover.chain_table_stride(), (*stride).max(1));
+                let commitment = prover.commit(&secret, &params)?;
+                assert_eq!(
+                    commitment.serialize(),
+                    secret.commit(*base, 32)?.serialize()
+                );
+                for threshold in [1u32, 2999, 3413].iter() {
+                    let threshold = BigUint::from(*threshold);
+                    let proof = prover.commit_and_prove(&secret, &params, &threshold)?.1;
+                    assert_eq!(
+                        proof.serialize(),
+                        secret.prove(*base, 32, &threshold)?.serialize()
+                    );
+                }
+
+                // the imported state keeps its stride
+                let envelope = prover.export_state(&key, &mut OsRng)?;
+                let mut restored = HwProver::<Blake3>::import_state(&envelope, &key)?;
+                assert_eq!(restored.chain_table_stride(), prover.chain_table_stride());
+                let threshold = BigUint::from(2999u32);
+                assert_eq!(
+                    restored
+                        .commit_and_prove(&secret, &params, &threshold)?
+                        .1
+                        .serialize(),
+                    secret.prove(*base, 32, &threshold)?.serialize()
+                );
+                envelope_sizes.push(envelope.len());
+            }
+            assert!(envelope_sizes.windows(2).all(|w| w[1] < w[0]));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,744 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! A registry of issued commitments, with membership proofs
+//!
+//! An issuer inserts each commitment it issues into a [`CommitmentSet`], a sparse Merkle tree
+//! indexed by credential identifier, and publishes the root. A holder then presents a
+//! [`MembershipProof`], combining the range proof with an inclusion proof of its commitment,
+//! which [`verify_with_membership`] checks against the published root.
+//!
+//! A holder presenting several attributes at once, each committed under its own identifier,
+//! presents a [`BatchMembershipProof`] instead: a single inclusion multiproof covers all of the
+//! commitments, sharing the nodes of their paths, which [`verify_batch_membership`] checks.
+//!
+//! Attributes may be inserted under string labels instead of identifiers, e.g. `"balance"`,
+//! so that adding attributes to a schema does not shift the positions of the existing ones.
+//! The identifier of a label is derived from its hash, truncated to the height of the set:
+//!
+//! ```text
+//! id = the `height` most significant bits of H(LABEL_SALT || label)
+//! ```
+//!
+//! [`CommitmentSet::insert_labeled`] rejects a label whose identifier is already taken by
+//! another attribute with [`HwError::LabelCollisionError`], and [`verify_labeled_membership`]
+//! checks that a membership proof is for the identifier of the expected label.
+//!
+//! The padding nodes of the sparse Merkle tree cannot be told apart from its leaves, so it
+//! cannot show that an identifier is absent. The set also maintains a Merkle tree over its
+//! sorted identifiers, whose [`CommitmentSet::identifier_root`] the issuer publishes along with
+//! the root. An [`AbsenceProof`] then shows that the identifiers adjacent in this tree enclose
+//! the absent one, which [`verify_absence`] checks, e.g. for an authority to prove that no
+//! sanction entry exists for a person. The identifier root is
+//!
+//! ```text
+//! H(IDENTIFIERS_SALT || #identifiers (8) || root of the tree over H(ID_LEAF_SALT || id))
+//! ```
+//!
+//! A serialized membership proof is laid out as follows (all integers are big-endian):
+//!
+//! ```text
+//! id (8) || I2OSP(len, 2) || inclusion proof || range proof
+//! ```
+//!
+//! a serialized batch membership proof as follows, the identifiers being strictly increasing:
+//!
+//! ```text
+//! #proofs (2) || I2OSP(len, 2) || inclusion proof
+//! for each proof: id (8) || I2OSP(len, 2) || range proof
+//! ```
+//!
+//! and a serialized absence proof as follows, the lower neighbour being present if
+//! `position > 0` and the upper one if `position < #identifiers`:
+//!
+//! ```text
+//! id (8) || #identifiers (8) || position (8)
+//! for each neighbour: neighbour id (8) || I2OSP(#siblings, 1) || siblings
+//! ```
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+use digest::Digest;
+use generic_array::typenum::U32;
+use num_bigint::BigUint;
+use smtree::index::TreeIndex;
+use smtree::node_template::HashWiresNodeSmt;
+use smtree::pad_secret::Secret as SmtSecret;
+use smtree::proof::MerkleProof;
+use smtree::traits::{InclusionProvable, Serializable};
+use smtree::tree::SparseMerkleTree;
+
+use crate::errors::HwError;
+use crate::hashes::{generate_subseeds, SMTREE_PADDING_SALT};
+use crate::hashwires::{Commitment, Proof};
+use crate::merkle::{deserialize_path, root_from_path, serialize_path, MerkleTree};
+use crate::serialization::{
+    i2osp_bounded, os2ip, os2ip_u64, serialize_bounded, take_slice, tokenize_slice,
+};
+use crate::traits::Hash;
+
+const REGISTRY_SALT: &[u8; 32] = b"91234567890123456789012345678901";
+const ID_LEAF_SALT: &[u8; 32] = b"i1234567890123456789012345678901";
+const ID_NODE_SALT: &[u8; 32] = b"j1234567890123456789012345678901";
+const IDENTIFIERS_SALT: &[u8; 32] = b"k1234567890123456789012345678901";
+const LABEL_SALT: &[u8; 32] = b"z1234567890123456789012345678901";
+
+/// The maximum height of a [`CommitmentSet`], as identifiers are 64-bit integers.
+pub const MAX_REGISTRY_HEIGHT: usize = 64;
+/// The maximum number of range proofs of a [`BatchMembershipProof`].
+pub const MAX_BATCH_SIZE: usize = u16::MAX as usize;
+
+/// A set of issued commitments, indexed by credential identifier.
+pub struct CommitmentSet<D: Hash> {
+    height: usize,
+    padding_secret: SmtSecret,
+    leaves: BTreeMap<u64, [u8; 32]>,
+    // the labels of the attributes inserted with `insert_labeled`
+    labels: BTreeMap<u64, String>,
+    _d: PhantomData<D>,
+}
+
+impl<D: Hash> CommitmentSet<D> {
+    /// Create an empty set holding up to `2^height` commitments. The padding nodes of the tree
+    /// are derived from the issuer's `seed`, which must be kept secret.
+    pub fn new(height: usize, seed: &[u8]) -> Result<Self, HwError> {
+        if height == 0 || height > MAX_REGISTRY_HEIGHT {
+            return Err(HwError::ParameterError);
+        }
+        let padding_secret = generate_subseeds::<D, U32>(SMTREE_PADDING_SALT, seed, 1);
+        Ok(Self {
+            height,
+            padding_secret: SmtSecret::from_bytes(&padding_secret[0]).unwrap(),
+            leaves: BTreeMap::new(),
+            labels: BTreeMap::new(),
+            _d: PhantomData,
+        })
+    }
+
+    /// Insert the commitment issued for credential `id`, failing with
+    /// [`HwError::LabelCollisionError`] if `id` is the identifier of a labeled attribute.
+    pub fn insert(&mut self, id: u64, commitment: &Commitment<D>) -> Result<(), HwError> {
+        if self.height < MAX_REGISTRY_HEIGHT && id >> self.height != 0 {
+            return Err(HwError::ParameterError);
+        }
+        if self.labels.contains_key(&id) {
+            return Err(HwError::LabelCollisionError);
+        }
+        self.leaves.insert(id, leaf::<D>(id, commitment));
+        Ok(())
+    }
+
+    /// Insert the commitment of the attribute `label`, returning its identifier. Inserting a
+    /// label again replaces its commitment, while a label whose identifier is already taken by
+    /// another attribute fails with [`HwError::LabelCollisionError`].
+    pub fn insert_labeled(
+        &mut self,
+        label: &str,
+        commitment: &Commitment<D>,
+    ) -> Result<u64, HwError> {
+        let id = self.label_id(label);
+        let taken = match self.labels.get(&id) {
+            Some(other) => other != label,
+            None => self.leaves.contains_key(&id),
+        };
+        if taken {
+            return Err(HwError::LabelCollisionError);
+        }
+        self.labels.insert(id, label.to_string());
+        self.leaves.insert(id, leaf::<D>(id, commitment));
+        Ok(id)
+    }
+
+    /// The identifier of the attribute `label` in this set.
+    pub fn label_id(&self, label: &str) -> u64 {
+        label_id::<D>(label, self.height)
+    }
+
+    /// Combine a range proof over the commitment of the attribute `label` with a proof that
+    /// this commitment belongs to the set.
+    pub fn prove_labeled_membership(
+        &self,
+        label: &str,
+        proof: Proof,
+    ) -> Result<MembershipProof, HwError> {
+        let id = self.label_id(label);
+        if self.labels.get(&id).map(String::as_str) != Some(label) {
+            return Err(HwError::InclusionProofError);
+        }
+        self.prove_membership(id, proof)
+    }
+
+    /// The number of commitments in the set.
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// The root of the set, to be published by the issuer.
+    pub fn root(&self) -> Vec<u8> {
+        self.tree().get_root_raw().serialize()
+    }
+
+    /// Combine a range proof over the commitment of credential `id` with a proof that this
+    /// commitment belongs to the set.
+    pub fn prove_membership(&self, id: u64, proof: Proof) -> Result<MembershipProof, HwError> {
+        if !self.leaves.contains_key(&id) {
+            return Err(HwError::InclusionProofError);
+        }
+        let index = TreeIndex::from_u64(self.height, id);
+        let inclusion =
+            MerkleProof::<HashWiresNodeSmt<D>>::generate_inclusion_proof(&self.tree(), &[index])
+                .ok_or(HwError::InclusionProofError)?;
+        Ok(MembershipProof {
+            id,
+            inclusion: inclusion.serialize(),
+            proof,
+        })
+    }
+
+    /// Combine range proofs over the commitments of several credentials with a single proof
+    /// that these commitments belong to the set.
+    pub fn prove_batch_membership(
+        &self,
+        mut proofs: Vec<(u64, Proof)>,
+    ) -> Result<BatchMembershipProof, HwError> {
+        proofs.sort_by_key(|(id, _)| *id);
+        if proofs.is_empty()
+            || proofs.len() > MAX_BATCH_SIZE
+            || proofs.windows(2).any(|w| w[0].0 == w[1].0)
+        {
+            return Err(HwError::ParameterError);
+        }
+        if proofs.iter().any(|(id, _)| !self.leaves.contains_key(id)) {
+            return Err(HwError::InclusionProofError);
+        }
+        let indexes: Vec<TreeIndex> = proofs
+            .iter()
+            .map(|(id, _)| TreeIndex::from_u64(self.height, *id))
+            .collect();
+        let inclusion =
+            MerkleProof::<HashWiresNodeSmt<D>>::generate_inclusion_proof(&self.tree(), &indexes)
+                .ok_or(HwError::InclusionProofError)?;
+        Ok(BatchMembershipProof {
+            inclusion: inclusion.serialize(),
+            proofs,
+        })
+    }
+
+    /// The root of the tree over the sorted identifiers of the set, to be published by the
+    /// issuer along with [`CommitmentSet::root`] to support absence proofs.
+    pub fn identifier_root(&self) -> [u8; 32] {
+        identifiers_digest::<D>(self.leaves.len(), &self.identifier_tree().root())
+    }
+
+    /// Prove that no commitment was issued for credential `id`.
+    pub fn prove_absent(&self, id: u64) -> Result<AbsenceProof, HwError> {
+        if self.leaves.contains_key(&id) {
+            return Err(HwError::InclusionProofError);
+        }
+        let ids: Vec<u64> = self.leaves.keys().copied().collect();
+        let position = ids.partition_point(|other| *other < id);
+        let tree = self.identifier_tree();
+        let neighbour = |index: usize| (ids[index], tree.path(index));
+        Ok(AbsenceProof {
+            id,
+            count: ids.len(),
+            position,
+            lower: position.checked_sub(1).map(neighbour),
+            upper: ids.get(position).map(|_| neighbour(position)),
+        })
+    }
+
+    fn identifier_tree(&self) -> MerkleTree {
+        let leaves: Vec<[u8; 32]> = self.leaves.keys().map(|id| id_leaf::<D>(*id)).collect();
+        MerkleTree::new::<D>(&leaves, ID_NODE_SALT)
+    }
+
+    fn tree(&self) -> SparseMerkleTree<HashWiresNodeSmt<D>> {
+        let leaves: Vec<(TreeIndex, HashWiresNodeSmt<D>)> = self
+            .leaves
+            .iter()
+            .map(|(id, leaf)| {
+                (
+                    TreeIndex::from_u64(self.height, *id),
+                    HashWiresNodeSmt::<D>::new(leaf.to_vec()),
+                )
+            })
+            .collect();
+        let mut tree = SparseMerkleTree::new(self.height);
+        tree.build(&leaves, &self.padding_secret);
+        tree
+    }
+}
+
+/// The identifier of the attribute `label` in a [`CommitmentSet`] of height `height`.
+pub fn label_id<D: Hash>(label: &str, height: usize) -> u64 {
+    let mut hasher = D::new();
+    hasher.update(LABEL_SALT);
+    hasher.update(label.as_bytes());
+    let mut output = [0u8; 8];
+    output.copy_from_slice(&hasher.finalize()[..8]);
+    match height {
+        0 => 0,
+        _ => u64::from_be_bytes(output) >> (MAX_REGISTRY_HEIGHT - height.min(MAX_REGISTRY_HEIGHT)),
+    }
+}
+
+// The identifier is part of the leaf, so a commitment cannot be claimed under another one.
+fn leaf<D: Hash>(id: u64, commitment: &Commitment<D>) -> [u8; 32] {
+    let mut hasher = D::new();
+    hasher.update(REGISTRY_SALT);
+    hasher.update(id.to_be_bytes());
+    hasher.update(commitment.base().to_be_bytes());
+    hasher.update(commitment.serialize());
+    let mut output = [0u8; 32];
+    output.copy_from_slice(&hasher.finalize()[..32]);
+    output
+}
+
+fn id_leaf<D: Hash>(id: u64) -> [u8; 32] {
+    let mut hasher = D::new();
+    hasher.update(ID_LEAF_SALT);
+    hasher.update(id.to_be_bytes());
+    let mut output = [0u8; 32];
+    output.copy_from_slice(&hasher.finalize()[..32]);
+    output
+}
+
+fn identifiers_digest<D: Hash>(count: usize, tree_root: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = D::new();
+    hasher.update(IDENTIFIERS_SALT);
+    hasher.update((count as u64).to_be_bytes());
+    hasher.update(tree_root);
+    let mut output = [0u8; 32];
+    output.copy_from_slice(&hasher.finalize()[..32]);
+    output
+}
+
+/// A range proof along with a proof that its commitment belongs to a [`CommitmentSet`].
+pub struct MembershipProof {
+    id: u64,
+    inclusion: Vec<u8>,
+    proof: Proof,
+}
+
+impl MembershipProof {
+    /// The credential identifier.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The range proof.
+    pub fn proof(&self) -> &Proof {
+        &self.proof
+    }
+
+    /// Serialize the membership proof.
+    pub fn serialize(&self) -> Vec<u8> {
+        [
+            &self.id.to_be_bytes()[..],
+            &serialize_bounded(&self.inclusion, 2),
+            &self.proof.serialize(),
+        ]
+        .concat()
+    }
+
+    /// Deserialize a membership proof.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (id, remainder) = take_slice(input, 8)?;
+        let (inclusion, remainder) = tokenize_slice(remainder, 2)?;
+        Ok(Self {
+            id: os2ip_u64(id)?,
+            inclusion: inclusion.to_vec(),
+            proof: Proof::deserialize(remainder)?,
+        })
+    }
+}
+
+/// Verify that `commitment` belongs to the set with root `root`, and that the range proof of
+/// `proof` shows its value is larger than or equal to `threshold`.
+pub fn verify_with_membership<D: Hash>(
+    root: &[u8],
+    commitment: &Commitment<D>,
+    proof: &MembershipProof,
+    threshold: &BigUint,
+) -> Result<(), HwError> {
+    let inclusion = MerkleProof::<HashWiresNodeSmt<D>>::deserialize(&proof.inclusion)?;
+    let leaf = HashWiresNodeSmt::<D>::new(leaf::<D>(proof.id, commitment).to_vec());
+    if !inclusion.verify_inclusion_proof(&[leaf], &HashWiresNodeSmt::<D>::new(root.to_vec())) {
+        return Err(HwError::ProofVerificationError);
+    }
+    commitment.verify(&proof.proof, threshold)
+}
+
+/// Verify that `commitment` is the attribute `label` of the set of height `height` with root
+/// `root`, and that the range proof of `proof` shows its value is larger than or equal to
+/// `threshold`.
+pub fn verify_labeled_membership<D: Hash>(
+    root: &[u8],
+    height: usize,
+    label: &str,
+    commitment: &Commitment<D>,
+    proof: &MembershipProof,
+    threshold: &BigUint,
+) -> Result<(), HwError> {
+    if proof.id != label_id::<D>(label, height) {
+        return Err(HwError::ProofVerificationError);
+    }
+    verify_with_membership(root, commitment, proof, threshold)
+}
+
+/// Range proofs along with a single proof that their commitments belong to a
+/// [`CommitmentSet`].
+pub struct BatchMembershipProof {
+    inclusion: Vec<u8>,
+    // sorted by identifier
+    proofs: Vec<(u64, Proof)>,
+}
+
+impl BatchMembershipProof {
+    /// The credential identifiers, in increasing order.
+    pub fn ids(&self) -> Vec<u64> {
+        self.proofs.iter().map(|(id, _)| *id).collect()
+    }
+
+    /// The range proof over the commitment of credential `id`, if any.
+    pub fn proof(&self, id: u64) -> Option<&Proof> {
+        self.proofs
+            .iter()
+            .find(|(other, _)| *other == id)
+            .map(|(_, proof)| proof)
+    }
+
+    /// Serialize the batch membership proof.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = [
+            &i2osp_bounded(self.proofs.len(), 2)[..],
+            &serialize_bounded(&self.inclusion, 2),
+        ]
+        .concat();
+        for (id, proof) in self.proofs.iter() {
+            result.extend_from_slice(&id.to_be_bytes());
+            result.extend_from_slice(&serialize_bounded(&proof.serialize(), 2));
+        }
+        result
+    }
+
+    /// Deserialize a batch membership proof.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (count, remainder) = take_slice(input, 2)?;
+        let (inclusion, mut remainder) = tokenize_slice(remainder, 2)?;
+        let mut proofs: Vec<(u64, Proof)> = Vec::new();
+        for _ in 0..os2ip(count)? {
+            let (id, rest) = take_slice(remainder, 8)?;
+            let (proof, rest) = tokenize_slice(rest, 2)?;
+            let id = os2ip_u64(id)?;
+            if proofs.last().map(|(last, _)| *last >= id).unwrap_or(false) {
+                return Err(HwError::SerializationError);
+            }
+            proofs.push((id, Proof::deserialize(proof)?));
+            remainder = rest;
+        }
+        if proofs.is_empty() || !remainder.is_empty() {
+            return Err(HwError::SerializationError);
+        }
+        Ok(Self {
+            inclusion: inclusion.to_vec(),
+            proofs,
+        })
+    }
+}
+
+/// Verify that the commitments of `statements`, each given along with its credential
+/// identifier and a threshold, belong to the set with root `root`, and that the range proofs
+/// of `proof` show their values are larger than or equal to their thresholds. The statements
+/// must cover exactly the identifiers of `proof`, in any order.
+pub fn verify_batch_membership<D: Hash>(
+    root: &[u8],
+    statements: &[(u64, &Commitment<D>, &BigUint)],
+    proof: &BatchMembershipProof,
+) -> Result<(), HwError> {
+    let mut statements = statements.to_vec();
+    statements.sort_by_key(|(id, _, _)| *id);
+    if statements.len() != proof.proofs.len()
+        || statements
+            .iter()
+            .zip(proof.proofs.iter())
+            .any(|((id, _, _), (other, _))| id != other)
+    {
+        return Err(HwError::ProofVerificationError);
+    }
+
+    let inclusion = MerkleProof::<HashWiresNodeSmt<D>>::deserialize(&proof.inclusion)?;
+    let leaves: Vec<HashWiresNodeSmt<D>> = statements
+        .iter()
+        .map(|(id, commitment, _)| HashWiresNodeSmt::<D>::new(leaf::<D>(*id, commitment).to_vec()))
+        .collect();
+    if !inclusion.verify_inclusion_proof(&leaves, &HashWiresNodeSmt::<D>::new(root.to_vec())) {
+        return Err(HwError::ProofVerificationError);
+    }
+    for ((_, commitment, threshold), (_, range_proof)) in statements.iter().zip(proof.proofs.iter())
+    {
+        commitment.verify(range_proof, *threshold)?;
+    }
+    Ok(())
+}
+
+/// A proof that no commitment of a [`CommitmentSet`] was issued for an identifier.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AbsenceProof {
+    id: u64,
+    count: usize,
+    // the number of identifiers smaller than `id`
+    position: usize,
+    // the identifiers at `position - 1` and `position`, with their paths
+    lower: Option<(u64, Vec<[u8; 32]>)>,
+    upper: Option<(u64, Vec<[u8; 32]>)>,
+}
+
+impl AbsenceProof {
+    /// The absent credential identifier.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Serialize the absence proof.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = [
+            &self.id.to_be_bytes()[..],
+            &i2osp_bounded(self.count, 8),
+            &i2osp_bounded(self.position, 8),
+        ]
+        .concat();
+        for (id, path) in self.lower.iter().chain(self.upper.iter()) {
+            result.extend_from_slice(&id.to_be_bytes());
+            result.extend_from_slice(&serialize_path(path));
+        }
+        result
+    }
+
+    /// Deserialize an absence proof.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (id, remainder) = take_slice(input, 8)?;
+        let (count, remainder) = take_slice(remainder, 8)?;
+        let (position, mut remainder) = take_slice(remainder, 8)?;
+        let (count, position) = (os2ip(count)?, os2ip(position)?);
+        if position > count {
+            return Err(HwError::SerializationError);
+        }
+        let mut neighbour = |present: bool| -> Result<_, HwError> {
+            if !present {
+                return Ok(None);
+            }
+            let (id, rest) = take_slice(remainder, 8)?;
+            let (path, rest) = deserialize_path(rest)?;
+            remainder = rest;
+            Ok(Some((os2ip_u64(id)?, path)))
+        };
+        let lower = neighbour(position > 0)?;
+        let upper = neighbour(position < count)?;
+        if !remainder.is_empty() {
+            return Err(HwError::SerializationError);
+        }
+        Ok(Self {
+            id: os2ip_u64(id)?,
+            count,
+            position,
+            lower,
+            upper,
+        })
+    }
+}
+
+/// Verify that no commitment was issued for credential `id` in the set with identifier root
+/// `identifier_root` (see [`CommitmentSet::identifier_root`]).
+pub fn verify_absence<D: Hash>(
+    identifier_root: &[u8; 32],
+    id: u64,
+    proof: &AbsenceProof,
+) -> Result<(), HwError> {
+    if proof.id != id
+        || proof.lower.is_some() != (proof.position > 0)
+        || proof.upper.is_some() != (proof.position < proof.count)
+    {
+        return Err(HwError::ProofVerificationError);
+    }
+    // The neighbours are adjacent in the sorted identifiers, and enclose `id`.
+    let mut tree_roots = Vec::new();
+    if let Some((lower, path)) = &proof.lower {
+        if *lower >= id {
+            return Err(HwError::ProofVerificationError);
+        }
+        let leaf = id_leaf::<D>(*lower);
+        tree_roots.push(root_from_path::<D>(
+            ID_NODE_SALT,
+            leaf,
+            proof.position - 1,
+            proof.count,
+            path,
+        )?);
+    }
+    if let Some((upper, path)) = &proof.upper {
+        if *upper <= id {
+            return Err(HwError::ProofVerificationError);
+        }
+        let leaf = id_leaf::<D>(*upper);
+        tree_roots.push(root_from_path::<D>(
+            ID_NODE_SALT,
+            leaf,
+            proof.position,
+            proof.count,
+            path,
+        )?);
+    }
+    let tree_root = tree_roots.first().copied().unwrap_or([0u8; 32]);
+    if tree_roots.iter().any(|root| *root != tree_root)
+        || identifiers_digest::<D>(proof.count, &tree_root) != *identifier_root
+    {
+        return Err(HwError::ProofVerificationError);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::Secret;
+    use blake3::Hasher as Blake3;
+
+    #[test]
+    fn test_verify_with_membership() -> Result<(), HwError> {
+        let mut set = CommitmentSet::<Blake3>::new(16, &[9u8; 32])?;
+        let secrets: Vec<Secret<Blake3>> = [402u32, 3413, 9999]
+            .iter()
+            .enumerate()
+            .map(|(i, v)| Secret::gen(&[i as u8; 32], &BigUint::from(*v)))
+            .collect();
+        let commitments = secrets
+            .iter()
+            .map(|s| s.commit(16, 32))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (id, commitment) in commitments.iter().enumerate() {
+            set.insert(100 + id as u64, commitment)?;
+        }
+        assert_eq!(set.len(), 3);
+        assert!(set.insert(1 << 16, &commitments[0]).is_err());
+        let root = set.root();
+
+        let threshold = BigUint::from(400u32);
+        let proof = set.prove_membership(101, secrets[1].prove(16, 32, &threshold)?)?;
+        let proof = MembershipProof::deserialize(&proof.serialize())?;
+        assert_eq!(proof.id(), 101);
+        verify_with_membership(&root, &commitments[1], &proof, &threshold)?;
+
+        // Another commitment, or the same one under another root, is not a member.
+        assert!(verify_with_membership(&root, &commitments[0], &proof, &threshold).is_err());
+        assert!(verify_with_membership(&[0u8; 32], &commitments[1], &proof, &threshold).is_err());
+        assert!(set
+            .prove_membership(7, secrets[0].prove(16, 32, &threshold)?)
+            .is_err());
+
+        // One inclusion proof for several attributes.
+        let batch = set.prove_batch_membership(vec![
+            (102, secrets[2].prove(16, 32, &threshold)?),
+            (100, secrets[0].prove(16, 32, &threshold)?),
+        ])?;
+        let batch = BatchMembershipProof::deserialize(&batch.serialize())?;
+        assert_eq!(batch.ids(), vec![100, 102]);
+        let statements = [
+            (102, &commitments[2], &threshold),
+            (100, &commitments[0], &threshold),
+        ];
+        verify_batch_membership(&root, &statements, &batch)?;
+        assert!(verify_batch_membership(&root, &statements[..1], &batch).is_err());
+        let swapped = [
+            (102, &commitments[0], &threshold),
+            (100, &commitments[2], &threshold),
+        ];
+        assert!(verify_batch_membership(&root, &swapped, &batch).is_err());
+        let high = BigUint::from(500u32);
+        let too_high = [
+            (102, &commitments[2], &threshold),
+            (100, &commitments[0], &high),
+        ];
+        assert!(verify_batch_membership(&root, &too_high, &batch).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_labeled_membership() -> Result<(), HwError> {
+        let mut set = CommitmentSet::<Blake3>::new(16, &[9u8; 32])?;
+        let balance = Secret::<Blake3>::gen(&[1u8; 32], &BigUint::from(3413u32));
+        let age = Secret::<Blake3>::gen(&[2u8; 32], &BigUint::from(42u32));
+        let id = set.insert_labeled("balance", &balance.commit(16, 32)?)?;
+        assert_eq!(id, label_id::<Blake3>("balance", 16));
+        assert!(id >> 16 == 0);
+
+        // adding an attribute does not move the existing ones
+        let root = set.root();
+        set.insert_labeled("age", &age.commit(16, 32)?)?;
+        assert_eq!(set.label_id("balance"), id);
+        assert_ne!(set.root(), root);
+        let threshold = BigUint::from(3000u32);
+        let proof = set.prove_labeled_membership("balance", balance.prove(16, 32, &threshold)?)?;
+        let (root, commitment) = (set.root(), balance.commit(16, 32)?);
+        verify_labeled_membership(&root, 16, "balance", &commitment, &proof, &threshold)?;
+        assert!(
+            verify_labeled_membership(&root, 16, "age", &commitment, &proof, &threshold).is_err()
+        );
+        assert!(set
+            .prove_labeled_membership("income", balance.prove(16, 32, &threshold)?)
+            .is_err());
+
+        // in a set of 4 attributes, some label soon collides with another one
+        let mut small = CommitmentSet::<Blake3>::new(2, &[9u8; 32])?;
+        let commitment = age.commit(16, 32)?;
+        let collision = (0..5)
+            .map(|i| small.insert_labeled(&format!("attribute{}", i), &commitment))
+            .find(Result::is_err);
+        assert!(matches!(collision, Some(Err(HwError::LabelCollisionError))));
+        let taken = small.label_id("attribute0");
+        assert!(matches!(
+            small.insert(taken, &commitment),
+            Err(HwError::LabelCollisionError)
+        ));
+        small.insert_labeled("attribute0", &commitment)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_absence() -> Result<(), HwError> {
+        let mut set = CommitmentSet::<Blake3>::new(16, &[9u8; 32])?;
+        let empty_root = set.identifier_root();
+        verify_absence::<Blake3>(&empty_root, 5, &set.prove_absent(5)?)?;
+
+        let commitment =
+            Secret::<Blake3>::gen(&[1u8; 32], &BigUint::from(402u32)).commit(16, 32)?;
+        for id in [10u64, 20, 30, 40, 50].iter() {
+            set.insert(*id, &commitment)?;
+        }
+        let root = set.identifier_root();
+        assert_ne!(root, empty_root);
+
+        // Below, between and above the identifiers of the set.
+        for id in [0u64, 25, 41, 1000].iter() {
+            let proof = AbsenceProof::deserialize(&set.prove_absent(*id)?.serialize())?;
+            assert_eq!(proof.id(), *id);
+            verify_absence::<Blake3>(&root, *id, &proof)?;
+            assert!(verify_absence::<Blake3>(&empty_root, *id, &proof).is_err());
+        }
+
+        // Present identifiers cannot be proven absent, and proofs do not transfer.
+        assert!(set.prove_absent(30).is_err());
+        let proof = set.prove_absent(25)?;
+        assert!(verify_absence::<Blake3>(&root, 30, &proof).is_err());
+        assert!(verify_absence::<Blake3>(&root, 24, &proof).is_err());
+        Ok(())
+    }
+}
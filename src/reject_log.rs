@@ -0,0 +1,183 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Structured events for rejected proofs, for the analysis of forged-proof attempts
+//!
+//! [`verify_logged`] verifies a proof like [`Commitment::verify`], and reports every rejection
+//! to a [`RejectSink`] as a [`RejectEvent`]: the stage at which the proof failed, the
+//! [`HwErrorCode`] of the failure, a hash of the proof and the context of the request. Events
+//! hold no secret, and the proof hash is the SHA-256 of the serialized proof whatever its
+//! digest, so that attempts can be correlated across deployments.
+//!
+//! The [`std::fmt::Display`] output of an event is a single `key=value` line:
+//!
+//! ```text
+//! hashwires.reject stage=smt_inclusion error=4 proof=<hex> context=<hex>
+//! ```
+use std::cell::Cell;
+use std::fmt;
+use std::time::Duration;
+
+use num_bigint::BigUint;
+use sha2::{Digest, Sha256};
+
+use crate::errors::{HwError, HwErrorCode};
+use crate::hashwires::{Commitment, Proof};
+use crate::metrics::{HwMetrics, VerifyStage};
+use crate::traits::Hash;
+
+/// A rejected proof.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RejectEvent {
+    stage: Option<VerifyStage>,
+    error: HwErrorCode,
+    proof_hash: [u8; 32],
+    context: Vec<u8>,
+}
+
+impl RejectEvent {
+    /// The verification stage at which the proof failed, or `None` if it was rejected before
+    /// any verification stage (e.g. for a digest mismatch).
+    pub fn stage(&self) -> Option<VerifyStage> {
+        self.stage
+    }
+
+    /// The code of the failure.
+    pub fn error(&self) -> HwErrorCode {
+        self.error
+    }
+
+    /// The SHA-256 of the serialized proof.
+    pub fn proof_hash(&self) -> &[u8; 32] {
+        &self.proof_hash
+    }
+
+    /// The context of the request the proof was presented in.
+    pub fn context(&self) -> &[u8] {
+        &self.context
+    }
+}
+
+impl fmt::Display for RejectEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stage = match self.stage {
+            None => "none",
+            Some(VerifyStage::ChainNodes) => "chain_nodes",
+            Some(VerifyStage::SmtDecoding) => "smt_decoding",
+            Some(VerifyStage::SmtInclusion) => "smt_inclusion",
+        };
+        write!(
+            f,
+            "hashwires.reject stage={} error={} proof={} context={}",
+            stage,
+            self.error.as_u16(),
+            hex::encode(self.proof_hash),
+            hex::encode(&self.context)
+        )
+    }
+}
+
+/// Receives the events of rejected proofs, e.g. to forward them to a logging pipeline.
+pub trait RejectSink {
+    /// Called once per rejected proof.
+    fn on_reject(&self, event: &RejectEvent);
+}
+
+/// A sink writing events to the standard error, one line each.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StderrSink;
+
+impl RejectSink for StderrSink {
+    fn on_reject(&self, event: &RejectEvent) {
+        eprintln!("{}", event);
+    }
+}
+
+/// Verify a HashWires proof over a commitment, reporting its rejection to `sink` along with
+/// the `context` of the request (e.g. the identifier of the relying party).
+pub fn verify_logged<D: Hash>(
+    commitment: &Commitment<D>,
+    proof: &Proof,
+    threshold: &BigUint,
+    context: &[u8],
+    sink: &dyn RejectSink,
+) -> Result<(), HwError> {
+    let tracker = StageTracker::default();
+    let result = commitment.verify_with_metrics(proof, threshold, &tracker);
+    if let Err(e) = &result {
+        sink.on_reject(&RejectEvent {
+            stage: tracker.failed_stage(e),
+            error: e.code(),
+            proof_hash: Sha256::digest(&proof.serialize()).into(),
+            context: context.to_vec(),
+        });
+    }
+    result
+}
+
+// Records the last completed verification stage, to find the stage a proof failed at.
+#[derive(Default)]
+struct StageTracker {
+    completed: Cell<Option<VerifyStage>>,
+}
+
+impl StageTracker {
+    fn failed_stage(&self, error: &HwError) -> Option<VerifyStage> {
+        if let HwError::DigestMismatchError = error {
+            return None;
+        }
+        match self.completed.get() {
+            None => Some(VerifyStage::ChainNodes),
+            Some(VerifyStage::ChainNodes) => Some(VerifyStage::SmtDecoding),
+            Some(_) => Some(VerifyStage::SmtInclusion),
+        }
+    }
+}
+
+impl HwMetrics for StageTracker {
+    fn on_stage(&self, stage: VerifyStage, _elapsed: Duration) {
+        self.completed.set(Some(stage));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::Secret;
+    use blake3::Hasher as Blake3;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct Events(RefCell<Vec<RejectEvent>>);
+
+    impl RejectSink for Events {
+        fn on_reject(&self, event: &RejectEvent) {
+            self.0.borrow_mut().push(event.clone());
+        }
+    }
+
+    #[test]
+    fn test_verify_logged() -> Result<(), HwError> {
+        let secret = Secret::<Blake3>::gen(&[0u8; 32], &BigUint::from(402u32));
+        let commitment = secret.commit(4, 32)?;
+        let proof = secret.prove(4, 32, &BigUint::from(378u32))?;
+        let events = Events::default();
+
+        verify_logged(&commitment, &proof, &BigUint::from(378u32), b"rp", &events)?;
+        assert!(events.0.borrow().is_empty());
+
+        assert!(
+            verify_logged(&commitment, &proof, &BigUint::from(379u32), b"rp", &events).is_err()
+        );
+        let event = events.0.borrow()[0].clone();
+        assert_eq!(event.stage(), Some(VerifyStage::SmtInclusion));
+        assert_eq!(event.error(), HwErrorCode::ProofVerificationError);
+        assert_eq!(event.context(), b"rp");
+        assert!(event
+            .to_string()
+            .starts_with("hashwires.reject stage=smt_inclusion error=4 proof="));
+        Ok(())
+    }
+}
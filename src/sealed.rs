@@ -0,0 +1,312 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Sealed audit openings of commitments, for regulators
+//!
+//! Lawful audits may require the issuer to disclose the value behind a commitment, without
+//! the issuer keeping the secrets of its holders. Along with each commitment, the issuer then
+//! seals an opening (the parameters, seed and value of the secret) under the public key of the
+//! regulator, and files the [`SealedOpening`] with the commitment. Only the regulator can open
+//! it, with [`RegulatorKey::open`], which regenerates the commitment from the opening and
+//! rejects packages which do not correspond to it: verifiers, who never see the sealed
+//! openings, gain nothing.
+//!
+//! The opening is encrypted for the Ristretto public key `P` of the regulator with an
+//! ephemeral key `r`, and authenticated along with the serialized commitment:
+//!
+//! ```text
+//! shared = compress(r * P), ephemeral = compress(r * G)
+//! key = H(SEALED_KEY_SALT || shared || ephemeral || P)
+//! keystream block i = H(SEALED_KEYSTREAM_SALT || key || i (8))
+//! tag = H(SEALED_MAC_SALT || key || I2OSP(len, 2) || commitment || len(ciphertext) (8)
+//!     || ciphertext)
+//! ```
+//!
+//! The plaintext is laid out as `params (16) || I2OSP(len, 1) || seed || I2OSP(len, 2) ||
+//! value`, and a serialized sealed opening as `ephemeral (32) || I2OSP(len, 2) || ciphertext
+//! || tag (32)`. The module is only built with the `sealed-audit` feature.
+use curve25519_dalek_ng::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek_ng::ristretto::CompressedRistretto;
+use curve25519_dalek_ng::scalar::Scalar;
+use digest::Digest;
+use num_bigint::BigUint;
+use rand_core::{CryptoRng, RngCore};
+
+use crate::errors::HwError;
+use crate::hashwires::{Commitment, Secret};
+use crate::params::HwParams;
+use crate::serialization::{serialize_bounded, take_slice, tokenize_slice};
+use crate::traits::Hash;
+
+const SEALED_KEY_SALT: &[u8; 32] = b"G1234567890123456789012345678901";
+const SEALED_KEYSTREAM_SALT: &[u8; 32] = b"H1234567890123456789012345678901";
+const SEALED_MAC_SALT: &[u8; 32] = b"I1234567890123456789012345678901";
+
+/// The secret key of a regulator.
+pub struct RegulatorKey {
+    secret: Scalar,
+}
+
+impl RegulatorKey {
+    /// Generate a regulator key from `rng`.
+    pub fn gen<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        Self {
+            secret: Scalar::random(rng),
+        }
+    }
+
+    /// The key encoded as `bytes`, reduced modulo the group order.
+    pub fn from_bytes(bytes: &[u8; 32]) -> Self {
+        Self {
+            secret: Scalar::from_bytes_mod_order(*bytes),
+        }
+    }
+
+    /// The encoding of the key.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.secret.to_bytes()
+    }
+
+    /// The public key of the regulator, to which issuers seal openings.
+    pub fn public_key(&self) -> RegulatorPublicKey {
+        RegulatorPublicKey((self.secret * RISTRETTO_BASEPOINT_POINT).compress())
+    }
+
+    /// Open `sealed`, filed along with `commitment`. Fails with
+    /// [`HwError::ProofVerificationError`] if the package was not sealed to this key along
+    /// with `commitment`, or if the opening does not regenerate it.
+    pub fn open<D: Hash>(
+        &self,
+        sealed: &SealedOpening,
+        commitment: &Commitment<D>,
+    ) -> Result<AuditOpening<D>, HwError> {
+        let ephemeral = CompressedRistretto(sealed.ephemeral)
+            .decompress()
+            .ok_or(HwError::ProofVerificationError)?;
+        let shared = (self.secret * ephemeral).compress();
+        let key = derive_key::<D>(&shared, &sealed.ephemeral, &self.public_key());
+        let expected = sealed_tag::<D>(&key, commitment, &sealed.ciphertext);
+        // constant-time comparison, as the tag authenticates the opening
+        let difference = expected
+            .iter()
+            .zip(sealed.tag.iter())
+            .fold(0u8, |difference, (a, b)| difference | (a ^ b));
+        if difference != 0 {
+            return Err(HwError::ProofVerificationError);
+        }
+
+        let mut plaintext = sealed.ciphertext.clone();
+        apply_keystream::<D>(&key, &mut plaintext);
+        let (params, remainder) = HwParams::deserialize_prefix(&plaintext)?;
+        let (seed, remainder) = tokenize_slice(remainder, 1)?;
+        let (value, remainder) = tokenize_slice(remainder, 2)?;
+        if !remainder.is_empty() {
+            return Err(HwError::SerializationError);
+        }
+        let secret = Secret::gen(seed, &BigUint::from_bytes_be(value));
+        if secret.commit_with_params(&params)?.serialize() != commitment.serialize() {
+            return Err(HwError::ProofVerificationError);
+        }
+        Ok(AuditOpening { params, secret })
+    }
+}
+
+/// The public key of a regulator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegulatorPublicKey(CompressedRistretto);
+
+impl RegulatorPublicKey {
+    /// The public key encoded as `bytes`. Fails with [`HwError::SerializationError`] if it is
+    /// not the encoding of a Ristretto point.
+    pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self, HwError> {
+        let point = CompressedRistretto(*bytes);
+        point.decompress().ok_or(HwError::SerializationError)?;
+        Ok(Self(point))
+    }
+
+    /// The encoding of the public key.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes()
+    }
+}
+
+/// An opening of a commitment, sealed to a regulator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SealedOpening {
+    ephemeral: [u8; 32],
+    ciphertext: Vec<u8>,
+    tag: [u8; 32],
+}
+
+impl SealedOpening {
+    /// Seal the opening of `secret` under `params` to `regulator`, along with the commitment
+    /// it generates.
+    pub fn seal<D: Hash, R: RngCore + CryptoRng>(
+        secret: &Secret<D>,
+        params: &HwParams,
+        regulator: &RegulatorPublicKey,
+        rng: &mut R,
+    ) -> Result<(Commitment<D>, Self), HwError> {
+        let commitment = secret.commit_with_params(params)?;
+        let value = secret.value.to_bytes_be();
+        if secret.seed.len() > u8::MAX as usize || value.len() > u16::MAX as usize {
+            return Err(HwError::SerializationError);
+        }
+        let mut ciphertext = [
+            params.serialize(),
+            serialize_bounded(&secret.seed, 1),
+            serialize_bounded(&value, 2),
+        ]
+        .concat();
+
+        let point = regulator
+            .0
+            .decompress()
+            .ok_or(HwError::SerializationError)?;
+        let ephemeral_secret = Scalar::random(rng);
+        let ephemeral = (ephemeral_secret * RISTRETTO_BASEPOINT_POINT)
+            .compress()
+            .to_bytes();
+        let shared = (ephemeral_secret * point).compress();
+        let key = derive_key::<D>(&shared, &ephemeral, regulator);
+        apply_keystream::<D>(&key, &mut ciphertext);
+        let tag = sealed_tag::<D>(&key, &commitment, &ciphertext);
+        Ok((
+            commitment,
+            Self {
+                ephemeral,
+                ciphertext,
+                tag,
+            },
+        ))
+    }
+
+    /// Serialize the sealed opening.
+    pub fn serialize(&self) -> Vec<u8> {
+        [
+            &self.ephemeral[..],
+            &serialize_bounded(&self.ciphertext, 2),
+            &self.tag,
+        ]
+        .concat()
+    }
+
+    /// Deserialize a sealed opening.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (ephemeral_bytes, remainder) = take_slice(input, 32)?;
+        let (ciphertext, remainder) = tokenize_slice(remainder, 2)?;
+        let (tag_bytes, remainder) = take_slice(remainder, 32)?;
+        if !remainder.is_empty() {
+            return Err(HwError::SerializationError);
+        }
+        let (mut ephemeral, mut tag) = ([0u8; 32], [0u8; 32]);
+        ephemeral.copy_from_slice(ephemeral_bytes);
+        tag.copy_from_slice(tag_bytes);
+        Ok(Self {
+            ephemeral,
+            ciphertext: ciphertext.to_vec(),
+            tag,
+        })
+    }
+}
+
+/// The opening of a commitment, as recovered by a regulator.
+pub struct AuditOpening<D: Hash> {
+    params: HwParams,
+    secret: Secret<D>,
+}
+
+impl<D: Hash> AuditOpening<D> {
+    /// The parameters the commitment was generated with.
+    pub fn params(&self) -> &HwParams {
+        &self.params
+    }
+
+    /// The committed value.
+    pub fn value(&self) -> &BigUint {
+        &self.secret.value
+    }
+
+    /// The secret of the commitment.
+    pub fn secret(&self) -> &Secret<D> {
+        &self.secret
+    }
+}
+
+fn derive_key<D: Hash>(
+    shared: &CompressedRistretto,
+    ephemeral: &[u8; 32],
+    regulator: &RegulatorPublicKey,
+) -> [u8; 32] {
+    let mut hasher = D::new();
+    hasher.update(SEALED_KEY_SALT);
+    hasher.update(shared.as_bytes());
+    hasher.update(ephemeral);
+    hasher.update(regulator.0.as_bytes());
+    let mut output = [0u8; 32];
+    output.copy_from_slice(&hasher.finalize()[..32]);
+    output
+}
+
+fn apply_keystream<D: Hash>(key: &[u8; 32], data: &mut [u8]) {
+    for (i, block) in data.chunks_mut(32).enumerate() {
+        let mut hasher = D::new();
+        hasher.update(SEALED_KEYSTREAM_SALT);
+        hasher.update(key);
+        hasher.update((i as u64).to_be_bytes());
+        for (byte, k) in block.iter_mut().zip(hasher.finalize()) {
+            *byte ^= k;
+        }
+    }
+}
+
+fn sealed_tag<D: Hash>(key: &[u8; 32], commitment: &Commitment<D>, ciphertext: &[u8]) -> [u8; 32] {
+    let mut hasher = D::new();
+    hasher.update(SEALED_MAC_SALT);
+    hasher.update(key);
+    hasher.update(serialize_bounded(&commitment.serialize(), 2));
+    hasher.update((ciphertext.len() as u64).to_be_bytes());
+    hasher.update(ciphertext);
+    let mut output = [0u8; 32];
+    output.copy_from_slice(&hasher.finalize()[..32]);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+    use rand_core::OsRng;
+
+    #[test]
+    fn test_sealed_opening() -> Result<(), HwError> {
+        let params = HwParams::new(16, 32);
+        let regulator = RegulatorKey::gen(&mut OsRng);
+        let public_key = RegulatorPublicKey::from_bytes(&regulator.public_key().to_bytes())?;
+        let value = BigUint::from(3413u32);
+        let secret = Secret::<Blake3>::gen(&[5u8; 32], &value);
+        let (commitment, sealed) = SealedOpening::seal(&secret, &params, &public_key, &mut OsRng)?;
+        assert_eq!(
+            commitment.serialize(),
+            secret.commit_with_params(&params)?.serialize()
+        );
+
+        let sealed = SealedOpening::deserialize(&sealed.serialize())?;
+        let opening = RegulatorKey::from_bytes(&regulator.to_bytes()).open(&sealed, &commitment)?;
+        assert_eq!(opening.value(), &value);
+        assert_eq!(opening.params(), &params);
+
+        // another regulator, commitment or ciphertext is rejected
+        assert!(RegulatorKey::gen(&mut OsRng)
+            .open(&sealed, &commitment)
+            .is_err());
+        let other = Secret::<Blake3>::gen(&[6u8; 32], &value).commit_with_params(&params)?;
+        assert!(regulator.open(&sealed, &other).is_err());
+        let mut tampered = sealed.clone();
+        tampered.ciphertext[0] ^= 1;
+        assert!(regulator.open(&tampered, &commitment).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Power-on self-test of the HashWires implementation
+//!
+//! Regulated deployments require cryptographic modules to test themselves at startup.
+//! [`selftest`] runs a quick battery in each of the [`SUPPORTED_BASES`], with SHA-256:
+//!
+//! - `roundtrip`: a commitment and a proof are generated, serialized, deserialized back to
+//!   the same bytes, and the proof verifies;
+//! - `threshold`: the proof does not verify a larger threshold, and no proof is generated for
+//!   a threshold above the committed value;
+//! - `tamper`: flipping a bit of any chain node, of the MDP salt, of the PLR padding or of
+//!   the MDP tree inclusion proof, or of any byte of the commitment root, makes verification
+//!   fail.
+//!
+//! Each check is reported separately in a [`SelfTestReport`], which the caller inspects
+//! before serving requests.
+use num_bigint::BigUint;
+use sha2::Sha256;
+
+use crate::errors::{HwError, HwErrorCode};
+use crate::hashwires::{Commitment, Proof, Secret};
+use crate::params::{HwParams, SUPPORTED_BASES};
+
+const SEED: [u8; 32] = [0x5e; 32];
+const MAX_NUMBER_BITS: usize = 32;
+const VALUE: u32 = 3413;
+const THRESHOLD: u32 = 2999;
+
+/// The outcome of one check of the self-test.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelfTestCheck {
+    /// The name of the check, along with its base, e.g. `roundtrip/base16`.
+    pub name: String,
+    /// Whether the check passed.
+    pub passed: bool,
+    /// The error which made the check fail, if any. A check also fails without error when a
+    /// larger threshold or a tampered input is accepted.
+    pub error: Option<HwErrorCode>,
+}
+
+/// The outcome of [`selftest`], one entry per check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelfTestReport {
+    /// The checks, in the order they ran.
+    pub checks: Vec<SelfTestCheck>,
+}
+
+impl SelfTestReport {
+    /// Whether all the checks passed.
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+
+    /// The checks which failed.
+    pub fn failures(&self) -> impl Iterator<Item = &SelfTestCheck> {
+        self.checks.iter().filter(|check| !check.passed)
+    }
+}
+
+/// Run the self-test battery, see the [module documentation](self).
+pub fn selftest() -> SelfTestReport {
+    let mut checks = Vec::new();
+    for base in SUPPORTED_BASES.iter() {
+        let params = HwParams::new(*base, MAX_NUMBER_BITS);
+        let fixture = Fixture::generate(&params);
+        let mut record = |check: &str, outcome: Result<bool, HwError>| {
+            checks.push(SelfTestCheck {
+                name: format!("{}/base{}", check, base),
+                passed: matches!(outcome, Ok(true)),
+                error: outcome.err().map(|e| e.code()),
+            })
+        };
+        match fixture {
+            Ok(fixture) => {
+                record("roundtrip", fixture.roundtrip(&params));
+                record("threshold", fixture.threshold(&params));
+                record("tamper", fixture.tamper(&params));
+            }
+            Err(e) => record("roundtrip", Err(e)),
+        }
+    }
+    SelfTestReport { checks }
+}
+
+struct Fixture {
+    secret: Secret<Sha256>,
+    commitment: Vec<u8>,
+    proof: Vec<u8>,
+}
+
+impl Fixture {
+    fn generate(params: &HwParams) -> Result<Self, HwError> {
+        let secret = Secret::gen(&SEED, &BigUint::from(VALUE));
+        let (commitment, proof) = secret.commit_and_prove(params, &THRESHOLD)?;
+        Ok(Self {
+            secret,
+            commitment: commitment.serialize(),
+            proof: proof.serialize(),
+        })
+    }
+
+    fn verify(&self, commitment: &[u8], proof: &[u8], params: &HwParams) -> Result<(), HwError> {
+        let commitment = Commitment::<Sha256>::deserialize(commitment, params.base)?;
+        commitment.verify(&Proof::deserialize(proof)?, &BigUint::from(THRESHOLD))
+    }
+
+    fn roundtrip(&self, params: &HwParams) -> Result<bool, HwError> {
+        let commitment = Commitment::<Sha256>::deserialize(&self.commitment, params.base)?;
+        let proof = Proof::deserialize(&self.proof)?;
+        self.verify(&self.commitment, &self.proof, params)?;
+        Ok(commitment.serialize() == self.commitment && proof.serialize() == self.proof)
+    }
+
+    fn threshold(&self, params: &HwParams) -> Result<bool, HwError> {
+        let commitment = Commitment::<Sha256>::deserialize(&self.commitment, params.base)?;
+        let proof = Proof::deserialize(&self.proof)?;
+        let above = BigUint::from(VALUE + 1);
+        Ok(commitment
+            .verify(&proof, &BigUint::from(THRESHOLD + 1))
+            .is_err()
+            && self
+                .secret
+                .prove(params.base, params.max_number_bits, &above)
+                .is_err())
+    }
+
+    fn tamper(&self, params: &HwParams) -> Result<bool, HwError> {
+        let commitment = Commitment::<Sha256>::deserialize(&self.commitment, params.base)?;
+        let threshold = BigUint::from(THRESHOLD);
+        let rejects = |tamper: &dyn Fn(&mut Proof)| -> Result<bool, HwError> {
+            let mut proof = Proof::deserialize(&self.proof)?;
+            tamper(&mut proof);
+            Ok(commitment.verify(&proof, &threshold).is_err())
+        };
+        let chain_nodes = Proof::deserialize(&self.proof)?.chain_nodes.len();
+        for i in 0..chain_nodes {
+            if !rejects(&|proof| proof.chain_nodes[i][0] ^= 1)? {
+                return Ok(false);
+            }
+        }
+        if !rejects(&|proof| proof.mdp_salt[0] ^= 1)?
+            || !rejects(&|proof| {
+                if let Some(padding) = proof.plr_padding.as_mut() {
+                    padding[0] ^= 1;
+                }
+            })?
+            // the siblings come last in the serialized inclusion proof
+            || !rejects(&|proof| {
+                if let Some(sibling) = proof.smt_inclusion_proof.last_mut() {
+                    *sibling ^= 1;
+                }
+            })?
+        {
+            return Ok(false);
+        }
+        let root = self.commitment.len() - 32;
+        for i in root..self.commitment.len() {
+            let mut commitment = self.commitment.clone();
+            commitment[i] ^= 0x80;
+            if self.verify(&commitment, &self.proof, params).is_ok() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selftest() {
+        let report = selftest();
+        assert_eq!(report.checks.len(), 3 * SUPPORTED_BASES.len());
+        assert!(
+            report.passed(),
+            "{:?}",
+            report.failures().collect::<Vec<_>>()
+        );
+        assert_eq!(report.checks[2].name, "tamper/base2");
+    }
+}
@@ -3,84 +3,167 @@
 // This source code is licensed under the MIT license found in the
 // LICENSE file in the root directory of this source tree.
 
+use std::convert::TryFrom;
+
 use crate::errors::HwError;
 
-/// Corresponds to the I2OSP() function from RFC8017
-pub(crate) fn i2osp(input: usize, length: usize) -> Vec<u8> {
-    if length <= std::mem::size_of::<usize>() {
-        return (&input.to_be_bytes()[std::mem::size_of::<usize>() - length..]).to_vec();
+/// Corresponds to the I2OSP() function from RFC8017, failing with
+/// [`HwError::SerializationError`] ("integer too large") if `input` does not fit in `length`
+/// bytes. The encoding goes through a `u64`, so that 32-bit and 64-bit targets agree.
+pub fn i2osp(input: usize, length: usize) -> Result<Vec<u8>, HwError> {
+    let bytes = (input as u64).to_be_bytes();
+    if length <= bytes.len() {
+        if length < bytes.len() && (input as u64) >> (8 * length) != 0 {
+            return Err(HwError::SerializationError);
+        }
+        return Ok(bytes[bytes.len() - length..].to_vec());
     }
 
     let mut output = vec![0u8; length];
-    output.splice(
-        length - std::mem::size_of::<usize>()..length,
-        input.to_be_bytes().iter().cloned(),
-    );
-    output
+    output[length - bytes.len()..].copy_from_slice(&bytes);
+    Ok(output)
+}
+
+/// [`i2osp`] of an integer the caller bounds to fit in `length` bytes, such as a count checked
+/// on construction. Panics rather than truncating the encoding if the bound does not hold.
+pub(crate) fn i2osp_bounded(input: usize, length: usize) -> Vec<u8> {
+    match i2osp(input, length) {
+        Ok(output) => output,
+        Err(_) => panic!("integer too large"),
+    }
 }
 
-/// Corresponds to the OS2IP() function from RFC8017
-pub(crate) fn os2ip(input: &[u8]) -> Result<usize, HwError> {
-    if input.len() > std::mem::size_of::<usize>() {
+/// Corresponds to the OS2IP() function from RFC8017, for integers of up to 8 bytes.
+pub fn os2ip_u64(input: &[u8]) -> Result<u64, HwError> {
+    if input.len() > 8 {
         return Err(HwError::SerializationError);
     }
 
-    let mut output_array = [0u8; std::mem::size_of::<usize>()];
-    output_array[std::mem::size_of::<usize>() - input.len()..].copy_from_slice(input);
-    Ok(usize::from_be_bytes(output_array))
+    let mut output_array = [0u8; 8];
+    output_array[8 - input.len()..].copy_from_slice(input);
+    Ok(u64::from_be_bytes(output_array))
+}
+
+/// Corresponds to the OS2IP() function from RFC8017, for integers of up to 8 bytes which fit
+/// in a `usize`. The decoding goes through a `u64`, so that 32-bit and 64-bit targets agree
+/// on every input whose value fits.
+pub fn os2ip(input: &[u8]) -> Result<usize, HwError> {
+    usize::try_from(os2ip_u64(input)?).map_err(|_| HwError::SerializationError)
+}
+
+/// Computes I2OSP(len(input), max_bytes) || input, failing with
+/// [`HwError::SerializationError`] if the length of `input` does not fit in `max_bytes` bytes
+pub fn serialize(input: &[u8], max_bytes: usize) -> Result<Vec<u8>, HwError> {
+    Ok([&i2osp(input.len(), max_bytes)?, input].concat())
 }
 
-/// Computes I2OSP(len(input), max_bytes) || input
-pub(crate) fn serialize(input: &[u8], max_bytes: usize) -> Vec<u8> {
-    [&i2osp(input.len(), max_bytes), input].concat()
+/// [`serialize`] of an input the caller bounds in length, such as a digest or a proof of
+/// bounded size. Panics rather than truncating the length prefix if the bound does not hold.
+pub(crate) fn serialize_bounded(input: &[u8], max_bytes: usize) -> Vec<u8> {
+    [&i2osp_bounded(input.len(), max_bytes), input].concat()
 }
 
 /// Tokenizes an input of the format I2OSP(len(input), max_bytes) || input, outputting
 /// (input, remainder)
 pub(crate) fn tokenize(input: &[u8], size_bytes: usize) -> Result<(Vec<u8>, Vec<u8>), HwError> {
-    if size_bytes > std::mem::size_of::<usize>() || input.len() < size_bytes {
+    let (token, remainder) = tokenize_slice(input, size_bytes)?;
+    Ok((token.to_vec(), remainder.to_vec()))
+}
+
+/// Tokenizes an input of the format I2OSP(len(input), size_bytes) || input, outputting
+/// (input, remainder) borrowed from `input`
+pub fn tokenize_slice(input: &[u8], size_bytes: usize) -> Result<(&[u8], &[u8]), HwError> {
+    if size_bytes > 8 || input.len() < size_bytes {
         return Err(HwError::SerializationError);
     }
 
     let size = os2ip(&input[..size_bytes])?;
-    if size_bytes + size > input.len() {
+    // the length prefix is untrusted, so the bound must not overflow
+    if size > input.len() - size_bytes {
         return Err(HwError::SerializationError);
     }
 
     Ok((
-        input[size_bytes..size_bytes + size].to_vec(),
-        input[size_bytes + size..].to_vec(),
+        &input[size_bytes..size_bytes + size],
+        &input[size_bytes + size..],
     ))
 }
 
 /// Returns a slice of input of length len along with the remainder, throwing an error if it is
 /// too short
-pub(crate) fn take_slice(input: &[u8], len: usize) -> Result<(&[u8], &[u8]), HwError> {
+pub fn take_slice(input: &[u8], len: usize) -> Result<(&[u8], &[u8]), HwError> {
     if input.len() < len {
         return Err(HwError::SerializationError);
     }
     Ok((&input[..len], &input[len..]))
 }
 
+/// Takes the 1-byte version of a format from the input, failing if it is not `version`, and
+/// outputs the remainder
+pub fn take_version(input: &[u8], version: u8) -> Result<&[u8], HwError> {
+    match take_slice(input, 1)? {
+        (found, remainder) if found[0] == version => Ok(remainder),
+        _ => Err(HwError::SerializationError),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::serialize;
+    use super::{i2osp, os2ip, os2ip_u64, serialize, take_version, tokenize_slice};
+    use crate::digest_id::DigestId;
     use crate::errors::HwError;
     use crate::hashwires::{Commitment, Proof};
+    use crate::params::ChainLengthPolicy;
     use blake3::Hasher as Blake3;
     use generic_array::typenum::Unsigned;
     use rand_core::{OsRng, RngCore};
+    use sha2::Sha256;
+
+    #[test]
+    fn test_integer_codec() -> Result<(), HwError> {
+        assert_eq!(i2osp(258, 2)?, vec![1, 2]);
+        assert_eq!(i2osp(1, 10)?, vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(os2ip(&i2osp(65535, 2)?)?, 65535);
+        // integer too large
+        assert!(i2osp(65536, 2).is_err());
+        assert!(i2osp(1, 0).is_err());
+        assert!(serialize(&[0u8; 256], 1).is_err());
+        assert_eq!(serialize(&[7u8; 2], 1)?, vec![2, 7, 7]);
+        assert_eq!(os2ip(&[])?, 0);
+        assert!(os2ip(&[1u8; 9]).is_err());
+        // 8-byte fields decode the same on 32-bit and 64-bit targets
+        assert_eq!(
+            i2osp(u32::MAX as usize, 8)?,
+            [0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(os2ip(&[0, 0, 0, 0, 0, 0, 1, 2])?, 258);
+        assert_eq!(os2ip_u64(&[0xffu8; 8])?, u64::MAX);
+        assert!(os2ip_u64(&[0u8; 9]).is_err());
+
+        // a length prefix close to usize::MAX is rejected rather than overflowing
+        let mut input = vec![0xffu8; 8];
+        input.extend_from_slice(&[0u8; 4]);
+        assert!(tokenize_slice(&input, 8).is_err());
+        assert!(tokenize_slice(&[0, 3, 1, 2], 2).is_err());
+        assert_eq!(tokenize_slice(&[0, 1, 1, 2], 2)?, (&[1u8][..], &[2u8][..]));
+        assert_eq!(take_version(&[1, 2], 1)?, &[2u8][..]);
+        assert!(take_version(&[2, 2], 1).is_err());
+        assert!(take_version(&[], 1).is_err());
+        Ok(())
+    }
 
     #[test]
     fn test_commit_serialization() -> Result<(), HwError> {
         let mut rng = OsRng;
-        let mut bytes = [0u8; 32];
-        rng.fill_bytes(&mut bytes);
+        let mut bytes = [DigestId::Blake3.as_u8(); 35];
+        bytes[1] = ChainLengthPolicy::Uniform.as_u8();
+        rng.fill_bytes(&mut bytes[2..]);
 
-        let commitment = Commitment::<Blake3>::deserialize(&bytes, 4);
+        let commitment = Commitment::<Blake3>::deserialize(&bytes, 4)?;
         let output = commitment.serialize();
 
         assert_eq!(bytes.to_vec(), output);
+        assert!(Commitment::<Sha256>::deserialize(&bytes, 4).is_err());
         Ok(())
     }
 
@@ -95,16 +178,22 @@ mod tests {
             chain_nodes_flattened.extend_from_slice(&cn[..]);
         }
 
-        let mut mdp_salt = vec![0u8; crate::hashwires::MdpSaltSize::to_usize()];
+        let mut mdp_salt = vec![0u8; crate::params::DEFAULT_MDP_SALT_SIZE];
         rng.fill_bytes(&mut mdp_salt);
 
         let mut smt_inclusion_proof = [0u8; 32];
         rng.fill_bytes(&mut smt_inclusion_proof);
 
         let mut bytes = [
-            &serialize(&chain_nodes_flattened, 2),
+            &[
+                crate::hashwires::PROOF_VERSION,
+                crate::params::FramingPolicy::Concatenated.as_u8(),
+                DigestId::Blake3.as_u8(),
+                mdp_salt.len() as u8,
+            ],
+            &serialize(&chain_nodes_flattened, 2).unwrap()[..],
             &mdp_salt.to_vec()[..],
-            &serialize(&smt_inclusion_proof, 2),
+            &serialize(&smt_inclusion_proof, 2).unwrap(),
         ]
         .concat();
 
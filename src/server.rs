@@ -0,0 +1,281 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Framework-agnostic handling of verification requests
+//!
+//! A [`RequestVerifier`] takes the body of a verification request and outputs a
+//! [`VerifyResponse`], so that an HTTP handler (or any other transport) only moves bytes:
+//!
+//! ```ignore
+//! async fn verify(State(verifier): State<Arc<RequestVerifier>>, body: Bytes) -> Response {
+//!     let response = verifier.verify_request(&body);
+//!     let status = StatusCode::from_u16(response.http_status()).unwrap();
+//!     (status, response.serialize()).into_response()
+//! }
+//! ```
+//!
+//! Requests are rejected before any parsing if they exceed the maximum request size, and before
+//! any hashing if their proof exceeds the maximum verification cost. A request is bound to the
+//! context of the endpoint (e.g. the identifier of the relying party), and requests carrying
+//! another context are rejected with [`HwError::ContextError`], and requests for an unsupported
+//! base with [`HwError::ParameterError`].
+//!
+//! A serialized request is laid out as follows (all integers are big-endian):
+//!
+//! ```text
+//! version (1) || I2OSP(len, 2) || context || base (4) || I2OSP(len, 2) || commitment
+//! || I2OSP(len, 2) || threshold || I2OSP(len, 4) || proof
+//! ```
+//!
+//! and a serialized response is the [`HwErrorCode`] of the failure as 2 bytes, or zero on
+//! success.
+use num_bigint::BigUint;
+
+use crate::errors::{HwError, HwErrorCategory, HwErrorCode};
+use crate::hashwires::{verify_any_constant_work, verify_any_with_limit, Commitment, Proof};
+use crate::params::take_base;
+use crate::serialization::{i2osp, serialize, take_version, tokenize_slice};
+use crate::traits::Hash;
+
+const REQUEST_VERSION: u8 = 1;
+
+/// The default maximum size of a request, in bytes.
+pub const DEFAULT_MAX_REQUEST_SIZE: usize = 1 << 16;
+/// The default maximum verification cost of a proof, in hash iterations.
+pub const DEFAULT_MAX_HASH_ITERATIONS: usize = 1 << 16;
+
+/// A request to verify a HashWires proof over a commitment, bound to the context of an
+/// endpoint.
+pub struct VerifyRequest {
+    context: Vec<u8>,
+    base: u32,
+    commitment: Vec<u8>,
+    threshold: BigUint,
+    proof: Vec<u8>,
+}
+
+impl VerifyRequest {
+    /// Create a request to verify `proof` of `threshold` over `commitment`, for the endpoint
+    /// of `context`.
+    pub fn new<D: Hash>(
+        context: &[u8],
+        commitment: &Commitment<D>,
+        proof: &Proof,
+        threshold: &BigUint,
+    ) -> Self {
+        Self {
+            context: context.to_vec(),
+            base: commitment.base(),
+            commitment: commitment.serialize(),
+            threshold: threshold.clone(),
+            proof: proof.serialize(),
+        }
+    }
+
+    /// Serialize the request, failing with [`HwError::SerializationError`] if the context or
+    /// the threshold does not fit its length prefix.
+    pub fn serialize(&self) -> Result<Vec<u8>, HwError> {
+        let mut result = vec![REQUEST_VERSION];
+        result.extend_from_slice(&serialize(&self.context, 2)?);
+        result.extend_from_slice(&self.base.to_be_bytes());
+        result.extend_from_slice(&serialize(&self.commitment, 2)?);
+        result.extend_from_slice(&serialize(&self.threshold.to_bytes_be(), 2)?);
+        result.extend_from_slice(&i2osp(self.proof.len(), 4)?);
+        result.extend_from_slice(&self.proof);
+        Ok(result)
+    }
+}
+
+/// The outcome of a verification request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifyResponse {
+    error: Option<HwErrorCode>,
+}
+
+impl VerifyResponse {
+    /// Whether the proof verified.
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// The code of the failure, if any.
+    pub fn error(&self) -> Option<HwErrorCode> {
+        self.error
+    }
+
+    /// The HTTP status of the response, from the [`HwErrorCategory`] of the failure: 200 on
+    /// success, 400 for a malformed request, 422 if the proof does not verify, 403 if the
+    /// request violates the policy of the endpoint and 500 for an internal error.
+    pub fn http_status(&self) -> u16 {
+        match self.error.map(HwErrorCode::category) {
+            None => 200,
+            Some(HwErrorCategory::Malformed) => 400,
+            Some(HwErrorCategory::InvalidProof) => 422,
+            Some(HwErrorCategory::PolicyViolation) => 403,
+            Some(HwErrorCategory::InternalError) => 500,
+        }
+    }
+
+    /// Serialize the response.
+    pub fn serialize(&self) -> Vec<u8> {
+        let code = self.error.map(HwErrorCode::as_u16).unwrap_or(0);
+        code.to_be_bytes().to_vec()
+    }
+}
+
+impl From<Result<(), HwError>> for VerifyResponse {
+    fn from(result: Result<(), HwError>) -> Self {
+        Self {
+            error: result.err().map(HwErrorCode::from),
+        }
+    }
+}
+
+/// Verifies the requests of an endpoint.
+pub struct RequestVerifier {
+    context: Vec<u8>,
+    max_request_size: usize,
+    max_hash_iterations: usize,
+    constant_work: bool,
+}
+
+impl RequestVerifier {
+    /// Create a verifier for the requests bound to `context`, with the default limits.
+    pub fn new(context: &[u8]) -> Self {
+        Self {
+            context: context.to_vec(),
+            max_request_size: DEFAULT_MAX_REQUEST_SIZE,
+            max_hash_iterations: DEFAULT_MAX_HASH_ITERATIONS,
+            constant_work: false,
+        }
+    }
+
+    /// Set the maximum size of a request, in bytes.
+    pub fn with_max_request_size(mut self, max_request_size: usize) -> Self {
+        self.max_request_size = max_request_size;
+        self
+    }
+
+    /// Set the maximum verification cost of a proof, in hash iterations.
+    pub fn with_max_hash_iterations(mut self, max_hash_iterations: usize) -> Self {
+        self.max_hash_iterations = max_hash_iterations;
+        self
+    }
+
+    /// Verify proofs with the same work wherever they fail, see
+    /// [`Commitment::verify_constant_work`], so that response times do not tell which part of
+    /// a proof is wrong. This costs up to the worst-case verification for every request.
+    pub fn with_constant_work(mut self, constant_work: bool) -> Self {
+        self.constant_work = constant_work;
+        self
+    }
+
+    /// Verify a serialized [`VerifyRequest`].
+    pub fn verify_request(&self, request: &[u8]) -> VerifyResponse {
+        self.verify(request).into()
+    }
+
+    fn verify(&self, request: &[u8]) -> Result<(), HwError> {
+        if request.len() > self.max_request_size {
+            return Err(HwError::WorkLimitError);
+        }
+        let remainder = take_version(request, REQUEST_VERSION)?;
+        let (context, remainder) = tokenize_slice(remainder, 2)?;
+        let (base, remainder) = take_base(remainder)?;
+        let (commitment, remainder) = tokenize_slice(remainder, 2)?;
+        let (threshold, remainder) = tokenize_slice(remainder, 2)?;
+        let (proof, remainder) = tokenize_slice(remainder, 4)?;
+        if !remainder.is_empty() {
+            return Err(HwError::SerializationError);
+        }
+        if context != self.context.as_slice() {
+            return Err(HwError::ContextError);
+        }
+
+        let verify = match self.constant_work {
+            true => verify_any_constant_work,
+            false => verify_any_with_limit,
+        };
+        verify(
+            commitment,
+            base,
+            proof,
+            &BigUint::from_bytes_be(threshold),
+            self.max_hash_iterations,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::Secret;
+    use sha2::Sha256;
+
+    #[test]
+    fn test_verify_request() -> Result<(), HwError> {
+        let secret = Secret::<Sha256>::gen(&[5u8; 32], &BigUint::from(3413u32));
+        let commitment = secret.commit(16, 32)?;
+        let threshold = BigUint::from(2999u32);
+        let proof = secret.prove(16, 32, &threshold)?;
+        let request = VerifyRequest::new(b"rp", &commitment, &proof, &threshold).serialize()?;
+
+        let verifier = RequestVerifier::new(b"rp");
+        let response = verifier.verify_request(&request);
+        assert!(response.is_ok());
+        assert_eq!(response.serialize(), vec![0, 0]);
+
+        let other = RequestVerifier::new(b"other").verify_request(&request);
+        assert_eq!(other.error(), Some(HwErrorCode::ContextError));
+        let failing =
+            VerifyRequest::new(b"rp", &commitment, &proof, &BigUint::from(3500u32)).serialize()?;
+        let failed = verifier.verify_request(&failing);
+        assert_eq!(failed.http_status(), 422);
+        let limited = RequestVerifier::new(b"rp").with_max_hash_iterations(1);
+        assert_eq!(
+            limited.verify_request(&request).error(),
+            Some(HwErrorCode::WorkLimitError)
+        );
+        let constant_work = RequestVerifier::new(b"rp").with_constant_work(true);
+        assert!(constant_work.verify_request(&request).is_ok());
+        assert_eq!(constant_work.verify_request(&failing).http_status(), 422);
+        let small = RequestVerifier::new(b"rp").with_max_request_size(request.len() - 1);
+        assert_eq!(small.verify_request(&request).http_status(), 403);
+        assert!(!verifier.verify_request(&request[1..]).is_ok());
+
+        // the base follows the version and the context
+        let mut unsupported = request.clone();
+        unsupported[5..9].copy_from_slice(&3u32.to_be_bytes());
+        assert_eq!(
+            verifier.verify_request(&unsupported).error(),
+            Some(HwErrorCode::ParameterError)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_http_status() -> Result<(), HwError> {
+        let secret = Secret::<Sha256>::gen(&[5u8; 32], &BigUint::from(3413u32));
+        let commitment = secret.commit(16, 32)?;
+        let proof = secret.prove(16, 32, &BigUint::from(2999u32))?;
+        let request = |threshold: u32| {
+            VerifyRequest::new(b"rp", &commitment, &proof, &BigUint::from(threshold)).serialize()
+        };
+        let verifier = RequestVerifier::new(b"rp");
+
+        assert_eq!(verifier.verify_request(&request(2999)?).http_status(), 200);
+        // malformed
+        assert_eq!(verifier.verify_request(&[]).http_status(), 400);
+        // invalid proof
+        assert_eq!(verifier.verify_request(&request(3500)?).http_status(), 422);
+        // policy violation
+        let other = RequestVerifier::new(b"other");
+        assert_eq!(other.verify_request(&request(2999)?).http_status(), 403);
+        // internal error
+        let internal = VerifyResponse::from(Err(HwError::SecretStoreError));
+        assert_eq!(internal.http_status(), 500);
+        Ok(())
+    }
+}
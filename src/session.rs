@@ -0,0 +1,252 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Verifier-side replay protection through single-use nonces
+//!
+//! A [`SessionGuard`] issues a fresh nonce per verification request, and only accepts a proof
+//! presented along with a nonce it issued, which has not expired and has not been used yet.
+//!
+//! HashWires proofs are deterministic given the secret and the threshold, so anyone who saw a
+//! proof could present it again under a fresh nonce. The holder thus signs the transcript hash
+//! of the proof (see [`Proof::transcript_hash`]) with the context `SESSION_CONTEXT_SALT ||
+//! nonce`, and the guard checks the signature against the key it associates with the holder
+//! (e.g. the key of its credential or of its authenticated channel). Signatures are produced
+//! through the [`ManifestSigner`] trait and checked through the [`SignatureVerifier`] trait.
+//!
+//! A serialized session proof is laid out as `nonce (32) || I2OSP(len, 2) || signature ||
+//! proof`.
+use std::collections::HashMap;
+
+use num_bigint::BigUint;
+use rand_core::{CryptoRng, RngCore};
+
+use crate::errors::HwError;
+use crate::hashwires::{Commitment, Proof};
+use crate::manifest::{ManifestSigner, SignatureVerifier};
+use crate::serialization::{serialize_bounded, take_slice, tokenize_slice};
+use crate::traits::Hash;
+
+const SESSION_CONTEXT_SALT: &[u8; 32] = b"J1234567890123456789012345678901";
+
+/// The transcript hash binding `proof` of `threshold` over `commitment` to the session of
+/// `nonce`, which the holder signs.
+pub fn session_transcript<D: Hash>(
+    nonce: &[u8; 32],
+    commitment: &Commitment<D>,
+    proof: &Proof,
+    threshold: &BigUint,
+) -> Result<[u8; 32], HwError> {
+    proof.transcript_hash(
+        commitment,
+        threshold,
+        &[&SESSION_CONTEXT_SALT[..], nonce].concat(),
+    )
+}
+
+/// A proof presented in the session of a nonce, signed by the holder.
+#[derive(Clone)]
+pub struct SessionProof {
+    nonce: [u8; 32],
+    signature: Vec<u8>,
+    proof: Proof,
+}
+
+impl SessionProof {
+    /// Present `proof` of `threshold` over `commitment` in the session of `nonce`, signing its
+    /// session transcript with the key of the holder. Fails if the transcript cannot be computed
+    /// (see [`Proof::transcript_hash`]).
+    pub fn new<D: Hash, S: ManifestSigner>(
+        nonce: &[u8; 32],
+        commitment: &Commitment<D>,
+        proof: Proof,
+        threshold: &BigUint,
+        holder: &S,
+    ) -> Result<Self, HwError> {
+        let signature = holder.sign(&session_transcript(nonce, commitment, &proof, threshold)?);
+        Ok(Self {
+            nonce: *nonce,
+            signature,
+            proof,
+        })
+    }
+
+    /// The nonce of the session.
+    pub fn nonce(&self) -> &[u8; 32] {
+        &self.nonce
+    }
+
+    /// The HashWires proof.
+    pub fn proof(&self) -> &Proof {
+        &self.proof
+    }
+
+    /// Serialize the session proof.
+    pub fn serialize(&self) -> Vec<u8> {
+        [
+            &self.nonce[..],
+            &serialize_bounded(&self.signature, 2),
+            &self.proof.serialize(),
+        ]
+        .concat()
+    }
+
+    /// Deserialize a session proof.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (nonce_bytes, remainder) = take_slice(input, 32)?;
+        let (signature, remainder) = tokenize_slice(remainder, 2)?;
+        let mut nonce = [0u8; 32];
+        nonce.copy_from_slice(nonce_bytes);
+        Ok(Self {
+            nonce,
+            signature: signature.to_vec(),
+            proof: Proof::deserialize(remainder)?,
+        })
+    }
+}
+
+/// Issues single-use nonces and rejects proofs presented with unknown, expired or already
+/// used nonces. Times are provided by the caller, in seconds.
+pub struct SessionGuard {
+    window: u64,
+    // issued and not yet used nonces, with their issuance time
+    outstanding: HashMap<[u8; 32], u64>,
+}
+
+impl SessionGuard {
+    /// Create a guard accepting nonces for `window` seconds after they are issued.
+    pub fn new(window: u64) -> Self {
+        Self {
+            window,
+            outstanding: HashMap::new(),
+        }
+    }
+
+    /// Issue a fresh nonce at time `now`, sampled from `rng`.
+    pub fn issue_nonce<R: RngCore + CryptoRng>(&mut self, rng: &mut R, now: u64) -> [u8; 32] {
+        self.prune(now);
+        let mut nonce = [0u8; 32];
+        rng.fill_bytes(&mut nonce);
+        self.outstanding.insert(nonce, now);
+        nonce
+    }
+
+    /// The number of issued nonces which are neither used nor expired.
+    pub fn outstanding(&self) -> usize {
+        self.outstanding.len()
+    }
+
+    /// Consume the nonce of `proof` at time `now`, then verify it as a proof of `threshold`
+    /// over `commitment`, signed by the key of `holder`. The nonce is consumed even if the
+    /// proof does not verify. Fails with [`HwError::ReplayError`] for an unknown, expired or
+    /// already used nonce, and with [`HwError::ProofVerificationError`] if the signature does
+    /// not cover the session transcript.
+    pub fn verify<D: Hash, V: SignatureVerifier>(
+        &mut self,
+        proof: &SessionProof,
+        now: u64,
+        commitment: &Commitment<D>,
+        threshold: &BigUint,
+        holder: &V,
+    ) -> Result<(), HwError> {
+        self.prune(now);
+        match self.outstanding.remove(&proof.nonce) {
+            Some(issued) if issued <= now => (),
+            _ => return Err(HwError::ReplayError),
+        }
+        let transcript = session_transcript(&proof.nonce, commitment, &proof.proof, threshold)?;
+        if !holder.verify(&transcript, &proof.signature) {
+            return Err(HwError::ProofVerificationError);
+        }
+        commitment.verify(&proof.proof, threshold)
+    }
+
+    // Drop the nonces issued more than `window` seconds before `now`.
+    fn prune(&mut self, now: u64) {
+        let window = self.window;
+        self.outstanding
+            .retain(|_, issued| now.saturating_sub(*issued) <= window);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::Secret;
+    use blake3::Hasher as Blake3;
+    use rand_core::OsRng;
+
+    // A keyed hash standing in for the holder's signature scheme.
+    struct TestKey([u8; 32]);
+
+    impl ManifestSigner for TestKey {
+        fn sign(&self, message: &[u8]) -> Vec<u8> {
+            blake3::keyed_hash(&self.0, message).as_bytes().to_vec()
+        }
+    }
+
+    impl SignatureVerifier for TestKey {
+        fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+            self.sign(message) == signature
+        }
+    }
+
+    #[test]
+    fn test_session_guard() -> Result<(), HwError> {
+        let secret = Secret::<Blake3>::gen(&[3u8; 32], &BigUint::from(402u32));
+        let commitment = secret.commit(4, 32)?;
+        let threshold = BigUint::from(378u32);
+        let proof = secret.prove(4, 32, &threshold)?;
+        let holder = TestKey([9u8; 32]);
+        let present = |nonce: &[u8; 32]| {
+            SessionProof::new(nonce, &commitment, proof.clone(), &threshold, &holder)
+        };
+
+        let mut guard = SessionGuard::new(60);
+        let nonce = guard.issue_nonce(&mut OsRng, 1000);
+        let presented = SessionProof::deserialize(&present(&nonce)?.serialize())?;
+        guard.verify(&presented, 1010, &commitment, &threshold, &holder)?;
+        // replayed
+        assert!(guard
+            .verify(&presented, 1011, &commitment, &threshold, &holder)
+            .is_err());
+        // unknown
+        assert!(guard
+            .verify(
+                &present(&[0u8; 32])?,
+                1011,
+                &commitment,
+                &threshold,
+                &holder
+            )
+            .is_err());
+        // expired
+        let nonce = guard.issue_nonce(&mut OsRng, 1000);
+        assert_eq!(guard.outstanding(), 1);
+        assert!(guard
+            .verify(&present(&nonce)?, 1061, &commitment, &threshold, &holder)
+            .is_err());
+        assert_eq!(guard.outstanding(), 0);
+
+        // a proof signed for another session cannot be moved to a fresh nonce
+        let nonce = guard.issue_nonce(&mut OsRng, 1100);
+        let mut moved = presented.clone();
+        moved.nonce = nonce;
+        assert!(matches!(
+            guard.verify(&moved, 1100, &commitment, &threshold, &holder),
+            Err(HwError::ProofVerificationError)
+        ));
+        let nonce = guard.issue_nonce(&mut OsRng, 1100);
+        assert!(guard
+            .verify(
+                &present(&nonce)?,
+                1100,
+                &commitment,
+                &threshold,
+                &TestKey([8u8; 32])
+            )
+            .is_err());
+        Ok(())
+    }
+}
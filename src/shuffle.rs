@@ -47,7 +47,8 @@ impl<T> Shuffler<T> for Durstenfeld {
         for i in 0..shuffle_len {
             // TODO: document the range implementation,
             //       so we can replicate the logic to other programming languages too.
-            let j = rng.gen_range(i..dlen);
+            // Sampling is done over u64 so that 32-bit and 64-bit targets agree.
+            let j = rng.gen_range(i as u64..dlen as u64) as usize;
             data.swap(i, j);
         }
         Ok(())
@@ -95,4 +96,10 @@ mod tests {
         durstenfeld.shuffle(&mut input, length, &mut rng).unwrap();
         assert_eq!(&input, &[5, 3, 2, 4, 1]);
     }
+
+    #[test]
+    fn test_deterministic_index_shuffling() {
+        let indexes = deterministic_index_shuffling(4, 16, [0u8; 32]).unwrap();
+        assert_eq!(indexes, vec![5, 13, 3, 12]);
+    }
 }
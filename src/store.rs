@@ -0,0 +1,247 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Integration with external secret stores
+//!
+//! Wallets keeping their seeds in an HSM or an OS keychain implement [`SecretStore`], or
+//! [`AsyncSecretStore`] when the store is reached asynchronously, and build their secrets
+//! with [`Secret::from_store`] or [`Secret::from_async_store`]: the seed of a credential is
+//! only fetched for the lifetime of the secret, the store remaining the place where it is
+//! kept. Every [`SecretStore`] is also an [`AsyncSecretStore`], completing immediately.
+//!
+//! A store may also keep the encrypted state of the prover of a credential, exported with
+//! [`HwProver::export_state`], from which [`HwProver::from_store`] restores the prover instead
+//! of recomputing it. [`MemorySecretStore`] keeps seeds and states in memory, for tests and
+//! for wallets without a platform store.
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+
+use num_bigint::BigUint;
+
+use crate::errors::HwError;
+use crate::hashwires::Secret;
+use crate::prover::HwProver;
+use crate::traits::Hash;
+
+/// A store of seeds, and optionally of prover states, by credential identifier.
+pub trait SecretStore {
+    /// The seed of the credential `credential_id`, or `None` if the store holds none.
+    fn get_seed(&self, credential_id: &str) -> Result<Option<Vec<u8>>, HwError>;
+
+    /// Keep `seed` as the seed of the credential `credential_id`, replacing any previous one.
+    fn put_seed(&self, credential_id: &str, seed: &[u8]) -> Result<(), HwError>;
+
+    /// The encrypted prover state of the credential `credential_id`, if the store keeps one.
+    /// Stores which do not keep prover states return `None`.
+    fn get_state(&self, _credential_id: &str) -> Result<Option<Vec<u8>>, HwError> {
+        Ok(None)
+    }
+
+    /// Keep `state` as the encrypted prover state of the credential `credential_id`. Stores
+    /// which do not keep prover states ignore it.
+    fn put_state(&self, _credential_id: &str, _state: &[u8]) -> Result<(), HwError> {
+        Ok(())
+    }
+}
+
+/// The future returned by the methods of an [`AsyncSecretStore`].
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, HwError>> + Send + 'a>>;
+
+/// The asynchronous variant of [`SecretStore`], for stores reached over I/O.
+pub trait AsyncSecretStore {
+    /// See [`SecretStore::get_seed`].
+    fn get_seed<'a>(&'a self, credential_id: &'a str) -> StoreFuture<'a, Option<Vec<u8>>>;
+
+    /// See [`SecretStore::put_seed`].
+    fn put_seed<'a>(&'a self, credential_id: &'a str, seed: &'a [u8]) -> StoreFuture<'a, ()>;
+
+    /// See [`SecretStore::get_state`].
+    fn get_state<'a>(&'a self, credential_id: &'a str) -> StoreFuture<'a, Option<Vec<u8>>>;
+
+    /// See [`SecretStore::put_state`].
+    fn put_state<'a>(&'a self, credential_id: &'a str, state: &'a [u8]) -> StoreFuture<'a, ()>;
+}
+
+impl<S: SecretStore + Sync + ?Sized> AsyncSecretStore for S {
+    fn get_seed<'a>(&'a self, credential_id: &'a str) -> StoreFuture<'a, Option<Vec<u8>>> {
+        Box::pin(std::future::ready(SecretStore::get_seed(
+            self,
+            credential_id,
+        )))
+    }
+
+    fn put_seed<'a>(&'a self, credential_id: &'a str, seed: &'a [u8]) -> StoreFuture<'a, ()> {
+        Box::pin(std::future::ready(SecretStore::put_seed(
+            self,
+            credential_id,
+            seed,
+        )))
+    }
+
+    fn get_state<'a>(&'a self, credential_id: &'a str) -> StoreFuture<'a, Option<Vec<u8>>> {
+        Box::pin(std::future::ready(SecretStore::get_state(
+            self,
+            credential_id,
+        )))
+    }
+
+    fn put_state<'a>(&'a self, credential_id: &'a str, state: &'a [u8]) -> StoreFuture<'a, ()> {
+        Box::pin(std::future::ready(SecretStore::put_state(
+            self,
+            credential_id,
+            state,
+        )))
+    }
+}
+
+/// A [`SecretStore`] keeping seeds and prover states in memory.
+#[derive(Default)]
+pub struct MemorySecretStore {
+    seeds: Mutex<HashMap<String, Vec<u8>>>,
+    states: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemorySecretStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SecretStore for MemorySecretStore {
+    fn get_seed(&self, credential_id: &str) -> Result<Option<Vec<u8>>, HwError> {
+        let seeds = self.seeds.lock().map_err(|_| HwError::SecretStoreError)?;
+        Ok(seeds.get(credential_id).cloned())
+    }
+
+    fn put_seed(&self, credential_id: &str, seed: &[u8]) -> Result<(), HwError> {
+        let mut seeds = self.seeds.lock().map_err(|_| HwError::SecretStoreError)?;
+        seeds.insert(credential_id.to_string(), seed.to_vec());
+        Ok(())
+    }
+
+    fn get_state(&self, credential_id: &str) -> Result<Option<Vec<u8>>, HwError> {
+        let states = self.states.lock().map_err(|_| HwError::SecretStoreError)?;
+        Ok(states.get(credential_id).cloned())
+    }
+
+    fn put_state(&self, credential_id: &str, state: &[u8]) -> Result<(), HwError> {
+        let mut states = self.states.lock().map_err(|_| HwError::SecretStoreError)?;
+        states.insert(credential_id.to_string(), state.to_vec());
+        Ok(())
+    }
+}
+
+impl<D: Hash> Secret<D> {
+    /// Generate the HashWires secret of `value` with the seed of the credential
+    /// `credential_id` in `store`, failing with [`HwError::SecretStoreError`] if the store
+    /// holds no seed for it.
+    pub fn from_store<S: SecretStore + ?Sized>(
+        store: &S,
+        credential_id: &str,
+        value: &BigUint,
+    ) -> Result<Self, HwError> {
+        let seed = SecretStore::get_seed(store, credential_id)?;
+        Ok(Self::gen(&seed.ok_or(HwError::SecretStoreError)?, value))
+    }
+
+    /// The asynchronous variant of [`Secret::from_store`].
+    pub async fn from_async_store<S: AsyncSecretStore + ?Sized>(
+        store: &S,
+        credential_id: &str,
+        value: &BigUint,
+    ) -> Result<Self, HwError> {
+        let seed = AsyncSecretStore::get_seed(store, credential_id).await?;
+        Ok(Self::gen(&seed.ok_or(HwError::SecretStoreError)?, value))
+    }
+}
+
+impl<D: Hash> HwProver<D> {
+    /// Restore the prover of the credential `credential_id` from the state kept in `store`,
+    /// decrypted under `key`, or create an empty prover if the store keeps none.
+    pub fn from_store<S: SecretStore + ?Sized>(
+        store: &S,
+        credential_id: &str,
+        key: &[u8; 32],
+    ) -> Result<Self, HwError> {
+        match SecretStore::get_state(store, credential_id)? {
+            Some(state) => Self::import_state(&state, key),
+            None => Ok(Self::new()),
+        }
+    }
+
+    /// The asynchronous variant of [`HwProver::from_store`].
+    pub async fn from_async_store<S: AsyncSecretStore + ?Sized>(
+        store: &S,
+        credential_id: &str,
+        key: &[u8; 32],
+    ) -> Result<Self, HwError> {
+        match AsyncSecretStore::get_state(store, credential_id).await? {
+            Some(state) => Self::import_state(&state, key),
+            None => Ok(Self::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::HwParams;
+    use blake3::Hasher as Blake3;
+    use rand_core::OsRng;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    // The futures of a `SecretStore` complete immediately.
+    fn poll_ready<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut future = Box::pin(future);
+        match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("the future is pending"),
+        }
+    }
+
+    #[test]
+    fn test_secret_store() -> Result<(), HwError> {
+        let store = MemorySecretStore::new();
+        let value = BigUint::from(3413u32);
+        let threshold = BigUint::from(2999u32);
+        let params = HwParams::new(16, 32);
+        assert!(matches!(
+            Secret::<Blake3>::from_store(&store, "age", &value),
+            Err(HwError::SecretStoreError)
+        ));
+        SecretStore::put_seed(&store, "age", &[4u8; 32])?;
+        let secret = Secret::<Blake3>::from_store(&store, "age", &value)?;
+        let expected = Secret::<Blake3>::gen(&[4u8; 32], &value).prove(16, 32, &threshold)?;
+        assert_eq!(
+            secret.prove(16, 32, &threshold)?.serialize(),
+            expected.serialize()
+        );
+
+        // the prover state round-trips through the store, synchronously or not
+        let key = [7u8; 32];
+        let mut prover = HwProver::<Blake3>::from_store(&store, "age", &key)?;
+        prover.commit_and_prove(&secret, &params, &threshold)?;
+        SecretStore::put_state(&store, "age", &prover.export_state(&key, &mut OsRng)?)?;
+        let dyn_store: &dyn AsyncSecretStore = &store;
+        let secret = poll_ready(Secret::<Blake3>::from_async_store(dyn_store, "age", &value))?;
+        let mut restored =
+            poll_ready(HwProver::<Blake3>::from_async_store(dyn_store, "age", &key))?;
+        let proof = restored.commit_and_prove(&secret, &params, &threshold)?.1;
+        assert_eq!(proof.serialize(), expected.serialize());
+        assert!(HwProver::<Blake3>::from_store(&store, "age", &[8u8; 32]).is_err());
+        Ok(())
+    }
+}
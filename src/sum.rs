@@ -0,0 +1,225 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Range proofs over committed sums
+//!
+//! HashWires commitments are not homomorphic, so the sum of `k` values (e.g. the salaries of a
+//! payroll) is committed to as an extra attribute. A [`SumSecret`] derives the secrets of the
+//! `k` addends and of their sum from a single seed, and the holder proves a threshold on the sum
+//! with a regular proof over the sum commitment, without revealing the addends.
+//!
+//! That the sum commitment hides the sum of the values hidden by the addend commitments is a
+//! statement only the issuer can make: [`SumCommitment::digest`] is the message the issuer
+//! countersigns, and an auditor given the [`SumOpening`] checks the consistency of the
+//! commitments with [`SumOpening::check`]. This check is a disclosure and not a zero-knowledge
+//! proof: the opening reveals the seed and every value to the auditor, who must be trusted
+//! with them, and relying parties only have the countersignature of the issuer.
+//!
+//! A serialized sum commitment is laid out as follows (all integers are big-endian):
+//!
+//! ```text
+//! base (4) || max_number_bits (8) || mdp_salt_size (1) || chain_length_policy (1)
+//! || byte_order_policy (1) || framing_policy (1) || #addends (2)
+//! || for each addend: I2OSP(len, 2) || commitment || I2OSP(len, 2) || sum commitment
+//! ```
+use digest::Digest;
+use generic_array::typenum::U32;
+use num_bigint::BigUint;
+
+use crate::errors::HwError;
+use crate::hashes::generate_subseeds;
+use crate::hashwires::{Commitment, Proof, Secret};
+use crate::params::HwParams;
+use crate::serialization::{i2osp_bounded, os2ip, serialize_bounded, take_slice, tokenize_slice};
+use crate::traits::Hash;
+
+const SUM_SEED_SALT: &[u8; 32] = b"l1234567890123456789012345678901";
+const SUM_SALT: &[u8; 32] = b"m1234567890123456789012345678901";
+
+/// The maximum number of addends of a sum.
+pub const MAX_ADDENDS: usize = u16::MAX as usize;
+
+/// The secrets of `k` values and of their sum.
+pub struct SumSecret<D: Hash> {
+    addends: Vec<Secret<D>>,
+    sum: Secret<D>,
+}
+
+impl<D: Hash> SumSecret<D> {
+    /// Generate the secrets of `values` and of their sum, deriving their seeds from `seed`.
+    pub fn gen(seed: &[u8], values: &[BigUint]) -> Result<Self, HwError> {
+        if values.is_empty() || values.len() > MAX_ADDENDS {
+            return Err(HwError::ParameterError);
+        }
+        let seeds = generate_subseeds::<D, U32>(SUM_SEED_SALT, seed, values.len() + 1);
+        let sum: BigUint = values.iter().sum();
+        Ok(Self {
+            addends: values
+                .iter()
+                .zip(seeds.iter())
+                .map(|(value, seed)| Secret::gen(seed, value))
+                .collect(),
+            sum: Secret::gen(&seeds[values.len()], &sum),
+        })
+    }
+
+    /// Commit to the values and to their sum.
+    pub fn commit(&self, params: &HwParams) -> Result<SumCommitment<D>, HwError> {
+        Ok(SumCommitment {
+            params: *params,
+            addends: self
+                .addends
+                .iter()
+                .map(|addend| addend.commit_with_params(params))
+                .collect::<Result<_, _>>()?,
+            sum: self.sum.commit_with_params(params)?,
+        })
+    }
+
+    /// Prove that the sum of the values is larger than or equal to `threshold`.
+    pub fn prove_sum(&self, params: &HwParams, threshold: &BigUint) -> Result<Proof, HwError> {
+        Ok(self.sum.commit_and_prove(params, threshold)?.1)
+    }
+}
+
+/// The commitments to `k` values and to their sum.
+pub struct SumCommitment<D: Hash> {
+    params: HwParams,
+    addends: Vec<Commitment<D>>,
+    sum: Commitment<D>,
+}
+
+impl<D: Hash> SumCommitment<D> {
+    /// The parameters of the commitments.
+    pub fn params(&self) -> &HwParams {
+        &self.params
+    }
+
+    /// The commitments to the values.
+    pub fn addends(&self) -> &[Commitment<D>] {
+        &self.addends
+    }
+
+    /// The commitment to the sum of the values.
+    pub fn sum(&self) -> &Commitment<D> {
+        &self.sum
+    }
+
+    /// Verify a proof that the sum of the values is larger than or equal to `threshold`.
+    pub fn verify_sum(&self, proof: &Proof, threshold: &BigUint) -> Result<(), HwError> {
+        self.sum.verify(proof, threshold)
+    }
+
+    /// The digest of the commitments, to be countersigned by the issuer.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = D::new();
+        hasher.update(SUM_SALT);
+        hasher.update(self.serialize());
+        let mut output = [0u8; 32];
+        output.copy_from_slice(&hasher.finalize()[..32]);
+        output
+    }
+
+    /// Serialize the commitments.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = self.params.serialize();
+        result.extend_from_slice(&i2osp_bounded(self.addends.len(), 2));
+        for commitment in self.addends.iter().chain(std::iter::once(&self.sum)) {
+            result.extend_from_slice(&serialize_bounded(&commitment.serialize(), 2));
+        }
+        result
+    }
+
+    /// Deserialize the commitments. Fails with [`HwError::ParameterError`] if the parameters
+    /// are not supported.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (params, remainder) = HwParams::deserialize_prefix(input)?;
+        params.validate()?;
+        let (count, mut remainder) = take_slice(remainder, 2)?;
+        let mut addends = Vec::new();
+        for _ in 0..os2ip(count)? {
+            let (commitment, rest) = tokenize_slice(remainder, 2)?;
+            addends.push(Commitment::deserialize(commitment, params.base)?);
+            remainder = rest;
+        }
+        let (sum, remainder) = tokenize_slice(remainder, 2)?;
+        if addends.is_empty() || !remainder.is_empty() {
+            return Err(HwError::SerializationError);
+        }
+        Ok(Self {
+            params,
+            addends,
+            sum: Commitment::deserialize(sum, params.base)?,
+        })
+    }
+}
+
+/// The opening of a [`SumCommitment`], for an auditor to check its consistency. It discloses
+/// the seed and the values of the holder.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SumOpening {
+    seed: Vec<u8>,
+    values: Vec<BigUint>,
+}
+
+impl SumOpening {
+    /// The opening of the secrets generated by [`SumSecret::gen`] from `seed` and `values`.
+    pub fn new(seed: &[u8], values: &[BigUint]) -> Self {
+        Self {
+            seed: seed.to_vec(),
+            values: values.to_vec(),
+        }
+    }
+
+    /// Check that the addend commitments of `commitment` hide the values of this opening, and
+    /// that its sum commitment hides their sum.
+    pub fn check<D: Hash>(&self, commitment: &SumCommitment<D>) -> Result<(), HwError> {
+        let expected = SumSecret::<D>::gen(&self.seed, &self.values)?.commit(&commitment.params)?;
+        if expected.serialize() != commitment.serialize() {
+            return Err(HwError::ProofVerificationError);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+
+    #[test]
+    fn test_sum() -> Result<(), HwError> {
+        let params = HwParams::new(16, 32);
+        let values: Vec<BigUint> = [3000u32, 4500, 2500]
+            .iter()
+            .map(|v| BigUint::from(*v))
+            .collect();
+        let secret = SumSecret::<Blake3>::gen(&[3u8; 32], &values)?;
+        let commitment =
+            SumCommitment::<Blake3>::deserialize(&secret.commit(&params)?.serialize())?;
+        assert_eq!(commitment.addends().len(), 3);
+
+        let threshold = BigUint::from(10000u32);
+        commitment.verify_sum(&secret.prove_sum(&params, &threshold)?, &threshold)?;
+        assert!(secret.prove_sum(&params, &BigUint::from(10001u32)).is_err());
+
+        SumOpening::new(&[3u8; 32], &values).check(&commitment)?;
+        let mut other_values = values.clone();
+        other_values[0] = BigUint::from(3001u32);
+        assert!(SumOpening::new(&[3u8; 32], &other_values)
+            .check(&commitment)
+            .is_err());
+        assert!(SumSecret::<Blake3>::gen(&[3u8; 32], &[]).is_err());
+
+        // unsupported parameters are rejected before any verification
+        let mut unsupported = commitment.serialize();
+        unsupported[..4].copy_from_slice(&3u32.to_be_bytes());
+        assert!(matches!(
+            SumCommitment::<Blake3>::deserialize(&unsupported),
+            Err(HwError::ParameterError)
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,329 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Commitments to a time series of values, for continuous-compliance attestations
+//!
+//! A [`TimeSeries`] commits to one value per epoch (e.g. the daily balance of an account) in a
+//! sparse Merkle tree indexed by epoch, whose root the issuer publishes. The holder then proves
+//! that the value at an epoch, or at every epoch of an interval, is larger than or equal to a
+//! threshold, e.g. that a minimum balance was maintained over a quarter. Intervals must be
+//! fully committed to: an epoch without a value cannot be skipped by a proof.
+//!
+//! The secret of each epoch is derived from the series seed, and the leaf of each epoch binds
+//! it to its commitment:
+//!
+//! ```text
+//! leaf = H(SERIES_SALT || epoch (8) || base (4) || commitment)
+//! ```
+//!
+//! A serialized series proof is laid out as follows (all integers are big-endian):
+//!
+//! ```text
+//! first epoch (8) || last epoch (8) || I2OSP(len, 2) || inclusion proof
+//! for each epoch: I2OSP(len, 2) || commitment || I2OSP(len, 2) || range proof
+//! ```
+use std::collections::BTreeMap;
+
+use digest::Digest;
+use generic_array::typenum::U32;
+use num_bigint::BigUint;
+use smtree::index::TreeIndex;
+use smtree::node_template::HashWiresNodeSmt;
+use smtree::pad_secret::Secret as SmtSecret;
+use smtree::proof::MerkleProof;
+use smtree::traits::{InclusionProvable, Serializable};
+use smtree::tree::SparseMerkleTree;
+
+use crate::errors::HwError;
+use crate::hashes::{generate_subseeds, SMTREE_PADDING_SALT};
+use crate::hashwires::{Commitment, Proof, Secret};
+use crate::params::HwParams;
+use crate::serialization::{os2ip_u64, serialize_bounded, take_slice, tokenize_slice};
+use crate::traits::Hash;
+
+const SERIES_SALT: &[u8; 32] = b"n1234567890123456789012345678901";
+const EPOCH_SEED_SALT: &[u8; 32] = b"o1234567890123456789012345678901";
+
+/// The maximum height of a [`TimeSeries`], as epochs are 64-bit integers.
+pub const MAX_SERIES_HEIGHT: usize = 64;
+/// The maximum number of epochs covered by a [`SeriesProof`].
+pub const MAX_PROOF_EPOCHS: usize = u16::MAX as usize;
+
+/// A time series of committed values, one per epoch.
+pub struct TimeSeries<D: Hash> {
+    height: usize,
+    params: HwParams,
+    seed: Vec<u8>,
+    padding_secret: SmtSecret,
+    epochs: BTreeMap<u64, Epoch<D>>,
+}
+
+// The secret, serialized commitment and leaf of an epoch.
+struct Epoch<D: Hash> {
+    secret: Secret<D>,
+    commitment: Vec<u8>,
+    leaf: [u8; 32],
+}
+
+impl<D: Hash> TimeSeries<D> {
+    /// Create an empty series over up to `2^height` epochs, committing under `params`. The
+    /// secrets of the epochs and the padding nodes of the tree are derived from `seed`, which
+    /// must be kept secret.
+    pub fn new(height: usize, params: &HwParams, seed: &[u8]) -> Result<Self, HwError> {
+        params.validate()?;
+        if height == 0 || height > MAX_SERIES_HEIGHT {
+            return Err(HwError::ParameterError);
+        }
+        let padding_secret = generate_subseeds::<D, U32>(SMTREE_PADDING_SALT, seed, 1);
+        Ok(Self {
+            height,
+            params: *params,
+            seed: seed.to_vec(),
+            padding_secret: SmtSecret::from_bytes(&padding_secret[0]).unwrap(),
+            epochs: BTreeMap::new(),
+        })
+    }
+
+    /// Commit to `value` at `epoch`, replacing any previous value.
+    pub fn insert(&mut self, epoch: u64, value: &BigUint) -> Result<(), HwError> {
+        if self.height < MAX_SERIES_HEIGHT && epoch >> self.height != 0 {
+            return Err(HwError::ParameterError);
+        }
+        let secret = Secret::gen(&epoch_seed::<D>(&self.seed, epoch), value);
+        let commitment = secret.commit_with_params(&self.params)?;
+        let leaf = leaf::<D>(epoch, &commitment);
+        self.epochs.insert(
+            epoch,
+            Epoch {
+                secret,
+                commitment: commitment.serialize(),
+                leaf,
+            },
+        );
+        Ok(())
+    }
+
+    /// The number of epochs with a value.
+    pub fn len(&self) -> usize {
+        self.epochs.len()
+    }
+
+    /// Whether no epoch has a value.
+    pub fn is_empty(&self) -> bool {
+        self.epochs.is_empty()
+    }
+
+    /// The root of the series, to be published by the issuer along with the base.
+    pub fn root(&self) -> Vec<u8> {
+        self.tree().get_root_raw().serialize()
+    }
+
+    /// Prove that the value at `epoch` is larger than or equal to `threshold`.
+    pub fn prove_at(&self, epoch: u64, threshold: &BigUint) -> Result<SeriesProof, HwError> {
+        self.prove_interval(epoch, epoch, threshold)
+    }
+
+    /// Prove that the value at every epoch between `first` and `last` (included) is larger
+    /// than or equal to `threshold`. Every epoch of the interval must have a value.
+    pub fn prove_interval(
+        &self,
+        first: u64,
+        last: u64,
+        threshold: &BigUint,
+    ) -> Result<SeriesProof, HwError> {
+        if first > last || last - first >= MAX_PROOF_EPOCHS as u64 {
+            return Err(HwError::ParameterError);
+        }
+        let mut entries = Vec::new();
+        for epoch in first..=last {
+            let entry = self
+                .epochs
+                .get(&epoch)
+                .ok_or(HwError::InclusionProofError)?;
+            let (_, proof) = entry.secret.commit_and_prove(&self.params, threshold)?;
+            entries.push((entry.commitment.clone(), proof));
+        }
+        let indexes: Vec<TreeIndex> = (first..=last)
+            .map(|epoch| TreeIndex::from_u64(self.height, epoch))
+            .collect();
+        let inclusion =
+            MerkleProof::<HashWiresNodeSmt<D>>::generate_inclusion_proof(&self.tree(), &indexes)
+                .ok_or(HwError::InclusionProofError)?;
+        Ok(SeriesProof {
+            first,
+            last,
+            inclusion: inclusion.serialize(),
+            entries,
+        })
+    }
+
+    fn tree(&self) -> SparseMerkleTree<HashWiresNodeSmt<D>> {
+        let leaves: Vec<(TreeIndex, HashWiresNodeSmt<D>)> = self
+            .epochs
+            .iter()
+            .map(|(epoch, entry)| {
+                (
+                    TreeIndex::from_u64(self.height, *epoch),
+                    HashWiresNodeSmt::<D>::new(entry.leaf.to_vec()),
+                )
+            })
+            .collect();
+        let mut tree = SparseMerkleTree::new(self.height);
+        tree.build(&leaves, &self.padding_secret);
+        tree
+    }
+}
+
+fn epoch_seed<D: Hash>(seed: &[u8], epoch: u64) -> [u8; 32] {
+    let mut hasher = D::new();
+    hasher.update(EPOCH_SEED_SALT);
+    hasher.update(epoch.to_be_bytes());
+    hasher.update(seed);
+    let mut output = [0u8; 32];
+    output.copy_from_slice(&hasher.finalize()[..32]);
+    output
+}
+
+// The epoch is part of the leaf, so a commitment cannot be claimed at another epoch.
+fn leaf<D: Hash>(epoch: u64, commitment: &Commitment<D>) -> [u8; 32] {
+    let mut hasher = D::new();
+    hasher.update(SERIES_SALT);
+    hasher.update(epoch.to_be_bytes());
+    hasher.update(commitment.base().to_be_bytes());
+    hasher.update(commitment.serialize());
+    let mut output = [0u8; 32];
+    output.copy_from_slice(&hasher.finalize()[..32]);
+    output
+}
+
+/// A proof that the values of a [`TimeSeries`] over an interval of epochs are all larger than
+/// or equal to a threshold.
+pub struct SeriesProof {
+    first: u64,
+    last: u64,
+    inclusion: Vec<u8>,
+    // the serialized commitment and range proof of each epoch, in order
+    entries: Vec<(Vec<u8>, Proof)>,
+}
+
+impl SeriesProof {
+    /// The first epoch of the interval.
+    pub fn first(&self) -> u64 {
+        self.first
+    }
+
+    /// The last epoch of the interval (included).
+    pub fn last(&self) -> u64 {
+        self.last
+    }
+
+    /// Serialize the series proof.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = [
+            &self.first.to_be_bytes()[..],
+            &self.last.to_be_bytes(),
+            &serialize_bounded(&self.inclusion, 2),
+        ]
+        .concat();
+        for (commitment, proof) in self.entries.iter() {
+            result.extend_from_slice(&serialize_bounded(commitment, 2));
+            result.extend_from_slice(&serialize_bounded(&proof.serialize(), 2));
+        }
+        result
+    }
+
+    /// Deserialize a series proof.
+    pub fn deserialize(input: &[u8]) -> Result<Self, HwError> {
+        let (first, remainder) = take_slice(input, 8)?;
+        let (last, remainder) = take_slice(remainder, 8)?;
+        let (inclusion, mut remainder) = tokenize_slice(remainder, 2)?;
+        let (first, last) = (os2ip_u64(first)?, os2ip_u64(last)?);
+        if first > last || last - first >= MAX_PROOF_EPOCHS as u64 {
+            return Err(HwError::SerializationError);
+        }
+        let mut entries = Vec::new();
+        for _ in first..=last {
+            let (commitment, rest) = tokenize_slice(remainder, 2)?;
+            let (proof, rest) = tokenize_slice(rest, 2)?;
+            entries.push((commitment.to_vec(), Proof::deserialize(proof)?));
+            remainder = rest;
+        }
+        if !remainder.is_empty() {
+            return Err(HwError::SerializationError);
+        }
+        Ok(Self {
+            first,
+            last,
+            inclusion: inclusion.to_vec(),
+            entries,
+        })
+    }
+}
+
+/// Verify that the values of the series with root `root`, committed in base `base`, are larger
+/// than or equal to `threshold` at every epoch between `first` and `last` (included).
+pub fn verify_series<D: Hash>(
+    root: &[u8],
+    base: u32,
+    first: u64,
+    last: u64,
+    threshold: &BigUint,
+    proof: &SeriesProof,
+) -> Result<(), HwError> {
+    if proof.first != first || proof.last != last {
+        return Err(HwError::ProofVerificationError);
+    }
+    let mut leaves = Vec::with_capacity(proof.entries.len());
+    for (epoch, (commitment, range_proof)) in (first..=last).zip(proof.entries.iter()) {
+        let commitment = Commitment::<D>::deserialize(commitment, base)?;
+        commitment.verify(range_proof, threshold)?;
+        leaves.push(HashWiresNodeSmt::<D>::new(
+            leaf::<D>(epoch, &commitment).to_vec(),
+        ));
+    }
+    let inclusion = MerkleProof::<HashWiresNodeSmt<D>>::deserialize(&proof.inclusion)?;
+    if !inclusion.verify_inclusion_proof(&leaves, &HashWiresNodeSmt::<D>::new(root.to_vec())) {
+        return Err(HwError::ProofVerificationError);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+
+    #[test]
+    fn test_time_series() -> Result<(), HwError> {
+        let params = HwParams::new(16, 32);
+        let mut series = TimeSeries::<Blake3>::new(16, &params, &[4u8; 32])?;
+        for (epoch, balance) in [(10u64, 1200u32), (11, 1500), (12, 1000), (14, 3000)].iter() {
+            series.insert(*epoch, &BigUint::from(*balance))?;
+        }
+        let root = series.root();
+        let threshold = BigUint::from(1000u32);
+
+        let proof =
+            SeriesProof::deserialize(&series.prove_interval(10, 12, &threshold)?.serialize())?;
+        verify_series::<Blake3>(&root, 16, 10, 12, &threshold, &proof)?;
+        assert!(verify_series::<Blake3>(&root, 16, 10, 11, &threshold, &proof).is_err());
+        verify_series::<Blake3>(
+            &root,
+            16,
+            14,
+            14,
+            &threshold,
+            &series.prove_at(14, &threshold)?,
+        )?;
+
+        // epoch 12 is below the threshold, and epoch 13 has no value
+        assert!(series
+            .prove_interval(10, 12, &BigUint::from(1001u32))
+            .is_err());
+        assert!(series.prove_interval(12, 14, &threshold).is_err());
+        assert!(series.insert(1 << 16, &threshold).is_err());
+        Ok(())
+    }
+}
@@ -4,8 +4,119 @@
 // LICENSE file in the root directory of this source tree.
 
 use digest::{BlockInput, FixedOutput, Reset, Update};
+use generic_array::typenum::Unsigned;
+#[cfg(feature = "digest10")]
+use generic_array::GenericArray;
+use smtree::node_template::HashWiresNodeSmt;
+use smtree::proof::MerkleProof;
 
 /// A convenience trait for digest bounds used throughout the library
+///
+/// The bounds are the traits of `digest` 0.9, which hashers of that version (e.g. `sha2` 0.9 or
+/// `blake3` 0.3) implement. Hashers of `digest` 0.10 are wrapped in [`Digest10`], with the
+/// `digest10` feature.
 pub trait Hash: Update + BlockInput + FixedOutput + Reset + Default + Clone {}
 
 impl<T: Update + BlockInput + FixedOutput + Reset + Default + Clone> Hash for T {}
+
+/// Verification of an inclusion proof against the raw bytes of a root, for verifiers holding
+/// only the published root rather than tree nodes.
+pub trait VerifyAgainstRootBytes {
+    /// Check that this proof includes the leaf of hash `leaf` under the root of hash `root`.
+    /// Hashes of another length than the output of the digest of the tree do not verify.
+    fn verify_against_root_bytes(&self, leaf: &[u8], root: &[u8]) -> bool;
+}
+
+impl<D: Hash> VerifyAgainstRootBytes for MerkleProof<HashWiresNodeSmt<D>> {
+    fn verify_against_root_bytes(&self, leaf: &[u8], root: &[u8]) -> bool {
+        let output_size = D::OutputSize::to_usize();
+        if leaf.len() != output_size || root.len() != output_size {
+            return false;
+        }
+        self.verify(
+            &HashWiresNodeSmt::new(leaf.to_vec()),
+            &HashWiresNodeSmt::new(root.to_vec()),
+        )
+    }
+}
+
+/// Adapter of a hasher implementing the traits of `digest` 0.10 to the traits of `digest` 0.9,
+/// and thus to [`Hash`], e.g. `Secret::<Digest10<sha2::Sha256>>` with `sha2` 0.10. The outputs
+/// are those of the wrapped hasher, so commitments and proofs do not depend on the version of
+/// `digest` the hasher was built against.
+#[cfg(feature = "digest10")]
+#[derive(Clone, Default)]
+pub struct Digest10<H>(H);
+
+#[cfg(feature = "digest10")]
+impl<H> Digest10<H> {
+    /// Wrap `hasher`.
+    pub fn new(hasher: H) -> Self {
+        Self(hasher)
+    }
+
+    /// The wrapped hasher.
+    pub fn into_inner(self) -> H {
+        self.0
+    }
+}
+
+#[cfg(feature = "digest10")]
+impl<H: digest10::Update> Update for Digest10<H> {
+    fn update(&mut self, data: impl AsRef<[u8]>) {
+        digest10::Update::update(&mut self.0, data.as_ref());
+    }
+}
+
+#[cfg(feature = "digest10")]
+impl<H: digest10::core_api::BlockSizeUser> BlockInput for Digest10<H> {
+    type BlockSize = H::BlockSize;
+}
+
+#[cfg(feature = "digest10")]
+impl<H: digest10::FixedOutputReset> FixedOutput for Digest10<H> {
+    type OutputSize = H::OutputSize;
+
+    fn finalize_into(self, out: &mut GenericArray<u8, Self::OutputSize>) {
+        digest10::FixedOutput::finalize_into(self.0, out);
+    }
+
+    fn finalize_into_reset(&mut self, out: &mut GenericArray<u8, Self::OutputSize>) {
+        digest10::FixedOutputReset::finalize_into_reset(&mut self.0, out);
+    }
+}
+
+#[cfg(feature = "digest10")]
+impl<H: digest10::Reset> Reset for Digest10<H> {
+    fn reset(&mut self) {
+        digest10::Reset::reset(&mut self.0);
+    }
+}
+
+#[cfg(all(test, feature = "digest10"))]
+mod tests {
+    use super::*;
+    use crate::digest_id::DigestId;
+    use crate::errors::HwError;
+    use crate::hashwires::Secret;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn test_digest10() -> Result<(), HwError> {
+        type Sha256v10 = Digest10<sha2_10::Sha256>;
+        assert_eq!(DigestId::of::<Sha256v10>(), DigestId::Sha256);
+
+        // the same outputs as the hasher of digest 0.9
+        let value = BigUint::from(3413u32);
+        let secret = Secret::<Sha256v10>::gen(&[3u8; 32], &value);
+        let expected = Secret::<sha2::Sha256>::gen(&[3u8; 32], &value);
+        assert_eq!(
+            secret.commit(16, 32)?.serialize(),
+            expected.commit(16, 32)?.serialize()
+        );
+        let proof = secret.prove(16, 32, &BigUint::from(2999u32))?;
+        expected
+            .commit(16, 32)?
+            .verify(&proof, &BigUint::from(2999u32))
+    }
+}
@@ -0,0 +1,79 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Conversions of application values into the integers committed to by HashWires
+//!
+//! The committed values and thresholds are [`BigUint`]s, but the APIs accept any [`HwValue`]:
+//! the primitive unsigned integers, [`BigUint`], and `(&str, radix)` pairs spelling out a
+//! number in an explicit radix between 2 and 36.
+use num_bigint::BigUint;
+
+use crate::errors::HwError;
+
+/// A value which can be committed to, or used as a threshold.
+pub trait HwValue {
+    /// The value as an unsigned integer.
+    fn to_biguint(&self) -> Result<BigUint, HwError>;
+}
+
+impl<T: HwValue + ?Sized> HwValue for &T {
+    fn to_biguint(&self) -> Result<BigUint, HwError> {
+        (**self).to_biguint()
+    }
+}
+
+impl HwValue for BigUint {
+    fn to_biguint(&self) -> Result<BigUint, HwError> {
+        Ok(self.clone())
+    }
+}
+
+macro_rules! impl_hw_value {
+    ($($t:ty),*) => {
+        $(
+            impl HwValue for $t {
+                fn to_biguint(&self) -> Result<BigUint, HwError> {
+                    Ok(BigUint::from(*self))
+                }
+            }
+        )*
+    };
+}
+
+impl_hw_value!(u32, u64, u128);
+
+impl HwValue for (&str, u32) {
+    /// Parse the digits in the radix, rejecting radixes outside of `2..=36`, empty strings and
+    /// invalid digits with [`HwError::ParameterError`].
+    fn to_biguint(&self) -> Result<BigUint, HwError> {
+        let (digits, radix) = *self;
+        if !(2..=36).contains(&radix) {
+            return Err(HwError::ParameterError);
+        }
+        BigUint::parse_bytes(digits.as_bytes(), radix).ok_or(HwError::ParameterError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hw_value() -> Result<(), HwError> {
+        let expected = BigUint::from(3413u32);
+        assert_eq!(3413u32.to_biguint()?, expected);
+        assert_eq!(3413u64.to_biguint()?, expected);
+        assert_eq!(3413u128.to_biguint()?, expected);
+        assert_eq!(expected.to_biguint()?, expected);
+        assert_eq!(("d55", 16).to_biguint()?, expected);
+        assert_eq!(("3413", 10).to_biguint()?, expected);
+
+        assert!(("d55", 10).to_biguint().is_err());
+        assert!(("", 10).to_biguint().is_err());
+        assert!(("3413", 1).to_biguint().is_err());
+        assert!(("3413", 37).to_biguint().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! A verification handle shared across threads
+//!
+//! A [`Verifier`] wraps a deserialized commitment in an [`Arc`], so that the request handlers of
+//! a multi-threaded server can verify proofs against it without re-parsing it. Cloning a
+//! verifier is cheap, and all clones share a cache of the proofs that already verified, so that
+//! a proof presented several times is only verified once. Cached proofs are keyed by the
+//! transcript hash of their verification (see [`Proof::transcript_hash`]), which frames the
+//! commitment, the threshold and the proof with their lengths.
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use num_bigint::BigUint;
+
+use crate::errors::HwError;
+use crate::hashwires::{Commitment, Proof};
+use crate::traits::Hash;
+
+/// The default number of verified proofs remembered by a [`Verifier`].
+pub const DEFAULT_CACHE_CAPACITY: usize = 1024;
+
+/// A thread-safe handle verifying proofs against a shared commitment.
+pub struct Verifier<D: Hash> {
+    commitment: Arc<Commitment<D>>,
+    verified: Arc<Mutex<HashSet<[u8; 32]>>>,
+    capacity: usize,
+}
+
+impl<D: Hash> Clone for Verifier<D> {
+    fn clone(&self) -> Self {
+        Self {
+            commitment: Arc::clone(&self.commitment),
+            verified: Arc::clone(&self.verified),
+            capacity: self.capacity,
+        }
+    }
+}
+
+impl<D: Hash> Verifier<D> {
+    /// Create a verifier for `commitment`.
+    pub fn new(commitment: Commitment<D>) -> Self {
+        Self::from_arc(Arc::new(commitment), DEFAULT_CACHE_CAPACITY)
+    }
+
+    /// Create a verifier for an already shared `commitment`, remembering up to `capacity`
+    /// verified proofs (0 disables the cache).
+    pub fn from_arc(commitment: Arc<Commitment<D>>, capacity: usize) -> Self {
+        Self {
+            commitment,
+            verified: Arc::new(Mutex::new(HashSet::new())),
+            capacity,
+        }
+    }
+
+    /// The shared commitment.
+    pub fn commitment(&self) -> &Arc<Commitment<D>> {
+        &self.commitment
+    }
+
+    /// Verify a HashWires proof over the commitment.
+    pub fn verify(&self, proof: &Proof, threshold: &BigUint) -> Result<(), HwError> {
+        let key = proof.transcript_hash(&self.commitment, threshold, &[])?;
+        if self.verified.lock().unwrap().contains(&key) {
+            return Ok(());
+        }
+        // The lock is not held while verifying, so that threads verify concurrently.
+        self.commitment.verify(proof, threshold)?;
+        if self.capacity > 0 {
+            let mut verified = self.verified.lock().unwrap();
+            if verified.len() >= self.capacity {
+                verified.clear();
+            }
+            verified.insert(key);
+        }
+        Ok(())
+    }
+
+    /// Deserialize and verify a HashWires proof over the commitment.
+    pub fn verify_bytes(&self, proof: &[u8], threshold: &BigUint) -> Result<(), HwError> {
+        self.verify(&Proof::deserialize(proof)?, threshold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashwires::Secret;
+    use blake3::Hasher as Blake3;
+    use std::thread;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_shared_verifier() -> Result<(), HwError> {
+        assert_send_sync::<Commitment<Blake3>>();
+        assert_send_sync::<Verifier<Blake3>>();
+
+        let secret = Secret::<Blake3>::gen(&[3u8; 32], &BigUint::from(402u32));
+        let verifier = Verifier::new(secret.commit(4, 32)?);
+        let proof = secret.prove(4, 32, &BigUint::from(378u32))?.serialize();
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let verifier = verifier.clone();
+                let proof = proof.clone();
+                thread::spawn(move || verifier.verify_bytes(&proof, &BigUint::from(378u32)))
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap()?;
+        }
+        assert_eq!(verifier.verified.lock().unwrap().len(), 1);
+
+        // A cached proof is only valid for its own threshold.
+        assert!(verifier
+            .verify_bytes(&proof, &BigUint::from(403u32))
+            .is_err());
+
+        // Moving the PLR padding from the proof to the threshold is not a cached proof.
+        let (unpadded, padding) = proof.split_at(proof.len() - 32);
+        let threshold = [padding, &BigUint::from(378u32).to_bytes_be()].concat();
+        let threshold = BigUint::from_bytes_be(&threshold);
+        assert!(verifier.verify_bytes(unpadded, &threshold).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Checks that `Commitment::verify_in_place` does not allocate in the HashWires stages, with a
+//! counting global allocator. This file holds a single test, so that no other test allocates
+//! concurrently.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use blake3::Hasher as Blake3;
+use num_bigint::BigUint;
+
+use hashwires::errors::HwError;
+use hashwires::hashwires::{scratch_size, Secret};
+use hashwires::params::HwParams;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn count_allocations<F: FnMut() -> Result<(), HwError>>(mut f: F) -> Result<usize, HwError> {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f()?;
+    Ok(ALLOCATIONS.load(Ordering::Relaxed) - before)
+}
+
+#[test]
+fn test_verify_in_place_allocations() -> Result<(), HwError> {
+    let params = HwParams::new(16, 64);
+    let secret = Secret::<Blake3>::gen(&[0u8; 32], &BigUint::from(u64::MAX - 1));
+    let commitment = secret.commit_with_params(&params)?;
+    let mut scratch = vec![0u8; scratch_size(&params)?];
+
+    // Only the inclusion proof allocates, independently of the number of digits.
+    let mut counts = Vec::new();
+    for threshold in [1u64, 3413, u64::MAX >> 1].iter() {
+        let threshold = BigUint::from(*threshold);
+        let proof = secret.prove(params.base, params.max_number_bits, &threshold)?;
+        let in_place =
+            count_allocations(|| commitment.verify_in_place(&proof, &threshold, &mut scratch))?;
+        let allocating = count_allocations(|| commitment.verify(&proof, &threshold))?;
+        assert!(in_place < allocating);
+        counts.push(in_place);
+    }
+    assert!(counts.iter().all(|count| *count == counts[0]));
+    Ok(())
+}